@@ -63,6 +63,14 @@ pub struct BuilderArgs {
     )]
     private_key: Option<String>,
 
+    /// URL of a remote JSON-RPC signer to use for signing transactions
+    #[arg(
+        long = "builder.remote_signer_url",
+        name = "builder.remote_signer_url",
+        env = "BUILDER_REMOTE_SIGNER_URL"
+    )]
+    remote_signer_url: Option<String>,
+
     /// AWS KMS key IDs to use for signing transactions
     #[arg(
         long = "builder.aws_kms_key_ids",
@@ -205,6 +213,7 @@ impl BuilderArgs {
                 .parse()
                 .context("should parse entry point address")?,
             private_key: self.private_key.clone(),
+            remote_signer_url: self.remote_signer_url.clone(),
             aws_kms_key_ids: self.aws_kms_key_ids.clone(),
             aws_kms_region: common
                 .aws_region
@@ -213,6 +222,7 @@ impl BuilderArgs {
             redis_uri: self.redis_uri.clone(),
             redis_lock_ttl_millis: self.redis_lock_ttl_millis,
             chain_id: common.chain_id,
+            gas_overheads: common.into(),
             max_bundle_size: self.max_bundle_size,
             max_bundle_gas: common.max_bundle_gas,
             submit_url,
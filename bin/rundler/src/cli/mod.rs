@@ -11,8 +11,11 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
+use std::time::Duration;
+
 use anyhow::Context;
-use clap::{builder::PossibleValuesParser, Args, Parser, Subcommand};
+use clap::{builder::PossibleValuesParser, ArgAction, Args, Parser, Subcommand};
+use ethers::{abi::Selector, types::Bytes};
 
 mod builder;
 mod json;
@@ -28,8 +31,10 @@ use pool::PoolCliArgs;
 use rpc::RpcCliArgs;
 use rundler_rpc::EthApiSettings;
 use rundler_sim::{
-    EstimationSettings, PrecheckSettings, PriorityFeeMode, SimulationSettings, MIN_CALL_GAS_LIMIT,
+    gas::GasOverheads, EstimationSettings, GasEstimateMargins, PrecheckSettings, PriorityFeeMode,
+    SimulationSettings, MIN_CALL_GAS_LIMIT,
 };
+use url::Url;
 
 /// Main entry point for the CLI
 ///
@@ -112,7 +117,8 @@ pub struct CommonArgs {
     )]
     chain_id: u64,
 
-    /// ETH Node HTTP URL to connect to
+    /// ETH Node HTTP URL to connect to. May be a comma-separated list of URLs
+    /// to fail over between, in priority order
     #[arg(
         long = "node_http",
         name = "node_http",
@@ -139,6 +145,48 @@ pub struct CommonArgs {
     )]
     max_bundle_gas: u64,
 
+    /// Overrides of the chain's pre-verification gas overheads, for chains
+    /// with non-standard intrinsic gas or calldata pricing
+    #[arg(
+        long = "pvg_transaction_gas_overhead",
+        name = "pvg_transaction_gas_overhead",
+        env = "PVG_TRANSACTION_GAS_OVERHEAD",
+        global = true
+    )]
+    pvg_transaction_gas_overhead: Option<u64>,
+
+    #[arg(
+        long = "pvg_per_user_op",
+        name = "pvg_per_user_op",
+        env = "PVG_PER_USER_OP",
+        global = true
+    )]
+    pvg_per_user_op: Option<u64>,
+
+    #[arg(
+        long = "pvg_per_user_op_word",
+        name = "pvg_per_user_op_word",
+        env = "PVG_PER_USER_OP_WORD",
+        global = true
+    )]
+    pvg_per_user_op_word: Option<u64>,
+
+    #[arg(
+        long = "pvg_zero_byte",
+        name = "pvg_zero_byte",
+        env = "PVG_ZERO_BYTE",
+        global = true
+    )]
+    pvg_zero_byte: Option<u64>,
+
+    #[arg(
+        long = "pvg_non_zero_byte",
+        name = "pvg_non_zero_byte",
+        env = "PVG_NON_ZERO_BYTE",
+        global = true
+    )]
+    pvg_non_zero_byte: Option<u64>,
+
     #[arg(
         long = "min_stake_value",
         name = "min_stake_value",
@@ -167,6 +215,123 @@ pub struct CommonArgs {
     )]
     user_operation_event_block_distance: Option<u64>,
 
+    /// Maximum number of entry point addresses to include in a single log
+    /// query when calling eth_getUserOperationByHash. Defaults to all
+    #[arg(
+        long = "user_operation_event_address_batch_size",
+        name = "user_operation_event_address_batch_size",
+        env = "USER_OPERATION_EVENT_ADDRESS_BATCH_SIZE",
+        global = true
+    )]
+    user_operation_event_address_batch_size: Option<usize>,
+
+    /// Maximum number of blocks to include in a single log query when calling
+    /// eth_getUserOperationByHash. Defaults to the full range
+    #[arg(
+        long = "user_operation_event_block_page_size",
+        name = "user_operation_event_block_page_size",
+        env = "USER_OPERATION_EVENT_BLOCK_PAGE_SIZE",
+        global = true
+    )]
+    user_operation_event_block_page_size: Option<u64>,
+
+    /// Whether eth_getUserOperationByHash searches blocks older than
+    /// user_operation_event_block_distance when the operation isn't found
+    #[arg(
+        long = "user_operation_event_historical_fallback",
+        name = "user_operation_event_historical_fallback",
+        env = "USER_OPERATION_EVENT_HISTORICAL_FALLBACK",
+        global = true
+    )]
+    user_operation_event_historical_fallback: bool,
+
+    /// Maximum number of blocks older than user_operation_event_block_distance
+    /// that the historical fallback searches. Defaults to all blocks back to
+    /// genesis
+    #[arg(
+        long = "user_operation_event_historical_max_depth",
+        name = "user_operation_event_historical_max_depth",
+        env = "USER_OPERATION_EVENT_HISTORICAL_MAX_DEPTH",
+        global = true
+    )]
+    user_operation_event_historical_max_depth: Option<u64>,
+
+    /// URL of an external service that indexes user operations by hash. If
+    /// set, eth_getUserOperationByHash asks it for the transaction that
+    /// included an operation instead of searching logs
+    #[arg(
+        long = "user_operation_indexer_url",
+        name = "user_operation_indexer_url",
+        env = "USER_OPERATION_INDEXER_URL",
+        global = true
+    )]
+    user_operation_indexer_url: Option<Url>,
+
+    /// Maximum number of eth_getUserOperationByHash results to cache
+    #[arg(
+        long = "user_operation_cache_size",
+        name = "user_operation_cache_size",
+        env = "USER_OPERATION_CACHE_SIZE",
+        default_value = "10000",
+        global = true
+    )]
+    user_operation_cache_size: usize,
+
+    /// How long to cache eth_getUserOperationByHash results for operations that weren't found
+    #[arg(
+        long = "user_operation_cache_pending_ttl_millis",
+        name = "user_operation_cache_pending_ttl_millis",
+        env = "USER_OPERATION_CACHE_PENDING_TTL_MILLIS",
+        default_value = "1000",
+        global = true
+    )]
+    user_operation_cache_pending_ttl_millis: u64,
+
+    /// Function selectors of contracts known to wrap handleOps calls, e.g. multicalls,
+    /// whose calldata is searched by eth_getUserOperationByHash
+    ///
+    /// Defaults to Multicall aggregate, Multicall3 aggregate3 and multicall(bytes[])
+    #[arg(
+        long = "bundle_wrapper_selectors",
+        name = "bundle_wrapper_selectors",
+        env = "BUNDLE_WRAPPER_SELECTORS",
+        default_values_t = vec!["0x252dba42".to_string(), "0x82ad56cb".to_string(), "0xac9650d8".to_string()],
+        value_delimiter = ',',
+        global = true
+    )]
+    bundle_wrapper_selectors: Vec<String>,
+
+    /// Maximum number of operations the RPC server simulates or estimates at once
+    #[arg(
+        long = "max_concurrent_simulations",
+        name = "max_concurrent_simulations",
+        env = "MAX_CONCURRENT_SIMULATIONS",
+        default_value = "64",
+        global = true
+    )]
+    max_concurrent_simulations: usize,
+
+    /// How long a request waits to start simulating before failing as unavailable
+    #[arg(
+        long = "simulation_wait_timeout_millis",
+        name = "simulation_wait_timeout_millis",
+        env = "SIMULATION_WAIT_TIMEOUT_MILLIS",
+        default_value = "5000",
+        global = true
+    )]
+    simulation_wait_timeout_millis: u64,
+
+    /// Maximum time `eth_sendUserOperation` spends validating an operation and
+    /// adding it to the pool
+    #[arg(
+        long = "send_user_operation_deadline_millis",
+        name = "send_user_operation_deadline_millis",
+        env = "SEND_USER_OPERATION_DEADLINE_MILLIS",
+        default_value = "10000",
+        global = true
+    )]
+    send_user_operation_deadline_millis: u64,
+
     #[arg(
         long = "max_simulate_handle_ops_gas",
         name = "max_simulate_handle_ops_gas",
@@ -176,6 +341,56 @@ pub struct CommonArgs {
     )]
     max_simulate_handle_ops_gas: u64,
 
+    /// Forbidden opcodes to allow for specific entities
+    ///
+    /// Format: address1:OPCODE1,address2:OPCODE2,...
+    #[arg(
+        long = "allowed_opcodes",
+        name = "allowed_opcodes",
+        env = "ALLOWED_OPCODES",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        global = true
+    )]
+    allowed_opcodes: Vec<String>,
+
+    /// Storage slots that operations may not access during validation
+    ///
+    /// Format: address1:slot1,address2:slot2,...
+    #[arg(
+        long = "banned_storage_slots",
+        name = "banned_storage_slots",
+        env = "BANNED_STORAGE_SLOTS",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ',',
+        global = true
+    )]
+    banned_storage_slots: Vec<String>,
+
+    #[arg(
+        long = "estimation_call_gas_limit_percent",
+        name = "estimation_call_gas_limit_percent",
+        env = "ESTIMATION_CALL_GAS_LIMIT_PERCENT",
+        default_value = "100"
+    )]
+    estimation_call_gas_limit_percent: u64,
+
+    #[arg(
+        long = "estimation_verification_gas_limit_percent",
+        name = "estimation_verification_gas_limit_percent",
+        env = "ESTIMATION_VERIFICATION_GAS_LIMIT_PERCENT",
+        default_value = "100"
+    )]
+    estimation_verification_gas_limit_percent: u64,
+
+    #[arg(
+        long = "estimation_gas_limit_buffer",
+        name = "estimation_gas_limit_buffer",
+        env = "ESTIMATION_GAS_LIMIT_BUFFER",
+        default_value = "0"
+    )]
+    estimation_gas_limit_buffer: u64,
+
     #[arg(
         long = "use_bundle_priority_fee",
         name = "use_bundle_priority_fee",
@@ -216,6 +431,24 @@ pub struct CommonArgs {
     )]
     fee_accept_percent: u64,
 
+    #[arg(
+        long = "check_sender_funds",
+        name = "check_sender_funds",
+        env = "CHECK_SENDER_FUNDS",
+        default_value = "true",
+        action = ArgAction::Set
+    )]
+    check_sender_funds: bool,
+
+    #[arg(
+        long = "reject_unsupported_entry_point_calls",
+        name = "reject_unsupported_entry_point_calls",
+        env = "REJECT_UNSUPPORTED_ENTRY_POINT_CALLS",
+        default_value = "true",
+        action = ArgAction::Set
+    )]
+    reject_unsupported_entry_point_calls: bool,
+
     /// Interval at which the builder polls an Eth node for new blocks and
     /// mined transactions.
     #[arg(
@@ -274,11 +507,43 @@ impl TryFrom<&CommonArgs> for EstimationSettings {
                 value.max_simulate_handle_ops_gas,
             );
         }
-        Ok(Self {
+        let settings = Self {
             max_verification_gas: value.max_verification_gas,
+            gas_overheads: value.into(),
             max_call_gas,
             max_simulate_handle_ops_gas: value.max_simulate_handle_ops_gas,
-        })
+            margins: GasEstimateMargins {
+                call_gas_limit_percent: value.estimation_call_gas_limit_percent,
+                verification_gas_limit_percent: value.estimation_verification_gas_limit_percent,
+                gas_limit_buffer: value.estimation_gas_limit_buffer,
+            },
+        };
+        if let Some(err) = settings.validate() {
+            anyhow::bail!(err);
+        }
+        Ok(settings)
+    }
+}
+
+impl From<&CommonArgs> for GasOverheads {
+    fn from(value: &CommonArgs) -> Self {
+        let defaults = GasOverheads::for_chain(value.chain_id);
+        Self {
+            transaction_gas_overhead: value
+                .pvg_transaction_gas_overhead
+                .map_or(defaults.transaction_gas_overhead, Into::into),
+            per_user_op: value
+                .pvg_per_user_op
+                .map_or(defaults.per_user_op, Into::into),
+            per_user_op_word: value
+                .pvg_per_user_op_word
+                .map_or(defaults.per_user_op_word, Into::into),
+            zero_byte: value.pvg_zero_byte.map_or(defaults.zero_byte, Into::into),
+            non_zero_byte: value
+                .pvg_non_zero_byte
+                .map_or(defaults.non_zero_byte, Into::into),
+            ..defaults
+        }
     }
 }
 
@@ -288,6 +553,7 @@ impl TryFrom<&CommonArgs> for PrecheckSettings {
     fn try_from(value: &CommonArgs) -> anyhow::Result<Self> {
         Ok(Self {
             chain_id: value.chain_id,
+            gas_overheads: value.into(),
             max_verification_gas: value.max_verification_gas.into(),
             max_total_execution_gas: value.max_bundle_gas.into(),
             use_bundle_priority_fee: value.use_bundle_priority_fee,
@@ -297,24 +563,109 @@ impl TryFrom<&CommonArgs> for PrecheckSettings {
                 value.priority_fee_mode_value,
             )?,
             fee_accept_percent: value.fee_accept_percent,
+            check_sender_funds: value.check_sender_funds,
+            reject_unsupported_entry_point_calls: value.reject_unsupported_entry_point_calls,
         })
     }
 }
 
-impl From<&CommonArgs> for SimulationSettings {
-    fn from(value: &CommonArgs) -> Self {
-        Self::new(
+impl TryFrom<&CommonArgs> for SimulationSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &CommonArgs) -> Result<Self, Self::Error> {
+        let allowed_opcodes = value
+            .allowed_opcodes
+            .iter()
+            .map(|entry| {
+                let (address, opcode) = entry
+                    .split_once(':')
+                    .context("allowed opcode should be formatted as address:OPCODE")?;
+                Ok((
+                    address
+                        .parse()
+                        .context("allowed opcode address should parse")?,
+                    opcode
+                        .parse()
+                        .with_context(|| format!("unknown opcode {opcode}"))?,
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let banned_storage_slots = value
+            .banned_storage_slots
+            .iter()
+            .map(|entry| {
+                let (address, slot) = entry
+                    .split_once(':')
+                    .context("banned storage slot should be formatted as address:slot")?;
+                Ok((
+                    address
+                        .parse()
+                        .context("banned storage slot address should parse")?,
+                    slot.parse()
+                        .with_context(|| format!("invalid storage slot {slot}"))?,
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self::new(
             value.min_unstake_delay,
             value.min_stake_value,
             value.max_simulate_handle_ops_gas,
             value.max_verification_gas,
-        )
+            allowed_opcodes,
+            banned_storage_slots,
+        ))
     }
 }
 
-impl From<&CommonArgs> for EthApiSettings {
-    fn from(value: &CommonArgs) -> Self {
-        Self::new(value.user_operation_event_block_distance)
+impl TryFrom<&CommonArgs> for EthApiSettings {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &CommonArgs) -> Result<Self, Self::Error> {
+        let bundle_wrapper_selectors = value
+            .bundle_wrapper_selectors
+            .iter()
+            .map(|selector| {
+                let bytes: Bytes = selector
+                    .parse()
+                    .with_context(|| format!("invalid bundle wrapper selector {selector}"))?;
+                Selector::try_from(bytes.as_ref()).with_context(|| {
+                    format!("bundle wrapper selector {selector} should be 4 bytes")
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        if value.max_concurrent_simulations == 0 {
+            anyhow::bail!("max_concurrent_simulations must be at least 1");
+        }
+        if value.user_operation_event_historical_fallback
+            && value.user_operation_event_block_page_size.is_none()
+        {
+            anyhow::bail!(
+                "user_operation_event_historical_fallback requires user_operation_event_block_page_size"
+            );
+        }
+
+        Ok(Self {
+            user_operation_event_block_distance: value.user_operation_event_block_distance,
+            user_operation_event_address_batch_size: value.user_operation_event_address_batch_size,
+            user_operation_event_block_page_size: value.user_operation_event_block_page_size,
+            user_operation_event_historical_fallback: value
+                .user_operation_event_historical_fallback,
+            user_operation_event_historical_max_depth: value
+                .user_operation_event_historical_max_depth,
+            user_operation_indexer_url: value.user_operation_indexer_url.clone(),
+            user_operation_cache_size: value.user_operation_cache_size,
+            user_operation_cache_pending_ttl: Duration::from_millis(
+                value.user_operation_cache_pending_ttl_millis,
+            ),
+            bundle_wrapper_selectors,
+            max_concurrent_simulations: value.max_concurrent_simulations,
+            simulation_wait_timeout: Duration::from_millis(value.simulation_wait_timeout_millis),
+            send_user_operation_deadline: Duration::from_millis(
+                value.send_user_operation_deadline_millis,
+            ),
+        })
     }
 }
 
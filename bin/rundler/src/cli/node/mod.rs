@@ -30,6 +30,7 @@ mod events;
 
 const REQUEST_CHANNEL_CAPACITY: usize = 1024;
 const BLOCK_CHANNEL_CAPACITY: usize = 1024;
+const MEMPOOL_EVENT_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Debug, Args)]
 pub struct NodeCliArgs {
@@ -57,6 +58,7 @@ pub async fn run(bundler_args: NodeCliArgs, common_args: CommonArgs) -> anyhow::
         (&common_args).try_into()?,
         (&common_args).try_into()?,
         (&common_args).try_into()?,
+        (&common_args).try_into()?,
     )?;
 
     let (event_sender, event_rx) =
@@ -82,7 +84,11 @@ pub async fn run(bundler_args: NodeCliArgs, common_args: CommonArgs) -> anyhow::
         }
     });
 
-    let pool_builder = LocalPoolBuilder::new(REQUEST_CHANNEL_CAPACITY, BLOCK_CHANNEL_CAPACITY);
+    let pool_builder = LocalPoolBuilder::new(
+        REQUEST_CHANNEL_CAPACITY,
+        BLOCK_CHANNEL_CAPACITY,
+        MEMPOOL_EVENT_CHANNEL_CAPACITY,
+    );
     let pool_handle = pool_builder.get_handle();
 
     let builder_builder = LocalBuilderBuilder::new(REQUEST_CHANNEL_CAPACITY);
@@ -11,14 +11,15 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, time::Duration};
 
 use anyhow::Context;
 use clap::Args;
 use ethers::types::{Chain, H256};
-use rundler_pool::{LocalPoolBuilder, PoolConfig, PoolTask, PoolTaskArgs};
-use rundler_sim::MempoolConfig;
+use rundler_pool::{LocalPoolBuilder, PoolConfig, PoolTask, PoolTaskArgs, ReputationParams};
+use rundler_sim::{MempoolConfig, PrecheckSettings};
 use rundler_task::spawn_tasks_with_shutdown;
+use rundler_types::Entity;
 use rundler_utils::emit::{self, EVENT_CHANNEL_CAPACITY};
 use tokio::sync::broadcast;
 
@@ -27,6 +28,7 @@ use crate::cli::json::get_json_config;
 
 const REQUEST_CHANNEL_CAPACITY: usize = 1024;
 const BLOCK_CHANNEL_CAPACITY: usize = 1024;
+const MEMPOOL_EVENT_CHANNEL_CAPACITY: usize = 1024;
 
 /// CLI options for the OP Pool
 #[derive(Args, Debug)]
@@ -58,6 +60,14 @@ pub struct PoolArgs {
     )]
     pub max_size_in_bytes: usize,
 
+    #[arg(
+        long = "pool.max_pool_size",
+        name = "pool.max_pool_size",
+        env = "POOL_MAX_POOL_SIZE",
+        default_value = "100000"
+    )]
+    pub max_pool_size: usize,
+
     #[arg(
         long = "pool.max_userops_per_sender",
         name = "pool.max_userops_per_sender",
@@ -74,6 +84,88 @@ pub struct PoolArgs {
     )]
     pub min_replacement_fee_increase_percentage: u64,
 
+    #[arg(
+        long = "pool.min_priority_fee_per_gas",
+        name = "pool.min_priority_fee_per_gas",
+        env = "POOL_MIN_PRIORITY_FEE_PER_GAS",
+        default_value = "0"
+    )]
+    pub min_priority_fee_per_gas: u128,
+
+    #[arg(
+        long = "pool.min_max_fee_per_gas",
+        name = "pool.min_max_fee_per_gas",
+        env = "POOL_MIN_MAX_FEE_PER_GAS",
+        default_value = "0"
+    )]
+    pub min_max_fee_per_gas: u128,
+
+    #[arg(
+        long = "pool.max_user_operation_gas",
+        name = "pool.max_user_operation_gas",
+        env = "POOL_MAX_USER_OPERATION_GAS"
+    )]
+    pub max_user_operation_gas: Option<u64>,
+
+    #[arg(
+        long = "pool.max_op_size_bytes",
+        name = "pool.max_op_size_bytes",
+        env = "POOL_MAX_OP_SIZE_BYTES",
+        default_value = "131072"
+    )]
+    pub max_op_size_bytes: usize,
+
+    /// Number of pool processes that user operations are split between, by sender.
+    #[arg(
+        long = "pool.shard_count",
+        name = "pool.shard_count",
+        env = "POOL_SHARD_COUNT",
+        default_value = "1"
+    )]
+    pub shard_count: u64,
+
+    /// Index of the shard, out of `pool.shard_count`, served by this pool.
+    #[arg(
+        long = "pool.shard_index",
+        name = "pool.shard_index",
+        env = "POOL_SHARD_INDEX",
+        default_value = "0"
+    )]
+    pub shard_index: u64,
+
+    /// Maximum number of ops admitted per entity within a rolling window.
+    ///
+    /// Format: type:address:count:seconds,...
+    #[arg(
+        long = "pool.rate_limits",
+        name = "pool.rate_limits",
+        env = "POOL_RATE_LIMITS",
+        default_values_t = Vec::<String>::new(),
+        value_delimiter = ','
+    )]
+    pub rate_limits: Vec<String>,
+
+    #[arg(
+        long = "pool.revalidate_before_bundle",
+        name = "pool.revalidate_before_bundle",
+        env = "POOL_REVALIDATE_BEFORE_BUNDLE"
+    )]
+    pub revalidate_before_bundle: bool,
+
+    #[arg(
+        long = "pool.require_stake_for_gossip",
+        name = "pool.require_stake_for_gossip",
+        env = "POOL_REQUIRE_STAKE_FOR_GOSSIP"
+    )]
+    pub require_stake_for_gossip: bool,
+
+    #[arg(
+        long = "pool.revalidate_reorged_ops",
+        name = "pool.revalidate_reorged_ops",
+        env = "POOL_REVALIDATE_REORGED_OPS"
+    )]
+    pub revalidate_reorged_ops: bool,
+
     #[arg(
         long = "pool.blocklist_path",
         name = "pool.blocklist_path",
@@ -102,6 +194,14 @@ pub struct PoolArgs {
     )]
     pub chain_update_channel_capacity: Option<usize>,
 
+    #[arg(
+        long = "pool.max_block_staleness_secs",
+        name = "pool.max_block_staleness_secs",
+        env = "POOL_MAX_BLOCK_STALENESS_SECS",
+        default_value = "60"
+    )]
+    pub max_block_staleness_secs: u64,
+
     #[arg(
         long = "pool.throttled_entity_mempool_count",
         name = "pool.throttled_entity_mempool_count",
@@ -117,6 +217,53 @@ pub struct PoolArgs {
         default_value = "4"
     )]
     pub throttled_entity_live_blocks: u64,
+
+    #[arg(
+        long = "pool.reputation_min_inclusion_rate_denominator",
+        name = "pool.reputation_min_inclusion_rate_denominator",
+        env = "POOL_REPUTATION_MIN_INCLUSION_RATE_DENOMINATOR",
+        default_value = "10"
+    )]
+    pub reputation_min_inclusion_rate_denominator: u64,
+
+    #[arg(
+        long = "pool.reputation_throttling_slack",
+        name = "pool.reputation_throttling_slack",
+        env = "POOL_REPUTATION_THROTTLING_SLACK",
+        default_value = "10"
+    )]
+    pub reputation_throttling_slack: u64,
+
+    #[arg(
+        long = "pool.reputation_ban_slack",
+        name = "pool.reputation_ban_slack",
+        env = "POOL_REPUTATION_BAN_SLACK",
+        default_value = "50"
+    )]
+    pub reputation_ban_slack: u64,
+
+    #[arg(
+        long = "pool.reputation_decay_interval_secs",
+        name = "pool.reputation_decay_interval_secs",
+        env = "POOL_REPUTATION_DECAY_INTERVAL_SECS",
+        default_value = "3600"
+    )]
+    pub reputation_decay_interval_secs: u64,
+
+    #[arg(
+        long = "pool.reputation_decay_denominator",
+        name = "pool.reputation_decay_denominator",
+        env = "POOL_REPUTATION_DECAY_DENOMINATOR",
+        default_value = "24"
+    )]
+    pub reputation_decay_denominator: u64,
+
+    #[arg(
+        long = "pool.reputation_persistence_dir",
+        name = "pool.reputation_persistence_dir",
+        env = "POOL_REPUTATION_PERSISTENCE_DIR"
+    )]
+    pub reputation_persistence_dir: Option<String>,
 }
 
 impl PoolArgs {
@@ -146,6 +293,65 @@ impl PoolArgs {
         };
         tracing::info!("Mempool channel configs: {:?}", mempool_channel_configs);
 
+        if self.reputation_min_inclusion_rate_denominator == 0 {
+            anyhow::bail!("pool.reputation_min_inclusion_rate_denominator must be at least 1");
+        }
+        if self.reputation_decay_interval_secs == 0 {
+            anyhow::bail!("pool.reputation_decay_interval_secs must be at least 1");
+        }
+        if self.max_block_staleness_secs == 0 {
+            anyhow::bail!("pool.max_block_staleness_secs must be at least 1");
+        }
+        if self.reputation_decay_denominator == 0 {
+            anyhow::bail!("pool.reputation_decay_denominator must be at least 1");
+        }
+        let reputation_params = ReputationParams {
+            min_inclusion_rate_denominator: self.reputation_min_inclusion_rate_denominator,
+            throttling_slack: self.reputation_throttling_slack,
+            ban_slack: self.reputation_ban_slack,
+            decay_interval: Duration::from_secs(self.reputation_decay_interval_secs),
+            decay_denominator: self.reputation_decay_denominator,
+            ..ReputationParams::bundler_default()
+        };
+
+        let rate_limits = self
+            .rate_limits
+            .iter()
+            .map(|entry| {
+                let parts = entry.split(':').collect::<Vec<_>>();
+                let [kind, address, count, seconds] = parts[..] else {
+                    anyhow::bail!(
+                        "rate limit {entry} should be formatted as type:address:count:seconds"
+                    );
+                };
+                let entity = Entity::new(
+                    kind.parse()?,
+                    address
+                        .parse()
+                        .with_context(|| format!("invalid rate limit address {address}"))?,
+                );
+                let count = count
+                    .parse()
+                    .with_context(|| format!("invalid rate limit count {count}"))?;
+                let seconds = seconds
+                    .parse()
+                    .with_context(|| format!("invalid rate limit window {seconds}"))?;
+                Ok((entity, (count, Duration::from_secs(seconds))))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        tracing::info!("Rate limits: {:?}", rate_limits);
+
+        if self.shard_count == 0 {
+            anyhow::bail!("pool.shard_count must be at least 1");
+        }
+        if self.shard_index >= self.shard_count {
+            anyhow::bail!(
+                "pool.shard_index {} must be less than pool.shard_count {}",
+                self.shard_index,
+                self.shard_count
+            );
+        }
+
         let pool_configs = common
             .entry_points
             .iter()
@@ -156,17 +362,49 @@ impl PoolArgs {
                     chain_id: common.chain_id,
                     // Currently use the same shard count as the number of builders
                     num_shards: common.num_builders,
+                    pool_shard_count: self.shard_count,
+                    pool_shard_index: self.shard_index,
                     max_userops_per_sender: self.max_userops_per_sender,
                     min_replacement_fee_increase_percentage: self
                         .min_replacement_fee_increase_percentage,
                     max_size_of_pool_bytes: self.max_size_in_bytes,
+                    max_pool_size: self.max_pool_size,
+                    min_priority_fee_per_gas: self.min_priority_fee_per_gas.into(),
+                    min_max_fee_per_gas: self.min_max_fee_per_gas.into(),
+                    max_op_size_bytes: self.max_op_size_bytes,
+                    max_bundle_gas: common.max_bundle_gas,
                     blocklist: blocklist.clone(),
                     allowlist: allowlist.clone(),
-                    precheck_settings: common.try_into()?,
+                    precheck_settings: PrecheckSettings {
+                        max_total_execution_gas: self
+                            .max_user_operation_gas
+                            .unwrap_or(common.max_bundle_gas)
+                            .into(),
+                        ..common.try_into()?
+                    },
                     sim_settings: common.try_into()?,
                     mempool_channel_configs: mempool_channel_configs.clone(),
                     throttled_entity_mempool_count: self.throttled_entity_mempool_count,
                     throttled_entity_live_blocks: self.throttled_entity_live_blocks,
+                    reputation_params,
+                    reputation_persistence_path: self.reputation_persistence_dir.as_ref().map(
+                        |dir| {
+                            // pool shards may share a directory, but not their reputation
+                            let file_name = if self.shard_count > 1 {
+                                format!(
+                                    "reputation_{entry_point:?}_shard_{}.json",
+                                    self.shard_index
+                                )
+                            } else {
+                                format!("reputation_{entry_point:?}.json")
+                            };
+                            PathBuf::from(dir).join(file_name)
+                        },
+                    ),
+                    rate_limits: rate_limits.clone(),
+                    revalidate_before_bundle: self.revalidate_before_bundle,
+                    require_stake_for_gossip: self.require_stake_for_gossip,
+                    revalidate_reorged_ops: self.revalidate_reorged_ops,
                 })
             })
             .collect::<anyhow::Result<Vec<PoolConfig>>>()?;
@@ -183,6 +421,7 @@ impl PoolArgs {
             http_poll_interval: Duration::from_millis(common.eth_poll_interval_millis),
             pool_configs,
             remote_address,
+            max_block_staleness: Duration::from_secs(self.max_block_staleness_secs),
             chain_update_channel_capacity: self.chain_update_channel_capacity.unwrap_or(1024),
         })
     }
@@ -230,7 +469,11 @@ pub async fn run(pool_args: PoolCliArgs, common_args: CommonArgs) -> anyhow::Res
         [PoolTask::new(
             task_args,
             event_sender,
-            LocalPoolBuilder::new(REQUEST_CHANNEL_CAPACITY, BLOCK_CHANNEL_CAPACITY),
+            LocalPoolBuilder::new(
+                REQUEST_CHANNEL_CAPACITY,
+                BLOCK_CHANNEL_CAPACITY,
+                MEMPOOL_EVENT_CHANNEL_CAPACITY,
+            ),
         )
         .boxed()],
         tokio::signal::ctrl_c(),
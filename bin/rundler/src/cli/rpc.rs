@@ -16,9 +16,9 @@ use std::time::Duration;
 use anyhow::Context;
 use clap::Args;
 use rundler_builder::RemoteBuilderClient;
-use rundler_pool::RemotePoolClient;
+use rundler_pool::ShardedPoolClient;
 use rundler_rpc::{EthApiSettings, RpcTask, RpcTaskArgs};
-use rundler_sim::{EstimationSettings, PrecheckSettings};
+use rundler_sim::{EstimationSettings, PrecheckSettings, SimulationSettings};
 use rundler_task::{server::connect_with_retries_shutdown, spawn_tasks_with_shutdown};
 
 use super::CommonArgs;
@@ -83,6 +83,7 @@ impl RpcArgs {
         &self,
         common: &CommonArgs,
         precheck_settings: PrecheckSettings,
+        sim_settings: SimulationSettings,
         eth_api_settings: EthApiSettings,
         estimation_settings: EstimationSettings,
     ) -> anyhow::Result<RpcTaskArgs> {
@@ -108,6 +109,7 @@ impl RpcArgs {
             chain_id: common.chain_id,
             api_namespaces: apis,
             precheck_settings,
+            sim_settings,
             eth_api_settings,
             estimation_settings,
             rpc_timeout: Duration::from_secs(self.timeout_seconds.parse()?),
@@ -151,14 +153,15 @@ pub async fn run(rpc_args: RpcCliArgs, common_args: CommonArgs) -> anyhow::Resul
     let task_args = rpc_args.to_args(
         &common_args,
         (&common_args).try_into()?,
-        (&common_args).into(),
+        (&common_args).try_into()?,
+        (&common_args).try_into()?,
         (&common_args).try_into()?,
     )?;
 
     let pool = connect_with_retries_shutdown(
         "op pool from rpc",
         &pool_url,
-        RemotePoolClient::connect,
+        ShardedPoolClient::connect,
         tokio::signal::ctrl_c(),
     )
     .await?;
@@ -27,7 +27,7 @@ use futures_util::TryFutureExt;
 use linked_hash_map::LinkedHashMap;
 #[cfg(test)]
 use mockall::automock;
-use rundler_pool::{PoolOperation, PoolServer};
+use rundler_pool::{GetOpsFilter, PoolOperation, PoolServer};
 use rundler_provider::{EntryPoint, HandleOpsOut, Provider};
 use rundler_sim::{
     gas::{self, GasOverheads},
@@ -99,6 +99,7 @@ where
 #[derive(Debug)]
 pub(crate) struct Settings {
     pub(crate) chain_id: u64,
+    pub(crate) gas_overheads: GasOverheads,
     pub(crate) max_bundle_size: u64,
     pub(crate) max_bundle_gas: u64,
     pub(crate) beneficiary: Address,
@@ -287,7 +288,7 @@ where
         let mut rejected_ops = Vec::<UserOperation>::new();
         let mut paymasters_to_reject = Vec::<Address>::new();
 
-        let ov = GasOverheads::default();
+        let ov = self.settings.gas_overheads;
         let mut gas_spent = ov.transaction_gas_overhead;
         for (op, simulation) in ops_with_simulations {
             let simulation = match simulation {
@@ -365,6 +366,7 @@ where
             gas_spent += gas::user_operation_execution_gas_limit(
                 &op,
                 self.settings.chain_id,
+                &ov,
                 false,
                 simulation.requires_post_op,
             );
@@ -438,7 +440,7 @@ where
         // sum up the gas needed for all the ops in the bundle
         // and apply an overhead multiplier
         let gas = math::increase_by_percent(
-            context.get_bundle_gas_limit(self.settings.chain_id),
+            context.get_bundle_gas_limit(self.settings.chain_id, self.settings.gas_overheads),
             BUNDLE_TRANSACTION_GAS_OVERHEAD_PERCENT,
         );
         let handle_ops_out = self
@@ -483,6 +485,7 @@ where
                 self.entry_point.address(),
                 self.settings.max_bundle_size,
                 self.builder_index,
+                GetOpsFilter::default(),
             )
             .await
             .context("should get ops from pool")
@@ -574,6 +577,7 @@ where
             let gas = gas::user_operation_execution_gas_limit(
                 &op.uo,
                 self.settings.chain_id,
+                &self.settings.gas_overheads,
                 false,
                 false,
             );
@@ -776,8 +780,7 @@ impl ProposalContext {
             .collect()
     }
 
-    fn get_bundle_gas_limit(&self, chain_id: u64) -> U256 {
-        let ov = GasOverheads::default();
+    fn get_bundle_gas_limit(&self, chain_id: u64, ov: GasOverheads) -> U256 {
         let mut gas_spent = ov.transaction_gas_overhead;
         let mut max_gas = U256::zero();
         for op_with_sim in self.iter_ops_with_simulations() {
@@ -793,6 +796,7 @@ impl ProposalContext {
             gas_spent += gas::user_operation_gas_limit(
                 op,
                 chain_id,
+                &ov,
                 false,
                 op_with_sim.simulation.requires_post_op,
             );
@@ -825,7 +829,7 @@ fn get_gas_required_for_op(
     };
 
     gas_spent
-        + gas::user_operation_pre_verification_gas_limit(op, chain_id, false)
+        + gas::user_operation_pre_verification_gas_limit(op, chain_id, &ov, false)
         + op.verification_gas_limit * 2
         + op.call_gas_limit
         + post_exec_req_gas
@@ -1324,7 +1328,10 @@ mod tests {
             + 5_000
             + 21_000;
 
-        assert_eq!(context.get_bundle_gas_limit(chain_id), expected_gas_limit);
+        assert_eq!(
+            context.get_bundle_gas_limit(chain_id, GasOverheads::default()),
+            expected_gas_limit
+        );
     }
 
     #[tokio::test]
@@ -1360,7 +1367,7 @@ mod tests {
             rejected_ops: vec![],
             rejected_entities: vec![],
         };
-        let gas_limit = context.get_bundle_gas_limit(chain_id);
+        let gas_limit = context.get_bundle_gas_limit(chain_id, GasOverheads::default());
 
         // The gas requirement from the execution of the first UO is: g >= p_1 + 3v_1 + c_1
         // The gas requirement from the execution of the second UO is: g >= p_1 + 3v_1 + c_1 + p_2 + 2v_2 + c_2 + 5000
@@ -1425,7 +1432,7 @@ mod tests {
         let mut pool_client = MockPoolServer::new();
         pool_client
             .expect_get_ops()
-            .returning(move |_, _, _| Ok(ops.clone()));
+            .returning(move |_, _, _, _| Ok(ops.clone()));
 
         let simulations_by_op: HashMap<_, _> = mock_ops
             .into_iter()
@@ -1482,6 +1489,7 @@ mod tests {
             Arc::new(provider),
             Settings {
                 chain_id: 0,
+                gas_overheads: GasOverheads::default(),
                 max_bundle_size,
                 max_bundle_gas: 10_000_000,
                 beneficiary,
@@ -20,7 +20,7 @@ use anyhow::{bail, Context};
 use async_trait::async_trait;
 use ethers::types::{transaction::eip2718::TypedTransaction, Address, H256, U256};
 use futures_util::StreamExt;
-use rundler_pool::PoolServer;
+use rundler_pool::{PoolServer, RemovalReason};
 use rundler_provider::EntryPoint;
 use rundler_sim::ExpectedStorage;
 use rundler_types::{Entity, GasFees, UserOperation};
@@ -134,6 +134,10 @@ where
             }
         });
 
+        // Set once the debug request channel closes, after which auto mode
+        // keeps bundling on new blocks without listening for requests.
+        let mut send_bundle_closed = false;
+
         loop {
             let mut send_bundle_response: Option<oneshot::Sender<SendBundleResult>> = None;
             let mut last_block = None;
@@ -146,28 +150,41 @@ where
                     bail!("Bundle stream closed in manual mode");
                 }
             } else {
-                // Wait for new block. Block number doesn't matter as the pool will only notify of new blocks
-                // after the pool has updated its state. The bundle will be formed using the latest pool state
-                // and can land in the next block
-                last_block = rx.recv().await;
-
-                if last_block.is_none() {
-                    error!("Block stream closed");
-                    bail!("Block stream closed");
-                }
-                // Consume any other blocks that may have been buffered up
-                loop {
-                    match rx.try_recv() {
-                        Ok(b) => {
-                            last_block = Some(b);
-                        }
-                        Err(mpsc::error::TryRecvError::Empty) => {
-                            break;
-                        }
-                        Err(mpsc::error::TryRecvError::Disconnected) => {
+                // Wait for new block, or for a request to bundle immediately. Block number doesn't
+                // matter as the pool will only notify of new blocks after the pool has updated its
+                // state. The bundle will be formed using the latest pool state and can land in the
+                // next block
+                tokio::select! {
+                    r = self.send_bundle_receiver.recv(), if !send_bundle_closed => {
+                        let Some(r) = r else {
+                            warn!("Bundle request stream closed, continuing to bundle on new blocks");
+                            send_bundle_closed = true;
+                            continue;
+                        };
+                        send_bundle_response = Some(r.responder);
+                    }
+                    b = rx.recv() => {
+                        last_block = b;
+
+                        if last_block.is_none() {
                             error!("Block stream closed");
                             bail!("Block stream closed");
                         }
+                        // Consume any other blocks that may have been buffered up
+                        loop {
+                            match rx.try_recv() {
+                                Ok(b) => {
+                                    last_block = Some(b);
+                                }
+                                Err(mpsc::error::TryRecvError::Empty) => {
+                                    break;
+                                }
+                                Err(mpsc::error::TryRecvError::Disconnected) => {
+                                    error!("Block stream closed");
+                                    bail!("Block stream closed");
+                                }
+                            }
+                        }
                     }
                 }
             }
@@ -494,6 +511,7 @@ where
                 ops.iter()
                     .map(|op| op.op_hash(self.entry_point.address(), self.chain_id))
                     .collect(),
+                RemovalReason::Invalidated,
             )
             .await
             .context("builder should remove rejected ops from pool")
@@ -29,6 +29,8 @@ mod sender;
 pub use sender::TransactionSenderType;
 
 mod server;
+#[cfg(feature = "test-utils")]
+pub use server::MockBuilderServer;
 pub use server::{
     BuilderResult, BuilderServer, BuilderServerError, BundlingMode, LocalBuilderBuilder,
     LocalBuilderHandle, RemoteBuilderClient,
@@ -114,7 +114,20 @@ impl BuilderServer for LocalBuilderHandle {
     }
 
     async fn debug_send_bundle_now(&self) -> BuilderResult<(H256, u64)> {
-        let req = ServerRequestKind::DebugSendBundleNow;
+        let req = ServerRequestKind::DebugSendBundleNow {
+            require_manual: true,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::DebugSendBundleNow { hash, block_number } => Ok((hash, block_number)),
+            _ => Err(BuilderServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn debug_bundle_now(&self) -> BuilderResult<(H256, u64)> {
+        let req = ServerRequestKind::DebugSendBundleNow {
+            require_manual: false,
+        };
         let resp = self.send(req).await?;
         match resp {
             ServerResponse::DebugSendBundleNow { hash, block_number } => Ok((hash, block_number)),
@@ -176,9 +189,11 @@ impl LocalBuilderServerRunner {
                                     entry_points: self.entry_points.clone()
                                 })
                             },
-                            ServerRequestKind::DebugSendBundleNow => {
-                                if !self.manual_bundling_mode.load(Ordering::Relaxed) {
+                            ServerRequestKind::DebugSendBundleNow { require_manual } => {
+                                if require_manual && !self.manual_bundling_mode.load(Ordering::Relaxed) {
                                     break 'a Err(anyhow::anyhow!("bundling mode is not manual").into())
+                                } else if self.send_bundle_requesters.is_empty() {
+                                    break 'a Err(anyhow::anyhow!("no bundle builder with a signer is configured").into())
                                 } else if self.send_bundle_requesters.len() != 1 {
                                     break 'a Err(anyhow::anyhow!("more than 1 bundle builder not supported in debug mode").into())
                                 }
@@ -229,7 +244,7 @@ impl LocalBuilderServerRunner {
 #[derive(Clone, Debug)]
 enum ServerRequestKind {
     GetSupportedEntryPoints,
-    DebugSendBundleNow,
+    DebugSendBundleNow { require_manual: bool },
     DebugSetBundlingMode { mode: BundlingMode },
 }
 
@@ -50,6 +50,12 @@ pub trait BuilderServer: Send + Sync + 'static {
     /// Bundling mode must be set to `Manual`, or this will error
     async fn debug_send_bundle_now(&self) -> BuilderResult<(H256, u64)>;
 
+    /// Trigger the builder to build and send a bundle immediately, in either
+    /// bundling mode, rather than waiting for the next block.
+    ///
+    /// Errors if no bundle builder or more than one bundle builder is running.
+    async fn debug_bundle_now(&self) -> BuilderResult<(H256, u64)>;
+
     /// Set the bundling mode
     async fn debug_set_bundling_mode(&self, mode: BundlingMode) -> BuilderResult<()>;
 }
@@ -65,6 +71,7 @@ pub enum BundlingMode {
     Manual,
     /// Auto bundling mode for normal operation.
     ///
-    /// Bundles will be sent automatically.
+    /// Bundles will be sent automatically on each new block, or immediately
+    /// when `debug_bundle_now` is called.
     Auto,
 }
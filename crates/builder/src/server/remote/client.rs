@@ -29,8 +29,8 @@ use tonic_health::{
 
 use super::protos::{
     builder_client::BuilderClient, debug_send_bundle_now_response,
-    debug_set_bundling_mode_response, BundlingMode as ProtoBundlingMode, DebugSendBundleNowRequest,
-    DebugSetBundlingModeRequest, GetSupportedEntryPointsRequest,
+    debug_set_bundling_mode_response, BundlingMode as ProtoBundlingMode, DebugBundleNowRequest,
+    DebugSendBundleNowRequest, DebugSetBundlingModeRequest, GetSupportedEntryPointsRequest,
 };
 use crate::server::{BuilderResult, BuilderServer, BuilderServerError, BundlingMode};
 
@@ -89,6 +89,26 @@ impl BuilderServer for RemoteBuilderClient {
         }
     }
 
+    async fn debug_bundle_now(&self) -> BuilderResult<(H256, u64)> {
+        let res = self
+            .grpc_client
+            .clone()
+            .debug_bundle_now(DebugBundleNowRequest {})
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(debug_send_bundle_now_response::Result::Success(s)) => {
+                Ok((H256::from_slice(&s.transaction_hash), s.block_number))
+            }
+            Some(debug_send_bundle_now_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(BuilderServerError::Other(anyhow::anyhow!(
+                "should have received result from builder"
+            )))?,
+        }
+    }
+
     async fn debug_set_bundling_mode(&self, mode: BundlingMode) -> BuilderResult<()> {
         let res = self
             .grpc_client
@@ -19,10 +19,10 @@ use tonic::{async_trait, transport::Server, Request, Response, Status};
 
 use super::protos::{
     builder_server::{Builder as GrpcBuilder, BuilderServer as GrpcBuilderServer},
-    debug_send_bundle_now_response, debug_set_bundling_mode_response, DebugSendBundleNowRequest,
-    DebugSendBundleNowResponse, DebugSetBundlingModeRequest, DebugSetBundlingModeResponse,
-    DebugSetBundlingModeSuccess, GetSupportedEntryPointsRequest, GetSupportedEntryPointsResponse,
-    BUILDER_FILE_DESCRIPTOR_SET,
+    debug_send_bundle_now_response, debug_set_bundling_mode_response, DebugBundleNowRequest,
+    DebugSendBundleNowRequest, DebugSendBundleNowResponse, DebugSetBundlingModeRequest,
+    DebugSetBundlingModeResponse, DebugSetBundlingModeSuccess, GetSupportedEntryPointsRequest,
+    GetSupportedEntryPointsResponse, BUILDER_FILE_DESCRIPTOR_SET,
 };
 use crate::server::{
     local::LocalBuilderHandle, remote::protos::DebugSendBundleNowSuccess, BuilderServer,
@@ -118,6 +118,27 @@ impl GrpcBuilder for GrpcBuilderServerImpl {
         Ok(Response::new(resp))
     }
 
+    async fn debug_bundle_now(
+        &self,
+        _request: Request<DebugBundleNowRequest>,
+    ) -> tonic::Result<Response<DebugSendBundleNowResponse>> {
+        let resp = match self.local_builder.debug_bundle_now().await {
+            Ok((hash, block_number)) => DebugSendBundleNowResponse {
+                result: Some(debug_send_bundle_now_response::Result::Success(
+                    DebugSendBundleNowSuccess {
+                        transaction_hash: hash.as_bytes().to_vec(),
+                        block_number,
+                    },
+                )),
+            },
+            Err(e) => {
+                return Err(Status::internal(format!("Failed to send bundle: {e}")));
+            }
+        };
+
+        Ok(Response::new(resp))
+    }
+
     async fn debug_set_bundling_mode(
         &self,
         request: Request<DebugSetBundlingModeRequest>,
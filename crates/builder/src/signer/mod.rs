@@ -12,6 +12,7 @@
 // If not, see https://www.gnu.org/licenses/.
 
 mod aws;
+mod remote;
 use std::sync::Arc;
 
 use anyhow::Context;
@@ -26,6 +27,7 @@ use ethers::{
     },
 };
 use ethers_signers::{AwsSignerError, LocalWallet, Signer, WalletError};
+pub(crate) use remote::*;
 use rundler_utils::handle::SpawnGuard;
 
 /// A local signer handle
@@ -73,11 +75,13 @@ pub(crate) async fn monitor_account_balance<M: Middleware>(addr: Address, provid
     }
 }
 
-/// A `Signer` which is backed by either a local signer or a KMS signer.
+/// A `Signer` which is backed by either a local signer, a KMS signer, or a remote
+/// JSON-RPC signer.
 #[derive(Debug)]
 pub(crate) enum BundlerSigner {
     Local(LocalSigner),
     Kms(KmsSigner),
+    Remote(RemoteSigner),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -86,6 +90,8 @@ pub(crate) enum BundlerSignerError {
     Local(#[from] WalletError),
     #[error(transparent)]
     Kms(#[from] AwsSignerError),
+    #[error(transparent)]
+    Remote(#[from] RemoteSignerError),
 }
 
 #[async_trait]
@@ -99,6 +105,7 @@ impl Signer for BundlerSigner {
         let out = match self {
             BundlerSigner::Local(s) => s.signer.sign_message(message).await?,
             BundlerSigner::Kms(s) => s.signer.sign_message(message).await?,
+            BundlerSigner::Remote(s) => s.sign_message(message.as_ref()).await?,
         };
         Ok(out)
     }
@@ -107,6 +114,7 @@ impl Signer for BundlerSigner {
         let out = match self {
             BundlerSigner::Local(s) => s.signer.sign_transaction(message).await?,
             BundlerSigner::Kms(s) => s.signer.sign_transaction(message).await?,
+            BundlerSigner::Remote(s) => s.sign_transaction(message).await?,
         };
         Ok(out)
     }
@@ -118,6 +126,7 @@ impl Signer for BundlerSigner {
         let out = match self {
             BundlerSigner::Local(s) => s.signer.sign_typed_data(payload).await?,
             BundlerSigner::Kms(s) => s.signer.sign_typed_data(payload).await?,
+            BundlerSigner::Remote(_) => Err(RemoteSignerError::Unsupported("typed data signing"))?,
         };
         Ok(out)
     }
@@ -126,6 +135,7 @@ impl Signer for BundlerSigner {
         match self {
             BundlerSigner::Local(s) => s.signer.address(),
             BundlerSigner::Kms(s) => s.signer.address(),
+            BundlerSigner::Remote(s) => s.address(),
         }
     }
 
@@ -133,6 +143,7 @@ impl Signer for BundlerSigner {
         match self {
             BundlerSigner::Local(s) => s.signer.chain_id(),
             BundlerSigner::Kms(s) => s.signer.chain_id(),
+            BundlerSigner::Remote(s) => s.chain_id(),
         }
     }
 
@@ -146,6 +157,10 @@ impl Signer for BundlerSigner {
                 s.signer = s.signer.with_chain_id(chain_id);
                 BundlerSigner::Kms(s)
             }
+            BundlerSigner::Remote(mut s) => {
+                s.set_chain_id(chain_id.into());
+                BundlerSigner::Remote(s)
+            }
         }
     }
 }
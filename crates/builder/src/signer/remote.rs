@@ -0,0 +1,264 @@
+// This file is part of Rundler.
+//
+// Rundler is free software: you can redistribute it and/or modify it under the
+// terms of the GNU Lesser General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// Rundler is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Rundler.
+// If not, see https://www.gnu.org/licenses/.
+
+use std::{fmt, sync::Arc};
+
+use anyhow::Context;
+use ethers::{
+    providers::Middleware,
+    types::{transaction::eip2718::TypedTransaction, Address, Bytes, Signature},
+    utils::rlp::Rlp,
+};
+use jsonrpsee::{
+    core::client::ClientT,
+    http_client::{transport::HttpBackend, HttpClient, HttpClientBuilder},
+    rpc_params,
+};
+use rundler_utils::handle::SpawnGuard;
+use serde::Deserialize;
+
+use super::monitor_account_balance;
+
+/// A signer backed by a remote JSON-RPC signing service, such as Clef or Web3Signer.
+///
+/// Keys never leave the remote service: transactions are signed with
+/// `eth_signTransaction` and messages with `eth_sign`.
+pub(crate) struct RemoteSigner {
+    client: HttpClient<HttpBackend>,
+    address: Address,
+    chain_id: u64,
+    _monitor_guard: SpawnGuard,
+}
+
+impl fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteSigner")
+            .field("address", &self.address)
+            .field("chain_id", &self.chain_id)
+            .finish()
+    }
+}
+
+/// Error type for the remote signer
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum RemoteSignerError {
+    /// The remote signer returned an error or could not be reached
+    #[error(transparent)]
+    Rpc(#[from] jsonrpsee::core::Error),
+    /// The remote signer returned a response that couldn't be decoded
+    #[error("invalid response from remote signer: {0}")]
+    InvalidResponse(String),
+    /// The remote signer signed something other than what was requested, or
+    /// signed it with another key
+    #[error("remote signer returned a mismatched signature: {0}")]
+    Mismatch(String),
+    /// The remote signer doesn't support the requested operation
+    #[error("remote signer does not support {0}")]
+    Unsupported(&'static str),
+}
+
+/// Response to `eth_signTransaction`. Web3Signer returns the raw signed
+/// transaction, while Clef and Geth return it alongside the decoded transaction.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SignTransactionResponse {
+    Raw(Bytes),
+    WithTx { raw: Bytes },
+}
+
+impl SignTransactionResponse {
+    fn raw(&self) -> &Bytes {
+        match self {
+            Self::Raw(raw) | Self::WithTx { raw } => raw,
+        }
+    }
+}
+
+impl RemoteSigner {
+    /// Connects to the remote signer at `url`, signing with the first account it reports.
+    pub(crate) async fn connect<M: Middleware + 'static>(
+        provider: Arc<M>,
+        chain_id: u64,
+        url: String,
+    ) -> anyhow::Result<Self> {
+        let client = HttpClientBuilder::default()
+            .build(url)
+            .context("should create remote signer client")?;
+        let accounts: Vec<Address> = client
+            .request("eth_accounts", rpc_params![])
+            .await
+            .context("should fetch remote signer accounts")?;
+        let address = *accounts
+            .first()
+            .context("remote signer should have at least one account")?;
+
+        let _monitor_guard =
+            SpawnGuard::spawn_with_guard(monitor_account_balance(address, Arc::clone(&provider)));
+
+        Ok(Self {
+            client,
+            address,
+            chain_id,
+            _monitor_guard,
+        })
+    }
+
+    pub(crate) fn address(&self) -> Address {
+        self.address
+    }
+
+    pub(crate) fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    pub(crate) fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+    }
+
+    pub(crate) async fn sign_message(
+        &self,
+        message: &[u8],
+    ) -> Result<Signature, RemoteSignerError> {
+        let signature: Bytes = self
+            .client
+            .request(
+                "eth_sign",
+                rpc_params![self.address, Bytes::from(message.to_vec())],
+            )
+            .await?;
+        Signature::try_from(signature.as_ref())
+            .map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))
+    }
+
+    pub(crate) async fn sign_transaction(
+        &self,
+        tx: &TypedTransaction,
+    ) -> Result<Signature, RemoteSignerError> {
+        let mut tx = tx.clone();
+        tx.set_from(self.address);
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+
+        let response: SignTransactionResponse = self
+            .client
+            .request("eth_signTransaction", rpc_params![&tx])
+            .await?;
+        verify_signed_transaction(&tx, self.address, response.raw())
+    }
+}
+
+/// Extracts the signature from a raw signed transaction, checking that the
+/// signed transaction is `tx` and that it was signed by `signer`.
+fn verify_signed_transaction(
+    tx: &TypedTransaction,
+    signer: Address,
+    raw_tx: &Bytes,
+) -> Result<Signature, RemoteSignerError> {
+    let (signed_tx, signature) = TypedTransaction::decode_signed(&Rlp::new(raw_tx))
+        .map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))?;
+    let sighash = signed_tx.sighash();
+    if sighash != tx.sighash() {
+        return Err(RemoteSignerError::Mismatch(format!(
+            "signed transaction {sighash:?} differs from requested transaction {:?}",
+            tx.sighash()
+        )));
+    }
+    let recovered = signature
+        .recover(sighash)
+        .map_err(|e| RemoteSignerError::InvalidResponse(e.to_string()))?;
+    if recovered != signer {
+        return Err(RemoteSignerError::Mismatch(format!(
+            "transaction signed by {recovered:?} instead of {signer:?}"
+        )));
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Eip1559TransactionRequest;
+    use ethers_signers::{LocalWallet, Signer};
+
+    use super::*;
+
+    fn wallet() -> LocalWallet {
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(1_u64)
+    }
+
+    fn tx(nonce: u64) -> TypedTransaction {
+        Eip1559TransactionRequest::new()
+            .to(Address::zero())
+            .nonce(nonce)
+            .gas(21_000)
+            .max_fee_per_gas(100)
+            .max_priority_fee_per_gas(10)
+            .chain_id(1)
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_verify_signed_transaction() {
+        let wallet = wallet();
+        let tx = tx(1);
+        let signature = wallet.sign_transaction(&tx).await.unwrap();
+
+        let verified =
+            verify_signed_transaction(&tx, wallet.address(), &tx.rlp_signed(&signature)).unwrap();
+        assert_eq!(verified, signature);
+    }
+
+    #[tokio::test]
+    async fn test_verify_signed_transaction_different_tx() {
+        let wallet = wallet();
+        let signed_tx = tx(2);
+        let signature = wallet.sign_transaction(&signed_tx).await.unwrap();
+
+        let err =
+            verify_signed_transaction(&tx(1), wallet.address(), &signed_tx.rlp_signed(&signature))
+                .unwrap_err();
+        assert!(matches!(err, RemoteSignerError::Mismatch(_)));
+    }
+
+    #[tokio::test]
+    async fn test_verify_signed_transaction_different_signer() {
+        let wallet = wallet();
+        let tx = tx(1);
+        let signature = wallet.sign_transaction(&tx).await.unwrap();
+
+        let err = verify_signed_transaction(&tx, Address::random(), &tx.rlp_signed(&signature))
+            .unwrap_err();
+        assert!(matches!(err, RemoteSignerError::Mismatch(_)));
+    }
+
+    #[test]
+    fn test_verify_signed_transaction_invalid() {
+        let err = verify_signed_transaction(&tx(1), Address::random(), &Bytes::from(vec![1, 2, 3]))
+            .unwrap_err();
+        assert!(matches!(err, RemoteSignerError::InvalidResponse(_)));
+    }
+
+    #[test]
+    fn test_sign_transaction_response() {
+        let raw: SignTransactionResponse = serde_json::from_str(r#""0x0102""#).unwrap();
+        assert_eq!(raw.raw(), &Bytes::from(vec![1, 2]));
+
+        // Clef and Geth also return the decoded transaction
+        let with_tx: SignTransactionResponse =
+            serde_json::from_str(r#"{"raw":"0x0102","tx":{"nonce":"0x1"}}"#).unwrap();
+        assert_eq!(with_tx.raw(), &Bytes::from(vec![1, 2]));
+    }
+}
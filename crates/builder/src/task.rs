@@ -29,7 +29,8 @@ use futures::future;
 use futures_util::TryFutureExt;
 use rundler_pool::PoolServer;
 use rundler_sim::{
-    MempoolConfig, PriorityFeeMode, SimulateValidationTracerImpl, SimulationSettings, SimulatorImpl,
+    gas::GasOverheads, MempoolConfig, PriorityFeeMode, SimulateValidationTracerImpl,
+    SimulationSettings, SimulatorImpl,
 };
 use rundler_task::Task;
 use rundler_types::contracts::i_entry_point::IEntryPoint;
@@ -49,7 +50,7 @@ use crate::{
     emit::BuilderEvent,
     sender::TransactionSenderType,
     server::{spawn_remote_builder_server, LocalBuilderBuilder},
-    signer::{BundlerSigner, KmsSigner, LocalSigner},
+    signer::{BundlerSigner, KmsSigner, LocalSigner, RemoteSigner},
     transaction_tracker::{self, TransactionTrackerImpl},
 };
 
@@ -61,10 +62,13 @@ pub struct Args {
     /// Address of the entry point contract this builder targets
     pub entry_point_address: Address,
     /// Private key to use for signing transactions
-    /// If not provided, AWS KMS will be used
+    /// If not provided, a remote signer or AWS KMS will be used
     pub private_key: Option<String>,
-    /// AWS KMS key ids to use for signing transactions
+    /// URL of a remote JSON-RPC signer to use for signing transactions
     /// Only used if private_key is not provided
+    pub remote_signer_url: Option<String>,
+    /// AWS KMS key ids to use for signing transactions
+    /// Only used if neither private_key nor remote_signer_url is provided
     pub aws_kms_key_ids: Vec<String>,
     /// AWS KMS region
     pub aws_kms_region: Region,
@@ -74,6 +78,8 @@ pub struct Args {
     pub redis_lock_ttl_millis: u64,
     /// Chain ID
     pub chain_id: u64,
+    /// Gas overheads used to calculate the pre-verification gas
+    pub gas_overheads: GasOverheads,
     /// Maximum bundle size in number of operations
     pub max_bundle_size: u64,
     /// Maximum bundle size in gas limit
@@ -132,6 +138,11 @@ where
     async fn run(mut self: Box<Self>, shutdown_token: CancellationToken) -> anyhow::Result<()> {
         info!("Mempool config: {:?}", self.args.mempool_configs);
 
+        let entry_points = [self.args.entry_point_address];
+        eth::check_chain(&self.args.rpc_url, self.args.chain_id, &entry_points).await?;
+        if self.args.submit_url != self.args.rpc_url {
+            eth::check_chain(&self.args.submit_url, self.args.chain_id, &entry_points).await?;
+        }
         let provider = eth::new_provider(&self.args.rpc_url, Some(self.args.eth_poll_interval))?;
         let manual_bundling_mode = Arc::new(AtomicBool::new(false));
 
@@ -236,6 +247,12 @@ where
                 LocalSigner::connect(Arc::clone(&provider), self.args.chain_id, pk.to_owned())
                     .await?,
             )
+        } else if let Some(url) = &self.args.remote_signer_url {
+            info!("Using remote signer");
+            BundlerSigner::Remote(
+                RemoteSigner::connect(Arc::clone(&provider), self.args.chain_id, url.to_owned())
+                    .await?,
+            )
         } else {
             info!("Using AWS KMS signer");
             let signer = time::timeout(
@@ -263,6 +280,7 @@ where
         let beneficiary = signer.address();
         let proposer_settings = bundle_proposer::Settings {
             chain_id: self.args.chain_id,
+            gas_overheads: self.args.gas_overheads,
             max_bundle_size: self.args.max_bundle_size,
             max_bundle_gas: self.args.max_bundle_gas,
             beneficiary,
@@ -278,7 +296,7 @@ where
             Arc::clone(&provider),
             entry_point.address(),
             simulate_validation_tracer,
-            self.args.sim_settings,
+            self.args.sim_settings.clone(),
             self.args.mempool_configs.clone(),
         );
 
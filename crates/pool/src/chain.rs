@@ -57,6 +57,8 @@ pub(crate) struct Chain<P: Provider> {
 pub struct ChainUpdate {
     pub latest_block_number: u64,
     pub latest_block_hash: H256,
+    /// Base fee of the latest block, if the chain supports EIP-1559
+    pub latest_block_base_fee: Option<U256>,
     /// Blocks before this number are no longer tracked in this `Chain`, so no
     /// further updates related to them will be sent.
     pub earliest_remembered_block_number: u64,
@@ -94,6 +96,7 @@ struct BlockSummary {
     number: u64,
     hash: H256,
     parent_hash: H256,
+    base_fee: Option<U256>,
     ops: Vec<MinedOp>,
 }
 
@@ -380,6 +383,7 @@ impl<P: Provider> Chain<P> {
         ChainUpdate {
             latest_block_number: latest_block.number,
             latest_block_hash: latest_block.hash,
+            latest_block_base_fee: latest_block.base_fee,
             earliest_remembered_block_number: self.blocks[0].number,
             reorg_depth,
             mined_ops,
@@ -416,6 +420,7 @@ impl BlockSummary {
                 .as_u64(),
             hash: block.hash.context("block hash should exist")?,
             parent_hash: block.parent_hash,
+            base_fee: block.base_fee_per_gas,
             ops: Vec::new(),
         })
     }
@@ -557,6 +562,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 3,
                 latest_block_hash: hash(3),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 1,
                 reorg_depth: 0,
                 mined_ops: vec![fake_mined_op(103), fake_mined_op(104), fake_mined_op(105),],
@@ -584,6 +590,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 4,
                 latest_block_hash: hash(4),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 2,
                 reorg_depth: 0,
                 mined_ops: vec![fake_mined_op(106)],
@@ -617,6 +624,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 4,
                 latest_block_hash: hash(14),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 2,
                 reorg_depth: 1,
                 mined_ops: vec![fake_mined_op(112), fake_mined_op(113), fake_mined_op(114)],
@@ -650,6 +658,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 2,
                 latest_block_hash: hash(12),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 0,
                 reorg_depth: 2,
                 mined_ops: vec![fake_mined_op(111), fake_mined_op(112)],
@@ -680,6 +689,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 1,
                 latest_block_hash: hash(11),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 0,
                 reorg_depth: 2,
                 mined_ops: vec![fake_mined_op(111)],
@@ -711,6 +721,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 3,
                 latest_block_hash: hash(13),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 1,
                 reorg_depth: 3,
                 mined_ops: vec![fake_mined_op(111), fake_mined_op(112), fake_mined_op(113)],
@@ -740,6 +751,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 6,
                 latest_block_hash: hash(16),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 4,
                 reorg_depth: 0,
                 mined_ops: vec![fake_mined_op(104), fake_mined_op(105), fake_mined_op(106)],
@@ -763,6 +775,7 @@ mod tests {
             ChainUpdate {
                 latest_block_number: 1,
                 latest_block_hash: hash(1),
+                latest_block_base_fee: None,
                 earliest_remembered_block_number: 0,
                 reorg_depth: 0,
                 mined_ops: vec![fake_mined_op(101), fake_mined_op(102), fake_mined_op(103),],
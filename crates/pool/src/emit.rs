@@ -51,6 +51,17 @@ pub enum OpPoolEvent {
         /// The removed entity
         entity: Entity,
     },
+    /// A reorg replaced previously processed blocks
+    ReorgDetected {
+        /// Number of the new head block
+        block_number: u64,
+        /// Hash of the new head block
+        block_hash: H256,
+        /// Number of previously processed blocks that were replaced
+        reorg_depth: u64,
+        /// Hashes of the operations that were unmined by the reorg
+        unmined_op_hashes: Vec<H256>,
+    },
 }
 
 /// Summary of the entities associated with an operation
@@ -174,6 +185,24 @@ impl Display for OpPoolEvent {
                     entity,
                 )
             }
+            OpPoolEvent::ReorgDetected {
+                block_number,
+                block_hash,
+                reorg_depth,
+                unmined_op_hashes,
+            } => {
+                write!(
+                    f,
+                    concat!(
+                        "Reorg detected.",
+                        "    Block number: {}",
+                        "    Block hash: {:?}",
+                        "    Depth: {}",
+                        "    Unmined ops: {:?}",
+                    ),
+                    block_number, block_hash, reorg_depth, unmined_op_hashes,
+                )
+            }
         }
     }
 }
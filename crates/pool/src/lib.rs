@@ -25,13 +25,20 @@ mod emit;
 pub use emit::OpPoolEvent as PoolEvent;
 
 mod mempool;
-pub use mempool::{MempoolError, PoolConfig, PoolOperation, Reputation, ReputationStatus};
+#[cfg(feature = "test-utils")]
+pub use mempool::MockReputationManager;
+pub use mempool::{
+    check_op_metadata, sender_shard_index, AddOpOptions, GetOpsFilter, MempoolError, MempoolEvent,
+    OpMetadata, OpPriority, OperationRank, PoolConfig, PoolOperation, RemovalReason, Reputation,
+    ReputationManager, ReputationParams, ReputationStatus, SenderSummary, ValidBlockRange,
+};
 
 mod server;
 #[cfg(feature = "test-utils")]
 pub use server::MockPoolServer;
 pub use server::{
-    LocalPoolBuilder, LocalPoolHandle, PoolResult, PoolServer, PoolServerError, RemotePoolClient,
+    DumpMempoolFilter, ImportMempoolResult, LocalPoolBuilder, LocalPoolHandle, MempoolSnapshot,
+    NewHead, PoolResult, PoolServer, PoolServerError, RemotePoolClient, ShardedPoolClient,
 };
 
 mod task;
@@ -13,8 +13,13 @@
 
 use std::mem;
 
-use ethers::{abi::Address, types::U256};
-use rundler_sim::{PrecheckError, PrecheckViolation, SimulationError, SimulationViolation};
+use ethers::{
+    abi::Address,
+    types::{H256, U256},
+};
+use rundler_sim::{
+    DisplayViolations, PrecheckError, PrecheckViolation, SimulationError, SimulationViolation,
+};
 use rundler_types::Entity;
 
 /// Mempool result type.
@@ -29,10 +34,43 @@ pub enum MempoolError {
     /// Operation with the same hash already in pool
     #[error("Operation already known")]
     OperationAlreadyKnown,
+    /// Operation received from a peer with the same hash as another recently
+    /// received from a peer, which is either still being validated or was rejected
+    #[error("Duplicate operation")]
+    DuplicateOperation,
     /// Operation with same sender/nonce already in pool
     /// and the replacement operation has lower gas price.
-    #[error("Replacement operation underpriced. Existing priority fee: {0}. Existing fee: {1}")]
-    ReplacementUnderpriced(U256, U256),
+    ///
+    /// Carries the existing operation's priority fee and fee, followed by the
+    /// minimum priority fee and fee required to replace it.
+    #[error("Replacement operation underpriced. Existing priority fee: {0}. Existing fee: {1}. Required priority fee: {2}. Required fee: {3}")]
+    ReplacementUnderpriced(U256, U256, U256, U256),
+    /// The operation's fees are below the static minimums configured for the pool.
+    ///
+    /// Carries the minimum priority fee and fee required for admission.
+    #[error("Operation fees below pool minimum. Required priority fee: {0}. Required fee: {1}")]
+    FeesBelowMinimum(U256, U256),
+    /// The operation's ABI encoded size exceeds the maximum configured for the pool.
+    ///
+    /// Carries the operation's size, followed by the maximum size, in bytes.
+    #[error("Operation size {0} bytes exceeds maximum of {1} bytes")]
+    OperationTooLarge(usize, usize),
+    /// The metadata submitted with the operation exceeds the size limits.
+    ///
+    /// Carries a description of the limit exceeded.
+    #[error("Invalid operation metadata: {0}")]
+    InvalidMetadata(String),
+    /// The operation's sender belongs to a different pool shard than this pool's.
+    ///
+    /// Carries the pool shard the operation belongs to, followed by this pool's shard.
+    #[error("Operation belongs to pool shard {0}, but this pool serves shard {1}")]
+    WrongPoolShard(u64, u64),
+    /// The operation's nonce has already been used on chain.
+    ///
+    /// Carries the operation's nonce, followed by the sender's next nonce on chain
+    /// for the same nonce key.
+    #[error("Nonce {0} is below the sender's on-chain nonce {1}")]
+    NonceTooLow(U256, U256),
     /// Max operations reached for this sender
     #[error("Max operations ({0}) reached for sender {1}")]
     MaxOperationsReached(usize, Address),
@@ -48,12 +86,29 @@ pub enum MempoolError {
     /// Operation was rejected due to a simulation violation
     #[error("Operation violation during simulation {0}")]
     SimulationViolation(SimulationViolation),
+    /// Operation was rejected due to multiple simulation violations, ordered
+    /// by importance
+    #[error("Operation violations during simulation {}", DisplayViolations(.0))]
+    SimulationViolations(Vec<SimulationViolation>),
     /// Operation was rejected because it used an unsupported aggregator
     #[error("Unsupported aggregator {0}")]
     UnsupportedAggregator(Address),
     /// An unknown entry point was specified
     #[error("Unknown entry point {0}")]
     UnknownEntryPoint(Address),
+    /// The operation is only valid at a block hash that is not the one the pool
+    /// validated it against.
+    ///
+    /// Carries the expected block hash, followed by the block hash the operation was validated at.
+    #[error("Expected block hash {0:?} does not match current block hash {1:?}")]
+    BlockHashMismatch(H256, H256),
+    /// An entity has submitted too many operations within its configured rate limit window
+    #[error("Entity {0} is rate limited")]
+    RateLimited(Entity),
+    /// Operation received from a peer has neither a staked account nor a staked
+    /// paymaster, and the pool requires one for peer operations
+    #[error("Operation received from a peer requires a staked account or paymaster")]
+    StakeRequiredForGossip,
 }
 
 impl From<SimulationError> for MempoolError {
@@ -62,12 +117,15 @@ impl From<SimulationError> for MempoolError {
             return Self::Other(error.into());
         };
 
-        let Some(violation) = violations.iter_mut().min() else {
-            return Self::Other(error.into());
-        };
-
-        // extract violation and replace with dummy
-        Self::SimulationViolation(mem::replace(violation, SimulationViolation::DidNotRevert))
+        match violations.len() {
+            0 => Self::Other(error.into()),
+            1 => Self::SimulationViolation(violations.remove(0)),
+            _ => {
+                let mut violations = mem::take(violations);
+                violations.sort();
+                Self::SimulationViolations(violations)
+            }
+        }
     }
 }
 
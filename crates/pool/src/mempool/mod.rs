@@ -17,22 +17,27 @@ pub use error::MempoolError;
 mod pool;
 
 mod reputation;
-pub(crate) use reputation::{HourlyMovingAverageReputation, ReputationParams};
-pub use reputation::{Reputation, ReputationStatus};
+pub(crate) use reputation::HourlyMovingAverageReputation;
+#[cfg(feature = "test-utils")]
+pub use reputation::MockReputationManager;
+pub use reputation::{Reputation, ReputationManager, ReputationParams, ReputationStatus};
 
 mod size;
 
 mod uo_pool;
 use std::{
     collections::{HashMap, HashSet},
+    path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
-use ethers::types::{Address, H256};
+use ethers::types::{Address, H256, U256};
 #[cfg(test)]
 use mockall::automock;
 use rundler_sim::{MempoolConfig, PrecheckSettings, SimulationSettings};
-use rundler_types::{Entity, EntityType, EntityUpdate, UserOperation, ValidTimeRange};
+use rundler_types::{Entity, EntityType, EntityUpdate, GasFees, UserOperation, ValidTimeRange};
+use serde::Serialize;
 use strum::IntoEnumIterator;
 use tonic::async_trait;
 pub(crate) use uo_pool::UoPool;
@@ -45,20 +50,47 @@ use super::chain::ChainUpdate;
 /// In-memory operation pool
 pub trait Mempool: Send + Sync + 'static {
     /// Call to update the mempool with a new chain update
-    fn on_chain_update(&self, update: &ChainUpdate);
+    ///
+    /// Returns the operations that were unmined by a reorg and must be validated
+    /// again before being re-admitted to the pool. Always empty unless the pool is
+    /// configured with `revalidate_reorged_ops`.
+    fn on_chain_update(&self, update: &ChainUpdate) -> Vec<Arc<PoolOperation>>;
 
     /// Returns the entry point address this pool targets.
     fn entry_point(&self) -> Address;
 
-    /// Adds a user operation to the pool
+    /// Returns the number of the last block processed by `on_chain_update`
+    fn block_number(&self) -> u64;
+
+    /// Returns the minimum fees an operation must pay to be admitted to the pool
+    fn min_fees(&self) -> GasFees;
+
+    /// Adds a user operation to the pool, subject to the submitter's `options`.
     async fn add_operation(
         &self,
         origin: OperationOrigin,
         op: UserOperation,
+        options: AddOpOptions,
     ) -> MempoolResult<H256>;
 
-    /// Removes a set of operations from the pool.
-    fn remove_operations(&self, hashes: &[H256]);
+    /// Puts an operation that was previously admitted to the pool back into it,
+    /// such as one unmined by a reorg, if it still passes simulation.
+    ///
+    /// Checks that only apply to new submissions, like rate limits, reputation
+    /// throttling, fee floors and nonce checks, are skipped, and the operation's
+    /// entities are not counted as seen again.
+    async fn readmit_operation(&self, op: Arc<PoolOperation>) -> MempoolResult<H256>;
+
+    /// Removes a set of operations from the pool, recording why they were removed.
+    fn remove_operations(&self, hashes: &[H256], reason: RemovalReason);
+
+    /// Returns the reason an operation was removed from the pool, if it was removed
+    /// recently.
+    fn removal_reason(&self, hash: H256) -> Option<RemovalReason>;
+
+    /// Returns the options the operation with the given hash was added with, if it
+    /// is in the pool.
+    fn operation_options(&self, hash: H256) -> Option<AddOpOptions>;
 
     /// Removes all operations associated with a given entity from the pool.
     fn remove_entity(&self, entity: Entity);
@@ -70,19 +102,67 @@ pub trait Mempool: Send + Sync + 'static {
     ///
     /// Returns the best operations from the pool based on their gas bids up to
     /// the specified maximum number of operations, limiting to one per sender.
+    /// Operations that would take the total gas limit past the pool's
+    /// `max_bundle_gas` are skipped.
     ///
     /// The `shard_index` is used to divide the mempool into disjoint shards to ensure
     /// that two bundle builders don't attempt to but bundle the same operations. If
     /// the supplied `shard_index` does not exist, the call will error.
-    fn best_operations(
+    ///
+    /// If the pool is configured with `revalidate_before_bundle`, each candidate
+    /// is simulated again against the latest block and skipped if it is no longer valid.
+    /// Candidates are revalidated concurrently, a batch at a time, and only as many
+    /// as are needed to fill the result.
+    async fn best_operations(
+        &self,
+        max: usize,
+        shard_index: u64,
+    ) -> MempoolResult<Vec<Arc<PoolOperation>>>;
+
+    /// Returns the operations `best_operations` selects from only those matching
+    /// the filter, skipping the filter's `offset` and returning at most `max`.
+    ///
+    /// Filtering happens before revalidation, so only operations that may be
+    /// returned are simulated again.
+    async fn best_operations_matching(
         &self,
         max: usize,
         shard_index: u64,
+        filter: GetOpsFilter,
     ) -> MempoolResult<Vec<Arc<PoolOperation>>>;
 
+    /// Returns the same operations as `best_operations`, each with the priority
+    /// metadata the pool ordered it by.
+    async fn best_operations_with_priority(
+        &self,
+        max: usize,
+        shard_index: u64,
+    ) -> MempoolResult<Vec<(Arc<PoolOperation>, OpPriority)>>;
+
+    /// Returns the position of an operation in the order `best_operations` selects
+    /// operations from its shard.
+    ///
+    /// This follows the same ordering, shard and one-op-per-sender rules as
+    /// `best_operations`, without limiting each bundle's gas or revalidating
+    /// operations, so the rank counts operations across the next several bundles.
+    /// Only the first 1000 operations of the pool in this order are searched,
+    /// including those that are not eligible, operations behind them have no rank.
+    fn operation_rank(&self, hash: H256) -> OperationRank;
+
     /// Returns the all operations from the pool up to a max size
     fn all_operations(&self, max: usize) -> Vec<Arc<PoolOperation>>;
 
+    /// Returns the total maximum gas cost, in wei, of the operations in the pool
+    /// sponsored by `paymaster`.
+    fn paymaster_max_cost(&self, paymaster: Address) -> U256;
+
+    /// Returns the next usable nonce for `sender` on the default nonce key.
+    ///
+    /// This is the sender's on-chain nonce, advanced past any of the sender's operations
+    /// in the pool with contiguous nonces from it. Pooled operations with nonces below
+    /// the on-chain nonce, or after a gap, are ignored.
+    async fn next_nonce(&self, sender: Address) -> MempoolResult<U256>;
+
     /// Debug methods
 
     /// Clears the mempool
@@ -91,6 +171,10 @@ pub trait Mempool: Send + Sync + 'static {
     /// Dumps the mempool's reputation tracking
     fn dump_reputation(&self) -> Vec<Reputation>;
 
+    /// Dumps a summary of each sender with operations in the pool, senders with
+    /// the most operations first
+    fn dump_senders(&self) -> Vec<SenderSummary>;
+
     /// Overwrites the mempool's reputation for an address
     fn set_reputation(&self, address: Address, ops_seen: u64, ops_included: u64);
 }
@@ -109,6 +193,17 @@ pub struct PoolConfig {
     pub min_replacement_fee_increase_percentage: u64,
     /// After this threshold is met, we will start to drop the worst userops from the mempool
     pub max_size_of_pool_bytes: usize,
+    /// The maximum number of operations in the mempool. Once reached, the lowest
+    /// gas bid operation is evicted to make room for a higher bidding operation
+    pub max_pool_size: usize,
+    /// Minimum `max_priority_fee_per_gas` an operation must pay to be admitted to the pool
+    pub min_priority_fee_per_gas: U256,
+    /// Minimum `max_fee_per_gas` an operation must pay to be admitted to the pool
+    pub min_max_fee_per_gas: U256,
+    /// Maximum size, in bytes, of the ABI encoded form of a single operation admitted to the pool
+    pub max_op_size_bytes: usize,
+    /// Maximum total execution gas limit of the operations returned by `best_operations`
+    pub max_bundle_gas: u64,
     /// Operations that are always banned from the mempool
     pub blocklist: Option<HashSet<Address>>,
     /// Operations that are always allowed in the mempool, regardless of reputation
@@ -124,10 +219,123 @@ pub struct PoolConfig {
     /// operations. The mempool is divided into shards by taking the hash of the operation
     /// and modding it by the number of shards.
     pub num_shards: u64,
+    /// Number of pool processes that the operations of this entry point are split
+    /// between, by sender. See `sender_shard_index`.
+    pub pool_shard_count: u64,
+    /// Index of the pool shard served by this pool. Operations from senders that
+    /// belong to other pool shards are rejected.
+    pub pool_shard_index: u64,
     /// the maximum number of user operations with a throttled entity that can stay in the mempool
     pub throttled_entity_mempool_count: u64,
     /// The maximum number of blocks a user operation with a throttled entity can stay in the mempool
     pub throttled_entity_live_blocks: u64,
+    /// Parameters for reputation tracking, including throttling/banning thresholds and decay
+    pub reputation_params: ReputationParams,
+    /// File the reputation map is periodically written to and reloaded from on startup.
+    /// If not set, reputation is not persisted across restarts.
+    pub reputation_persistence_path: Option<PathBuf>,
+    /// Maximum number of operations each entity may have admitted to the pool
+    /// within a rolling window, independent of reputation
+    pub rate_limits: HashMap<Entity, (u32, Duration)>,
+    /// Whether to simulate operations again at the latest block before returning
+    /// them as the best operations for a bundle
+    pub revalidate_before_bundle: bool,
+    /// Whether operations received from peers must have a staked account or
+    /// a staked paymaster to be admitted. Local submissions are not affected.
+    pub require_stake_for_gossip: bool,
+    /// Whether operations unmined by a reorg are simulated again before being
+    /// re-admitted to the pool, instead of being put back as they were
+    pub revalidate_reorged_ops: bool,
+}
+
+/// Reason an operation was removed from the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The operation was mined
+    Mined,
+    /// The operation stayed in the pool for too long
+    Expired,
+    /// The operation was replaced by another operation from the same sender
+    Replaced,
+    /// The operation became invalid, e.g. it failed validation while building a bundle
+    Invalidated,
+    /// The operation was evicted to make room in a full pool
+    Evicted,
+}
+
+/// A change to the contents of a mempool, streamed to subscribers of pool events.
+///
+/// Applying the events of a mempool in the order they are received reproduces
+/// the set of operations in the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolEvent {
+    /// An operation was added to the pool, including an operation returned to
+    /// the pool after the block it was mined in was reorged away
+    OpAdded {
+        /// Hash of the added operation
+        op_hash: H256,
+        /// The added operation
+        op: UserOperation,
+    },
+    /// An operation was removed from the pool
+    OpRemoved {
+        /// Hash of the removed operation
+        op_hash: H256,
+        /// Why the operation was removed
+        reason: RemovalReason,
+    },
+    /// An operation was replaced by another operation with the same sender and
+    /// nonce. The replacement is announced by a separate `OpAdded` event.
+    OpReplaced {
+        /// Hash of the replaced operation
+        op_hash: H256,
+        /// Hash of the operation that replaced it
+        replaced_by: H256,
+    },
+    /// The pool finished processing a new block
+    BlockProcessed {
+        /// Number of the processed block
+        block_number: u64,
+    },
+}
+
+/// Position of an operation in the order operations are selected for bundles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationRank {
+    /// Number of operations selected before this one from its shard, or `None` if
+    /// the operation is not in the pool or would not currently be selected
+    pub rank: Option<u64>,
+    /// Total number of operations in the pool
+    pub pool_size: u64,
+}
+
+/// The metadata the pool orders operations by when selecting them for bundles.
+///
+/// Operations are ordered by descending `effective_gas_price`, then by ascending
+/// `submission_seq`. Only the first operation in this order from each sender is
+/// selected for a bundle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpPriority {
+    /// Gas price the operation pays per unit of gas at the current base fee
+    pub effective_gas_price: U256,
+    /// Whether the operation's account is staked
+    pub is_staked: bool,
+    /// Order in which the operation was added to the pool, lowest first
+    pub submission_seq: u64,
+    /// The operation's nonce
+    pub sender_nonce: U256,
+}
+
+/// Summary of the operations in the pool from a single sender
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenderSummary {
+    /// The sender address
+    pub sender: Address,
+    /// Number of the sender's operations in the pool
+    pub op_count: usize,
+    /// Total maximum gas cost, in wei, of the sender's operations in the pool
+    pub max_total_cost: U256,
 }
 
 /// Origin of an operation.
@@ -143,6 +351,117 @@ pub enum OperationOrigin {
     ReturnedAfterReorg,
 }
 
+/// Informational key-value tags attached to an operation when it is submitted,
+/// such as the wallet or dapp it came from. Has no effect on how the operation
+/// is validated or bundled.
+pub type OpMetadata = HashMap<String, String>;
+
+/// Maximum number of tags in the metadata submitted with an operation
+pub const MAX_OP_METADATA_ENTRIES: usize = 8;
+
+/// Maximum length, in bytes, of each key and value in an operation's metadata
+pub const MAX_OP_METADATA_ENTRY_LEN: usize = 128;
+
+/// Checks that metadata submitted with an operation is within the size limits,
+/// as it is stored with the operation for as long as it stays in the pool.
+pub fn check_op_metadata(metadata: &OpMetadata) -> Result<(), MempoolError> {
+    if metadata.len() > MAX_OP_METADATA_ENTRIES {
+        return Err(MempoolError::InvalidMetadata(format!(
+            "metadata can have at most {MAX_OP_METADATA_ENTRIES} entries"
+        )));
+    }
+    if metadata
+        .iter()
+        .any(|(k, v)| k.len() > MAX_OP_METADATA_ENTRY_LEN || v.len() > MAX_OP_METADATA_ENTRY_LEN)
+    {
+        return Err(MempoolError::InvalidMetadata(format!(
+            "metadata keys and values can be at most {MAX_OP_METADATA_ENTRY_LEN} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Submitter-supplied options for adding an operation to the pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddOpOptions {
+    /// If set, the operation is rejected unless it validates at this block, and
+    /// is dropped once the chain head moves past it without the operation being
+    /// mined.
+    pub expected_block_hash: Option<H256>,
+    /// The operation is only selected for bundles landing within this range, and
+    /// is removed once the range's last block can no longer be reached.
+    pub valid_block_range: ValidBlockRange,
+    /// Stored with the operation for informational purposes only
+    pub metadata: OpMetadata,
+}
+
+/// Filtering and pagination options for selecting bundle candidates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GetOpsFilter {
+    /// Only return operations using this paymaster
+    pub paymaster: Option<Address>,
+    /// Only return operations using this factory
+    pub factory: Option<Address>,
+    /// Number of matching operations to skip
+    pub offset: u64,
+}
+
+impl GetOpsFilter {
+    /// Returns true if the filter has no effect
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if the operation passes the entity filters
+    pub fn matches(&self, op: &PoolOperation) -> bool {
+        self.paymaster
+            .map_or(true, |p| op.uo.paymaster() == Some(p))
+            && self.factory.map_or(true, |f| op.uo.factory() == Some(f))
+    }
+}
+
+/// Returns the index of the shard, out of `shard_count`, that operations from
+/// `sender` belong to.
+///
+/// All of a sender's operations belong to the same shard, so that replacements
+/// and per-sender limits are handled within a single shard.
+pub fn sender_shard_index(sender: Address, shard_count: u64) -> u64 {
+    // short-circuit the mod if there is only 1 shard
+    if shard_count == 1 {
+        return 0;
+    }
+    U256::from_little_endian(sender.as_bytes())
+        .div_mod(shard_count.into())
+        .1
+        .as_u64()
+}
+
+/// The block numbers an operation's submitter allows it to be bundled in.
+///
+/// This is enforced by the pool only, independently of the time range the
+/// operation's account or paymaster returns from validation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidBlockRange {
+    /// If set, the operation is not bundled before this block
+    pub min_block: Option<u64>,
+    /// If set, the operation is not bundled after this block, and is removed
+    /// from the pool once it has passed
+    pub max_block: Option<u64>,
+}
+
+impl ValidBlockRange {
+    /// Returns true if the operation may be bundled in the given block.
+    pub fn contains(&self, block_number: u64) -> bool {
+        self.min_block.map_or(true, |min| block_number >= min)
+            && self.max_block.map_or(true, |max| block_number <= max)
+    }
+
+    /// Returns true if the range ends before the given block.
+    pub fn has_passed(&self, block_number: u64) -> bool {
+        self.max_block.map_or(false, |max| block_number > max)
+    }
+}
+
 /// A user operation with additional metadata from validation.
 #[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct PoolOperation {
@@ -160,6 +479,10 @@ pub struct PoolOperation {
     pub entities_needing_stake: Vec<EntityType>,
     /// Whether the account is staked.
     pub account_is_staked: bool,
+    /// Tags attached to the operation when it was submitted
+    pub metadata: OpMetadata,
+    /// The block numbers the submitter allows the operation to be bundled in
+    pub valid_block_range: ValidBlockRange,
 }
 
 impl PoolOperation {
@@ -226,6 +549,29 @@ impl PoolOperation {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_check_op_metadata() {
+        let metadata = OpMetadata::from([("source".to_string(), "wallet".to_string())]);
+        assert!(check_op_metadata(&metadata).is_ok());
+
+        let too_many = (0..=MAX_OP_METADATA_ENTRIES)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        assert!(matches!(
+            check_op_metadata(&too_many),
+            Err(MempoolError::InvalidMetadata(_))
+        ));
+
+        let too_long = OpMetadata::from([(
+            "source".to_string(),
+            "a".repeat(MAX_OP_METADATA_ENTRY_LEN + 1),
+        )]);
+        assert!(matches!(
+            check_op_metadata(&too_long),
+            Err(MempoolError::InvalidMetadata(_))
+        ));
+    }
+
     #[test]
     fn test_entities() {
         let sender = Address::random();
@@ -246,6 +592,8 @@ mod tests {
             sim_block_hash: H256::random(),
             entities_needing_stake: vec![EntityType::Account, EntityType::Aggregator],
             account_is_staked: true,
+            metadata: OpMetadata::default(),
+            valid_block_range: ValidBlockRange::default(),
         };
 
         assert!(po.is_staked(EntityType::Account));
@@ -269,4 +617,43 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_sender_shard_index() {
+        let mut sender = Address::zero();
+        assert_eq!(sender_shard_index(sender, 1), 0);
+        assert_eq!(sender_shard_index(sender, 3), 0);
+
+        // addresses are read as little endian numbers
+        sender.0[0] = 5;
+        assert_eq!(sender_shard_index(sender, 1), 0);
+        assert_eq!(sender_shard_index(sender, 3), 2);
+        assert_eq!(sender_shard_index(sender, 5), 0);
+        assert_eq!(sender_shard_index(sender, 6), 5);
+
+        let sender = Address::repeat_byte(0xff);
+        assert_eq!(sender_shard_index(sender, 2), 1);
+        assert_eq!(sender_shard_index(sender, u64::MAX), 0xffff_ffff);
+        for shard_count in 1..10 {
+            assert!(sender_shard_index(Address::random(), shard_count) < shard_count);
+        }
+    }
+
+    #[test]
+    fn test_valid_block_range() {
+        let range = ValidBlockRange {
+            min_block: Some(10),
+            max_block: Some(20),
+        };
+        assert!(!range.contains(9));
+        assert!(range.contains(10));
+        assert!(range.contains(20));
+        assert!(!range.contains(21));
+        assert!(!range.has_passed(20));
+        assert!(range.has_passed(21));
+
+        let unbounded = ValidBlockRange::default();
+        assert!(unbounded.contains(0));
+        assert!(!unbounded.has_passed(u64::MAX));
+    }
 }
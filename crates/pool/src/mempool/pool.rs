@@ -12,8 +12,9 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use std::{
-    cmp::Ordering,
-    collections::{hash_map::Entry, BTreeSet, HashMap},
+    cmp::Reverse,
+    collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
+    iter, mem,
     sync::Arc,
 };
 
@@ -22,6 +23,7 @@ use ethers::{
     abi::Address,
     types::{H256, U256},
 };
+use rundler_sim::gas;
 use rundler_types::{Entity, UserOperation, UserOperationId};
 use rundler_utils::math;
 use tracing::info;
@@ -29,7 +31,7 @@ use tracing::info;
 use super::{
     error::{MempoolError, MempoolResult},
     size::SizeTracker,
-    PoolConfig, PoolOperation,
+    OpPriority, PoolConfig, PoolOperation, RemovalReason, SenderSummary,
 };
 use crate::chain::MinedOp;
 
@@ -39,6 +41,7 @@ pub(crate) struct PoolInnerConfig {
     chain_id: u64,
     max_userops_per_sender: usize,
     max_size_of_pool_bytes: usize,
+    max_pool_size: usize,
     min_replacement_fee_increase_percentage: u64,
 }
 
@@ -49,6 +52,7 @@ impl From<PoolConfig> for PoolInnerConfig {
             chain_id: config.chain_id,
             max_userops_per_sender: config.max_userops_per_sender,
             max_size_of_pool_bytes: config.max_size_of_pool_bytes,
+            max_pool_size: config.max_pool_size,
             min_replacement_fee_increase_percentage: config.min_replacement_fee_increase_percentage,
         }
     }
@@ -63,8 +67,8 @@ pub(crate) struct PoolInner {
     by_hash: HashMap<H256, OrderedPoolOperation>,
     /// Operations by operation ID
     by_id: HashMap<UserOperationId, OrderedPoolOperation>,
-    /// Best operations, sorted by gas price
-    best: BTreeSet<OrderedPoolOperation>,
+    /// Best operations, sorted by effective gas price at the latest base fee
+    best: BestOperations,
     /// Removed operations, temporarily kept around in case their blocks are
     /// reorged away. Stored along with the block number at which it was
     /// removed.
@@ -74,12 +78,17 @@ pub(crate) struct PoolInner {
     mined_hashes_with_block_numbers: BTreeSet<(u64, H256)>,
     /// Count of operations by entity address
     count_by_address: HashMap<Address, usize>,
+    /// Total maximum gas cost of the operations sponsored by each paymaster
+    max_cost_by_paymaster: HashMap<Address, U256>,
     /// Submission ID counter
     submission_id: u64,
     /// keeps track of the size of the pool in bytes
     pool_size: SizeTracker,
     /// keeps track of the size of the removed cache in bytes
     cache_size: SizeTracker,
+    /// Operations removed as a side effect of adding another operation, by
+    /// being replaced or evicted, that have not yet been taken by the caller
+    displaced: Vec<(H256, RemovalReason)>,
 }
 
 impl PoolInner {
@@ -88,13 +97,15 @@ impl PoolInner {
             config,
             by_hash: HashMap::new(),
             by_id: HashMap::new(),
-            best: BTreeSet::new(),
+            best: BestOperations::default(),
             mined_at_block_number_by_hash: HashMap::new(),
             mined_hashes_with_block_numbers: BTreeSet::new(),
             count_by_address: HashMap::new(),
+            max_cost_by_paymaster: HashMap::new(),
             submission_id: 0,
             pool_size: SizeTracker::default(),
             cache_size: SizeTracker::default(),
+            displaced: Vec::new(),
         }
     }
 
@@ -118,6 +129,8 @@ impl PoolInner {
                 return Err(MempoolError::ReplacementUnderpriced(
                     pool_op.uo().max_priority_fee_per_gas,
                     pool_op.uo().max_fee_per_gas,
+                    replacement_priority_fee,
+                    replacement_fee,
                 ));
             }
 
@@ -137,14 +150,101 @@ impl PoolInner {
         ret
     }
 
+    /// Returns all operations in the pool, ordered by effective gas price.
     pub(crate) fn best_operations(&self) -> impl Iterator<Item = Arc<PoolOperation>> {
-        self.best.clone().into_iter().map(|v| v.po)
+        self.iter_best_operations()
+            .cloned()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Iterates over the same operations as `best_operations` without copying
+    /// the ordering, while the pool is borrowed.
+    pub(crate) fn iter_best_operations(&self) -> impl Iterator<Item = &Arc<PoolOperation>> {
+        self.best.iter().map(|v| &v.po)
+    }
+
+    /// Returns the same operations as `best_operations`, each with the priority
+    /// it is ordered by.
+    pub(crate) fn best_operations_with_priority(
+        &self,
+    ) -> impl Iterator<Item = (Arc<PoolOperation>, OpPriority)> {
+        self.best
+            .iter()
+            .map(|v| (v.po.clone(), v.priority(self.best.base_fee)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Base fee of the latest block, if known
+    pub(crate) fn base_fee(&self) -> Option<U256> {
+        self.best.base_fee
+    }
+
+    /// Updates the base fee, reordering the operations whose effective gas price
+    /// it changes relative to the others.
+    pub(crate) fn update_base_fee(&mut self, base_fee: U256) {
+        self.best.set_base_fee(base_fee);
+    }
+
+    pub(crate) fn contains(&self, hash: H256) -> bool {
+        self.by_hash.contains_key(&hash)
+    }
+
+    pub(crate) fn get_operation_by_hash(&self, hash: H256) -> Option<Arc<PoolOperation>> {
+        self.by_hash.get(&hash).map(|o| o.po.clone())
+    }
+
+    pub(crate) fn num_operations(&self) -> usize {
+        self.by_hash.len()
     }
 
     pub(crate) fn address_count(&self, address: Address) -> usize {
         self.count_by_address.get(&address).copied().unwrap_or(0)
     }
 
+    /// Returns the total maximum gas cost of the operations in the pool sponsored
+    /// by `paymaster`.
+    pub(crate) fn paymaster_max_cost(&self, paymaster: Address) -> U256 {
+        self.max_cost_by_paymaster
+            .get(&paymaster)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns a summary of each sender's operations in the pool, ordered by
+    /// descending operation count, then by sender address.
+    pub(crate) fn sender_summaries(&self) -> Vec<SenderSummary> {
+        let mut summaries = HashMap::<Address, SenderSummary>::new();
+        for (id, op) in &self.by_id {
+            let summary = summaries.entry(id.sender).or_insert(SenderSummary {
+                sender: id.sender,
+                op_count: 0,
+                max_total_cost: U256::zero(),
+            });
+            summary.op_count += 1;
+            summary.max_total_cost = summary
+                .max_total_cost
+                .saturating_add(gas::user_operation_max_gas_cost(op.uo()));
+        }
+        let mut summaries = summaries.into_values().collect::<Vec<_>>();
+        summaries.sort_by(|a, b| {
+            b.op_count
+                .cmp(&a.op_count)
+                .then_with(|| a.sender.cmp(&b.sender))
+        });
+        summaries
+    }
+
+    /// Returns the first nonce at or after `nonce` that `sender` has no operation
+    /// in the pool for, skipping over the sender's operations with contiguous nonces.
+    pub(crate) fn next_free_nonce(&self, sender: Address, mut nonce: U256) -> U256 {
+        while self.by_id.contains_key(&UserOperationId { sender, nonce }) {
+            nonce += U256::one();
+        }
+        nonce
+    }
+
     pub(crate) fn remove_operation_by_hash(&mut self, hash: H256) -> Option<Arc<PoolOperation>> {
         let ret = self.remove_operation_internal(hash, None);
         self.update_metrics();
@@ -178,6 +278,22 @@ impl PoolInner {
         Some(op.po)
     }
 
+    /// Forgets a mined operation without putting it back in the pool, returning it
+    /// so that it can be validated again.
+    pub(crate) fn take_unmined_operation(&mut self, hash: H256) -> Option<Arc<PoolOperation>> {
+        let (op, block_number) = self.mined_at_block_number_by_hash.remove(&hash)?;
+        self.mined_hashes_with_block_numbers
+            .remove(&(block_number, hash));
+        self.update_metrics();
+        Some(op.po)
+    }
+
+    /// Returns the operations replaced or evicted by operations added to the pool
+    /// since the last call, oldest first.
+    pub(crate) fn take_displaced(&mut self) -> Vec<(H256, RemovalReason)> {
+        mem::take(&mut self.displaced)
+    }
+
     /// Removes all operations using the given entity, returning the hashes of
     /// the removed operations.
     pub(crate) fn remove_entity(&mut self, entity: Entity) -> Vec<H256> {
@@ -215,16 +331,20 @@ impl PoolInner {
         self.mined_at_block_number_by_hash.clear();
         self.mined_hashes_with_block_numbers.clear();
         self.count_by_address.clear();
+        self.max_cost_by_paymaster.clear();
         self.pool_size = SizeTracker::default();
         self.cache_size = SizeTracker::default();
+        self.displaced.clear();
         self.update_metrics();
     }
 
     fn enforce_size(&mut self) -> anyhow::Result<Vec<H256>> {
         let mut removed = Vec::new();
 
-        while self.pool_size > self.config.max_size_of_pool_bytes {
-            if let Some(worst) = self.best.pop_last() {
+        while self.pool_size > self.config.max_size_of_pool_bytes
+            || self.by_hash.len() > self.config.max_pool_size
+        {
+            if let Some(worst) = self.best.pop_worst() {
                 let hash = worst
                     .uo()
                     .op_hash(self.config.entry_point, self.config.chain_id);
@@ -233,6 +353,8 @@ impl PoolInner {
                     .remove_operation_internal(hash, None)
                     .context("should have removed the worst operation")?;
 
+                PoolMetrics::increment_evicted_ops(self.config.entry_point);
+                self.displaced.push((hash, RemovalReason::Evicted));
                 removed.push(hash);
             }
         }
@@ -250,22 +372,32 @@ impl PoolInner {
         submission_id: Option<u64>,
     ) -> MempoolResult<H256> {
         // Check if operation already known or replacing an existing operation
-        // if replacing, remove the existing operation
-        if let Some(hash) = self.check_replacement(&op.uo)? {
-            self.remove_operation_by_hash(hash);
-        }
-
-        // Check sender count in mempool. If sender has too many operations, must be staked
-        if *self.count_by_address.get(&op.uo.sender).unwrap_or(&0)
-            >= self.config.max_userops_per_sender
-            && !op.account_is_staked
-        {
+        let replaced_hash = self.check_replacement(&op.uo)?;
+
+        // Check sender count in mempool. If sender has too many operations, must be staked.
+        // A replaced operation has the same sender and doesn't count towards the limit.
+        let sender_count = self
+            .count_by_address
+            .get(&op.uo.sender)
+            .copied()
+            .unwrap_or(0)
+            .saturating_sub(usize::from(replaced_hash.is_some()));
+        if sender_count >= self.config.max_userops_per_sender && !op.account_is_staked {
             return Err(MempoolError::MaxOperationsReached(
                 self.config.max_userops_per_sender,
                 op.uo.sender,
             ));
         }
 
+        // If replacing, remove the existing operation, it is restored below if the
+        // new operation is discarded
+        let replaced = replaced_hash.and_then(|hash| {
+            let replaced = self.by_hash.get(&hash).cloned();
+            self.remove_operation_by_hash(hash);
+            self.displaced.push((hash, RemovalReason::Replaced));
+            replaced.map(|replaced| (hash, replaced))
+        });
+
         let pool_op = OrderedPoolOperation {
             po: op,
             submission_id: submission_id.unwrap_or_else(|| self.next_submission_id()),
@@ -275,6 +407,10 @@ impl PoolInner {
         for e in pool_op.po.entities() {
             *self.count_by_address.entry(e.address).or_insert(0) += 1;
         }
+        if let Some(paymaster) = pool_op.uo().paymaster() {
+            let max_cost = self.max_cost_by_paymaster.entry(paymaster).or_default();
+            *max_cost = max_cost.saturating_add(gas::user_operation_max_gas_cost(pool_op.uo()));
+        }
 
         // create and insert ordered operation
         let hash = pool_op
@@ -290,6 +426,12 @@ impl PoolInner {
             .context("should have succeeded in resizing the pool")?;
 
         if removed.contains(&hash) {
+            if let Some((replaced_hash, replaced)) = replaced {
+                self.displaced
+                    .retain(|&entry| entry != (replaced_hash, RemovalReason::Replaced));
+                // The restored operation may itself be evicted, it is then recorded as such
+                let _ = self.put_back_unmined_operation(replaced);
+            }
             Err(MempoolError::DiscardedOnInsert)?;
         }
 
@@ -303,7 +445,7 @@ impl PoolInner {
     ) -> Option<Arc<PoolOperation>> {
         let op = self.by_hash.remove(&hash)?;
         self.by_id.remove(&op.uo().id());
-        self.best.remove(&op);
+        self.best.remove(op.submission_id);
 
         if let Some(block_number) = block_number {
             self.cache_size += op.mem_size();
@@ -315,6 +457,9 @@ impl PoolInner {
         for e in op.po.entities() {
             self.decrement_address_count(e.address);
         }
+        if let Some(paymaster) = op.uo().paymaster() {
+            self.decrease_paymaster_max_cost(paymaster, gas::user_operation_max_gas_cost(op.uo()));
+        }
 
         self.pool_size -= op.mem_size();
         Some(op.po)
@@ -329,6 +474,15 @@ impl PoolInner {
         }
     }
 
+    fn decrease_paymaster_max_cost(&mut self, paymaster: Address, cost: U256) {
+        if let Entry::Occupied(mut max_cost_entry) = self.max_cost_by_paymaster.entry(paymaster) {
+            *max_cost_entry.get_mut() = max_cost_entry.get().saturating_sub(cost);
+            if max_cost_entry.get().is_zero() {
+                max_cost_entry.remove_entry();
+            }
+        }
+    }
+
     fn next_submission_id(&mut self) -> u64 {
         let id = self.submission_id;
         self.submission_id += 1;
@@ -361,8 +515,20 @@ impl PoolInner {
     }
 }
 
-/// Wrapper around PoolOperation that adds a submission ID to implement
-/// a custom ordering for the best operations
+/// The gas price per unit the bundler earns from an operation at the given base fee.
+/// Without a known base fee this falls back to the max fee per gas.
+fn effective_gas_price(op: &UserOperation, base_fee: Option<U256>) -> U256 {
+    match base_fee {
+        Some(base_fee) => op
+            .max_fee_per_gas
+            .min(base_fee.saturating_add(op.max_priority_fee_per_gas))
+            .saturating_sub(base_fee),
+        None => op.max_fee_per_gas,
+    }
+}
+
+/// Wrapper around PoolOperation that adds a submission ID, which breaks ties
+/// in the ordering of the best operations
 #[derive(Debug, Clone)]
 struct OrderedPoolOperation {
     po: Arc<PoolOperation>,
@@ -377,33 +543,189 @@ impl OrderedPoolOperation {
     fn mem_size(&self) -> usize {
         std::mem::size_of::<OrderedPoolOperation>() + self.po.mem_size()
     }
+
+    fn priority(&self, base_fee: Option<U256>) -> OpPriority {
+        OpPriority {
+            effective_gas_price: effective_gas_price(self.uo(), base_fee),
+            is_staked: self.po.account_is_staked,
+            submission_seq: self.submission_id,
+            sender_nonce: self.uo().nonce,
+        }
+    }
 }
 
-impl Eq for OrderedPoolOperation {}
+/// How the base fee determines an operation's effective gas price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FeeCase {
+    /// The max fee leaves room for the full priority fee above the base fee
+    Uncapped,
+    /// The max fee caps the priority fee, so the op pays its max fee less the base fee
+    Capped,
+    /// The max fee doesn't exceed the base fee, so the op pays nothing above it
+    Underpriced,
+}
 
-impl Ord for OrderedPoolOperation {
-    fn cmp(&self, other: &Self) -> Ordering {
-        // Sort by gas price descending then by id ascending
-        other
-            .uo()
-            .max_fee_per_gas
-            .cmp(&self.uo().max_fee_per_gas)
-            .then_with(|| self.submission_id.cmp(&other.submission_id))
+impl FeeCase {
+    fn of(op: &UserOperation, base_fee: Option<U256>) -> Self {
+        // Without a base fee, operations are ordered by max fee
+        let Some(base_fee) = base_fee else {
+            return FeeCase::Capped;
+        };
+        if op.max_fee_per_gas <= base_fee {
+            FeeCase::Underpriced
+        } else if base_fee.saturating_add(op.max_priority_fee_per_gas) > op.max_fee_per_gas {
+            FeeCase::Capped
+        } else {
+            FeeCase::Uncapped
+        }
     }
 }
 
-impl PartialOrd for OrderedPoolOperation {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+/// Operations ordered by descending effective gas price at the latest base fee,
+/// then by ascending submission ID.
+///
+/// Within each `FeeCase`, the order doesn't depend on the base fee: uncapped
+/// operations pay their priority fee, capped ones pay more the higher their max
+/// fee, and underpriced ones all pay nothing. Each case is kept in its own set and
+/// the sets are merged when iterating, so a new base fee only moves the operations
+/// whose case it changes instead of reordering the whole pool.
+#[derive(Debug, Default)]
+struct BestOperations {
+    base_fee: Option<U256>,
+    /// Operations by submission ID
+    ops: HashMap<u64, OrderedPoolOperation>,
+    /// Uncapped operations by descending priority fee
+    uncapped: BTreeSet<(Reverse<U256>, u64)>,
+    /// Capped operations by descending max fee
+    capped: BTreeSet<(Reverse<U256>, u64)>,
+    /// Underpriced operations by submission ID
+    underpriced: BTreeSet<(Reverse<U256>, u64)>,
+    /// The base fees at which each operation changes case: its max fee, and its
+    /// max fee less its priority fee
+    thresholds: BTreeSet<(U256, u64)>,
 }
 
-impl PartialEq for OrderedPoolOperation {
-    fn eq(&self, other: &Self) -> bool {
-        self.cmp(other) == Ordering::Equal
+impl BestOperations {
+    fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &OrderedPoolOperation> {
+        let mut sets = [
+            self.uncapped.iter().peekable(),
+            self.capped.iter().peekable(),
+            self.underpriced.iter().peekable(),
+        ];
+        iter::from_fn(move || {
+            let (next, _) = sets
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, set)| set.peek().map(|&&(_, id)| (i, self.order(id))))
+                .min_by_key(|&(_, order)| order)?;
+            let &(_, id) = sets[next].next()?;
+            Some(&self.ops[&id])
+        })
+    }
+
+    fn insert(&mut self, op: OrderedPoolOperation) {
+        let id = op.submission_id;
+        self.thresholds.insert((op.uo().max_fee_per_gas, id));
+        self.thresholds.insert((cap_threshold(op.uo()), id));
+        let case = FeeCase::of(op.uo(), self.base_fee);
+        let key = case_key(&op, case);
+        self.set_mut(case).insert(key);
+        self.ops.insert(id, op);
+    }
+
+    fn remove(&mut self, submission_id: u64) -> Option<OrderedPoolOperation> {
+        let op = self.ops.remove(&submission_id)?;
+        self.thresholds
+            .remove(&(op.uo().max_fee_per_gas, submission_id));
+        self.thresholds
+            .remove(&(cap_threshold(op.uo()), submission_id));
+        let case = FeeCase::of(op.uo(), self.base_fee);
+        let key = case_key(&op, case);
+        self.set_mut(case).remove(&key);
+        Some(op)
+    }
+
+    /// Removes and returns the operation with the lowest effective gas price
+    fn pop_worst(&mut self) -> Option<OrderedPoolOperation> {
+        let id = [&self.uncapped, &self.capped, &self.underpriced]
+            .into_iter()
+            .filter_map(|set| set.last())
+            .map(|&(_, id)| id)
+            .max_by_key(|&id| self.order(id))?;
+        self.remove(id)
+    }
+
+    fn set_base_fee(&mut self, base_fee: U256) {
+        let old_base_fee = self.base_fee.replace(base_fee);
+        let moved = match old_base_fee {
+            Some(old) if old == base_fee => return,
+            Some(old) => {
+                let (low, high) = (old.min(base_fee), old.max(base_fee));
+                self.thresholds
+                    .range((low, 0)..=(high, u64::MAX))
+                    .map(|&(_, id)| id)
+                    .collect::<HashSet<_>>()
+            }
+            None => self.ops.keys().copied().collect(),
+        };
+        for id in moved {
+            let op = &self.ops[&id];
+            let from = FeeCase::of(op.uo(), old_base_fee);
+            let to = FeeCase::of(op.uo(), self.base_fee);
+            if from != to {
+                let (from_key, to_key) = (case_key(op, from), case_key(op, to));
+                self.set_mut(from).remove(&from_key);
+                self.set_mut(to).insert(to_key);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn set_mut(&mut self, case: FeeCase) -> &mut BTreeSet<(Reverse<U256>, u64)> {
+        match case {
+            FeeCase::Uncapped => &mut self.uncapped,
+            FeeCase::Capped => &mut self.capped,
+            FeeCase::Underpriced => &mut self.underpriced,
+        }
+    }
+
+    // The position of an operation in the order, lowest first
+    fn order(&self, submission_id: u64) -> (Reverse<U256>, u64) {
+        let op = &self.ops[&submission_id];
+        (
+            Reverse(effective_gas_price(op.uo(), self.base_fee)),
+            submission_id,
+        )
     }
 }
 
+/// The key of an operation in the set for its fee case
+fn case_key(op: &OrderedPoolOperation, case: FeeCase) -> (Reverse<U256>, u64) {
+    let fee = match case {
+        FeeCase::Uncapped => op.uo().max_priority_fee_per_gas,
+        FeeCase::Capped => op.uo().max_fee_per_gas,
+        FeeCase::Underpriced => U256::zero(),
+    };
+    (Reverse(fee), op.submission_id)
+}
+
+/// The base fee above which an operation's max fee caps its priority fee
+fn cap_threshold(op: &UserOperation) -> U256 {
+    op.max_fee_per_gas
+        .saturating_sub(op.max_priority_fee_per_gas)
+}
+
 struct PoolMetrics {}
 
 impl PoolMetrics {
@@ -415,6 +737,9 @@ impl PoolMetrics {
         metrics::gauge!("op_pool_num_ops_in_cache", num_ops as f64, "entrypoint_addr" => entry_point.to_string());
         metrics::gauge!("op_pool_cache_size_bytes", size_bytes as f64, "entrypoint_addr" => entry_point.to_string());
     }
+    fn increment_evicted_ops(entry_point: Address) {
+        metrics::increment_counter!("op_pool_num_evicted_ops", "entrypoint_addr" => entry_point.to_string());
+    }
 }
 
 #[cfg(test)]
@@ -479,6 +804,140 @@ mod tests {
         check_map_entry(pool.best.iter().nth(2), Some(&ops[2]));
     }
 
+    #[test]
+    fn best_with_priority() {
+        let mut pool = PoolInner::new(conf());
+        let sender = Address::random();
+        let mut ops = vec![
+            create_op(sender, 0, 20),
+            create_op(sender, 1, 30),
+            create_op(Address::random(), 0, 20),
+        ];
+        ops[0].account_is_staked = true;
+        ops[1].account_is_staked = true;
+        for op in &mut ops {
+            op.uo.max_priority_fee_per_gas = 5.into();
+        }
+
+        for op in ops.iter() {
+            pool.add_operation(op.clone()).unwrap();
+        }
+
+        pool.update_base_fee(10.into());
+        let best = pool.best_operations_with_priority().collect::<Vec<_>>();
+        assert_eq!(best.len(), 3);
+        // equal effective gas prices are ordered by submission
+        assert_eq!(*best[0].0, ops[0]);
+        assert_eq!(
+            best[0].1,
+            OpPriority {
+                effective_gas_price: 5.into(),
+                is_staked: true,
+                submission_seq: 0,
+                sender_nonce: 0.into(),
+            }
+        );
+        assert_eq!(*best[1].0, ops[1]);
+        assert_eq!(best[1].1.submission_seq, 1);
+        assert_eq!(best[1].1.sender_nonce, 1.into());
+        assert_eq!(*best[2].0, ops[2]);
+        assert!(!best[2].1.is_staked);
+        assert_eq!(best[2].1.submission_seq, 2);
+
+        let best_ops = pool.best_operations().collect::<Vec<_>>();
+        assert_eq!(
+            best_ops,
+            best.into_iter().map(|(op, _)| op).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn best_by_effective_gas_price() {
+        let mut pool = PoolInner::new(conf());
+        let mut ops = vec![
+            create_op(Address::random(), 0, 30),
+            create_op(Address::random(), 0, 20),
+            create_op(Address::random(), 0, 5),
+        ];
+        ops[0].uo.max_priority_fee_per_gas = 1.into();
+        ops[1].uo.max_priority_fee_per_gas = 10.into();
+        ops[2].uo.max_priority_fee_per_gas = 5.into();
+
+        for op in ops.iter() {
+            pool.add_operation(op.clone()).unwrap();
+        }
+
+        // without a base fee, best is sorted by max fee
+        let best = pool.best_operations().collect::<Vec<_>>();
+        assert_eq!(best.len(), 3);
+        assert_eq!(*best[0], ops[0]);
+        assert_eq!(*best[1], ops[1]);
+        assert_eq!(*best[2], ops[2]);
+
+        // with a base fee of 10, effective prices are 1, 10 and underpriced. Underpriced
+        // ops stay in the pool, ordered last.
+        pool.update_base_fee(10.into());
+        let best = pool.best_operations().collect::<Vec<_>>();
+        assert_eq!(best.len(), 3);
+        assert_eq!(*best[0], ops[1]);
+        assert_eq!(*best[1], ops[0]);
+        assert_eq!(*best[2], ops[2]);
+
+        pool.update_base_fee(1.into());
+        let best = pool.best_operations().collect::<Vec<_>>();
+        assert_eq!(best.len(), 3);
+        assert_eq!(*best[0], ops[1]);
+        assert_eq!(*best[1], ops[2]);
+        assert_eq!(*best[2], ops[0]);
+
+        // removals still find the reordered operations
+        let hash = ops[1]
+            .uo
+            .op_hash(pool.config.entry_point, pool.config.chain_id);
+        assert!(pool.remove_operation_by_hash(hash).is_some());
+        assert_eq!(pool.best.len(), 2);
+    }
+
+    #[test]
+    fn best_reordered_incrementally() {
+        let mut pool = PoolInner::new(PoolInnerConfig {
+            max_pool_size: 100,
+            max_size_of_pool_bytes: 100 * mem_size_of_ordered_pool_op(),
+            ..conf()
+        });
+        for max_fee in [0, 5, 10, 15, 20, 25] {
+            for priority_fee in [0, 3, 10, 30] {
+                let mut op = create_op(Address::random(), 0, max_fee);
+                op.uo.max_priority_fee_per_gas = priority_fee.into();
+                pool.add_operation(op).unwrap();
+            }
+        }
+
+        // each base fee change only moves some operations, but the order matches
+        // sorting the whole pool at the new base fee
+        for base_fee in [12, 0, 7, 30, 15, 15, 3, 20] {
+            pool.update_base_fee(base_fee.into());
+            let mut expected = pool.by_hash.values().collect::<Vec<_>>();
+            expected.sort_by_key(|op| {
+                (
+                    Reverse(effective_gas_price(op.uo(), Some(base_fee.into()))),
+                    op.submission_id,
+                )
+            });
+            let best = pool.best.iter().collect::<Vec<_>>();
+            assert_eq!(best.len(), expected.len());
+            for (actual, expected) in best.iter().zip(&expected) {
+                assert_eq!(actual.submission_id, expected.submission_id);
+            }
+
+            // the worst operation is the last one in the order
+            let worst = expected.last().unwrap().submission_id;
+            let popped = pool.best.pop_worst().unwrap();
+            assert_eq!(popped.submission_id, worst);
+            pool.best.insert(popped);
+        }
+    }
+
     #[test]
     fn remove_op() {
         let mut pool = PoolInner::new(conf());
@@ -629,6 +1088,93 @@ mod tests {
         assert!(pool.best.is_empty());
     }
 
+    #[test]
+    fn sender_summaries() {
+        let mut pool = PoolInner::new(conf());
+        let sender = Address::random();
+        let ops = [
+            create_op(sender, 0, 10),
+            create_op(sender, 1, 20),
+            create_op(Address::random(), 0, 30),
+        ];
+        let mut hashes = vec![];
+        for op in ops.iter() {
+            hashes.push(pool.add_operation(op.clone()).unwrap());
+        }
+
+        let summaries = pool.sender_summaries();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(
+            summaries[0],
+            SenderSummary {
+                sender,
+                op_count: 2,
+                max_total_cost: gas::user_operation_max_gas_cost(&ops[0].uo)
+                    + gas::user_operation_max_gas_cost(&ops[1].uo),
+            }
+        );
+        assert_eq!(summaries[1].sender, ops[2].uo.sender);
+        assert_eq!(summaries[1].op_count, 1);
+
+        pool.remove_operation_by_hash(hashes[0]);
+        pool.remove_operation_by_hash(hashes[1]);
+        assert_eq!(pool.sender_summaries().len(), 1);
+    }
+
+    #[test]
+    fn paymaster_max_cost() {
+        let mut pool = PoolInner::new(conf());
+        let paymaster = Address::random();
+        let mut expected = U256::zero();
+        let mut hashes = vec![];
+        for i in 0..3 {
+            let mut op = create_op(Address::random(), 0, i + 1);
+            op.uo.call_gas_limit = 100_000.into();
+            if i < 2 {
+                op.uo.paymaster_and_data = paymaster.as_bytes().to_vec().into();
+                expected += gas::user_operation_max_gas_cost(&op.uo);
+            }
+            hashes.push(pool.add_operation(op).unwrap());
+        }
+
+        assert_eq!(pool.paymaster_max_cost(paymaster), expected);
+        assert_eq!(pool.paymaster_max_cost(Address::random()), U256::zero());
+
+        // the total follows the operations removed from the pool
+        let removed = pool.remove_operation_by_hash(hashes[0]).unwrap();
+        expected -= gas::user_operation_max_gas_cost(&removed.uo);
+        assert_eq!(pool.paymaster_max_cost(paymaster), expected);
+        pool.remove_operation_by_hash(hashes[1]);
+        assert_eq!(pool.paymaster_max_cost(paymaster), U256::zero());
+        assert!(pool.max_cost_by_paymaster.is_empty());
+    }
+
+    #[test]
+    fn paymaster_max_cost_replaced() {
+        let mut pool = PoolInner::new(conf());
+        let paymaster = Address::random();
+        let sender = Address::random();
+        let mut po1 = create_op(sender, 0, 10);
+        po1.uo.max_priority_fee_per_gas = 10.into();
+        po1.uo.call_gas_limit = 100_000.into();
+        po1.uo.paymaster_and_data = paymaster.as_bytes().to_vec().into();
+        pool.add_operation(po1).unwrap();
+
+        // the replacement is sponsored by another paymaster
+        let other_paymaster = Address::random();
+        let mut po2 = create_op(sender, 0, 11);
+        po2.uo.max_priority_fee_per_gas = 11.into();
+        po2.uo.call_gas_limit = 100_000.into();
+        po2.uo.paymaster_and_data = other_paymaster.as_bytes().to_vec().into();
+        pool.add_operation(po2.clone()).unwrap();
+
+        assert_eq!(pool.paymaster_max_cost(paymaster), U256::zero());
+        assert_eq!(
+            pool.paymaster_max_cost(other_paymaster),
+            gas::user_operation_max_gas_cost(&po2.uo)
+        );
+    }
+
     #[test]
     fn too_many_ops() {
         let args = conf();
@@ -712,6 +1258,112 @@ mod tests {
         assert!(result.is_ok(), "{:?}", result.err());
     }
 
+    #[test]
+    fn pool_max_size_evicts_lowest_bid() {
+        let args = PoolInnerConfig {
+            max_pool_size: 2,
+            max_size_of_pool_bytes: 100 * mem_size_of_ordered_pool_op(),
+            ..conf()
+        };
+        let mut pool = PoolInner::new(args);
+        let low = create_op(Address::random(), 0, 1);
+        let low_sender = low.uo.sender;
+        let low_hash = pool.add_operation(low).unwrap();
+        pool.add_operation(create_op(Address::random(), 0, 3))
+            .unwrap();
+
+        assert!(pool.take_displaced().is_empty());
+
+        // incoming op is the lowest bidder, so it is rejected
+        let rejected = create_op(Address::random(), 0, 1);
+        let rejected_hash = rejected
+            .uo
+            .op_hash(pool.config.entry_point, pool.config.chain_id);
+        let res = pool.add_operation(rejected);
+        assert!(matches!(res, Err(MempoolError::DiscardedOnInsert)));
+        assert_eq!(pool.by_hash.len(), 2);
+        assert_eq!(
+            pool.take_displaced(),
+            vec![(rejected_hash, RemovalReason::Evicted)]
+        );
+
+        // incoming op outbids the lowest, which is evicted
+        let high_hash = pool
+            .add_operation(create_op(Address::random(), 0, 2))
+            .unwrap();
+        assert_eq!(pool.by_hash.len(), 2);
+        assert!(pool.contains(high_hash));
+        assert!(!pool.contains(low_hash));
+        assert_eq!(pool.address_count(low_sender), 0);
+        assert_eq!(
+            pool.take_displaced(),
+            vec![(low_hash, RemovalReason::Evicted)]
+        );
+        assert!(pool.take_displaced().is_empty());
+    }
+
+    #[test]
+    fn replace_op_discarded_on_insert_restores_replaced() {
+        let args = PoolInnerConfig {
+            max_size_of_pool_bytes: 3 * mem_size_of_ordered_pool_op(),
+            ..conf()
+        };
+        let mut pool = PoolInner::new(args);
+        pool.add_operation(create_op(Address::random(), 0, 100))
+            .unwrap();
+        let sender = Address::random();
+        let mut po1 = create_op(sender, 0, 10);
+        po1.uo.max_priority_fee_per_gas = 10.into();
+        let hash1 = pool.add_operation(po1).unwrap();
+        assert!(pool.take_displaced().is_empty());
+
+        // the replacement outbids the op it replaces, but is too large to fit
+        // and is the lowest bidder in the pool
+        let mut po2 = create_op(sender, 0, 11);
+        po2.uo.max_priority_fee_per_gas = 11.into();
+        po2.uo.call_data = vec![0; 2 * mem_size_of_ordered_pool_op()].into();
+        let hash2 = po2
+            .uo
+            .op_hash(pool.config.entry_point, pool.config.chain_id);
+        let res = pool.add_operation(po2);
+        assert!(matches!(res, Err(MempoolError::DiscardedOnInsert)));
+
+        assert!(pool.contains(hash1));
+        assert_eq!(pool.by_hash.len(), 2);
+        assert_eq!(pool.address_count(sender), 1);
+        assert_eq!(pool.take_displaced(), vec![(hash2, RemovalReason::Evicted)]);
+    }
+
+    #[test]
+    fn replace_op_at_max_sender_ops() {
+        let args = PoolInnerConfig {
+            max_userops_per_sender: 1,
+            ..conf()
+        };
+        let mut pool = PoolInner::new(args);
+        let sender = Address::random();
+        let mut po1 = create_op(sender, 0, 10);
+        po1.uo.max_priority_fee_per_gas = 10.into();
+        let hash1 = pool.add_operation(po1).unwrap();
+
+        // another op from the sender is rejected without removing any op
+        let res = pool.add_operation(create_op(sender, 1, 10));
+        assert!(matches!(res, Err(MempoolError::MaxOperationsReached(1, _))));
+        assert!(pool.contains(hash1));
+        assert!(pool.take_displaced().is_empty());
+
+        // replacing the sender's op doesn't count towards the limit
+        let mut po2 = create_op(sender, 0, 11);
+        po2.uo.max_priority_fee_per_gas = 11.into();
+        let hash2 = pool.add_operation(po2).unwrap();
+        assert!(pool.contains(hash2));
+        assert_eq!(pool.address_count(sender), 1);
+        assert_eq!(
+            pool.take_displaced(),
+            vec![(hash1, RemovalReason::Replaced)]
+        );
+    }
+
     #[test]
     fn replace_op_underpriced() {
         let mut pool = PoolInner::new(conf());
@@ -725,9 +1377,11 @@ mod tests {
         let res = pool.add_operation(po2);
         assert!(res.is_err());
         match res.err().unwrap() {
-            MempoolError::ReplacementUnderpriced(a, b) => {
+            MempoolError::ReplacementUnderpriced(a, b, c, d) => {
                 assert_eq!(a, 100.into());
                 assert_eq!(b, 100.into());
+                assert_eq!(c, 110.into());
+                assert_eq!(d, 110.into());
             }
             _ => panic!("wrong error"),
         }
@@ -751,7 +1405,7 @@ mod tests {
         let mut po1 = create_op(sender, 0, 10);
         po1.uo.max_priority_fee_per_gas = 10.into();
         po1.uo.paymaster_and_data = paymaster1.as_bytes().to_vec().into();
-        let _ = pool.add_operation(po1).unwrap();
+        let hash1 = pool.add_operation(po1).unwrap();
         assert_eq!(pool.address_count(paymaster1), 1);
 
         let paymaster2 = Address::random();
@@ -763,11 +1417,15 @@ mod tests {
         assert_eq!(pool.address_count(sender), 1);
         assert_eq!(pool.address_count(paymaster1), 0);
         assert_eq!(pool.address_count(paymaster2), 1);
+        assert_eq!(
+            pool.take_displaced(),
+            vec![(hash1, RemovalReason::Replaced)]
+        );
         assert_eq!(
             pool.pool_size,
             OrderedPoolOperation {
                 po: Arc::new(po2),
-                submission_id: 0
+                submission_id: 0,
             }
             .mem_size()
         );
@@ -796,6 +1454,7 @@ mod tests {
             max_userops_per_sender: 16,
             min_replacement_fee_increase_percentage: 10,
             max_size_of_pool_bytes: 20 * mem_size_of_ordered_pool_op(),
+            max_pool_size: 20,
         }
     }
 
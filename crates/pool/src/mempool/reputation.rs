@@ -13,15 +13,26 @@
 
 use std::{
     collections::{HashMap, HashSet},
-    time::Duration,
+    fmt::Debug,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use ethers::types::Address;
-#[cfg(test)]
+#[cfg(feature = "test-utils")]
 use mockall::automock;
 use parking_lot::RwLock;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use tokio::time::interval;
+use tokio::{
+    task,
+    time::{self, interval, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// How often the reputation map is written to disk when persistence is enabled
+const PERSISTENCE_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Reputation status for an entity
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -79,8 +90,19 @@ pub struct Reputation {
 ///
 /// Interior mutability pattern used as ReputationManagers may
 /// need to be thread-safe.
-#[cfg_attr(test, automock)]
-pub(crate) trait ReputationManager: Send + Sync + 'static {
+///
+/// The pool uses an hourly moving average of each entity's operations by
+/// default. A different implementation, e.g. one backed by a reputation
+/// service shared by several bundlers, can be given to the pool task with
+/// `PoolTask::with_reputation_manager`.
+///
+/// Methods are called synchronously on the pool's hot paths, some while the
+/// pool's state lock is held, so implementations must not block. An
+/// implementation backed by a remote service should answer `status` and
+/// `get_ops_allowed` from a locally cached snapshot that is refreshed in the
+/// background, and queue the updates it receives to be sent asynchronously.
+#[cfg_attr(feature = "test-utils", automock)]
+pub trait ReputationManager: Debug + Send + Sync + 'static {
     /// Called by mempool before returning operations to bundler
     fn status(&self, address: Address) -> ReputationStatus;
 
@@ -102,10 +124,17 @@ pub(crate) trait ReputationManager: Send + Sync + 'static {
     /// stake is returned to the pool.
     fn remove_included(&self, address: Address);
 
+    /// Called by the mempool once per block with all entities whose operations were
+    /// mined (`included`) or returned to the pool by a reorg (`unincluded`), applying
+    /// every update under a single lock acquisition
+    fn update_included(&self, included: &[Address], unincluded: &[Address]);
+
     /// Called by debug API
     fn dump_reputation(&self) -> Vec<Reputation>;
 
     /// Called by debug API
+    ///
+    /// Only sets the counts, an allowlisted or blocklisted address keeps its status
     fn set_reputation(&self, address: Address, ops_seen: u64, ops_included: u64);
 
     /// Get the ops allowed for an unstaked entity
@@ -115,6 +144,7 @@ pub(crate) trait ReputationManager: Send + Sync + 'static {
 #[derive(Debug)]
 pub(crate) struct HourlyMovingAverageReputation {
     reputation: RwLock<AddressReputation>,
+    persistence_path: Option<PathBuf>,
 }
 
 impl HourlyMovingAverageReputation {
@@ -122,6 +152,7 @@ impl HourlyMovingAverageReputation {
         params: ReputationParams,
         blocklist: Option<HashSet<Address>>,
         allowlist: Option<HashSet<Address>>,
+        persistence_path: Option<PathBuf>,
     ) -> Self {
         let rep = AddressReputation::new(params)
             .with_blocklist(blocklist.unwrap_or_default())
@@ -129,15 +160,71 @@ impl HourlyMovingAverageReputation {
 
         Self {
             reputation: RwLock::new(rep),
+            persistence_path,
+        }
+    }
+
+    // restore the reputation map persisted to disk, if configured
+    pub(crate) async fn load_persisted(&self) {
+        let Some(path) = self.persistence_path.clone() else {
+            return;
+        };
+        let load_path = path.clone();
+        let loaded = task::spawn_blocking(move || ReputationSnapshot::load(&load_path))
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+        match loaded {
+            Ok(Some(snapshot)) => {
+                let mut rep = self.reputation.write();
+                rep.restore(snapshot);
+                info!(
+                    "Loaded reputation for {} entities from {path:?}",
+                    rep.counts.len()
+                );
+            }
+            Ok(None) => info!("No persisted reputation found at {path:?}"),
+            Err(e) => warn!("Failed to load persisted reputation from {path:?}: {e:?}"),
         }
     }
 
-    // run the reputation hourly update job
-    pub(crate) async fn run(&self) {
-        let mut tick = interval(Duration::from_secs(60 * 60));
+    // run the reputation decay job, hourly by default, and persist the
+    // reputation map to disk if configured, including once more on shutdown
+    pub(crate) async fn run(&self, shutdown_token: CancellationToken) {
+        let (decay_interval, next_decay) = {
+            let rep = self.reputation.read();
+            (
+                rep.params.decay_interval,
+                rep.next_decay_in(SystemTime::now()),
+            )
+        };
+        // the first decay continues the schedule of a restored reputation map
+        let mut decay_tick = time::interval_at(Instant::now() + next_decay, decay_interval);
+        let mut persist_tick = interval(PERSISTENCE_INTERVAL);
+        // the first tick completes immediately
+        persist_tick.tick().await;
         loop {
-            tick.tick().await;
-            self.reputation.write().hourly_update();
+            tokio::select! {
+                _ = decay_tick.tick() => self.reputation.write().decay(SystemTime::now()),
+                _ = persist_tick.tick(), if self.persistence_path.is_some() => self.persist().await,
+                _ = shutdown_token.cancelled() => {
+                    self.persist().await;
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn persist(&self) {
+        let Some(path) = self.persistence_path.clone() else {
+            return;
+        };
+        let snapshot = self.reputation.read().snapshot();
+        let save_path = path.clone();
+        let saved = task::spawn_blocking(move || snapshot.save(&save_path))
+            .await
+            .unwrap_or_else(|e| Err(e.into()));
+        if let Err(e) = saved {
+            warn!("Failed to persist reputation to {path:?}: {e:?}");
         }
     }
 }
@@ -167,6 +254,19 @@ impl ReputationManager for HourlyMovingAverageReputation {
         self.reputation.write().remove_included(address);
     }
 
+    fn update_included(&self, included: &[Address], unincluded: &[Address]) {
+        if included.is_empty() && unincluded.is_empty() {
+            return;
+        }
+        let mut reputation = self.reputation.write();
+        for &address in included {
+            reputation.add_included(address);
+        }
+        for &address in unincluded {
+            reputation.remove_included(address);
+        }
+    }
+
     fn dump_reputation(&self) -> Vec<Reputation> {
         let reputation = self.reputation.read();
         reputation
@@ -192,15 +292,28 @@ impl ReputationManager for HourlyMovingAverageReputation {
     }
 }
 
+/// Parameters for reputation tracking
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct ReputationParams {
-    bundle_invalidation_ops_seen_staked_penalty: u64,
-    bundle_invalidation_ops_seen_unstaked_penalty: u64,
-    same_unstaked_entity_mempool_count: u64,
-    min_inclusion_rate_denominator: u64,
-    inclusion_rate_factor: u64,
-    throttling_slack: u64,
-    ban_slack: u64,
+pub struct ReputationParams {
+    /// Value `ops_seen` is set to when a staked entity invalidates a bundle
+    pub bundle_invalidation_ops_seen_staked_penalty: u64,
+    /// Amount `ops_seen` is increased by when an unstaked entity invalidates a bundle
+    pub bundle_invalidation_ops_seen_unstaked_penalty: u64,
+    /// Number of ops an unstaked entity can have in the mempool before inclusion is considered
+    pub same_unstaked_entity_mempool_count: u64,
+    /// An entity is expected to have at least `ops_seen / min_inclusion_rate_denominator`
+    /// ops included
+    pub min_inclusion_rate_denominator: u64,
+    /// Factor applied to the inclusion rate when computing the ops allowed for an unstaked entity
+    pub inclusion_rate_factor: u64,
+    /// Number of missing inclusions tolerated before an entity is throttled
+    pub throttling_slack: u64,
+    /// Number of missing inclusions tolerated before an entity is banned
+    pub ban_slack: u64,
+    /// Interval at which `ops_seen` and `ops_included` are decayed
+    pub decay_interval: Duration,
+    /// On each decay, `ops_seen` and `ops_included` are reduced by `1 / decay_denominator`
+    pub decay_denominator: u64,
 }
 
 impl Default for ReputationParams {
@@ -213,17 +326,21 @@ impl Default for ReputationParams {
             inclusion_rate_factor: 10,
             throttling_slack: 10,
             ban_slack: 50,
+            decay_interval: Duration::from_secs(60 * 60),
+            decay_denominator: 24,
         }
     }
 }
 
 impl ReputationParams {
-    pub(crate) fn bundler_default() -> Self {
+    /// Default reputation parameters for a bundler
+    pub fn bundler_default() -> Self {
         Self::default()
     }
 
+    /// Default reputation parameters for a non-bundling client
     #[allow(dead_code)]
-    pub(crate) fn client_default() -> Self {
+    pub fn client_default() -> Self {
         Self {
             min_inclusion_rate_denominator: 100,
             ..Self::default()
@@ -239,6 +356,8 @@ struct AddressReputation {
     allowlist: HashSet<Address>,
     counts: HashMap<Address, AddressCount>,
     params: ReputationParams,
+    // When the counts were last decayed
+    last_decay: SystemTime,
 }
 
 impl AddressReputation {
@@ -248,6 +367,7 @@ impl AddressReputation {
             allowlist: HashSet::new(),
             counts: HashMap::new(),
             params,
+            last_decay: SystemTime::now(),
         }
     }
 
@@ -331,23 +451,106 @@ impl AddressReputation {
     }
 
     fn hourly_update(&mut self) {
+        let denominator = self.params.decay_denominator.max(1);
         for count in self.counts.values_mut() {
-            count.ops_seen -= count.ops_seen / 24;
-            count.ops_included -= count.ops_included / 24;
+            count.ops_seen -= count.ops_seen / denominator;
+            count.ops_included -= count.ops_included / denominator;
         }
         self.counts
             .retain(|_, count| count.ops_seen > 0 || count.ops_included > 0);
     }
+
+    fn decay(&mut self, now: SystemTime) {
+        self.hourly_update();
+        self.last_decay = now;
+    }
+
+    // Time until the next decay is due
+    fn next_decay_in(&self, now: SystemTime) -> Duration {
+        (self.last_decay + self.params.decay_interval)
+            .duration_since(now)
+            .unwrap_or_default()
+    }
+
+    fn snapshot(&self) -> ReputationSnapshot {
+        ReputationSnapshot {
+            last_decay_at: self
+                .last_decay
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            counts: self.counts.clone(),
+        }
+    }
+
+    // Replaces the counts with those from the snapshot, applying a decay for every
+    // decay interval that elapsed since the snapshot's last decay, so decays keep
+    // their schedule across restarts
+    fn restore(&mut self, snapshot: ReputationSnapshot, now: SystemTime) {
+        self.counts = snapshot.counts;
+
+        let last_decay = UNIX_EPOCH + Duration::from_secs(snapshot.last_decay_at);
+        let interval_secs = self.params.decay_interval.as_secs().max(1);
+        let elapsed = now.duration_since(last_decay).unwrap_or_default();
+        let decays = elapsed.as_secs() / interval_secs;
+        self.last_decay = last_decay + Duration::from_secs(decays * interval_secs);
+
+        let denominator = self.params.decay_denominator.max(1);
+        for _ in 0..decays {
+            // once every count is below the denominator further decays are no-ops
+            if self
+                .counts
+                .values()
+                .all(|c| c.ops_seen < denominator && c.ops_included < denominator)
+            {
+                break;
+            }
+            self.hourly_update();
+        }
+    }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 struct AddressCount {
     ops_seen: u64,
     ops_included: u64,
 }
 
+/// The reputation counts written to disk, so reputation survives restarts
+#[derive(Debug, Serialize, Deserialize)]
+struct ReputationSnapshot {
+    /// Seconds since the unix epoch at which the counts were last decayed
+    last_decay_at: u64,
+    counts: HashMap<Address, AddressCount>,
+}
+
+impl ReputationSnapshot {
+    fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_slice(&contents)?))
+    }
+
+    // Writes to a temporary file first so a crash mid-write never leaves a
+    // truncated snapshot behind
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
 
     // Test AddressReputation
@@ -439,6 +642,39 @@ mod tests {
         assert_eq!(counts.ops_included, 1000 - 1000 / 24);
     }
 
+    #[test]
+    fn decay_with_custom_denominator() {
+        let addr = Address::random();
+        let mut reputation = AddressReputation::new(ReputationParams {
+            decay_denominator: 2,
+            ..ReputationParams::bundler_default()
+        });
+        reputation.set_reputation(addr, 1000, 100);
+
+        reputation.hourly_update();
+        let counts = reputation.counts.get(&addr).unwrap();
+        assert_eq!(counts.ops_seen, 500);
+        assert_eq!(counts.ops_included, 50);
+    }
+
+    #[test]
+    fn reputation_custom_slack() {
+        let addr = Address::random();
+        let params = ReputationParams {
+            throttling_slack: 0,
+            ban_slack: 1,
+            ..ReputationParams::bundler_default()
+        };
+        let mut reputation = AddressReputation::new(params);
+
+        reputation.set_reputation(addr, 100, 10);
+        assert_eq!(reputation.status(addr), ReputationStatus::Ok);
+        reputation.set_reputation(addr, 100, 9);
+        assert_eq!(reputation.status(addr), ReputationStatus::Throttled);
+        reputation.set_reputation(addr, 100, 8);
+        assert_eq!(reputation.status(addr), ReputationStatus::Banned);
+    }
+
     #[test]
     fn test_blocklist() {
         let addr = Address::random();
@@ -459,12 +695,47 @@ mod tests {
         assert_eq!(reputation.status(addr), ReputationStatus::Ok);
     }
 
+    #[test]
+    fn test_allowlist_ignores_penalties() {
+        let addr = Address::random();
+        let mut reputation = AddressReputation::new(ReputationParams::bundler_default())
+            .with_allowlist(HashSet::from([addr]));
+        reputation.handle_srep_050_penalty(addr);
+        reputation.handle_urep_030_penalty(addr);
+
+        assert_eq!(reputation.status(addr), ReputationStatus::Ok);
+    }
+
+    #[test]
+    fn test_blocklist_ignores_inclusions() {
+        let addr = Address::random();
+        let mut reputation = AddressReputation::new(ReputationParams::bundler_default())
+            .with_blocklist(HashSet::from([addr]));
+        reputation.set_reputation(addr, 1000, 1000);
+
+        assert_eq!(reputation.status(addr), ReputationStatus::Banned);
+    }
+
+    #[test]
+    fn test_blocklist_precedence() {
+        let addr = Address::random();
+        let reputation = AddressReputation::new(ReputationParams::bundler_default())
+            .with_allowlist(HashSet::from([addr]))
+            .with_blocklist(HashSet::from([addr]));
+
+        assert_eq!(reputation.status(addr), ReputationStatus::Banned);
+    }
+
     // Test HourlyMovingAverageReputation
 
     #[test]
     fn manager_seen_included() {
-        let manager =
-            HourlyMovingAverageReputation::new(ReputationParams::bundler_default(), None, None);
+        let manager = HourlyMovingAverageReputation::new(
+            ReputationParams::bundler_default(),
+            None,
+            None,
+            None,
+        );
         let addrs = [Address::random(), Address::random(), Address::random()];
 
         for _ in 0..10 {
@@ -484,10 +755,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn manager_allowlist_blocklist() {
+        let allowed = Address::random();
+        let blocked = Address::random();
+        let manager = HourlyMovingAverageReputation::new(
+            ReputationParams::bundler_default(),
+            Some(HashSet::from([blocked])),
+            Some(HashSet::from([allowed])),
+            None,
+        );
+        // setting counts through the debug API doesn't change a listed status
+        manager.set_reputation(allowed, 1000000, 0);
+        manager.set_reputation(blocked, 0, 1000000);
+
+        assert_eq!(manager.status(allowed), ReputationStatus::Ok);
+        assert_eq!(manager.status(blocked), ReputationStatus::Banned);
+    }
+
     #[test]
     fn manager_set_dump_reputation() {
-        let manager =
-            HourlyMovingAverageReputation::new(ReputationParams::bundler_default(), None, None);
+        let manager = HourlyMovingAverageReputation::new(
+            ReputationParams::bundler_default(),
+            None,
+            None,
+            None,
+        );
         let addrs = [Address::random(), Address::random(), Address::random()];
 
         for addr in &addrs {
@@ -501,4 +794,123 @@ mod tests {
             assert!(addrs.contains(&rep.address));
         }
     }
+
+    #[test]
+    fn restore_applies_decay_for_downtime() {
+        let addr = Address::random();
+        let params = ReputationParams::bundler_default();
+        let mut reputation = AddressReputation::new(params);
+        reputation.set_reputation(addr, 1000, 100);
+
+        let last_decay = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        reputation.last_decay = last_decay;
+        let snapshot = reputation.snapshot();
+
+        let mut restored = AddressReputation::new(params);
+        restored.restore(snapshot, last_decay + params.decay_interval * 2);
+        let counts = restored.counts.get(&addr).unwrap();
+
+        reputation.hourly_update();
+        reputation.hourly_update();
+        let expected = reputation.counts.get(&addr).unwrap();
+        assert_eq!(counts.ops_seen, expected.ops_seen);
+        assert_eq!(counts.ops_included, expected.ops_included);
+        assert_eq!(restored.last_decay, last_decay + params.decay_interval * 2);
+    }
+
+    #[test]
+    fn restore_keeps_decay_schedule() {
+        let addr = Address::random();
+        let params = ReputationParams::bundler_default();
+        let mut reputation = AddressReputation::new(params);
+        reputation.set_reputation(addr, 1000, 100);
+
+        // saved shortly before a decay was due, and restored shortly after
+        let last_decay = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        reputation.last_decay = last_decay;
+        let snapshot = reputation.snapshot();
+        let now = last_decay + params.decay_interval + params.decay_interval / 4;
+
+        let mut restored = AddressReputation::new(params);
+        restored.restore(snapshot, now);
+        let counts = restored.counts.get(&addr).unwrap();
+        assert_eq!(counts.ops_seen, 1000 - 1000 / params.decay_denominator);
+        assert_eq!(restored.last_decay, last_decay + params.decay_interval);
+        assert_eq!(
+            restored.next_decay_in(now),
+            params.decay_interval - params.decay_interval / 4
+        );
+    }
+
+    #[test]
+    fn restore_without_downtime() {
+        let addr = Address::random();
+        let mut reputation = AddressReputation::new(ReputationParams::bundler_default());
+        reputation.set_reputation(addr, 1000, 100);
+
+        let mut restored = AddressReputation::new(ReputationParams::bundler_default());
+        restored.restore(reputation.snapshot(), SystemTime::now());
+        let counts = restored.counts.get(&addr).unwrap();
+        assert_eq!(counts.ops_seen, 1000);
+        assert_eq!(counts.ops_included, 100);
+    }
+
+    #[tokio::test]
+    async fn manager_persist_and_reload() {
+        let path = std::env::temp_dir().join(format!("reputation_{:?}.json", Address::random()));
+        let banned = Address::random();
+        let params = ReputationParams::bundler_default();
+
+        let manager = HourlyMovingAverageReputation::new(params, None, None, Some(path.clone()));
+        manager.set_reputation(banned, 1000, 0);
+        assert_eq!(manager.status(banned), ReputationStatus::Banned);
+        manager.persist().await;
+
+        let reloaded = HourlyMovingAverageReputation::new(params, None, None, Some(path.clone()));
+        reloaded.load_persisted().await;
+        assert_eq!(reloaded.status(banned), ReputationStatus::Banned);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn manager_persists_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("reputation_{:?}.json", Address::random()));
+        let banned = Address::random();
+        let params = ReputationParams::bundler_default();
+
+        let manager = Arc::new(HourlyMovingAverageReputation::new(
+            params,
+            None,
+            None,
+            Some(path.clone()),
+        ));
+        let shutdown_token = CancellationToken::new();
+        let runner = Arc::clone(&manager);
+        let run_token = shutdown_token.clone();
+        let handle = tokio::spawn(async move { runner.run(run_token).await });
+
+        manager.set_reputation(banned, 1000, 0);
+        shutdown_token.cancel();
+        handle.await.unwrap();
+
+        let reloaded = HourlyMovingAverageReputation::new(params, None, None, Some(path.clone()));
+        reloaded.load_persisted().await;
+        assert_eq!(reloaded.status(banned), ReputationStatus::Banned);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn manager_missing_persistence_file() {
+        let path = std::env::temp_dir().join(format!("reputation_{:?}.json", Address::random()));
+        let manager = HourlyMovingAverageReputation::new(
+            ReputationParams::bundler_default(),
+            None,
+            None,
+            Some(path),
+        );
+        manager.load_persisted().await;
+        assert!(manager.dump_reputation().is_empty());
+    }
 }
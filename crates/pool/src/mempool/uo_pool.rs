@@ -12,25 +12,30 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use ethers::types::{Address, H256, U256};
+use futures_util::{stream, StreamExt};
 use itertools::Itertools;
 use parking_lot::RwLock;
-use rundler_sim::{Prechecker, Simulator};
-use rundler_types::{Entity, EntityUpdate, EntityUpdateType, UserOperation};
+use rundler_provider::EntryPoint;
+use rundler_sim::{gas, Prechecker, SimulationError, Simulator};
+use rundler_types::{Entity, EntityUpdate, EntityUpdateType, GasFees, UserOperation};
 use rundler_utils::emit::WithEntryPoint;
 use tokio::sync::broadcast;
 use tonic::async_trait;
-use tracing::info;
+use tracing::{info, warn};
 
 use super::{
+    check_op_metadata,
     error::{MempoolError, MempoolResult},
     pool::PoolInner,
     reputation::{Reputation, ReputationManager, ReputationStatus},
-    Mempool, OperationOrigin, PoolConfig, PoolOperation,
+    sender_shard_index, AddOpOptions, GetOpsFilter, Mempool, MempoolEvent, OpPriority,
+    OperationOrigin, OperationRank, PoolConfig, PoolOperation, RemovalReason, SenderSummary,
 };
 use crate::{
     chain::ChainUpdate,
@@ -42,33 +47,217 @@ use crate::{
 /// Wrapper around a pool object that implements thread-safety
 /// via a RwLock. Safe to call from multiple threads. Methods
 /// block on write locks.
-pub(crate) struct UoPool<R: ReputationManager, P: Prechecker, S: Simulator> {
+pub(crate) struct UoPool<R: ReputationManager + ?Sized, P: Prechecker, S: Simulator, E: EntryPoint>
+{
     config: PoolConfig,
     reputation: Arc<R>,
     state: RwLock<UoPoolState>,
     event_sender: broadcast::Sender<WithEntryPoint<OpPoolEvent>>,
+    mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
     prechecker: P,
     simulator: S,
+    entry_point: E,
 }
 
+/// Number of recently removed operations to remember the removal reason of
+const REMOVED_OPS_HISTORY_SIZE: usize = 10_000;
+/// How long an operation received from a peer is remembered, so that copies of it
+/// received from other peers are rejected without being validated again
+const SEEN_EXTERNAL_OPS_TTL: Duration = Duration::from_secs(60);
+/// Maximum number of operations received from peers that are remembered at once
+const SEEN_EXTERNAL_OPS_CAPACITY: usize = 10_000;
+/// Maximum number of bundle candidates simulated at once when revalidating
+const REVALIDATION_CONCURRENCY: usize = 8;
+/// Number of pool operations, in bundling order, searched for an operation's rank.
+/// Operations further back are reported without a rank.
+const MAX_RANKED_OPERATIONS: usize = 1000;
+
 struct UoPoolState {
     pool: PoolInner,
     throttled_ops: HashMap<H256, u64>,
+    /// Ops that are only valid while the chain head has the given block hash
+    conditional_ops: HashMap<H256, H256>,
+    /// Ops that are removed once the given block number has passed
+    block_range_ops: HashMap<H256, u64>,
+    removed_ops: RemovedOps,
+    rate_limiter: RateLimiter,
+    seen_external_ops: SeenOps,
+    /// Whether ops were still valid when revalidated at the current block
+    revalidated_ops: HashMap<H256, bool>,
+    /// On-chain nonces of senders at the current block, by sender and nonce key
+    on_chain_nonces: HashMap<(Address, U256), U256>,
     block_number: u64,
 }
 
-impl<R, P, S> UoPool<R, P, S>
+/// Bounded history of why operations were removed from the pool. Once full,
+/// the oldest entries are forgotten first.
+struct RemovedOps {
+    capacity: usize,
+    order: VecDeque<H256>,
+    reasons: HashMap<H256, RemovalReason>,
+}
+
+impl RemovedOps {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::new(),
+            reasons: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, hash: H256, reason: RemovalReason) {
+        if self.reasons.insert(hash, reason).is_some() {
+            return;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.reasons.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forgets an operation that is back in the pool, e.g. after being unmined in a reorg
+    fn remove(&mut self, hash: H256) {
+        if self.reasons.remove(&hash).is_some() {
+            self.order.retain(|h| *h != hash);
+        }
+    }
+
+    fn get(&self, hash: H256) -> Option<RemovalReason> {
+        self.reasons.get(&hash).copied()
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.reasons.clear();
+    }
+}
+
+/// Hashes of operations received from peers within a time window, oldest first.
+/// Once full, the oldest entries are forgotten first.
+struct SeenOps {
+    ttl: Duration,
+    capacity: usize,
+    order: VecDeque<(H256, Instant)>,
+    seen_at: HashMap<H256, Instant>,
+}
+
+impl SeenOps {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            order: VecDeque::new(),
+            seen_at: HashMap::new(),
+        }
+    }
+
+    /// Records an operation as seen, returning false if it was already seen
+    /// within the window
+    fn insert(&mut self, hash: H256, now: Instant) -> bool {
+        while let Some(&(_, seen_at)) = self.order.front() {
+            if now.duration_since(seen_at) < self.ttl {
+                break;
+            }
+            self.pop_oldest();
+        }
+        if self.seen_at.contains_key(&hash) {
+            return false;
+        }
+        while !self.order.is_empty() && self.order.len() >= self.capacity {
+            self.pop_oldest();
+        }
+        self.seen_at.insert(hash, now);
+        self.order.push_back((hash, now));
+        true
+    }
+
+    /// Forgets an operation, so that it is no longer reported as seen
+    fn remove(&mut self, hash: H256) {
+        // The entry in `order` is skipped once it is the oldest
+        self.seen_at.remove(&hash);
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some((hash, seen_at)) = self.order.pop_front() {
+            // The op may have been removed and seen again since
+            if self.seen_at.get(&hash) == Some(&seen_at) {
+                self.seen_at.remove(&hash);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.seen_at.clear();
+    }
+}
+
+/// Rolling window of the times at which operations using each rate limited
+/// entity were admitted to the pool.
+#[derive(Default)]
+struct RateLimiter {
+    admitted: HashMap<Entity, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    /// Returns the first entity that has already used up its limit within its window
+    fn limited_entity(
+        &self,
+        limits: &HashMap<Entity, (u32, Duration)>,
+        entities: impl IntoIterator<Item = Entity>,
+        now: Instant,
+    ) -> Option<Entity> {
+        entities.into_iter().find(|entity| {
+            let Some((max_ops, window)) = limits.get(entity) else {
+                return false;
+            };
+            let admitted = self.admitted.get(entity).map_or(0, |times| {
+                times
+                    .iter()
+                    .filter(|t| now.duration_since(**t) < *window)
+                    .count()
+            });
+            admitted >= *max_ops as usize
+        })
+    }
+
+    fn record(
+        &mut self,
+        limits: &HashMap<Entity, (u32, Duration)>,
+        entities: impl IntoIterator<Item = Entity>,
+        now: Instant,
+    ) {
+        for entity in entities {
+            let Some((_, window)) = limits.get(&entity) else {
+                continue;
+            };
+            let times = self.admitted.entry(entity).or_default();
+            while matches!(times.front(), Some(t) if now.duration_since(*t) >= *window) {
+                times.pop_front();
+            }
+            times.push_back(now);
+        }
+    }
+}
+
+impl<R, P, S, E> UoPool<R, P, S, E>
 where
-    R: ReputationManager,
+    R: ReputationManager + ?Sized,
     P: Prechecker,
     S: Simulator,
+    E: EntryPoint,
 {
     pub(crate) fn new(
         config: PoolConfig,
         reputation: Arc<R>,
         event_sender: broadcast::Sender<WithEntryPoint<OpPoolEvent>>,
+        mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
         prechecker: P,
         simulator: S,
+        entry_point: E,
     ) -> Self {
         Self {
             config: config.clone(),
@@ -76,122 +265,326 @@ where
             state: RwLock::new(UoPoolState {
                 pool: PoolInner::new(config.into()),
                 throttled_ops: HashMap::new(),
+                conditional_ops: HashMap::new(),
+                block_range_ops: HashMap::new(),
+                removed_ops: RemovedOps::new(REMOVED_OPS_HISTORY_SIZE),
+                rate_limiter: RateLimiter::default(),
+                seen_external_ops: SeenOps::new(SEEN_EXTERNAL_OPS_TTL, SEEN_EXTERNAL_OPS_CAPACITY),
+                revalidated_ops: HashMap::new(),
+                on_chain_nonces: HashMap::new(),
                 block_number: 0,
             }),
             event_sender,
+            mempool_event_sender,
             prechecker,
             simulator,
+            entry_point,
         }
     }
 
-    fn emit(&self, event: OpPoolEvent) {
-        let _ = self.event_sender.send(WithEntryPoint {
-            entry_point: self.config.entry_point,
-            event,
-        });
+    fn shard_index(&self, sender: Address) -> u64 {
+        sender_shard_index(sender, self.config.num_shards)
     }
-}
 
-#[async_trait]
-impl<R, P, S> Mempool for UoPool<R, P, S>
-where
-    R: ReputationManager,
-    P: Prechecker,
-    S: Simulator,
-{
-    fn on_chain_update(&self, update: &ChainUpdate) {
-        let mut state = self.state.write();
-        let deduped_ops = update.deduped_ops();
-        let mined_ops = deduped_ops
-            .mined_ops
-            .iter()
-            .filter(|op| op.entry_point == self.config.entry_point);
-        let unmined_ops = deduped_ops
-            .unmined_ops
-            .iter()
-            .filter(|op| op.entry_point == self.config.entry_point);
-        let mut mined_op_count = 0;
-        let mut unmined_op_count = 0;
-        for op in mined_ops {
-            if op.entry_point != self.config.entry_point {
-                continue;
-            }
+    // The pool's operations from a shard in the order they are considered for a
+    // bundle. Ops whose sender has an op ahead of them are filtered out, so later
+    // nonces from a sender are not selected alongside its earlier ones.
+    fn bundle_candidates(
+        &self,
+        shard_index: u64,
+    ) -> impl Iterator<Item = (Arc<PoolOperation>, OpPriority)> + '_ {
+        let (ordered_ops, block_number, base_fee) = {
+            let state = self.state.read();
+            (
+                state.pool.best_operations_with_priority(),
+                state.block_number,
+                state.pool.base_fee().unwrap_or_default(),
+            )
+        };
+        let mut senders = HashSet::<Address>::new();
+        ordered_ops.filter(move |(op, _)| {
+            self.is_bundle_candidate(op, shard_index, block_number, base_fee, &mut senders)
+        })
+    }
 
-            // Remove throttled ops that were included in the block
-            state.throttled_ops.remove(&op.hash);
+    /// Whether `op`, visited in bundle selection order, is selected for a bundle
+    /// of the shard. `senders` holds the senders of the ops visited so far.
+    fn is_bundle_candidate(
+        &self,
+        op: &PoolOperation,
+        shard_index: u64,
+        block_number: u64,
+        base_fee: U256,
+        senders: &mut HashSet<Address>,
+    ) -> bool {
+        // Ops whose max fee is below the base fee can't be included now, so they
+        // are skipped without holding their sender's slot. They stay in the pool
+        // for when the base fee drops.
+        //
+        // Ops whose block range excludes the block a bundle built now would land
+        // in are skipped, but still hold their sender's slot
+        self.shard_index(op.uo.sender) == shard_index
+            && op.uo.max_fee_per_gas >= base_fee
+            && senders.insert(op.uo.sender)
+            && op.valid_block_range.contains(block_number + 1)
+    }
 
-            if let Some(op) = state.pool.mine_operation(op, update.latest_block_number) {
-                // Only account for a staked entity once
-                for entity_addr in op.staked_entities().map(|e| e.address).unique() {
-                    self.reputation.add_included(entity_addr);
+    // Use optimistic gas limits that assume no postOp calls, as the builder does,
+    // so that it can still pack a full bundle after simulating the selected ops.
+    fn bundle_gas_limit(&self, op: &PoolOperation) -> U256 {
+        gas::user_operation_execution_gas_limit(
+            &op.uo,
+            self.config.chain_id,
+            &self.config.precheck_settings.gas_overheads,
+            false,
+            false,
+        )
+    }
+
+    /// Selects bundle candidates matching the filter within the bundle gas limit,
+    /// skipping `filter.offset` of them and returning at most `max`.
+    async fn select_operations(
+        &self,
+        max: usize,
+        shard_index: u64,
+        filter: GetOpsFilter,
+    ) -> MempoolResult<Vec<(Arc<PoolOperation>, OpPriority)>> {
+        if shard_index >= self.config.num_shards {
+            Err(anyhow::anyhow!("Invalid shard ID"))?;
+        }
+
+        let wanted = max.saturating_add(filter.offset as usize);
+        let mut gas_left = U256::from(self.config.max_bundle_gas);
+        let mut candidates = self
+            .bundle_candidates(shard_index)
+            .filter(|(op, _)| filter.matches(op))
+            .peekable();
+
+        let mut selected = vec![];
+        loop {
+            // Take as many candidates as could still be selected if all of them are
+            // valid. A candidate that only fits if some of the batch turn out invalid
+            // waits for the next batch.
+            let mut batch = vec![];
+            let mut batch_gas_left = gas_left;
+            while selected.len() + batch.len() < wanted {
+                let Some((op, _)) = candidates.peek() else {
+                    break;
+                };
+                let gas = self.bundle_gas_limit(op);
+                if gas > batch_gas_left {
+                    if batch.is_empty() {
+                        candidates.next();
+                        continue;
+                    }
+                    break;
                 }
-                mined_op_count += 1;
+                batch_gas_left -= gas;
+                let (op, priority) = candidates.next().unwrap();
+                batch.push((op, priority, gas));
             }
-        }
-        for op in unmined_ops {
-            if op.entry_point != self.config.entry_point {
-                continue;
+            if batch.is_empty() {
+                break;
             }
 
-            if let Some(op) = state.pool.unmine_operation(op.hash) {
-                // Only account for a staked entity once
-                for entity_addr in op.staked_entities().map(|e| e.address).unique() {
-                    self.reputation.add_included(entity_addr);
+            let valid = if self.config.revalidate_before_bundle {
+                stream::iter(&batch)
+                    .map(|(op, _, _)| self.revalidate_operation(op))
+                    .buffered(REVALIDATION_CONCURRENCY)
+                    .collect::<Vec<_>>()
+                    .await
+            } else {
+                vec![true; batch.len()]
+            };
+            for ((op, priority, gas), valid) in batch.into_iter().zip(valid) {
+                if valid {
+                    gas_left -= gas;
+                    selected.push((op, priority));
                 }
-                unmined_op_count += 1;
             }
         }
-        if mined_op_count > 0 {
-            info!(
-                "{mined_op_count} op(s) mined on entry point {:?} when advancing to block with number {}, hash {:?}.",
-                self.config.entry_point,
-                update.latest_block_number,
-                update.latest_block_hash,
-            );
-        }
-        if unmined_op_count > 0 {
-            info!(
-                "{unmined_op_count} op(s) unmined in reorg on entry point {:?} when advancing to block with number {}, hash {:?}.",
-                self.config.entry_point,
-                update.latest_block_number,
-                update.latest_block_hash,
-            );
+        Ok(selected.into_iter().skip(filter.offset as usize).collect())
+    }
+
+    /// Re-simulates a pooled op against the latest block, returning whether it
+    /// is still valid. Results are cached until the next block.
+    async fn revalidate_operation(&self, op: &PoolOperation) -> bool {
+        let hash = op.uo.op_hash(self.config.entry_point, self.config.chain_id);
+        let block_number = {
+            let state = self.state.read();
+            if let Some(valid) = state.revalidated_ops.get(&hash) {
+                return *valid;
+            }
+            state.block_number
+        };
+
+        let valid = match self
+            .simulator
+            .simulate_validation(op.uo.clone(), None, Some(op.expected_code_hash))
+            .await
+        {
+            Ok(sim_result) => sim_result.aggregator.is_none(),
+            Err(SimulationError::Violations(violations)) => {
+                info!("Op {hash:?} failed revalidation, skipping it: {violations:?}");
+                false
+            }
+            Err(SimulationError::Other(error)) => {
+                // Don't drop ops because the provider failed, and retry next time
+                warn!("Failed to revalidate op {hash:?}, keeping it: {error:?}");
+                return true;
+            }
+        };
+
+        let mut state = self.state.write();
+        // Don't cache a result simulated before a new block arrived
+        if state.block_number == block_number {
+            state.revalidated_ops.insert(hash, valid);
         }
-        UoPoolMetrics::update_ops_seen(
-            mined_op_count as isize - unmined_op_count as isize,
-            self.config.entry_point,
-        );
-        UoPoolMetrics::increment_unmined_operations(unmined_op_count, self.config.entry_point);
+        valid
+    }
 
-        state
-            .pool
-            .forget_mined_operations_before_block(update.earliest_remembered_block_number);
-        // Remove throttled ops that are too old
-        let mut to_remove = HashSet::new();
-        for (hash, block) in state.throttled_ops.iter() {
-            if update.latest_block_number - block > self.config.throttled_entity_live_blocks {
-                to_remove.insert(*hash);
+    // Returns whether the address meets the minimum stake on the entry point
+    async fn is_staked(&self, address: Address) -> MempoolResult<bool> {
+        let info = self.entry_point.get_deposit_info(address).await?;
+        let settings = &self.config.sim_settings;
+        Ok(
+            U256::from(info.stake) >= U256::from(settings.min_stake_value)
+                && info.unstake_delay_sec >= settings.min_unstake_delay,
+        )
+    }
+
+    // Returns the sender's next nonce for the key on chain, cached until the next block
+    async fn on_chain_nonce(&self, sender: Address, key: U256) -> MempoolResult<U256> {
+        let block_number = {
+            let state = self.state.read();
+            if let Some(nonce) = state.on_chain_nonces.get(&(sender, key)) {
+                return Ok(*nonce);
             }
+            state.block_number
+        };
+
+        let nonce = self.entry_point.get_nonce(sender, key).await?;
+
+        let mut state = self.state.write();
+        // Don't cache a nonce read before a new block arrived
+        if state.block_number == block_number {
+            state.on_chain_nonces.insert((sender, key), nonce);
         }
-        for hash in to_remove {
-            state.pool.remove_operation_by_hash(hash);
-            state.throttled_ops.remove(&hash);
+        Ok(nonce)
+    }
+
+    fn emit(&self, event: OpPoolEvent) {
+        let _ = self.event_sender.send(WithEntryPoint {
+            entry_point: self.config.entry_point,
+            event,
+        });
+    }
+
+    // Sent while holding the state lock, so that subscribers see changes in the
+    // order they were applied to the pool
+    fn emit_mempool_event(&self, event: MempoolEvent) {
+        let _ = self.mempool_event_sender.send(WithEntryPoint {
+            entry_point: self.config.entry_point,
+            event,
+        });
+    }
+
+    // Adds a validated operation to the pool, recording any operations it displaced
+    fn insert_operation(
+        &self,
+        state: &mut UoPoolState,
+        pool_op: &PoolOperation,
+    ) -> MempoolResult<H256> {
+        let added = state.pool.add_operation(pool_op.clone());
+        self.record_displaced(
+            state,
+            pool_op
+                .uo
+                .op_hash(self.config.entry_point, self.config.chain_id),
+        );
+        let hash = added?;
+        self.emit_mempool_event(MempoolEvent::OpAdded {
+            op_hash: hash,
+            op: pool_op.uo.clone(),
+        });
+        state.removed_ops.remove(hash);
+        if let Some(max_block) = pool_op.valid_block_range.max_block {
+            state.block_range_ops.insert(hash, max_block);
         }
-        state.block_number = update.latest_block_number;
+        Ok(hash)
     }
 
-    fn entry_point(&self) -> Address {
-        self.config.entry_point
+    // Records the operations replaced or evicted while adding the operation
+    // with hash `added` to the pool
+    fn record_displaced(&self, state: &mut UoPoolState, added: H256) {
+        let added_to_pool = state.pool.contains(added);
+        for (hash, reason) in state.pool.take_displaced() {
+            // The added operation itself was evicted, so it was never in the pool
+            if hash == added {
+                continue;
+            }
+            state.removed_ops.insert(hash, reason);
+            state.throttled_ops.remove(&hash);
+            state.conditional_ops.remove(&hash);
+            state.block_range_ops.remove(&hash);
+            let event = if reason == RemovalReason::Replaced && added_to_pool {
+                MempoolEvent::OpReplaced {
+                    op_hash: hash,
+                    replaced_by: added,
+                }
+            } else {
+                MempoolEvent::OpRemoved {
+                    op_hash: hash,
+                    reason,
+                }
+            };
+            self.emit_mempool_event(event);
+        }
     }
 
-    async fn add_operation(
+    /// Validates an operation and adds it to the pool
+    async fn validate_and_add_operation(
         &self,
         origin: OperationOrigin,
         op: UserOperation,
+        options: AddOpOptions,
     ) -> MempoolResult<H256> {
+        let AddOpOptions {
+            expected_block_hash,
+            valid_block_range,
+            metadata,
+        } = options;
         // TODO(danc) aggregator reputation is not implemented
         // TODO(danc) catch ops with aggregators prior to simulation and reject
 
+        // Senders are routed to a single pool shard so that their ops can replace each other
+        let pool_shard = sender_shard_index(op.sender, self.config.pool_shard_count);
+        if pool_shard != self.config.pool_shard_index {
+            return Err(MempoolError::WrongPoolShard(
+                pool_shard,
+                self.config.pool_shard_index,
+            ));
+        }
+
+        check_op_metadata(&metadata)?;
+
+        let op_size = op.abi_encoded_size();
+        if op_size > self.config.max_op_size_bytes {
+            return Err(MempoolError::OperationTooLarge(
+                op_size,
+                self.config.max_op_size_bytes,
+            ));
+        }
+
+        if op.max_priority_fee_per_gas < self.config.min_priority_fee_per_gas
+            || op.max_fee_per_gas < self.config.min_max_fee_per_gas
+        {
+            return Err(MempoolError::FeesBelowMinimum(
+                self.config.min_priority_fee_per_gas,
+                self.config.min_max_fee_per_gas,
+            ));
+        }
+
         // Check reputation of entities in involved in the operation
         // If throttled, entity can have THROTTLED_ENTITY_MEMPOOL_COUNT inflight operation at a time, else reject
         // If banned, reject
@@ -225,10 +618,38 @@ where
             );
         }
 
+        // Reject entities that have already had too many ops admitted within their
+        // rate limit window, before spending any resources on simulation
+        if let Some(entity) = self.state.read().rate_limiter.limited_entity(
+            &self.config.rate_limits,
+            op.entities(),
+            Instant::now(),
+        ) {
+            return Err(MempoolError::RateLimited(entity));
+        }
+
         // Check if op is already known or replacing another, and if so, ensure its fees are high enough
         // do this before simulation to save resources
         self.state.read().pool.check_replacement(&op)?;
 
+        // Reject ops whose nonce has already been used on chain, as they can never execute
+        let on_chain_nonce = self.on_chain_nonce(op.sender, op.nonce >> 64).await?;
+        if op.nonce < on_chain_nonce {
+            return Err(MempoolError::NonceTooLow(op.nonce, on_chain_nonce));
+        }
+
+        // Limit spam from peers by requiring an entity with stake at risk, before
+        // spending any resources on simulation
+        if self.config.require_stake_for_gossip
+            && matches!(origin, OperationOrigin::External)
+            && !self.is_staked(op.sender).await?
+        {
+            match op.paymaster() {
+                Some(paymaster) if self.is_staked(paymaster).await? => {}
+                _ => return Err(MempoolError::StakeRequiredForGossip),
+            }
+        }
+
         // Prechecks
         self.prechecker.check(&op).await?;
 
@@ -240,6 +661,14 @@ where
         if let Some(agg) = &sim_result.aggregator {
             return Err(MempoolError::UnsupportedAggregator(agg.address));
         }
+        if let Some(expected_block_hash) = expected_block_hash {
+            if expected_block_hash != sim_result.block_hash {
+                return Err(MempoolError::BlockHashMismatch(
+                    expected_block_hash,
+                    sim_result.block_hash,
+                ));
+            }
+        }
         let valid_time_range = sim_result.valid_time_range;
         let pool_op = PoolOperation {
             uo: op,
@@ -249,16 +678,33 @@ where
             sim_block_hash: sim_result.block_hash,
             entities_needing_stake: sim_result.entities_needing_stake,
             account_is_staked: sim_result.account_is_staked,
+            metadata,
+            valid_block_range,
         };
 
         // Add op to pool
         let (hash, bn) = {
             let mut state = self.state.write();
-            let hash = state.pool.add_operation(pool_op.clone())?;
+            // Check rate limits again as other ops may have been admitted during simulation
+            let now = Instant::now();
+            if let Some(entity) = state.rate_limiter.limited_entity(
+                &self.config.rate_limits,
+                pool_op.uo.entities(),
+                now,
+            ) {
+                return Err(MempoolError::RateLimited(entity));
+            }
+            let hash = self.insert_operation(&mut state, &pool_op)?;
+            state
+                .rate_limiter
+                .record(&self.config.rate_limits, pool_op.uo.entities(), now);
             let bn = state.block_number;
             if throttled {
                 state.throttled_ops.insert(hash, bn);
             }
+            if let Some(expected_block_hash) = expected_block_hash {
+                state.conditional_ops.insert(hash, expected_block_hash);
+            }
             (hash, bn)
         };
 
@@ -286,8 +732,275 @@ where
 
         Ok(hash)
     }
+}
+
+#[async_trait]
+impl<R, P, S, E> Mempool for UoPool<R, P, S, E>
+where
+    R: ReputationManager + ?Sized,
+    P: Prechecker,
+    S: Simulator,
+    E: EntryPoint,
+{
+    fn on_chain_update(&self, update: &ChainUpdate) -> Vec<Arc<PoolOperation>> {
+        let mut state = self.state.write();
+        let deduped_ops = update.deduped_ops();
+        let mined_ops = deduped_ops
+            .mined_ops
+            .iter()
+            .filter(|op| op.entry_point == self.config.entry_point);
+        let unmined_ops = deduped_ops
+            .unmined_ops
+            .iter()
+            .filter(|op| op.entry_point == self.config.entry_point);
+        let mut mined_op_count = 0;
+        let mut unmined_op_count = 0;
+        let mut included_entities = vec![];
+        let mut unincluded_entities = vec![];
+        let mut unmined_op_hashes = vec![];
+        let mut reorged_ops = vec![];
+        for op in mined_ops {
+            if op.entry_point != self.config.entry_point {
+                continue;
+            }
+
+            // Remove throttled and conditional ops that were included in the block
+            state.throttled_ops.remove(&op.hash);
+            state.conditional_ops.remove(&op.hash);
+            state.block_range_ops.remove(&op.hash);
+
+            if let Some(pool_op) = state.pool.mine_operation(op, update.latest_block_number) {
+                // A different op with the same sender and nonce may have been mined
+                let pool_op_hash = pool_op
+                    .uo
+                    .op_hash(self.config.entry_point, self.config.chain_id);
+                let reason = if pool_op_hash == op.hash {
+                    RemovalReason::Mined
+                } else {
+                    RemovalReason::Replaced
+                };
+                state.removed_ops.insert(pool_op_hash, reason);
+                self.emit_mempool_event(MempoolEvent::OpRemoved {
+                    op_hash: pool_op_hash,
+                    reason,
+                });
+                // Only account for a staked entity once
+                included_entities.extend(pool_op.staked_entities().map(|e| e.address).unique());
+                mined_op_count += 1;
+            }
+        }
+        for op in unmined_ops {
+            if op.entry_point != self.config.entry_point {
+                continue;
+            }
+
+            if self.config.revalidate_reorged_ops {
+                // The op's validation may depend on state that was reorged away, so it
+                // is returned to the caller to be validated again before re-admission
+                if let Some(pool_op) = state.pool.take_unmined_operation(op.hash) {
+                    state.removed_ops.remove(op.hash);
+                    unincluded_entities
+                        .extend(pool_op.staked_entities().map(|e| e.address).unique());
+                    unmined_op_hashes.push(op.hash);
+                    unmined_op_count += 1;
+                    reorged_ops.push(pool_op);
+                }
+            } else if let Some(pool_op) = state.pool.unmine_operation(op.hash) {
+                // Putting the op back may have replaced or evicted other ops
+                self.record_displaced(&mut state, op.hash);
+                if state.pool.contains(op.hash) {
+                    self.emit_mempool_event(MempoolEvent::OpAdded {
+                        op_hash: op.hash,
+                        op: pool_op.uo.clone(),
+                    });
+                    if let Some(max_block) = pool_op.valid_block_range.max_block {
+                        state.block_range_ops.insert(op.hash, max_block);
+                    }
+                }
+                state.removed_ops.remove(op.hash);
+                // Only account for a staked entity once
+                unincluded_entities.extend(pool_op.staked_entities().map(|e| e.address).unique());
+                unmined_op_hashes.push(op.hash);
+                unmined_op_count += 1;
+            }
+        }
+        if update.reorg_depth > 0 {
+            self.emit(OpPoolEvent::ReorgDetected {
+                block_number: update.latest_block_number,
+                block_hash: update.latest_block_hash,
+                reorg_depth: update.reorg_depth,
+                unmined_op_hashes,
+            });
+        }
+        // Apply all reputation updates for the block at once to avoid contending
+        // on the reputation lock per entity
+        self.reputation
+            .update_included(&included_entities, &unincluded_entities);
+        if mined_op_count > 0 {
+            info!(
+                "{mined_op_count} op(s) mined on entry point {:?} when advancing to block with number {}, hash {:?}.",
+                self.config.entry_point,
+                update.latest_block_number,
+                update.latest_block_hash,
+            );
+        }
+        if unmined_op_count > 0 {
+            info!(
+                "{unmined_op_count} op(s) unmined in reorg on entry point {:?} when advancing to block with number {}, hash {:?}.",
+                self.config.entry_point,
+                update.latest_block_number,
+                update.latest_block_hash,
+            );
+        }
+        UoPoolMetrics::update_ops_seen(
+            mined_op_count as isize - unmined_op_count as isize,
+            self.config.entry_point,
+        );
+        UoPoolMetrics::increment_unmined_operations(unmined_op_count, self.config.entry_point);
+
+        state
+            .pool
+            .forget_mined_operations_before_block(update.earliest_remembered_block_number);
+        // Reorder by effective gas price at the latest base fee
+        if let Some(base_fee) = update.latest_block_base_fee {
+            state.pool.update_base_fee(base_fee);
+        }
+        // Remove throttled ops that are too old
+        let mut to_remove = HashSet::new();
+        for (hash, block) in state.throttled_ops.iter() {
+            if update.latest_block_number - block > self.config.throttled_entity_live_blocks {
+                to_remove.insert(*hash);
+            }
+        }
+        for hash in to_remove {
+            if state.pool.remove_operation_by_hash(hash).is_some() {
+                self.emit_mempool_event(MempoolEvent::OpRemoved {
+                    op_hash: hash,
+                    reason: RemovalReason::Expired,
+                });
+            }
+            state.throttled_ops.remove(&hash);
+            state.removed_ops.insert(hash, RemovalReason::Expired);
+        }
+        // Remove conditional ops whose expected block is no longer the head
+        let invalidated = state
+            .conditional_ops
+            .iter()
+            .filter(|(_, block_hash)| **block_hash != update.latest_block_hash)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        for hash in invalidated {
+            if state.pool.remove_operation_by_hash(hash).is_some() {
+                self.emit_mempool_event(MempoolEvent::OpRemoved {
+                    op_hash: hash,
+                    reason: RemovalReason::Invalidated,
+                });
+            }
+            state.conditional_ops.remove(&hash);
+            state.removed_ops.insert(hash, RemovalReason::Invalidated);
+        }
+        // Remove ops whose submitter-set block range ends before the next block,
+        // as no bundle built from now on can land within it
+        let expired = state
+            .block_range_ops
+            .iter()
+            .filter(|(_, max_block)| update.latest_block_number + 1 > **max_block)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        for hash in expired {
+            if state.pool.remove_operation_by_hash(hash).is_some() {
+                self.emit_mempool_event(MempoolEvent::OpRemoved {
+                    op_hash: hash,
+                    reason: RemovalReason::Expired,
+                });
+                state.removed_ops.insert(hash, RemovalReason::Expired);
+            }
+            state.block_range_ops.remove(&hash);
+        }
+        // Revalidation results and on-chain nonces only hold for the block they were read at
+        state.revalidated_ops.clear();
+        state.on_chain_nonces.clear();
+        state.block_number = update.latest_block_number;
+        self.emit_mempool_event(MempoolEvent::BlockProcessed {
+            block_number: update.latest_block_number,
+        });
+        reorged_ops
+    }
 
-    fn remove_operations(&self, hashes: &[H256]) {
+    fn entry_point(&self) -> Address {
+        self.config.entry_point
+    }
+
+    fn block_number(&self) -> u64 {
+        self.state.read().block_number
+    }
+
+    fn min_fees(&self) -> GasFees {
+        GasFees {
+            max_fee_per_gas: self.config.min_max_fee_per_gas,
+            max_priority_fee_per_gas: self.config.min_priority_fee_per_gas,
+        }
+    }
+
+    async fn add_operation(
+        &self,
+        origin: OperationOrigin,
+        op: UserOperation,
+        options: AddOpOptions,
+    ) -> MempoolResult<H256> {
+        if !matches!(origin, OperationOrigin::External) {
+            return self.validate_and_add_operation(origin, op, options).await;
+        }
+
+        // Ops received via gossip may be echoes of ops already in the pool, such as
+        // a locally submitted op relayed back by a peer. These are not new, so skip
+        // them silently instead of processing or propagating them again.
+        //
+        // The same op may also arrive from several peers at once. Only the first copy
+        // within a window is validated, later ones are rejected before any simulation.
+        let hash = op.op_hash(self.config.entry_point, self.config.chain_id);
+        {
+            let mut state = self.state.write();
+            if state.pool.contains(hash) {
+                return Ok(hash);
+            }
+            if !state.seen_external_ops.insert(hash, Instant::now()) {
+                UoPoolMetrics::increment_duplicate_operations(self.config.entry_point);
+                return Err(MempoolError::DuplicateOperation);
+            }
+        }
+
+        let result = self.validate_and_add_operation(origin, op, options).await;
+        // An op that failed for a reason other than being invalid, such as a
+        // provider error, may be valid, so a later copy is validated again
+        if let Err(MempoolError::Other(_)) = &result {
+            self.state.write().seen_external_ops.remove(hash);
+        }
+        result
+    }
+
+    async fn readmit_operation(&self, op: Arc<PoolOperation>) -> MempoolResult<H256> {
+        let sim_result = self
+            .simulator
+            .simulate_validation(op.uo.clone(), None, Some(op.expected_code_hash))
+            .await?;
+        if let Some(agg) = &sim_result.aggregator {
+            return Err(MempoolError::UnsupportedAggregator(agg.address));
+        }
+        let pool_op = PoolOperation {
+            valid_time_range: sim_result.valid_time_range,
+            expected_code_hash: sim_result.code_hash,
+            sim_block_hash: sim_result.block_hash,
+            entities_needing_stake: sim_result.entities_needing_stake,
+            account_is_staked: sim_result.account_is_staked,
+            ..(*op).clone()
+        };
+
+        let mut state = self.state.write();
+        self.insert_operation(&mut state, &pool_op)
+    }
+
+    fn remove_operations(&self, hashes: &[H256], reason: RemovalReason) {
         let mut count = 0;
         let mut removed_hashes = vec![];
         {
@@ -296,6 +1009,11 @@ where
                 if state.pool.remove_operation_by_hash(*hash).is_some() {
                     count += 1;
                     removed_hashes.push(*hash);
+                    state.removed_ops.insert(*hash, reason);
+                    self.emit_mempool_event(MempoolEvent::OpRemoved {
+                        op_hash: *hash,
+                        reason,
+                    });
                 }
             }
         }
@@ -309,8 +1027,33 @@ where
         UoPoolMetrics::increment_removed_operations(count, self.config.entry_point);
     }
 
+    fn removal_reason(&self, hash: H256) -> Option<RemovalReason> {
+        self.state.read().removed_ops.get(hash)
+    }
+
+    fn operation_options(&self, hash: H256) -> Option<AddOpOptions> {
+        let state = self.state.read();
+        let op = state.pool.get_operation_by_hash(hash)?;
+        Some(AddOpOptions {
+            expected_block_hash: state.conditional_ops.get(&hash).copied(),
+            valid_block_range: op.valid_block_range,
+            metadata: op.metadata.clone(),
+        })
+    }
+
     fn remove_entity(&self, entity: Entity) {
-        let removed_op_hashes = self.state.write().pool.remove_entity(entity);
+        let removed_op_hashes = {
+            let mut state = self.state.write();
+            let removed_op_hashes = state.pool.remove_entity(entity);
+            for hash in &removed_op_hashes {
+                state.removed_ops.insert(*hash, RemovalReason::Invalidated);
+                self.emit_mempool_event(MempoolEvent::OpRemoved {
+                    op_hash: *hash,
+                    reason: RemovalReason::Invalidated,
+                });
+            }
+            removed_op_hashes
+        };
         let count = removed_op_hashes.len();
         self.emit(OpPoolEvent::RemovedEntity { entity });
         for op_hash in removed_op_hashes {
@@ -339,48 +1082,117 @@ where
         }
     }
 
-    fn best_operations(
+    async fn best_operations(
         &self,
         max: usize,
         shard_index: u64,
     ) -> MempoolResult<Vec<Arc<PoolOperation>>> {
-        if shard_index >= self.config.num_shards {
-            Err(anyhow::anyhow!("Invalid shard ID"))?;
-        }
-
-        // get the best operations from the pool
-        let ordered_ops = self.state.read().pool.best_operations();
-        // keep track of senders to avoid sending multiple ops from the same sender
-        let mut senders = HashSet::<Address>::new();
+        Ok(self
+            .best_operations_with_priority(max, shard_index)
+            .await?
+            .into_iter()
+            .map(|(op, _)| op)
+            .collect())
+    }
 
-        Ok(ordered_ops
+    async fn best_operations_matching(
+        &self,
+        max: usize,
+        shard_index: u64,
+        filter: GetOpsFilter,
+    ) -> MempoolResult<Vec<Arc<PoolOperation>>> {
+        Ok(self
+            .select_operations(max, shard_index, filter)
+            .await?
             .into_iter()
-            .filter(|op| {
-                // short-circuit the mod if there is only 1 shard
-                ((self.config.num_shards == 1) ||
-                (U256::from_little_endian(op.uo.sender.as_bytes())
-                        .div_mod(self.config.num_shards.into())
-                        .1
-                        == shard_index.into())) &&
-                // filter out ops from senders we've already seen
-                senders.insert(op.uo.sender)
-            })
-            .take(max)
+            .map(|(op, _)| op)
             .collect())
     }
 
+    async fn best_operations_with_priority(
+        &self,
+        max: usize,
+        shard_index: u64,
+    ) -> MempoolResult<Vec<(Arc<PoolOperation>, OpPriority)>> {
+        self.select_operations(max, shard_index, GetOpsFilter::default())
+            .await
+    }
+
+    fn operation_rank(&self, hash: H256) -> OperationRank {
+        let state = self.state.read();
+        let pool_size = state.pool.num_operations() as u64;
+        // The rank is requested by public RPC calls, so the walk over the ordering
+        // is bounded rather than covering the whole pool
+        let rank = state
+            .pool
+            .get_operation_by_hash(hash)
+            .filter(|op| self.bundle_gas_limit(op) <= U256::from(self.config.max_bundle_gas))
+            .and_then(|op| {
+                let shard_index = self.shard_index(op.uo.sender);
+                let base_fee = state.pool.base_fee().unwrap_or_default();
+                let mut senders = HashSet::<Address>::new();
+                state
+                    .pool
+                    .iter_best_operations()
+                    .take(MAX_RANKED_OPERATIONS)
+                    .filter(|candidate| {
+                        self.is_bundle_candidate(
+                            candidate,
+                            shard_index,
+                            state.block_number,
+                            base_fee,
+                            &mut senders,
+                        )
+                    })
+                    .position(|candidate| Arc::ptr_eq(candidate, &op))
+            })
+            .map(|rank| rank as u64);
+        OperationRank { rank, pool_size }
+    }
+
     fn all_operations(&self, max: usize) -> Vec<Arc<PoolOperation>> {
         self.state.read().pool.best_operations().take(max).collect()
     }
 
+    fn paymaster_max_cost(&self, paymaster: Address) -> U256 {
+        self.state.read().pool.paymaster_max_cost(paymaster)
+    }
+
+    async fn next_nonce(&self, sender: Address) -> MempoolResult<U256> {
+        let on_chain = self.on_chain_nonce(sender, U256::zero()).await?;
+        Ok(self.state.read().pool.next_free_nonce(sender, on_chain))
+    }
+
     fn clear(&self) {
-        self.state.write().pool.clear()
+        let mut state = self.state.write();
+        let cleared = state
+            .pool
+            .best_operations()
+            .map(|op| op.uo.op_hash(self.config.entry_point, self.config.chain_id))
+            .collect::<Vec<_>>();
+        state.pool.clear();
+        for op_hash in cleared {
+            self.emit_mempool_event(MempoolEvent::OpRemoved {
+                op_hash,
+                reason: RemovalReason::Invalidated,
+            });
+        }
+        state.conditional_ops.clear();
+        state.block_range_ops.clear();
+        state.removed_ops.clear();
+        state.seen_external_ops.clear();
+        state.revalidated_ops.clear();
+        state.on_chain_nonces.clear();
     }
 
     fn dump_reputation(&self) -> Vec<Reputation> {
         self.reputation.dump_reputation()
     }
 
+    fn dump_senders(&self) -> Vec<SenderSummary> {
+        self.state.read().pool.sender_summaries()
+    }
+
     fn set_reputation(&self, address: Address, ops_seen: u64, ops_included: u64) {
         self.reputation
             .set_reputation(address, ops_seen, ops_included)
@@ -405,19 +1217,30 @@ impl UoPoolMetrics {
     fn increment_removed_entities(entry_point: Address) {
         metrics::increment_counter!("op_pool_removed_entities", "entrypoint" => entry_point.to_string());
     }
+
+    fn increment_duplicate_operations(entry_point: Address) {
+        metrics::increment_counter!("op_pool_duplicate_operations", "entrypoint" => entry_point.to_string());
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use rundler_provider::MockEntryPoint;
     use rundler_sim::{
         MockPrechecker, MockSimulator, PrecheckError, PrecheckSettings, PrecheckViolation,
         SimulationError, SimulationSettings, SimulationSuccess, SimulationViolation,
     };
-    use rundler_types::EntityType;
+    use rundler_types::{contracts::shared_types::DepositInfo, EntityType};
 
     use super::*;
-    use crate::chain::MinedOp;
+    use crate::{
+        chain::MinedOp,
+        mempool::{OpMetadata, ReputationParams, ValidBlockRange, MAX_OP_METADATA_ENTRIES},
+    };
 
+    const ON_CHAIN_NONCE: u64 = 7;
+    // Every other sender has not used any nonces on chain
+    const ON_CHAIN_NONCE_SENDER: Address = Address::repeat_byte(0xaa);
     const THROTTLE_SLACK: u64 = 5;
     const BAN_SLACK: u64 = 10;
 
@@ -429,12 +1252,35 @@ mod tests {
         let pool = create_pool(ops);
 
         let hash = pool
-            .add_operation(OperationOrigin::Local, op.op)
+            .add_operation(OperationOrigin::Local, op.op, AddOpOptions::default())
             .await
             .unwrap();
-        check_ops(pool.best_operations(1, 0).unwrap(), uos);
-        pool.remove_operations(&[hash]);
-        assert_eq!(pool.best_operations(1, 0).unwrap(), vec![]);
+        check_ops(pool.best_operations(1, 0).await.unwrap(), uos);
+        assert_eq!(pool.operation_options(hash), Some(AddOpOptions::default()));
+        pool.remove_operations(&[hash], RemovalReason::Invalidated);
+        assert_eq!(pool.operation_options(hash), None);
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+        assert_eq!(pool.removal_reason(hash), Some(RemovalReason::Invalidated));
+    }
+
+    #[tokio::test]
+    async fn add_op_with_metadata() {
+        let op = create_op(Address::random(), 0, 0);
+        let pool = create_pool(vec![op.clone()]);
+        let metadata = OpMetadata::from([("source".to_string(), "wallet".to_string())]);
+
+        pool.add_operation(
+            OperationOrigin::Local,
+            op.op,
+            AddOpOptions {
+                metadata: metadata.clone(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let best = pool.best_operations(1, 0).await.unwrap();
+        assert_eq!(best[0].metadata, metadata);
     }
 
     #[tokio::test]
@@ -450,14 +1296,14 @@ mod tests {
         let mut hashes = vec![];
         for op in &uos {
             let hash = pool
-                .add_operation(OperationOrigin::Local, op.clone())
+                .add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
                 .await
                 .unwrap();
             hashes.push(hash);
         }
-        check_ops(pool.best_operations(3, 0).unwrap(), uos);
-        pool.remove_operations(&hashes);
-        assert_eq!(pool.best_operations(3, 0).unwrap(), vec![]);
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos);
+        pool.remove_operations(&hashes, RemovalReason::Invalidated);
+        assert_eq!(pool.best_operations(3, 0).await.unwrap(), vec![]);
     }
 
     #[tokio::test]
@@ -472,13 +1318,13 @@ mod tests {
 
         for op in &uos {
             let _ = pool
-                .add_operation(OperationOrigin::Local, op.clone())
+                .add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
                 .await
                 .unwrap();
         }
-        check_ops(pool.best_operations(3, 0).unwrap(), uos);
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos);
         pool.clear();
-        assert_eq!(pool.best_operations(3, 0).unwrap(), vec![]);
+        assert_eq!(pool.best_operations(3, 0).await.unwrap(), vec![]);
     }
 
     #[tokio::test]
@@ -489,23 +1335,77 @@ mod tests {
             create_op(Address::random(), 0, 1),
         ])
         .await;
-        check_ops(pool.best_operations(3, 0).unwrap(), uos.clone());
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos.clone());
+
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
+            earliest_remembered_block_number: 0,
+            reorg_depth: 0,
+            mined_ops: vec![MinedOp {
+                entry_point: pool.config.entry_point,
+                hash: uos[0].op_hash(pool.config.entry_point, 1),
+                sender: uos[0].sender,
+                nonce: uos[0].nonce,
+            }],
+            unmined_ops: vec![],
+        });
+
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos[1..].to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_underpriced_ops_listed_but_not_bundled() {
+        let (pool, uos) = create_pool_insert_ops(vec![
+            create_op(Address::random(), 0, 10),
+            create_op(Address::random(), 0, 3),
+        ])
+        .await;
+        let mut events = pool.mempool_event_sender.subscribe();
+
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            latest_block_base_fee: Some(5.into()),
+            ..Default::default()
+        });
+
+        // the op below the base fee is not selected for bundles, but is still
+        // listed, e.g. for dumps and exports
+        check_ops(
+            pool.best_operations(2, 0).await.unwrap(),
+            vec![uos[0].clone()],
+        );
+        check_ops(pool.all_operations(2), uos.clone());
+
+        pool.clear();
+        let mut removed = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let MempoolEvent::OpRemoved { op_hash, .. } = event.event {
+                removed.push(op_hash);
+            }
+        }
+        assert_eq!(
+            removed,
+            uos.iter()
+                .map(|uo| uo.op_hash(pool.config.entry_point, pool.config.chain_id))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn chain_update_block_number() {
+        let (pool, _) = create_pool_insert_ops(vec![]).await;
+        assert_eq!(pool.block_number(), 0);
 
         pool.on_chain_update(&ChainUpdate {
-            latest_block_number: 1,
+            latest_block_number: 5,
             latest_block_hash: H256::random(),
-            earliest_remembered_block_number: 0,
-            reorg_depth: 0,
-            mined_ops: vec![MinedOp {
-                entry_point: pool.config.entry_point,
-                hash: uos[0].op_hash(pool.config.entry_point, 1),
-                sender: uos[0].sender,
-                nonce: uos[0].nonce,
-            }],
-            unmined_ops: vec![],
+            ..Default::default()
         });
 
-        check_ops(pool.best_operations(3, 0).unwrap(), uos[1..].to_vec());
+        assert_eq!(pool.block_number(), 5);
     }
 
     #[tokio::test]
@@ -516,11 +1416,12 @@ mod tests {
             create_op(Address::random(), 0, 1),
         ])
         .await;
-        check_ops(pool.best_operations(3, 0).unwrap(), uos.clone());
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos.clone());
 
         pool.on_chain_update(&ChainUpdate {
             latest_block_number: 1,
             latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
             earliest_remembered_block_number: 0,
             reorg_depth: 0,
             mined_ops: vec![MinedOp {
@@ -532,13 +1433,16 @@ mod tests {
             unmined_ops: vec![],
         });
         check_ops(
-            pool.best_operations(3, 0).unwrap(),
+            pool.best_operations(3, 0).await.unwrap(),
             uos.clone()[1..].to_vec(),
         );
+        let mined_hash = uos[0].op_hash(pool.config.entry_point, 1);
+        assert_eq!(pool.removal_reason(mined_hash), Some(RemovalReason::Mined));
 
         pool.on_chain_update(&ChainUpdate {
             latest_block_number: 1,
             latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
             earliest_remembered_block_number: 0,
             reorg_depth: 0,
             mined_ops: vec![],
@@ -549,7 +1453,122 @@ mod tests {
                 nonce: uos[0].nonce,
             }],
         });
-        check_ops(pool.best_operations(3, 0).unwrap(), uos);
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos);
+        assert_eq!(pool.removal_reason(mined_hash), None);
+    }
+
+    #[tokio::test]
+    async fn chain_update_revalidate_reorged_ops() {
+        let ops = vec![
+            create_op(Address::random(), 0, 3),
+            create_op(Address::random(), 0, 2),
+        ];
+        let uos = ops.iter().map(|op| op.op.clone()).collect::<Vec<_>>();
+        let pool = create_pool_with_config(
+            ops,
+            PoolConfig {
+                revalidate_reorged_ops: true,
+                ..default_config()
+            },
+        );
+        for uo in &uos {
+            pool.add_operation(OperationOrigin::Local, uo.clone(), AddOpOptions::default())
+                .await
+                .unwrap();
+        }
+        let mined_op = MinedOp {
+            entry_point: pool.config.entry_point,
+            hash: uos[0].op_hash(pool.config.entry_point, 1),
+            sender: uos[0].sender,
+            nonce: uos[0].nonce,
+        };
+
+        let reorged_ops = pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            earliest_remembered_block_number: 0,
+            mined_ops: vec![mined_op],
+            ..Default::default()
+        });
+        assert!(reorged_ops.is_empty());
+
+        let mut events = pool.event_sender.subscribe();
+        let reorged_ops = pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            earliest_remembered_block_number: 0,
+            reorg_depth: 1,
+            unmined_ops: vec![mined_op],
+            ..Default::default()
+        });
+
+        // The unmined op is handed back for validation instead of put back in the pool
+        assert_eq!(reorged_ops.len(), 1);
+        assert_eq!(reorged_ops[0].uo, uos[0]);
+        check_ops(pool.best_operations(2, 0).await.unwrap(), uos[1..].to_vec());
+        assert_eq!(pool.removal_reason(mined_op.hash), None);
+        let event = events.try_recv().unwrap();
+        assert!(matches!(
+            event.event,
+            OpPoolEvent::ReorgDetected {
+                reorg_depth: 1,
+                unmined_op_hashes,
+                ..
+            } if unmined_op_hashes == vec![mined_op.hash]
+        ));
+    }
+
+    #[tokio::test]
+    async fn readmit_reorged_op_skips_admission_checks() {
+        let sender = Address::random();
+        let op = create_op_with_errors(sender, 0, 2, None, None, true);
+        let uo = op.op.clone();
+        let pool = create_pool_with_config(
+            vec![op],
+            PoolConfig {
+                revalidate_reorged_ops: true,
+                rate_limits: HashMap::from([(
+                    Entity::account(sender),
+                    (1, Duration::from_secs(60)),
+                )]),
+                ..default_config()
+            },
+        );
+        pool.add_operation(OperationOrigin::Local, uo.clone(), AddOpOptions::default())
+            .await
+            .unwrap();
+        let mined_op = MinedOp {
+            entry_point: pool.config.entry_point,
+            hash: uo.op_hash(pool.config.entry_point, 1),
+            sender,
+            nonce: uo.nonce,
+        };
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            mined_ops: vec![mined_op],
+            ..Default::default()
+        });
+        let reorged_ops = pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            reorg_depth: 1,
+            unmined_ops: vec![mined_op],
+            ..Default::default()
+        });
+        assert_eq!(reorged_ops.len(), 1);
+
+        // The sender's rate limit is used up, but only applies to new submissions
+        let hash = pool
+            .readmit_operation(reorged_ops[0].clone())
+            .await
+            .unwrap();
+        assert_eq!(hash, mined_op.hash);
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![uo]);
+        // The op was already counted as seen when first admitted
+        let rep = pool.dump_reputation();
+        assert_eq!(rep.len(), 1);
+        assert_eq!(rep[0].ops_seen, 1);
     }
 
     #[tokio::test]
@@ -560,11 +1579,12 @@ mod tests {
             create_op(Address::random(), 0, 1),
         ])
         .await;
-        check_ops(pool.best_operations(3, 0).unwrap(), uos.clone());
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos.clone());
 
         pool.on_chain_update(&ChainUpdate {
             latest_block_number: 1,
             latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
             earliest_remembered_block_number: 0,
             reorg_depth: 0,
             mined_ops: vec![MinedOp {
@@ -576,7 +1596,7 @@ mod tests {
             unmined_ops: vec![],
         });
 
-        check_ops(pool.best_operations(3, 0).unwrap(), uos);
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos);
     }
 
     #[tokio::test]
@@ -589,7 +1609,10 @@ mod tests {
         ])
         .await;
         // Only return 1 op per sender
-        check_ops(pool.best_operations(3, 0).unwrap(), vec![uos[0].clone()]);
+        check_ops(
+            pool.best_operations(3, 0).await.unwrap(),
+            vec![uos[0].clone()],
+        );
 
         let rep = pool.dump_reputation();
         assert_eq!(rep.len(), 1);
@@ -600,6 +1623,7 @@ mod tests {
         pool.on_chain_update(&ChainUpdate {
             latest_block_number: 1,
             latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
             earliest_remembered_block_number: 0,
             reorg_depth: 0,
             mined_ops: vec![MinedOp {
@@ -618,6 +1642,271 @@ mod tests {
         assert_eq!(rep[0].ops_included, 1); // 1 op included
     }
 
+    #[tokio::test]
+    async fn test_reputation_updates_batched_per_block() {
+        let ops = (0..10)
+            .map(|_| create_op_with_errors(Address::random(), 0, 2, None, None, true))
+            .collect::<Vec<_>>();
+        let (pool, uos) = create_pool_insert_ops(ops).await;
+
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
+            earliest_remembered_block_number: 0,
+            reorg_depth: 0,
+            mined_ops: uos
+                .iter()
+                .map(|uo| MinedOp {
+                    entry_point: pool.config.entry_point,
+                    hash: uo.op_hash(pool.config.entry_point, 1),
+                    sender: uo.sender,
+                    nonce: uo.nonce,
+                })
+                .collect(),
+            unmined_ops: vec![],
+        });
+
+        let rep = pool.dump_reputation();
+        assert_eq!(rep.len(), 10);
+        assert!(rep.iter().all(|r| r.ops_included == 1));
+        assert_eq!(pool.reputation.counts.read().update_included_calls, 1);
+    }
+
+    #[tokio::test]
+    async fn test_next_nonce() {
+        let sender = Address::random();
+        // ops on other nonce keys don't affect the default key
+        let mut keyed_op = create_op(sender, 0, 1);
+        keyed_op.op.nonce = U256::from(1) << 64;
+        let (pool, _) = create_pool_insert_ops(vec![
+            create_op(sender, 0, 1),
+            create_op(sender, 1, 1),
+            create_op(sender, 3, 1),
+            keyed_op,
+            create_op(Address::random(), 5, 1),
+        ])
+        .await;
+
+        // stops at the first gap in the pooled nonces
+        assert_eq!(pool.next_nonce(sender).await.unwrap(), U256::from(2));
+        assert_eq!(
+            pool.next_nonce(ON_CHAIN_NONCE_SENDER).await.unwrap(),
+            U256::from(ON_CHAIN_NONCE)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_next_nonce_ignores_stale_ops() {
+        let pool = create_pool(vec![]);
+        // an op whose nonce was used on chain, but hasn't been removed from the pool yet
+        pool.state
+            .write()
+            .pool
+            .add_operation(PoolOperation {
+                uo: create_op(ON_CHAIN_NONCE_SENDER, ON_CHAIN_NONCE as usize - 2, 1).op,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            pool.next_nonce(ON_CHAIN_NONCE_SENDER).await.unwrap(),
+            U256::from(ON_CHAIN_NONCE)
+        );
+
+        pool.state
+            .write()
+            .pool
+            .add_operation(PoolOperation {
+                uo: create_op(ON_CHAIN_NONCE_SENDER, ON_CHAIN_NONCE as usize, 1).op,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            pool.next_nonce(ON_CHAIN_NONCE_SENDER).await.unwrap(),
+            U256::from(ON_CHAIN_NONCE + 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fees_below_minimum() {
+        let mut pool = create_pool(vec![]);
+        pool.config.min_priority_fee_per_gas = 10.into();
+        pool.config.min_max_fee_per_gas = 20.into();
+
+        let mut op = create_op(Address::random(), 0, 20).op;
+        op.max_priority_fee_per_gas = 9.into();
+        let err = pool
+            .add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
+            .await
+            .unwrap_err();
+        match err {
+            MempoolError::FeesBelowMinimum(priority_fee, fee) => {
+                assert_eq!(priority_fee, 10.into());
+                assert_eq!(fee, 20.into());
+            }
+            _ => panic!("wrong error {err:?}"),
+        }
+
+        op.max_priority_fee_per_gas = 10.into();
+        op.max_fee_per_gas = 19.into();
+        let err = pool
+            .add_operation(OperationOrigin::Local, op, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::FeesBelowMinimum(..)));
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_op_metadata_too_large() {
+        let op = create_op(Address::random(), 0, 1);
+        let pool = create_pool(vec![op.clone()]);
+
+        // ops added over gRPC have not been checked by the RPC server
+        let metadata = (0..=MAX_OP_METADATA_ENTRIES)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        let err = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op,
+                AddOpOptions {
+                    metadata,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::InvalidMetadata(_)));
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_op_too_large() {
+        let mut pool = create_pool(vec![]);
+        let mut op = create_op(Address::random(), 0, 1).op;
+        let max_size = op.abi_encoded_size();
+        pool.config.max_op_size_bytes = max_size;
+
+        op.call_data = vec![1; 32].into();
+        let size = op.abi_encoded_size();
+        let err = pool
+            .add_operation(OperationOrigin::Local, op, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        match err {
+            MempoolError::OperationTooLarge(op_size, max_op_size) => {
+                assert_eq!(op_size, size);
+                assert_eq!(max_op_size, max_size);
+            }
+            _ => panic!("wrong error {err:?}"),
+        }
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_pool_shard() {
+        let op = create_op(Address::zero(), 0, 1);
+        let mut pool = create_pool(vec![op.clone()]);
+        pool.config.pool_shard_count = 2;
+        pool.config.pool_shard_index = 1;
+
+        let err = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::WrongPoolShard(0, 1)));
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![]);
+
+        pool.config.pool_shard_index = 0;
+        pool.add_operation(
+            OperationOrigin::Local,
+            op.op.clone(),
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![op.op]);
+    }
+
+    #[tokio::test]
+    async fn test_nonce_too_low() {
+        let valid_op = create_op(ON_CHAIN_NONCE_SENDER, ON_CHAIN_NONCE as usize, 1);
+        let pool = create_pool(vec![valid_op.clone()]);
+        let stale_op = create_op(ON_CHAIN_NONCE_SENDER, ON_CHAIN_NONCE as usize - 1, 2).op;
+        let err = pool
+            .add_operation(OperationOrigin::Local, stale_op, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        match err {
+            MempoolError::NonceTooLow(nonce, on_chain_nonce) => {
+                assert_eq!(nonce, U256::from(ON_CHAIN_NONCE - 1));
+                assert_eq!(on_chain_nonce, U256::from(ON_CHAIN_NONCE));
+            }
+            _ => panic!("wrong error {err:?}"),
+        }
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![]);
+
+        // the sender's next on-chain nonce is accepted
+        pool.add_operation(
+            OperationOrigin::Local,
+            valid_op.op.clone(),
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![valid_op.op]);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_throttled_op_is_forgotten() {
+        let throttled = create_op_with_errors(Address::random(), 0, 1, None, None, true);
+        let outbidding = create_op(Address::random(), 0, 5);
+        let throttled_uo = throttled.op.clone();
+        let outbidding_uo = outbidding.op.clone();
+        let pool = create_pool_with_config(
+            vec![throttled, outbidding],
+            PoolConfig {
+                max_pool_size: 1,
+                ..default_config()
+            },
+        );
+        pool.set_reputation(throttled_uo.sender, 1 + THROTTLE_SLACK, 0);
+
+        let throttled_hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                throttled_uo,
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert!(pool
+            .state
+            .read()
+            .throttled_ops
+            .contains_key(&throttled_hash));
+
+        pool.add_operation(
+            OperationOrigin::Local,
+            outbidding_uo.clone(),
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        check_ops(pool.all_operations(2), vec![outbidding_uo]);
+        assert!(pool.state.read().throttled_ops.is_empty());
+        assert_eq!(
+            pool.removal_reason(throttled_hash),
+            Some(RemovalReason::Evicted)
+        );
+    }
+
     #[tokio::test]
     async fn test_throttled_account() {
         let address = Address::random();
@@ -633,7 +1922,7 @@ mod tests {
 
         // Ops 0 through 3 should be included
         for uo in uos.iter().take(4) {
-            pool.add_operation(OperationOrigin::Local, uo.clone())
+            pool.add_operation(OperationOrigin::Local, uo.clone(), AddOpOptions::default())
                 .await
                 .unwrap();
         }
@@ -650,7 +1939,11 @@ mod tests {
 
         // Second op should be throttled
         let ret = pool
-            .add_operation(OperationOrigin::Local, uos[4].clone())
+            .add_operation(
+                OperationOrigin::Local,
+                uos[4].clone(),
+                AddOpOptions::default(),
+            )
             .await;
 
         assert!(ret.is_err());
@@ -666,6 +1959,7 @@ mod tests {
         pool.on_chain_update(&ChainUpdate {
             latest_block_number: 1,
             latest_block_hash: H256::random(),
+            latest_block_base_fee: None,
             earliest_remembered_block_number: 0,
             reorg_depth: 0,
             mined_ops: vec![MinedOp {
@@ -678,9 +1972,13 @@ mod tests {
         });
 
         // Second op should be included
-        pool.add_operation(OperationOrigin::Local, uos[4].clone())
-            .await
-            .unwrap();
+        pool.add_operation(
+            OperationOrigin::Local,
+            uos[4].clone(),
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
         check_ops(
             pool.all_operations(4),
             vec![
@@ -696,62 +1994,260 @@ mod tests {
     async fn test_banned_account() {
         let address = Address::random();
 
-        let op = create_op_with_errors(address, 0, 2, None, None, true);
-        let uo = op.op.clone();
-        let pool = create_pool(vec![op]);
-        // Past ban slack
-        pool.set_reputation(address, 1 + BAN_SLACK, 0);
+        let op = create_op_with_errors(address, 0, 2, None, None, true);
+        let uo = op.op.clone();
+        let pool = create_pool(vec![op]);
+        // Past ban slack
+        pool.set_reputation(address, 1 + BAN_SLACK, 0);
+
+        // First op should be banned
+        let ret = pool
+            .add_operation(OperationOrigin::Local, uo.clone(), AddOpOptions::default())
+            .await;
+        assert!(ret.is_err());
+        match ret.unwrap_err() {
+            MempoolError::EntityThrottled(entity) => {
+                assert_eq!(entity.address, address);
+                assert_eq!(entity.kind, EntityType::Account)
+            }
+            _ => panic!("Expected throttled error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn precheck_error() {
+        let op = create_op_with_errors(
+            Address::random(),
+            0,
+            0,
+            Some(PrecheckViolation::InitCodeTooShort(0)),
+            None,
+            false,
+        );
+        let ops = vec![op.clone()];
+        let pool = create_pool(ops);
+
+        match pool
+            .add_operation(OperationOrigin::Local, op.op, AddOpOptions::default())
+            .await
+        {
+            Err(MempoolError::PrecheckViolation(PrecheckViolation::InitCodeTooShort(_))) => {}
+            _ => panic!("Expected InitCodeTooShort error"),
+        }
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn simulation_error() {
+        let op = create_op_with_errors(
+            Address::random(),
+            0,
+            0,
+            None,
+            Some(SimulationViolation::DidNotRevert),
+            false,
+        );
+        let ops = vec![op.clone()];
+        let pool = create_pool(ops);
+
+        match pool
+            .add_operation(OperationOrigin::Local, op.op, AddOpOptions::default())
+            .await
+        {
+            Err(MempoolError::SimulationViolation(SimulationViolation::DidNotRevert)) => {}
+            _ => panic!("Expected DidNotRevert error"),
+        }
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_external_duplicate_rejected_before_simulation() {
+        let op = create_op(Address::random(), 0, 0);
+        let mut simulator = MockSimulator::new();
+        simulator
+            .expect_simulate_validation()
+            .times(2)
+            .returning(|_, _, _| {
+                Err(SimulationError::Violations(vec![
+                    SimulationViolation::DidNotRevert,
+                ]))
+            });
+        let mut prechecker = MockPrechecker::new();
+        prechecker.expect_check().returning(|_| Ok(()));
+        let pool = create_pool_with_mocks(prechecker, simulator, default_config());
+
+        let err = pool
+            .add_operation(
+                OperationOrigin::External,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::SimulationViolation(_)));
+
+        // a copy from another peer is rejected without being simulated again
+        let err = pool
+            .add_operation(
+                OperationOrigin::External,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::DuplicateOperation));
+
+        // local submissions are always validated
+        let err = pool
+            .add_operation(OperationOrigin::Local, op.op, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::SimulationViolation(_)));
+    }
+
+    #[tokio::test]
+    async fn test_external_op_validated_again_after_provider_error() {
+        let op = create_op(Address::random(), 0, 0);
+        let mut simulator = MockSimulator::new();
+        simulator
+            .expect_simulate_validation()
+            .times(2)
+            .returning(|_, _, _| Err(SimulationError::Other(anyhow::anyhow!("provider error"))));
+        let mut prechecker = MockPrechecker::new();
+        prechecker.expect_check().returning(|_| Ok(()));
+        let pool = create_pool_with_mocks(prechecker, simulator, default_config());
+
+        // the op may be valid, so a copy from another peer is simulated again
+        for _ in 0..2 {
+            let err = pool
+                .add_operation(
+                    OperationOrigin::External,
+                    op.op.clone(),
+                    AddOpOptions::default(),
+                )
+                .await
+                .unwrap_err();
+            assert!(matches!(err, MempoolError::Other(_)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_require_stake_for_gossip() {
+        let unstaked = create_op(Address::random(), 0, 0);
+        let staked_account = create_op(Address::random(), 0, 0);
+        let mut staked_paymaster = create_op(Address::random(), 0, 0);
+        let paymaster = Address::random();
+        staked_paymaster.op.paymaster_and_data = paymaster.as_bytes().to_vec().into();
+        let staked_account_sender = staked_account.op.sender;
+
+        // only the staked ops and the local submission reach simulation
+        let mut simulator = MockSimulator::new();
+        simulator
+            .expect_simulate_validation()
+            .times(3)
+            .returning(|_, _, _| Ok(SimulationSuccess::default()));
+        let mut prechecker = MockPrechecker::new();
+        prechecker.expect_check().returning(|_| Ok(()));
+        let mut entry_point = MockEntryPoint::new();
+        entry_point
+            .expect_get_nonce()
+            .returning(|_, key| Ok(key << 64));
+        entry_point
+            .expect_get_deposit_info()
+            .returning(move |address| {
+                let staked = address == staked_account_sender || address == paymaster;
+                Ok(DepositInfo {
+                    stake: if staked { 1 } else { 0 },
+                    unstake_delay_sec: if staked { 1 } else { 0 },
+                    ..DepositInfo::default()
+                })
+            });
+        let pool = create_pool_with_entry_point(
+            prechecker,
+            simulator,
+            entry_point,
+            PoolConfig {
+                require_stake_for_gossip: true,
+                sim_settings: SimulationSettings {
+                    min_stake_value: 1,
+                    min_unstake_delay: 1,
+                    ..SimulationSettings::default()
+                },
+                ..default_config()
+            },
+        );
+
+        let err = pool
+            .add_operation(
+                OperationOrigin::External,
+                unstaked.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::StakeRequiredForGossip));
+        pool.add_operation(
+            OperationOrigin::External,
+            staked_account.op,
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+        pool.add_operation(
+            OperationOrigin::External,
+            staked_paymaster.op,
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+
+        // local submissions follow the normal rules
+        pool.add_operation(OperationOrigin::Local, unstaked.op, AddOpOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(pool.all_operations(16).len(), 3);
+    }
+
+    #[test]
+    fn test_seen_ops_expire() {
+        let mut seen = SeenOps::new(Duration::from_secs(10), 16);
+        let hash = H256::random();
+        let start = Instant::now();
 
-        // First op should be banned
-        let ret = pool.add_operation(OperationOrigin::Local, uo.clone()).await;
-        assert!(ret.is_err());
-        match ret.unwrap_err() {
-            MempoolError::EntityThrottled(entity) => {
-                assert_eq!(entity.address, address);
-                assert_eq!(entity.kind, EntityType::Account)
-            }
-            _ => panic!("Expected throttled error"),
-        }
+        assert!(seen.insert(hash, start));
+        assert!(!seen.insert(hash, start + Duration::from_secs(5)));
+        assert!(seen.insert(hash, start + Duration::from_secs(10)));
     }
 
-    #[tokio::test]
-    async fn precheck_error() {
-        let op = create_op_with_errors(
-            Address::random(),
-            0,
-            0,
-            Some(PrecheckViolation::InitCodeTooShort(0)),
-            None,
-            false,
-        );
-        let ops = vec![op.clone()];
-        let pool = create_pool(ops);
+    #[test]
+    fn test_seen_ops_capacity() {
+        let mut seen = SeenOps::new(Duration::from_secs(10), 2);
+        let hashes = [H256::random(), H256::random(), H256::random()];
+        let now = Instant::now();
 
-        match pool.add_operation(OperationOrigin::Local, op.op).await {
-            Err(MempoolError::PrecheckViolation(PrecheckViolation::InitCodeTooShort(_))) => {}
-            _ => panic!("Expected InitCodeTooShort error"),
+        for hash in hashes {
+            assert!(seen.insert(hash, now));
         }
-        assert_eq!(pool.best_operations(1, 0).unwrap(), vec![]);
+        // the oldest op was forgotten to make room
+        assert!(seen.insert(hashes[0], now));
+        assert!(!seen.insert(hashes[2], now));
+        assert!(seen.order.len() <= 2);
     }
 
-    #[tokio::test]
-    async fn simulation_error() {
-        let op = create_op_with_errors(
-            Address::random(),
-            0,
-            0,
-            None,
-            Some(SimulationViolation::DidNotRevert),
-            false,
-        );
-        let ops = vec![op.clone()];
-        let pool = create_pool(ops);
+    #[test]
+    fn test_seen_ops_remove() {
+        let mut seen = SeenOps::new(Duration::from_secs(10), 16);
+        let hash = H256::random();
+        let start = Instant::now();
 
-        match pool.add_operation(OperationOrigin::Local, op.op).await {
-            Err(MempoolError::SimulationViolation(SimulationViolation::DidNotRevert)) => {}
-            _ => panic!("Expected DidNotRevert error"),
-        }
-        assert_eq!(pool.best_operations(1, 0).unwrap(), vec![]);
+        assert!(seen.insert(hash, start));
+        seen.remove(hash);
+        assert!(seen.insert(hash, start + Duration::from_secs(5)));
+
+        // expiring the first entry doesn't forget the second
+        let other = H256::random();
+        assert!(seen.insert(other, start + Duration::from_secs(11)));
+        assert!(!seen.insert(hash, start + Duration::from_secs(12)));
     }
 
     #[tokio::test]
@@ -760,17 +2256,52 @@ mod tests {
         let pool = create_pool(vec![op.clone()]);
 
         let _ = pool
-            .add_operation(OperationOrigin::Local, op.op.clone())
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
             .await
             .unwrap();
 
         let err = pool
-            .add_operation(OperationOrigin::Local, op.op.clone())
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
             .await
             .unwrap_err();
         assert!(matches!(err, MempoolError::OperationAlreadyKnown));
 
-        check_ops(pool.best_operations(1, 0).unwrap(), vec![op.op]);
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![op.op]);
+    }
+
+    #[tokio::test]
+    async fn test_external_duplicate_ignored() {
+        let op = create_op(Address::random(), 0, 0);
+        let pool = create_pool(vec![op.clone()]);
+
+        let hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let echo_hash = pool
+            .add_operation(
+                OperationOrigin::External,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(hash, echo_hash);
+
+        check_ops(pool.all_operations(16), vec![op.op]);
     }
 
     #[tokio::test]
@@ -779,7 +2310,11 @@ mod tests {
         let pool = create_pool(vec![op.clone()]);
 
         let _ = pool
-            .add_operation(OperationOrigin::Local, op.op.clone())
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
             .await
             .unwrap();
 
@@ -787,13 +2322,13 @@ mod tests {
         replacement.max_fee_per_gas = replacement.max_fee_per_gas + 1;
 
         let err = pool
-            .add_operation(OperationOrigin::Local, replacement)
+            .add_operation(OperationOrigin::Local, replacement, AddOpOptions::default())
             .await
             .unwrap_err();
 
-        assert!(matches!(err, MempoolError::ReplacementUnderpriced(_, _)));
+        assert!(matches!(err, MempoolError::ReplacementUnderpriced(..)));
 
-        check_ops(pool.best_operations(1, 0).unwrap(), vec![op.op]);
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![op.op]);
     }
 
     #[tokio::test]
@@ -801,8 +2336,12 @@ mod tests {
         let op = create_op(Address::random(), 0, 5);
         let pool = create_pool(vec![op.clone()]);
 
-        let _ = pool
-            .add_operation(OperationOrigin::Local, op.op.clone())
+        let hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
             .await
             .unwrap();
 
@@ -810,11 +2349,450 @@ mod tests {
         replacement.max_fee_per_gas = replacement.max_fee_per_gas + 1;
 
         let _ = pool
-            .add_operation(OperationOrigin::Local, replacement.clone())
+            .add_operation(
+                OperationOrigin::Local,
+                replacement.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![replacement]);
+        assert_eq!(pool.removal_reason(hash), Some(RemovalReason::Replaced));
+    }
+
+    #[tokio::test]
+    async fn test_eviction_removal_reason() {
+        let low = create_op(Address::random(), 0, 2);
+        let high = create_op(Address::random(), 0, 5);
+        let lowest = create_op(Address::random(), 0, 1);
+        let pool = create_pool_with_config(
+            vec![low.clone(), high.clone(), lowest.clone()],
+            PoolConfig {
+                max_pool_size: 1,
+                ..default_config()
+            },
+        );
+
+        let low_hash = pool
+            .add_operation(OperationOrigin::Local, low.op, AddOpOptions::default())
+            .await
+            .unwrap();
+        pool.add_operation(
+            OperationOrigin::Local,
+            high.op.clone(),
+            AddOpOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(pool.removal_reason(low_hash), Some(RemovalReason::Evicted));
+
+        // an op rejected for bidding the lowest was never in the pool
+        let lowest_hash = lowest
+            .op
+            .op_hash(pool.config.entry_point, pool.config.chain_id);
+        let err = pool
+            .add_operation(OperationOrigin::Local, lowest.op, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MempoolError::DiscardedOnInsert));
+        assert_eq!(pool.removal_reason(lowest_hash), None);
+        check_ops(pool.all_operations(2), vec![high.op]);
+    }
+
+    #[tokio::test]
+    async fn test_mempool_events() {
+        let op = create_op(Address::random(), 0, 5);
+        let pool = create_pool(vec![op.clone()]);
+        let mut events = pool.mempool_event_sender.subscribe();
+        let ep = pool.config.entry_point;
+
+        let hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let mut replacement = op.op.clone();
+        replacement.max_fee_per_gas = replacement.max_fee_per_gas + 1;
+        let replacement_hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                replacement.clone(),
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        pool.remove_operations(&[replacement_hash], RemovalReason::Invalidated);
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::random(),
+            ..Default::default()
+        });
+
+        let mut received = vec![];
+        while let Ok(event) = events.try_recv() {
+            assert_eq!(event.entry_point, ep);
+            received.push(event.event);
+        }
+        assert_eq!(
+            received,
+            vec![
+                MempoolEvent::OpAdded {
+                    op_hash: hash,
+                    op: op.op,
+                },
+                MempoolEvent::OpReplaced {
+                    op_hash: hash,
+                    replaced_by: replacement_hash,
+                },
+                MempoolEvent::OpAdded {
+                    op_hash: replacement_hash,
+                    op: replacement,
+                },
+                MempoolEvent::OpRemoved {
+                    op_hash: replacement_hash,
+                    reason: RemovalReason::Invalidated,
+                },
+                MempoolEvent::BlockProcessed { block_number: 1 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_best_operations_max_bundle_gas() {
+        let mut ops = vec![
+            create_op(Address::random(), 0, 3),
+            create_op(Address::random(), 0, 2),
+            create_op(Address::random(), 0, 1),
+        ];
+        // only two of these fit in a bundle
+        for op in &mut ops {
+            op.op.call_gas_limit = 10_000_000.into();
+        }
+        let (pool, uos) = create_pool_insert_ops(ops).await;
+
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos[..2].to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_operation_rank() {
+        let sender = Address::random();
+        let (pool, uos) = create_pool_insert_ops(vec![
+            create_op(sender, 0, 3),
+            create_op(Address::random(), 0, 2),
+            create_op(sender, 1, 1),
+        ])
+        .await;
+        let rank = |uo: &UserOperation| {
+            pool.operation_rank(uo.op_hash(pool.config.entry_point, pool.config.chain_id))
+        };
+
+        assert_eq!(
+            rank(&uos[0]),
+            OperationRank {
+                rank: Some(0),
+                pool_size: 3
+            }
+        );
+        assert_eq!(rank(&uos[1]).rank, Some(1));
+        // not selected while the sender's earlier op is in the pool
+        assert_eq!(rank(&uos[2]).rank, None);
+        assert_eq!(pool.operation_rank(H256::random()).rank, None);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_op_block_hash_mismatch() {
+        let op = create_op(Address::random(), 0, 0);
+        let pool = create_pool(vec![op.clone()]);
+
+        // The mock simulator validates at the zero block hash
+        let expected = H256::random();
+        match pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op,
+                AddOpOptions {
+                    expected_block_hash: Some(expected),
+                    ..Default::default()
+                },
+            )
+            .await
+        {
+            Err(MempoolError::BlockHashMismatch(e, c)) => {
+                assert_eq!(e, expected);
+                assert_eq!(c, H256::zero());
+            }
+            _ => panic!("Expected BlockHashMismatch error"),
+        }
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_op_dropped_on_new_head() {
+        let op = create_op(Address::random(), 0, 0);
+        let pool = create_pool(vec![op.clone()]);
+
+        let hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions {
+                    expected_block_hash: Some(H256::zero()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // Still valid while the expected block is the head
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 1,
+            latest_block_hash: H256::zero(),
+            ..Default::default()
+        });
+        check_ops(pool.best_operations(1, 0).await.unwrap(), vec![op.op]);
+
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 2,
+            latest_block_hash: H256::random(),
+            ..Default::default()
+        });
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+        assert_eq!(pool.removal_reason(hash), Some(RemovalReason::Invalidated));
+    }
+
+    #[tokio::test]
+    async fn test_valid_block_range() {
+        let op = create_op(Address::random(), 0, 0);
+        let pool = create_pool(vec![op.clone()]);
+
+        let hash = pool
+            .add_operation(
+                OperationOrigin::Local,
+                op.op.clone(),
+                AddOpOptions {
+                    valid_block_range: ValidBlockRange {
+                        min_block: Some(2),
+                        max_block: Some(3),
+                    },
+                    ..Default::default()
+                },
+            )
             .await
             .unwrap();
 
-        check_ops(pool.best_operations(1, 0).unwrap(), vec![replacement]);
+        // Not selected for a bundle landing before its first block, but kept in the pool
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+        assert!(pool.operation_options(hash).is_some());
+
+        // Bundles built on these heads land in blocks 2 and 3
+        for block_number in [1, 2] {
+            pool.on_chain_update(&ChainUpdate {
+                latest_block_number: block_number,
+                ..Default::default()
+            });
+            check_ops(
+                pool.best_operations(1, 0).await.unwrap(),
+                vec![op.op.clone()],
+            );
+        }
+
+        // A bundle built on block 3 can no longer land within the range
+        pool.on_chain_update(&ChainUpdate {
+            latest_block_number: 3,
+            ..Default::default()
+        });
+        assert_eq!(pool.best_operations(1, 0).await.unwrap(), vec![]);
+        assert_eq!(pool.operation_options(hash), None);
+        assert_eq!(pool.removal_reason(hash), Some(RemovalReason::Expired));
+    }
+
+    #[test]
+    fn test_removed_ops_history_bounded() {
+        let mut removed = RemovedOps::new(2);
+        let hashes = [H256::random(), H256::random(), H256::random()];
+        removed.insert(hashes[0], RemovalReason::Mined);
+        removed.insert(hashes[1], RemovalReason::Expired);
+        removed.insert(hashes[2], RemovalReason::Invalidated);
+
+        assert_eq!(removed.get(hashes[0]), None);
+        assert_eq!(removed.get(hashes[1]), Some(RemovalReason::Expired));
+        assert_eq!(removed.get(hashes[2]), Some(RemovalReason::Invalidated));
+
+        removed.remove(hashes[1]);
+        assert_eq!(removed.get(hashes[1]), None);
+        assert_eq!(removed.order.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_paymaster() {
+        let paymaster = Address::random();
+        let mut ops = vec![];
+        for fee in [3, 2, 1] {
+            let mut op = create_op(Address::random(), 0, fee);
+            op.op.paymaster_and_data = paymaster.as_bytes().to_vec().into();
+            ops.push(op);
+        }
+        let uos = ops.iter().map(|op| op.op.clone()).collect::<Vec<_>>();
+        let rate_limits =
+            HashMap::from([(Entity::paymaster(paymaster), (2, Duration::from_secs(60)))]);
+        let pool = create_pool_with_config(
+            ops,
+            PoolConfig {
+                rate_limits,
+                ..default_config()
+            },
+        );
+
+        for op in &uos[..2] {
+            pool.add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
+                .await
+                .unwrap();
+        }
+        match pool
+            .add_operation(
+                OperationOrigin::Local,
+                uos[2].clone(),
+                AddOpOptions::default(),
+            )
+            .await
+        {
+            Err(MempoolError::RateLimited(entity)) => {
+                assert_eq!(entity, Entity::paymaster(paymaster));
+            }
+            _ => panic!("Expected RateLimited error"),
+        }
+        check_ops(pool.best_operations(3, 0).await.unwrap(), uos[..2].to_vec());
+    }
+
+    #[tokio::test]
+    async fn revalidate_before_bundle() {
+        let uos = vec![
+            create_op(Address::random(), 0, 3).op,
+            create_op(Address::random(), 0, 2).op,
+            create_op(Address::random(), 0, 1).op,
+        ];
+        let mut prechecker = MockPrechecker::new();
+        prechecker.expect_check().returning(|_| Ok(()));
+        let mut simulator = MockSimulator::new();
+        // every op is valid when added
+        simulator
+            .expect_simulate_validation()
+            .times(3)
+            .returning(|_, _, _| Ok(SimulationSuccess::default()));
+        // by the time of bundling the second op no longer validates. Each op is
+        // only revalidated once per block.
+        let invalid_sender = uos[1].sender;
+        simulator
+            .expect_simulate_validation()
+            .times(3)
+            .returning(move |op, _, _| {
+                if op.sender == invalid_sender {
+                    Err(SimulationError::Violations(vec![
+                        SimulationViolation::InvalidSignature,
+                    ]))
+                } else {
+                    Ok(SimulationSuccess::default())
+                }
+            });
+        let pool = create_pool_with_mocks(
+            prechecker,
+            simulator,
+            PoolConfig {
+                revalidate_before_bundle: true,
+                ..default_config()
+            },
+        );
+        for op in &uos {
+            pool.add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
+                .await
+                .unwrap();
+        }
+
+        for _ in 0..2 {
+            check_ops(
+                pool.best_operations(3, 0).await.unwrap(),
+                vec![uos[0].clone(), uos[2].clone()],
+            );
+        }
+        // the invalid op is skipped, not removed
+        assert_eq!(pool.all_operations(3).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn best_operations_matching_revalidates_only_matching_ops() {
+        let paymaster = Address::random();
+        let uos = (0..4)
+            .map(|i| {
+                let mut op = create_op(Address::random(), 0, 4 - i).op;
+                if i % 2 == 0 {
+                    op.paymaster_and_data = paymaster.as_bytes().to_vec().into();
+                }
+                op
+            })
+            .collect::<Vec<_>>();
+        let mut prechecker = MockPrechecker::new();
+        prechecker.expect_check().returning(|_| Ok(()));
+        let mut simulator = MockSimulator::new();
+        // every op is simulated when added, then only the two ops needed to
+        // cover the offset and the result are revalidated
+        simulator
+            .expect_simulate_validation()
+            .times(6)
+            .returning(|_, _, _| Ok(SimulationSuccess::default()));
+        let pool = create_pool_with_mocks(
+            prechecker,
+            simulator,
+            PoolConfig {
+                revalidate_before_bundle: true,
+                ..default_config()
+            },
+        );
+        for op in &uos {
+            pool.add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
+                .await
+                .unwrap();
+        }
+
+        let filter = GetOpsFilter {
+            paymaster: Some(paymaster),
+            offset: 1,
+            ..Default::default()
+        };
+        check_ops(
+            pool.best_operations_matching(1, 0, filter).await.unwrap(),
+            vec![uos[2].clone()],
+        );
+    }
+
+    #[test]
+    fn rate_limiter_window_rolls() {
+        let entity = Entity::account(Address::random());
+        let limits = HashMap::from([(entity, (1, Duration::from_secs(10)))]);
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+
+        assert_eq!(limiter.limited_entity(&limits, [entity], start), None);
+        limiter.record(&limits, [entity], start);
+        assert_eq!(
+            limiter.limited_entity(&limits, [entity], start + Duration::from_secs(5)),
+            Some(entity)
+        );
+        assert_eq!(
+            limiter.limited_entity(&limits, [entity], start + Duration::from_secs(10)),
+            None
+        );
+
+        // entities without a configured limit are never limited or tracked
+        let other = Entity::paymaster(Address::random());
+        limiter.record(&limits, [other], start);
+        assert_eq!(limiter.limited_entity(&limits, [other], start), None);
+        assert!(!limiter.admitted.contains_key(&other));
     }
 
     #[derive(Clone, Debug)]
@@ -827,8 +2805,14 @@ mod tests {
 
     fn create_pool(
         ops: Vec<OpWithErrors>,
-    ) -> UoPool<impl ReputationManager, impl Prechecker, impl Simulator> {
-        let reputation = Arc::new(MockReputationManager::new(THROTTLE_SLACK, BAN_SLACK));
+    ) -> UoPool<MockReputationManager, impl Prechecker, impl Simulator, impl EntryPoint> {
+        create_pool_with_config(ops, default_config())
+    }
+
+    fn create_pool_with_config(
+        ops: Vec<OpWithErrors>,
+        config: PoolConfig,
+    ) -> UoPool<MockReputationManager, impl Prechecker, impl Simulator, impl EntryPoint> {
         let mut simulator = MockSimulator::new();
         let mut prechecker = MockPrechecker::new();
         for op in ops {
@@ -852,36 +2836,88 @@ mod tests {
                     }
                 });
         }
+        create_pool_with_mocks(prechecker, simulator, config)
+    }
+
+    fn create_pool_with_mocks(
+        prechecker: MockPrechecker,
+        simulator: MockSimulator,
+        config: PoolConfig,
+    ) -> UoPool<MockReputationManager, impl Prechecker, impl Simulator, impl EntryPoint> {
+        let mut entry_point = MockEntryPoint::new();
+        entry_point.expect_get_nonce().returning(|sender, key| {
+            if sender == ON_CHAIN_NONCE_SENDER {
+                Ok(ON_CHAIN_NONCE.into())
+            } else {
+                Ok(key << 64)
+            }
+        });
+        create_pool_with_entry_point(prechecker, simulator, entry_point, config)
+    }
+
+    fn create_pool_with_entry_point(
+        prechecker: MockPrechecker,
+        simulator: MockSimulator,
+        entry_point: MockEntryPoint,
+        config: PoolConfig,
+    ) -> UoPool<MockReputationManager, impl Prechecker, impl Simulator, impl EntryPoint> {
+        let reputation = Arc::new(MockReputationManager::new(THROTTLE_SLACK, BAN_SLACK));
+        let (event_sender, _) = broadcast::channel(4);
+        let (mempool_event_sender, _) = broadcast::channel(16);
+        UoPool::new(
+            config,
+            reputation,
+            event_sender,
+            mempool_event_sender,
+            prechecker,
+            simulator,
+            entry_point,
+        )
+    }
 
-        let args = PoolConfig {
+    fn default_config() -> PoolConfig {
+        PoolConfig {
             entry_point: Address::random(),
             chain_id: 1,
             max_userops_per_sender: 16,
             min_replacement_fee_increase_percentage: 10,
             max_size_of_pool_bytes: 10000,
+            max_pool_size: 100,
+            min_priority_fee_per_gas: U256::zero(),
+            min_max_fee_per_gas: U256::zero(),
+            max_op_size_bytes: 131_072,
+            max_bundle_gas: 25_000_000,
             blocklist: None,
             allowlist: None,
             precheck_settings: PrecheckSettings::default(),
             sim_settings: SimulationSettings::default(),
             mempool_channel_configs: HashMap::new(),
             num_shards: 1,
+            pool_shard_count: 1,
+            pool_shard_index: 0,
             throttled_entity_mempool_count: 4,
             throttled_entity_live_blocks: 10,
-        };
-        let (event_sender, _) = broadcast::channel(4);
-        UoPool::new(args, reputation, event_sender, prechecker, simulator)
+            reputation_params: ReputationParams::bundler_default(),
+            reputation_persistence_path: None,
+            rate_limits: HashMap::new(),
+            revalidate_before_bundle: false,
+            require_stake_for_gossip: false,
+            revalidate_reorged_ops: false,
+        }
     }
 
     async fn create_pool_insert_ops(
         ops: Vec<OpWithErrors>,
     ) -> (
-        UoPool<impl ReputationManager, impl Prechecker, impl Simulator>,
+        UoPool<MockReputationManager, impl Prechecker, impl Simulator, impl EntryPoint>,
         Vec<UserOperation>,
     ) {
         let uos = ops.iter().map(|op| op.op.clone()).collect::<Vec<_>>();
         let pool = create_pool(ops);
         for op in &uos {
-            let _ = pool.add_operation(OperationOrigin::Local, op.clone()).await;
+            let _ = pool
+                .add_operation(OperationOrigin::Local, op.clone(), AddOpOptions::default())
+                .await;
         }
         (pool, uos)
     }
@@ -928,7 +2964,7 @@ mod tests {
         }
     }
 
-    #[derive(Default, Clone)]
+    #[derive(Debug, Default, Clone)]
     struct MockReputationManager {
         bundle_invalidation_ops_seen_staked_penalty: u64,
         bundle_invalidation_ops_seen_unstaked_penalty: u64,
@@ -939,10 +2975,11 @@ mod tests {
         counts: Arc<RwLock<Counts>>,
     }
 
-    #[derive(Default)]
+    #[derive(Debug, Default)]
     struct Counts {
         seen: HashMap<Address, u64>,
         included: HashMap<Address, u64>,
+        update_included_calls: usize,
     }
 
     impl MockReputationManager {
@@ -995,6 +3032,18 @@ mod tests {
             *included = included.saturating_sub(1);
         }
 
+        fn update_included(&self, included: &[Address], unincluded: &[Address]) {
+            let mut counts = self.counts.write();
+            counts.update_included_calls += 1;
+            for address in included {
+                *counts.included.entry(*address).or_default() += 1;
+            }
+            for address in unincluded {
+                let count = counts.included.entry(*address).or_default();
+                *count = count.saturating_sub(1);
+            }
+        }
+
         fn dump_reputation(&self) -> Vec<Reputation> {
             self.counts
                 .read()
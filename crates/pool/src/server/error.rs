@@ -22,6 +22,9 @@ pub enum PoolServerError {
     /// Unexpected response from PoolServer
     #[error("Unexpected response from PoolServer")]
     UnexpectedResponse,
+    /// The PoolServer could not be reached, e.g. because it is restarting
+    #[error("PoolServer unavailable: {0}")]
+    Unavailable(String),
     /// Internal error
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -11,14 +11,15 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use std::{collections::HashMap, pin::Pin, sync::Arc};
+use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
 
 use async_stream::stream;
 use async_trait::async_trait;
-use ethers::types::{Address, H256};
-use futures_util::Stream;
+use ethers::types::{Address, H256, U256};
+use futures_util::{Stream, StreamExt};
 use rundler_task::server::{HealthCheck, ServerStatus};
-use rundler_types::{Entity, EntityUpdate, UserOperation};
+use rundler_types::{Entity, EntityUpdate, GasFees, UserOperation};
+use rundler_utils::emit::WithEntryPoint;
 use tokio::{
     sync::{broadcast, mpsc, oneshot},
     task::JoinHandle,
@@ -29,30 +30,53 @@ use tracing::error;
 use super::{PoolResult, PoolServerError};
 use crate::{
     chain::ChainUpdate,
-    mempool::{Mempool, MempoolError, OperationOrigin, PoolOperation},
-    server::{NewHead, PoolServer, Reputation},
+    mempool::{
+        AddOpOptions, Mempool, MempoolError, MempoolEvent, OpPriority, OperationOrigin,
+        OperationRank, PoolOperation, RemovalReason, SenderSummary,
+    },
+    server::{
+        DumpMempoolFilter, GetOpsFilter, ImportMempoolResult, MempoolSnapshot, NewHead, PoolServer,
+        Reputation,
+    },
 };
 
+/// Maximum number of accounts whose operations are simulated at once when
+/// importing a mempool snapshot
+const IMPORT_MEMPOOL_CONCURRENCY: usize = 16;
+
 /// Local pool server builder
 #[derive(Debug)]
 pub struct LocalPoolBuilder {
     req_sender: mpsc::Sender<ServerRequest>,
     req_receiver: mpsc::Receiver<ServerRequest>,
     block_sender: broadcast::Sender<NewHead>,
+    mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
 }
 
 impl LocalPoolBuilder {
     /// Create a new local pool server builder
-    pub fn new(request_capacity: usize, block_capacity: usize) -> Self {
+    pub fn new(
+        request_capacity: usize,
+        block_capacity: usize,
+        mempool_event_capacity: usize,
+    ) -> Self {
         let (req_sender, req_receiver) = mpsc::channel(request_capacity);
         let (block_sender, _) = broadcast::channel(block_capacity);
+        let (mempool_event_sender, _) = broadcast::channel(mempool_event_capacity);
         Self {
             req_sender,
             req_receiver,
             block_sender,
+            mempool_event_sender,
         }
     }
 
+    /// Get the sender mempools publish their events on, which are streamed to
+    /// subscribers of pool events
+    pub(crate) fn mempool_event_sender(&self) -> broadcast::Sender<WithEntryPoint<MempoolEvent>> {
+        self.mempool_event_sender.clone()
+    }
+
     /// Get a handle to the local pool server that can be used to make requests
     pub fn get_handle(&self) -> LocalPoolHandle {
         LocalPoolHandle {
@@ -70,6 +94,7 @@ impl LocalPoolBuilder {
         let mut runner = LocalPoolServerRunner::new(
             self.req_receiver,
             self.block_sender,
+            self.mempool_event_sender,
             mempools,
             chain_updates,
         );
@@ -88,6 +113,7 @@ pub struct LocalPoolHandle {
 struct LocalPoolServerRunner<M> {
     req_receiver: mpsc::Receiver<ServerRequest>,
     block_sender: broadcast::Sender<NewHead>,
+    mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
     mempools: HashMap<Address, Arc<M>>,
     chain_updates: broadcast::Receiver<Arc<ChainUpdate>>,
 }
@@ -118,11 +144,17 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
-    async fn add_op(&self, entry_point: Address, op: UserOperation) -> PoolResult<H256> {
+    async fn add_op(
+        &self,
+        entry_point: Address,
+        op: UserOperation,
+        options: AddOpOptions,
+    ) -> PoolResult<H256> {
         let req = ServerRequestKind::AddOp {
             entry_point,
             op,
             origin: OperationOrigin::Local,
+            options,
         };
         let resp = self.send(req).await?;
         match resp {
@@ -131,16 +163,33 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
+    async fn add_external_ops(
+        &self,
+        ops: Vec<(Address, UserOperation)>,
+    ) -> PoolResult<Vec<Result<H256, MempoolError>>> {
+        let req = ServerRequestKind::AddOps {
+            ops,
+            origin: OperationOrigin::External,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::AddOps { results } => Ok(results),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
     async fn get_ops(
         &self,
         entry_point: Address,
         max_ops: u64,
         shard_index: u64,
+        filter: GetOpsFilter,
     ) -> PoolResult<Vec<PoolOperation>> {
         let req = ServerRequestKind::GetOps {
             entry_point,
             max_ops,
             shard_index,
+            filter,
         };
         let resp = self.send(req).await?;
         match resp {
@@ -149,8 +198,35 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
-    async fn remove_ops(&self, entry_point: Address, ops: Vec<H256>) -> PoolResult<()> {
-        let req = ServerRequestKind::RemoveOps { entry_point, ops };
+    async fn get_ops_with_priority(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+    ) -> PoolResult<Vec<(PoolOperation, OpPriority)>> {
+        let req = ServerRequestKind::GetOpsWithPriority {
+            entry_point,
+            max_ops,
+            shard_index,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetOpsWithPriority { ops } => Ok(ops),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn remove_ops(
+        &self,
+        entry_point: Address,
+        ops: Vec<H256>,
+        reason: RemovalReason,
+    ) -> PoolResult<()> {
+        let req = ServerRequestKind::RemoveOps {
+            entry_point,
+            ops,
+            reason,
+        };
         let resp = self.send(req).await?;
         match resp {
             ServerResponse::RemoveOps => Ok(()),
@@ -158,6 +234,41 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
+    async fn get_op_removal_reason(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<RemovalReason>> {
+        let req = ServerRequestKind::GetOpRemovalReason { entry_point, hash };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetOpRemovalReason { reason } => Ok(reason),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_op_options(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<AddOpOptions>> {
+        let req = ServerRequestKind::GetOpOptions { entry_point, hash };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetOpOptions { options } => Ok(options),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_op_rank(&self, entry_point: Address, hash: H256) -> PoolResult<OperationRank> {
+        let req = ServerRequestKind::GetOpRank { entry_point, hash };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetOpRank { rank } => Ok(rank),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
     async fn remove_entities(&self, entry_point: Address, entities: Vec<Entity>) -> PoolResult<()> {
         let req = ServerRequestKind::RemoveEntities {
             entry_point,
@@ -195,8 +306,15 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
-    async fn debug_dump_mempool(&self, entry_point: Address) -> PoolResult<Vec<PoolOperation>> {
-        let req = ServerRequestKind::DebugDumpMempool { entry_point };
+    async fn debug_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: DumpMempoolFilter,
+    ) -> PoolResult<Vec<PoolOperation>> {
+        let req = ServerRequestKind::DebugDumpMempool {
+            entry_point,
+            filter,
+        };
         let resp = self.send(req).await?;
         match resp {
             ServerResponse::DebugDumpMempool { ops } => Ok(ops),
@@ -229,6 +347,86 @@ impl PoolServer for LocalPoolHandle {
         }
     }
 
+    async fn debug_dump_senders(&self, entry_point: Address) -> PoolResult<Vec<SenderSummary>> {
+        let req = ServerRequestKind::DebugDumpSenders { entry_point };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::DebugDumpSenders { senders } => Ok(senders),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn export_mempool(&self, entry_point: Address) -> PoolResult<MempoolSnapshot> {
+        let req = ServerRequestKind::ExportMempool { entry_point };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::ExportMempool { snapshot } => Ok(snapshot),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn import_mempool(
+        &self,
+        entry_point: Address,
+        snapshot: MempoolSnapshot,
+    ) -> PoolResult<ImportMempoolResult> {
+        let req = ServerRequestKind::ImportMempool {
+            entry_point,
+            snapshot,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::ImportMempool { result } => Ok(result),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_last_block_number(&self, entry_point: Address) -> PoolResult<u64> {
+        let req = ServerRequestKind::GetLastBlockNumber { entry_point };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetLastBlockNumber { block_number } => Ok(block_number),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_next_nonce(&self, entry_point: Address, sender: Address) -> PoolResult<U256> {
+        let req = ServerRequestKind::GetNextNonce {
+            entry_point,
+            sender,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetNextNonce { nonce } => Ok(nonce),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_min_fees(&self, entry_point: Address) -> PoolResult<GasFees> {
+        let req = ServerRequestKind::GetMinFees { entry_point };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetMinFees { fees } => Ok(fees),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
+    async fn get_paymaster_max_cost(
+        &self,
+        entry_point: Address,
+        paymaster: Address,
+    ) -> PoolResult<U256> {
+        let req = ServerRequestKind::GetPaymasterMaxCost {
+            entry_point,
+            paymaster,
+        };
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::GetPaymasterMaxCost { max_cost } => Ok(max_cost),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
+
     async fn subscribe_new_heads(&self) -> PoolResult<Pin<Box<dyn Stream<Item = NewHead> + Send>>> {
         let req = ServerRequestKind::SubscribeNewHeads;
         let resp = self.send(req).await?;
@@ -250,6 +448,33 @@ impl PoolServer for LocalPoolHandle {
             _ => Err(PoolServerError::UnexpectedResponse),
         }
     }
+
+    async fn subscribe_pool_events(
+        &self,
+    ) -> PoolResult<Pin<Box<dyn Stream<Item = WithEntryPoint<MempoolEvent>> + Send>>> {
+        let req = ServerRequestKind::SubscribePoolEvents;
+        let resp = self.send(req).await?;
+        match resp {
+            ServerResponse::SubscribePoolEvents { mut events } => Ok(Box::pin(stream! {
+                loop {
+                    match events.recv().await {
+                        Ok(event) => yield event,
+                        Err(broadcast::error::RecvError::Lagged(c)) => {
+                            // The subscriber can't reconstruct the pool from here, so end
+                            // the stream to make it resubscribe and resynchronize
+                            error!("mempool_events_receiver lagged {c} events, ending stream");
+                            break;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            error!("mempool_events_receiver closed");
+                            break;
+                        }
+                    }
+                }
+            })),
+            _ => Err(PoolServerError::UnexpectedResponse),
+        }
+    }
 }
 
 #[async_trait]
@@ -274,12 +499,14 @@ where
     fn new(
         req_receiver: mpsc::Receiver<ServerRequest>,
         block_sender: broadcast::Sender<NewHead>,
+        mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
         mempools: HashMap<Address, Arc<M>>,
         chain_updates: broadcast::Receiver<Arc<ChainUpdate>>,
     ) -> Self {
         Self {
             req_receiver,
             block_sender,
+            mempool_event_sender,
             mempools,
             chain_updates,
         }
@@ -296,21 +523,70 @@ where
         entry_point: Address,
         max_ops: u64,
         shard_index: u64,
-    ) -> PoolResult<Vec<PoolOperation>> {
-        let mempool = self.get_pool(entry_point)?;
-        Ok(mempool
-            .best_operations(max_ops as usize, shard_index)?
-            .iter()
-            .map(|op| (**op).clone())
-            .collect())
+        filter: GetOpsFilter,
+    ) -> PoolResult<impl Future<Output = PoolResult<Vec<PoolOperation>>>> {
+        let mempool = Arc::clone(self.get_pool(entry_point)?);
+        Ok(async move {
+            let ops = if filter.is_empty() {
+                mempool
+                    .best_operations(max_ops as usize, shard_index)
+                    .await?
+            } else {
+                mempool
+                    .best_operations_matching(max_ops as usize, shard_index, filter)
+                    .await?
+            };
+            Ok(ops.iter().map(|op| (**op).clone()).collect())
+        })
     }
 
-    fn remove_ops(&self, entry_point: Address, ops: &[H256]) -> PoolResult<()> {
+    fn get_ops_with_priority(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+    ) -> PoolResult<impl Future<Output = PoolResult<Vec<(PoolOperation, OpPriority)>>>> {
+        let mempool = Arc::clone(self.get_pool(entry_point)?);
+        Ok(async move {
+            Ok(mempool
+                .best_operations_with_priority(max_ops as usize, shard_index)
+                .await?
+                .into_iter()
+                .map(|(op, priority)| ((*op).clone(), priority))
+                .collect())
+        })
+    }
+
+    fn remove_ops(
+        &self,
+        entry_point: Address,
+        ops: &[H256],
+        reason: RemovalReason,
+    ) -> PoolResult<()> {
         let mempool = self.get_pool(entry_point)?;
-        mempool.remove_operations(ops);
+        mempool.remove_operations(ops, reason);
         Ok(())
     }
 
+    fn get_op_removal_reason(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<RemovalReason>> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.removal_reason(hash))
+    }
+
+    fn get_op_options(&self, entry_point: Address, hash: H256) -> PoolResult<Option<AddOpOptions>> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.operation_options(hash))
+    }
+
+    fn get_op_rank(&self, entry_point: Address, hash: H256) -> PoolResult<OperationRank> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.operation_rank(hash))
+    }
+
     fn remove_entities<'a>(
         &self,
         entry_point: Address,
@@ -342,11 +618,17 @@ where
         Ok(())
     }
 
-    fn debug_dump_mempool(&self, entry_point: Address) -> PoolResult<Vec<PoolOperation>> {
+    fn debug_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: DumpMempoolFilter,
+    ) -> PoolResult<Vec<PoolOperation>> {
         let mempool = self.get_pool(entry_point)?;
         Ok(mempool
             .all_operations(usize::MAX)
             .iter()
+            .filter(|op| filter.matches(op))
+            .take(filter.limit.map_or(usize::MAX, |l| l as usize))
             .map(|op| (**op).clone())
             .collect())
     }
@@ -368,6 +650,39 @@ where
         Ok(mempool.dump_reputation())
     }
 
+    fn debug_dump_senders(&self, entry_point: Address) -> PoolResult<Vec<SenderSummary>> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.dump_senders())
+    }
+
+    fn export_mempool(&self, entry_point: Address) -> PoolResult<MempoolSnapshot> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(MempoolSnapshot {
+            operations: mempool
+                .all_operations(usize::MAX)
+                .into_iter()
+                .map(|op| (*op).clone())
+                .collect(),
+            reputations: mempool.dump_reputation(),
+            omitted_operations: 0,
+        })
+    }
+
+    fn get_last_block_number(&self, entry_point: Address) -> PoolResult<u64> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.block_number())
+    }
+
+    fn get_min_fees(&self, entry_point: Address) -> PoolResult<GasFees> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.min_fees())
+    }
+
+    fn get_paymaster_max_cost(&self, entry_point: Address, paymaster: Address) -> PoolResult<U256> {
+        let mempool = self.get_pool(entry_point)?;
+        Ok(mempool.paymaster_max_cost(paymaster))
+    }
+
     async fn run(&mut self, shutdown_token: CancellationToken) -> anyhow::Result<()> {
         loop {
             tokio::select! {
@@ -383,7 +698,14 @@ where
                         // its bundle building process will want to be able to query the mempool
                         // and only receive operations that have not yet been mined.
                         for mempool in self.mempools.values() {
-                            mempool.on_chain_update(&chain_update);
+                            let reorged_ops = mempool.on_chain_update(&chain_update);
+                            if !reorged_ops.is_empty() {
+                                // Re-admit ops unmined by a reorg only if they are still valid
+                                let mempool = Arc::clone(mempool);
+                                tokio::spawn(async move {
+                                    readmit_reorged_ops(&*mempool, reorged_ops).await
+                                });
+                            }
                         }
 
                         let _ = self.block_sender.send(NewHead {
@@ -399,12 +721,12 @@ where
                                 entry_points: self.mempools.keys().copied().collect()
                             })
                         },
-                        ServerRequestKind::AddOp { entry_point, op, origin } => {
+                        ServerRequestKind::AddOp { entry_point, op, origin, options } => {
                             match self.get_pool(entry_point) {
                                 Ok(mempool) => {
                                     let mempool = Arc::clone(mempool);
                                     tokio::spawn(async move {
-                                        let resp = match mempool.add_operation(origin, op).await {
+                                        let resp = match mempool.add_operation(origin, op, options).await {
                                             Ok(hash) => Ok(ServerResponse::AddOp { hash }),
                                             Err(e) => Err(e.into()),
                                         };
@@ -417,18 +739,85 @@ where
                                 Err(e) => Err(e),
                             }
                         },
-                        ServerRequestKind::GetOps { entry_point, max_ops, shard_index } => {
-                            match self.get_ops(entry_point, max_ops, shard_index) {
-                                Ok(ops) => Ok(ServerResponse::GetOps { ops }),
+                        ServerRequestKind::AddOps { ops, origin } => {
+                            let ops = ops
+                                .into_iter()
+                                .map(|(entry_point, op)| {
+                                    let mempool = self.mempools.get(&entry_point).cloned();
+                                    (entry_point, mempool, op)
+                                })
+                                .collect::<Vec<_>>();
+                            tokio::spawn(async move {
+                                let mut results = Vec::with_capacity(ops.len());
+                                for (entry_point, mempool, op) in ops {
+                                    let result = match mempool {
+                                        Some(mempool) => mempool.add_operation(origin, op, AddOpOptions::default()).await,
+                                        None => {
+                                            metrics::increment_counter!("op_pool_dropped_unknown_entry_point_ops", "entrypoint_addr" => entry_point.to_string());
+                                            Err(MempoolError::UnknownEntryPoint(entry_point))
+                                        }
+                                    };
+                                    results.push(result);
+                                }
+                                if let Err(e) = req.response.send(Ok(ServerResponse::AddOps { results })) {
+                                    tracing::error!("Failed to send response: {:?}", e);
+                                }
+                            });
+                            continue;
+                        },
+                        ServerRequestKind::GetOps { entry_point, max_ops, shard_index, filter } => {
+                            // Selecting ops may simulate them again, so don't block other requests
+                            match self.get_ops(entry_point, max_ops, shard_index, filter) {
+                                Ok(get_ops) => {
+                                    tokio::spawn(async move {
+                                        let resp = get_ops.await.map(|ops| ServerResponse::GetOps { ops });
+                                        if let Err(e) = req.response.send(resp) {
+                                            tracing::error!("Failed to send response: {:?}", e);
+                                        }
+                                    });
+                                    continue;
+                                },
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetOpsWithPriority { entry_point, max_ops, shard_index } => {
+                            match self.get_ops_with_priority(entry_point, max_ops, shard_index) {
+                                Ok(get_ops) => {
+                                    tokio::spawn(async move {
+                                        let resp = get_ops.await.map(|ops| ServerResponse::GetOpsWithPriority { ops });
+                                        if let Err(e) = req.response.send(resp) {
+                                            tracing::error!("Failed to send response: {:?}", e);
+                                        }
+                                    });
+                                    continue;
+                                },
                                 Err(e) => Err(e),
                             }
                         },
-                        ServerRequestKind::RemoveOps { entry_point, ops } => {
-                            match self.remove_ops(entry_point, &ops) {
+                        ServerRequestKind::RemoveOps { entry_point, ops, reason } => {
+                            match self.remove_ops(entry_point, &ops, reason) {
                                 Ok(_) => Ok(ServerResponse::RemoveOps),
                                 Err(e) => Err(e),
                             }
                         },
+                        ServerRequestKind::GetOpRemovalReason { entry_point, hash } => {
+                            match self.get_op_removal_reason(entry_point, hash) {
+                                Ok(reason) => Ok(ServerResponse::GetOpRemovalReason { reason }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetOpOptions { entry_point, hash } => {
+                            match self.get_op_options(entry_point, hash) {
+                                Ok(options) => Ok(ServerResponse::GetOpOptions { options }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetOpRank { entry_point, hash } => {
+                            match self.get_op_rank(entry_point, hash) {
+                                Ok(rank) => Ok(ServerResponse::GetOpRank { rank }),
+                                Err(e) => Err(e),
+                            }
+                        },
                         ServerRequestKind::RemoveEntities { entry_point, entities } => {
                             match self.remove_entities(entry_point, &entities) {
                                 Ok(_) => Ok(ServerResponse::RemoveEntities),
@@ -447,8 +836,8 @@ where
                                 Err(e) => Err(e),
                             }
                         },
-                        ServerRequestKind::DebugDumpMempool { entry_point } => {
-                            match self.debug_dump_mempool(entry_point) {
+                        ServerRequestKind::DebugDumpMempool { entry_point, filter } => {
+                            match self.debug_dump_mempool(entry_point, filter) {
                                 Ok(ops) => Ok(ServerResponse::DebugDumpMempool { ops }),
                                 Err(e) => Err(e),
                             }
@@ -465,9 +854,76 @@ where
                                 Err(e) => Err(e),
                             }
                         },
+                        ServerRequestKind::DebugDumpSenders { entry_point } => {
+                            match self.debug_dump_senders(entry_point) {
+                                Ok(senders) => Ok(ServerResponse::DebugDumpSenders { senders }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::ExportMempool { entry_point } => {
+                            match self.export_mempool(entry_point) {
+                                Ok(snapshot) => Ok(ServerResponse::ExportMempool { snapshot }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::ImportMempool { entry_point, snapshot } => {
+                            // Importing re-simulates every operation, so don't block other requests
+                            match self.get_pool(entry_point) {
+                                Ok(mempool) => {
+                                    let mempool = Arc::clone(mempool);
+                                    tokio::spawn(async move {
+                                        let result = import_mempool(&*mempool, snapshot).await;
+                                        if let Err(e) = req.response.send(Ok(ServerResponse::ImportMempool { result })) {
+                                            tracing::error!("Failed to send response: {:?}", e);
+                                        }
+                                    });
+                                    continue;
+                                },
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetLastBlockNumber { entry_point } => {
+                            match self.get_last_block_number(entry_point) {
+                                Ok(block_number) => Ok(ServerResponse::GetLastBlockNumber { block_number }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetNextNonce { entry_point, sender } => {
+                            match self.get_pool(entry_point) {
+                                Ok(mempool) => {
+                                    let mempool = Arc::clone(mempool);
+                                    tokio::spawn(async move {
+                                        let resp = match mempool.next_nonce(sender).await {
+                                            Ok(nonce) => Ok(ServerResponse::GetNextNonce { nonce }),
+                                            Err(e) => Err(e.into()),
+                                        };
+                                        if let Err(e) = req.response.send(resp) {
+                                            tracing::error!("Failed to send response: {:?}", e);
+                                        }
+                                    });
+                                    continue;
+                                },
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetMinFees { entry_point } => {
+                            match self.get_min_fees(entry_point) {
+                                Ok(fees) => Ok(ServerResponse::GetMinFees { fees }),
+                                Err(e) => Err(e),
+                            }
+                        },
+                        ServerRequestKind::GetPaymasterMaxCost { entry_point, paymaster } => {
+                            match self.get_paymaster_max_cost(entry_point, paymaster) {
+                                Ok(max_cost) => Ok(ServerResponse::GetPaymasterMaxCost { max_cost }),
+                                Err(e) => Err(e),
+                            }
+                        },
                         ServerRequestKind::SubscribeNewHeads => {
                             Ok(ServerResponse::SubscribeNewHeads { new_heads: self.block_sender.subscribe() } )
                         }
+                        ServerRequestKind::SubscribePoolEvents => {
+                            Ok(ServerResponse::SubscribePoolEvents { events: self.mempool_event_sender.subscribe() } )
+                        }
                     };
                     if let Err(e) = req.response.send(resp) {
                         tracing::error!("Failed to send response: {:?}", e);
@@ -486,6 +942,78 @@ struct ServerRequest {
     response: oneshot::Sender<PoolResult<ServerResponse>>,
 }
 
+/// Puts operations unmined by a reorg back into the mempool, dropping those that
+/// no longer pass simulation.
+async fn readmit_reorged_ops<M: Mempool>(mempool: &M, ops: Vec<Arc<PoolOperation>>) {
+    for op in ops {
+        let id = op.uo.id();
+        if let Err(e) = mempool.readmit_operation(op).await {
+            tracing::info!("Dropping op {id:?} unmined in reorg that failed validation: {e}");
+        }
+    }
+}
+
+/// Adds each operation in the snapshot to the mempool, restoring the snapshot's
+/// reputations both before, so that the operations of entities throttled or
+/// banned in the snapshot are treated as such, and after, as adding operations
+/// counts them as seen.
+///
+/// Each account's operations are added in nonce order so that they are
+/// validated against the nonces that precede them, and up to
+/// `IMPORT_MEMPOOL_CONCURRENCY` accounts are imported at once. Operations that
+/// fail validation are reported rather than failing the whole import.
+async fn import_mempool<M: Mempool>(mempool: &M, snapshot: MempoolSnapshot) -> ImportMempoolResult {
+    let MempoolSnapshot {
+        operations,
+        reputations,
+        ..
+    } = snapshot;
+    for rep in &reputations {
+        mempool.set_reputation(rep.address, rep.ops_seen, rep.ops_included);
+    }
+
+    let mut ops_by_sender: HashMap<Address, Vec<PoolOperation>> = HashMap::new();
+    for op in operations {
+        ops_by_sender.entry(op.uo.sender).or_default().push(op);
+    }
+    let outcomes = futures_util::stream::iter(ops_by_sender.into_values())
+        .map(|mut ops| async move {
+            ops.sort_by_key(|op| op.uo.nonce);
+            let mut outcomes = vec![];
+            for op in ops {
+                let id = op.uo.id();
+                let outcome = mempool
+                    .add_operation(
+                        OperationOrigin::Local,
+                        op.uo,
+                        AddOpOptions {
+                            valid_block_range: op.valid_block_range,
+                            metadata: op.metadata,
+                            ..Default::default()
+                        },
+                    )
+                    .await;
+                outcomes.push((id, outcome));
+            }
+            outcomes
+        })
+        .buffer_unordered(IMPORT_MEMPOOL_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut result = ImportMempoolResult::default();
+    for (id, outcome) in outcomes.into_iter().flatten() {
+        match outcome {
+            Ok(hash) => result.imported.push(hash),
+            Err(e) => result.rejected.push((id, e)),
+        }
+    }
+    for rep in reputations {
+        mempool.set_reputation(rep.address, rep.ops_seen, rep.ops_included);
+    }
+    result
+}
+
 #[derive(Debug)]
 enum ServerRequestKind {
     GetSupportedEntryPoints,
@@ -493,15 +1021,39 @@ enum ServerRequestKind {
         entry_point: Address,
         op: UserOperation,
         origin: OperationOrigin,
+        options: AddOpOptions,
+    },
+    AddOps {
+        ops: Vec<(Address, UserOperation)>,
+        origin: OperationOrigin,
     },
     GetOps {
         entry_point: Address,
         max_ops: u64,
         shard_index: u64,
+        filter: GetOpsFilter,
+    },
+    GetOpsWithPriority {
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
     },
     RemoveOps {
         entry_point: Address,
         ops: Vec<H256>,
+        reason: RemovalReason,
+    },
+    GetOpRemovalReason {
+        entry_point: Address,
+        hash: H256,
+    },
+    GetOpOptions {
+        entry_point: Address,
+        hash: H256,
+    },
+    GetOpRank {
+        entry_point: Address,
+        hash: H256,
     },
     RemoveEntities {
         entry_point: Address,
@@ -514,6 +1066,7 @@ enum ServerRequestKind {
     DebugClearState,
     DebugDumpMempool {
         entry_point: Address,
+        filter: DumpMempoolFilter,
     },
     DebugSetReputations {
         entry_point: Address,
@@ -522,7 +1075,32 @@ enum ServerRequestKind {
     DebugDumpReputation {
         entry_point: Address,
     },
+    DebugDumpSenders {
+        entry_point: Address,
+    },
+    ExportMempool {
+        entry_point: Address,
+    },
+    ImportMempool {
+        entry_point: Address,
+        snapshot: MempoolSnapshot,
+    },
+    GetLastBlockNumber {
+        entry_point: Address,
+    },
+    GetNextNonce {
+        entry_point: Address,
+        sender: Address,
+    },
+    GetMinFees {
+        entry_point: Address,
+    },
+    GetPaymasterMaxCost {
+        entry_point: Address,
+        paymaster: Address,
+    },
     SubscribeNewHeads,
+    SubscribePoolEvents,
 }
 
 #[derive(Debug)]
@@ -533,10 +1111,25 @@ enum ServerResponse {
     AddOp {
         hash: H256,
     },
+    AddOps {
+        results: Vec<Result<H256, MempoolError>>,
+    },
     GetOps {
         ops: Vec<PoolOperation>,
     },
+    GetOpsWithPriority {
+        ops: Vec<(PoolOperation, OpPriority)>,
+    },
     RemoveOps,
+    GetOpRemovalReason {
+        reason: Option<RemovalReason>,
+    },
+    GetOpOptions {
+        options: Option<AddOpOptions>,
+    },
+    GetOpRank {
+        rank: OperationRank,
+    },
     RemoveEntities,
     UpdateEntities,
     DebugClearState,
@@ -547,9 +1140,33 @@ enum ServerResponse {
     DebugDumpReputation {
         reputations: Vec<Reputation>,
     },
+    DebugDumpSenders {
+        senders: Vec<SenderSummary>,
+    },
+    ExportMempool {
+        snapshot: MempoolSnapshot,
+    },
+    ImportMempool {
+        result: ImportMempoolResult,
+    },
+    GetLastBlockNumber {
+        block_number: u64,
+    },
+    GetNextNonce {
+        nonce: U256,
+    },
+    GetMinFees {
+        fees: GasFees,
+    },
+    GetPaymasterMaxCost {
+        max_cost: U256,
+    },
     SubscribeNewHeads {
         new_heads: broadcast::Receiver<NewHead>,
     },
+    SubscribePoolEvents {
+        events: broadcast::Receiver<WithEntryPoint<MempoolEvent>>,
+    },
 }
 
 #[cfg(test)]
@@ -559,7 +1176,10 @@ mod tests {
     use futures_util::StreamExt;
 
     use super::*;
-    use crate::{chain::ChainUpdate, mempool::MockMempool};
+    use crate::{
+        chain::ChainUpdate,
+        mempool::{MockMempool, ReputationStatus},
+    };
 
     #[tokio::test]
     async fn test_add_op() {
@@ -567,23 +1187,124 @@ mod tests {
         let hash0 = H256::random();
         mock_pool
             .expect_add_operation()
-            .returning(move |_, _| Ok(hash0));
+            .returning(move |_, _, _| Ok(hash0));
 
         let ep = Address::random();
         let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
 
         let hash1 = state
             .handle
-            .add_op(ep, UserOperation::default())
+            .add_op(ep, UserOperation::default(), AddOpOptions::default())
             .await
             .unwrap();
         assert_eq!(hash0, hash1);
     }
 
+    #[tokio::test]
+    async fn test_get_next_nonce() {
+        let sender = Address::random();
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_next_nonce()
+            .withf(move |s| *s == sender)
+            .returning(|_| Ok(U256::from(3)));
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        let nonce = state.handle.get_next_nonce(ep, sender).await.unwrap();
+        assert_eq!(nonce, U256::from(3));
+
+        let err = state
+            .handle
+            .get_next_nonce(Address::random(), sender)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            PoolServerError::MempoolError(MempoolError::UnknownEntryPoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_min_fees() {
+        let fees = GasFees {
+            max_fee_per_gas: U256::from(20),
+            max_priority_fee_per_gas: U256::from(10),
+        };
+        let mut mock_pool = MockMempool::new();
+        mock_pool.expect_min_fees().return_const(fees);
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        assert_eq!(state.handle.get_min_fees(ep).await.unwrap(), fees);
+        assert!(state.handle.get_min_fees(Address::random()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_paymaster_max_cost() {
+        let paymaster = Address::random();
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_paymaster_max_cost()
+            .withf(move |p| *p == paymaster)
+            .return_const(U256::from(1_000));
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        let max_cost = state
+            .handle
+            .get_paymaster_max_cost(ep, paymaster)
+            .await
+            .unwrap();
+        assert_eq!(max_cost, U256::from(1_000));
+        assert!(state
+            .handle
+            .get_paymaster_max_cost(Address::random(), paymaster)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_ops() {
+        let hash = H256::random();
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_remove_operations()
+            .withf(move |hashes, reason| {
+                hashes.to_vec() == vec![hash] && *reason == RemovalReason::Invalidated
+            })
+            .return_const(());
+        mock_pool
+            .expect_removal_reason()
+            .withf(move |h| *h == hash)
+            .return_const(Some(RemovalReason::Invalidated));
+        mock_pool
+            .expect_operation_options()
+            .withf(move |h| *h == hash)
+            .return_const(None);
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        state
+            .handle
+            .remove_ops(ep, vec![hash], RemovalReason::Invalidated)
+            .await
+            .unwrap();
+        assert_eq!(
+            state.handle.get_op_removal_reason(ep, hash).await.unwrap(),
+            Some(RemovalReason::Invalidated)
+        );
+        assert_eq!(state.handle.get_op_options(ep, hash).await.unwrap(), None);
+    }
+
     #[tokio::test]
     async fn test_chain_update() {
         let mut mock_pool = MockMempool::new();
-        mock_pool.expect_on_chain_update().returning(|_| ());
+        mock_pool.expect_on_chain_update().returning(|_| vec![]);
 
         let ep = Address::random();
         let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
@@ -606,6 +1327,106 @@ mod tests {
         assert_eq!(number, new_block.block_number);
     }
 
+    #[tokio::test]
+    async fn test_chain_update_readmits_reorged_ops() {
+        let sender = Address::random();
+        let (tx, rx) = oneshot::channel();
+        let tx = std::sync::Mutex::new(Some(tx));
+        let mut mock_pool = MockMempool::new();
+        mock_pool.expect_on_chain_update().returning(move |_| {
+            vec![Arc::new(PoolOperation {
+                uo: UserOperation {
+                    sender,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })]
+        });
+        mock_pool.expect_add_operation().times(0);
+        mock_pool
+            .expect_readmit_operation()
+            .times(1)
+            .returning(move |op| {
+                let _ = tx.lock().unwrap().take().unwrap().send(op.uo.sender);
+                Ok(H256::random())
+            });
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+        state
+            .chain_update_tx
+            .send(Arc::new(ChainUpdate {
+                reorg_depth: 1,
+                ..Default::default()
+            }))
+            .unwrap();
+
+        assert_eq!(rx.await.unwrap(), sender);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_pool_events_ends_on_lag() {
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(MockMempool::new()))]));
+
+        let mut sub = state.handle.subscribe_pool_events().await.unwrap();
+
+        // Overflow the event channel before the subscriber reads anything
+        for block_number in 0..11 {
+            state
+                .mempool_event_tx
+                .send(WithEntryPoint {
+                    entry_point: ep,
+                    event: MempoolEvent::BlockProcessed { block_number },
+                })
+                .unwrap();
+        }
+
+        assert!(sub.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_pool_events() {
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(MockMempool::new()))]));
+
+        let mut sub = state.handle.subscribe_pool_events().await.unwrap();
+
+        let hash = H256::random();
+        state
+            .mempool_event_tx
+            .send(WithEntryPoint {
+                entry_point: ep,
+                event: MempoolEvent::OpRemoved {
+                    op_hash: hash,
+                    reason: RemovalReason::Mined,
+                },
+            })
+            .unwrap();
+        state
+            .mempool_event_tx
+            .send(WithEntryPoint {
+                entry_point: ep,
+                event: MempoolEvent::BlockProcessed { block_number: 1 },
+            })
+            .unwrap();
+
+        let event = sub.next().await.unwrap();
+        assert_eq!(event.entry_point, ep);
+        assert_eq!(
+            event.event,
+            MempoolEvent::OpRemoved {
+                op_hash: hash,
+                reason: RemovalReason::Mined,
+            }
+        );
+        let event = sub.next().await.unwrap();
+        assert_eq!(
+            event.event,
+            MempoolEvent::BlockProcessed { block_number: 1 }
+        );
+    }
+
     #[tokio::test]
     async fn test_get_supported_entry_points() {
         let mut eps0 = vec![Address::random(), Address::random(), Address::random()];
@@ -633,13 +1454,13 @@ mod tests {
         let hashes = [h0, h1, h2];
         pools[0]
             .expect_add_operation()
-            .returning(move |_, _| Ok(h0));
+            .returning(move |_, _, _| Ok(h0));
         pools[1]
             .expect_add_operation()
-            .returning(move |_, _| Ok(h1));
+            .returning(move |_, _, _| Ok(h1));
         pools[2]
             .expect_add_operation()
-            .returning(move |_, _| Ok(h2));
+            .returning(move |_, _, _| Ok(h2));
 
         let state = setup(
             zip(eps.iter(), pools.into_iter())
@@ -652,27 +1473,261 @@ mod tests {
                 *hash,
                 state
                     .handle
-                    .add_op(*ep, UserOperation::default())
+                    .add_op(*ep, UserOperation::default(), AddOpOptions::default(),)
                     .await
                     .unwrap()
             );
         }
     }
 
+    #[tokio::test]
+    async fn test_add_external_ops_unknown_entry_point() {
+        let mut mock_pool = MockMempool::new();
+        let hash = H256::random();
+        mock_pool
+            .expect_add_operation()
+            .withf(|origin, _, _| matches!(origin, OperationOrigin::External))
+            .times(2)
+            .returning(move |_, _, _| Ok(hash));
+
+        let ep = Address::random();
+        let unknown_ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        let results = state
+            .handle
+            .add_external_ops(vec![
+                (ep, UserOperation::default()),
+                (unknown_ep, UserOperation::default()),
+                (ep, UserOperation::default()),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &hash);
+        assert!(
+            matches!(results[1], Err(MempoolError::UnknownEntryPoint(addr)) if addr == unknown_ep)
+        );
+        assert_eq!(results[2].as_ref().unwrap(), &hash);
+    }
+
+    #[tokio::test]
+    async fn test_get_ops_filter() {
+        let paymaster = Address::random();
+        let filter = GetOpsFilter {
+            paymaster: Some(paymaster),
+            offset: 1,
+            ..Default::default()
+        };
+        let op = Arc::new(PoolOperation {
+            uo: UserOperation {
+                sender: Address::random(),
+                paymaster_and_data: paymaster.as_bytes().to_vec().into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let expected = (*op).clone();
+
+        // the filter is passed to the mempool, so that it only selects and
+        // revalidates matching ops
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_best_operations_matching()
+            .withf(move |max, shard_index, f| *max == 10 && *shard_index == 0 && *f == filter)
+            .returning(move |_, _, _| Ok(vec![op.clone()]));
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        let ret = state.handle.get_ops(ep, 10, 0, filter).await.unwrap();
+        assert_eq!(ret, vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn test_debug_dump_mempool_filter() {
+        let sender = Address::random();
+        let ops = (0..5_u64)
+            .map(|i| {
+                Arc::new(PoolOperation {
+                    uo: UserOperation {
+                        sender: if i < 3 { sender } else { Address::random() },
+                        nonce: i.into(),
+                        max_fee_per_gas: (10 * i).into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let expected = ops.clone();
+
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_all_operations()
+            .returning(move |_| ops.clone());
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        let ret = state
+            .handle
+            .debug_dump_mempool(ep, DumpMempoolFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(ret.len(), 5);
+
+        let filter = DumpMempoolFilter {
+            min_max_fee_per_gas: Some(10.into()),
+            sender: Some(sender),
+            ..Default::default()
+        };
+        let ret = state.handle.debug_dump_mempool(ep, filter).await.unwrap();
+        assert_eq!(ret, vec![(*expected[1]).clone(), (*expected[2]).clone()]);
+
+        let filter = DumpMempoolFilter {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let ret = state.handle.debug_dump_mempool(ep, filter).await.unwrap();
+        assert_eq!(ret, vec![(*expected[0]).clone(), (*expected[1]).clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_get_op_rank() {
+        let hash = H256::random();
+        let rank = OperationRank {
+            rank: Some(2),
+            pool_size: 10,
+        };
+        let mut mock_pool = MockMempool::new();
+        mock_pool
+            .expect_operation_rank()
+            .withf(move |h| *h == hash)
+            .return_const(rank);
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(mock_pool))]));
+
+        assert_eq!(state.handle.get_op_rank(ep, hash).await.unwrap(), rank);
+        assert!(state
+            .handle
+            .get_op_rank(Address::random(), hash)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_import_mempool() {
+        let sender = Address::random();
+        let ops = (0..3_u64)
+            .map(|i| {
+                Arc::new(PoolOperation {
+                    uo: UserOperation {
+                        sender,
+                        nonce: i.into(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let reputation = Reputation {
+            address: Address::random(),
+            status: ReputationStatus::Throttled,
+            ops_seen: 20,
+            ops_included: 1,
+        };
+
+        let mut export_pool = MockMempool::new();
+        let exported_ops = ops.clone();
+        export_pool
+            .expect_all_operations()
+            .returning(move |_| exported_ops.clone());
+        let exported_reputation = reputation.clone();
+        export_pool
+            .expect_dump_reputation()
+            .returning(move || vec![exported_reputation.clone()]);
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(export_pool))]));
+        let mut snapshot = state.handle.export_mempool(ep).await.unwrap();
+        assert_eq!(
+            snapshot.operations,
+            ops.iter().map(|op| (**op).clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(snapshot.reputations.len(), 1);
+
+        // Reputations are restored before and after the operations are added,
+        // operations are imported in nonce order, and a rejected operation
+        // doesn't stop the rest of the import
+        snapshot.operations.reverse();
+        let mut import_pool = MockMempool::new();
+        let mut seq = mockall::Sequence::new();
+        let imported_reputation = reputation.clone();
+        import_pool
+            .expect_set_reputation()
+            .withf(move |address, ops_seen, ops_included| {
+                *address == imported_reputation.address && *ops_seen == 20 && *ops_included == 1
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_const(());
+        for i in 0..3_u64 {
+            import_pool
+                .expect_add_operation()
+                .withf(move |origin, op, _| {
+                    matches!(origin, OperationOrigin::Local) && op.nonce == i.into()
+                })
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move |_, op, _| {
+                    if i == 1 {
+                        Err(MempoolError::OperationAlreadyKnown)
+                    } else {
+                        Ok(op.op_hash(Address::zero(), 0))
+                    }
+                });
+        }
+        import_pool
+            .expect_set_reputation()
+            .withf(move |address, ops_seen, ops_included| {
+                *address == reputation.address && *ops_seen == 20 && *ops_included == 1
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_const(());
+
+        let ep = Address::random();
+        let state = setup(HashMap::from([(ep, Arc::new(import_pool))]));
+        let result = state.handle.import_mempool(ep, snapshot).await.unwrap();
+        assert_eq!(result.imported.len(), 2);
+        assert_eq!(result.rejected.len(), 1);
+        assert_eq!(result.rejected[0].0, ops[1].uo.id());
+        assert!(matches!(
+            result.rejected[0].1,
+            MempoolError::OperationAlreadyKnown
+        ));
+    }
+
     struct State {
         handle: LocalPoolHandle,
         chain_update_tx: broadcast::Sender<Arc<ChainUpdate>>,
+        mempool_event_tx: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
         _run_handle: JoinHandle<anyhow::Result<()>>,
     }
 
     fn setup(pools: HashMap<Address, Arc<MockMempool>>) -> State {
-        let builder = LocalPoolBuilder::new(10, 10);
+        let builder = LocalPoolBuilder::new(10, 10, 10);
         let handle = builder.get_handle();
+        let mempool_event_tx = builder.mempool_event_sender();
         let (tx, rx) = broadcast::channel(10);
         let run_handle = builder.run(pools, rx, CancellationToken::new());
         State {
             handle,
             chain_update_tx: tx,
+            mempool_event_tx,
             _run_handle: run_handle,
         }
     }
@@ -14,21 +14,27 @@
 mod error;
 mod local;
 mod remote;
+mod sharded;
 
 use std::pin::Pin;
 
 use async_trait::async_trait;
 pub use error::PoolServerError;
-use ethers::types::{Address, H256};
+use ethers::types::{Address, H256, U256};
 use futures_util::Stream;
 pub use local::{LocalPoolBuilder, LocalPoolHandle};
-#[cfg(feature = "test-utils")]
+#[cfg(any(test, feature = "test-utils"))]
 use mockall::automock;
 pub(crate) use remote::spawn_remote_mempool_server;
 pub use remote::RemotePoolClient;
-use rundler_types::{Entity, EntityUpdate, UserOperation};
+use rundler_types::{Entity, EntityUpdate, GasFees, UserOperation, UserOperationId};
+use rundler_utils::emit::WithEntryPoint;
+pub use sharded::ShardedPoolClient;
 
-use crate::mempool::{PoolOperation, Reputation};
+use crate::mempool::{
+    AddOpOptions, GetOpsFilter, MempoolError, MempoolEvent, OpPriority, OperationRank,
+    PoolOperation, RemovalReason, Reputation, SenderSummary,
+};
 
 /// Result type for pool server operations.
 pub type PoolResult<T> = std::result::Result<T, PoolServerError>;
@@ -48,26 +54,118 @@ impl Default for NewHead {
     }
 }
 
+/// Filtering options for `debug_dump_mempool`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DumpMempoolFilter {
+    /// Only return operations with at least this `max_fee_per_gas`
+    pub min_max_fee_per_gas: Option<U256>,
+    /// Only return operations from this sender
+    pub sender: Option<Address>,
+    /// Maximum number of operations to return
+    pub limit: Option<u64>,
+}
+
+impl DumpMempoolFilter {
+    /// Returns true if the operation passes the filters
+    pub fn matches(&self, op: &PoolOperation) -> bool {
+        self.min_max_fee_per_gas
+            .map_or(true, |fee| op.uo.max_fee_per_gas >= fee)
+            && self.sender.map_or(true, |s| op.uo.sender == s)
+    }
+}
+
+/// Full state of an entry point's mempool, used to move a pool between hosts
+#[derive(Clone, Debug, Default)]
+pub struct MempoolSnapshot {
+    /// Operations in the pool, best first
+    pub operations: Vec<PoolOperation>,
+    /// Reputations of the entities the pool has seen
+    pub reputations: Vec<Reputation>,
+    /// Number of the pool's worst operations left out of the snapshot to bound
+    /// its size
+    pub omitted_operations: u64,
+}
+
+/// Outcome of importing a [`MempoolSnapshot`]
+#[derive(Debug, Default)]
+pub struct ImportMempoolResult {
+    /// Hashes of the operations that were added to the pool
+    pub imported: Vec<H256>,
+    /// Operations that failed validation against the current chain state, and why
+    pub rejected: Vec<(UserOperationId, MempoolError)>,
+}
+
 /// Pool server trait
-#[cfg_attr(feature = "test-utils", automock)]
+#[cfg_attr(any(test, feature = "test-utils"), automock)]
 #[async_trait]
 pub trait PoolServer: Send + Sync + 'static {
     /// Get the supported entry points of the pool
     async fn get_supported_entry_points(&self) -> PoolResult<Vec<Address>>;
 
-    /// Add an operation to the pool
-    async fn add_op(&self, entry_point: Address, op: UserOperation) -> PoolResult<H256>;
+    /// Add an operation to the pool, subject to the submitter's `options`
+    async fn add_op(
+        &self,
+        entry_point: Address,
+        op: UserOperation,
+        options: AddOpOptions,
+    ) -> PoolResult<H256>;
+
+    /// Add a batch of operations received from an external source, such as gossip
+    ///
+    /// Each operation is added independently and its result returned in order. Operations
+    /// targeting an entry point not served by the pool are dropped with
+    /// `MempoolError::UnknownEntryPoint` instead of failing the whole batch.
+    async fn add_external_ops(
+        &self,
+        ops: Vec<(Address, UserOperation)>,
+    ) -> PoolResult<Vec<Result<H256, MempoolError>>>;
 
     /// Get operations from the pool
+    ///
+    /// Returns up to `max_ops` of the best operations in the shard that match the
+    /// given filter, skipping the first `filter.offset` matches.
     async fn get_ops(
         &self,
         entry_point: Address,
         max_ops: u64,
         shard_index: u64,
+        filter: GetOpsFilter,
     ) -> PoolResult<Vec<PoolOperation>>;
 
+    /// Get up to `max_ops` of the best operations in the shard, each with the
+    /// priority the pool ordered it by
+    async fn get_ops_with_priority(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+    ) -> PoolResult<Vec<(PoolOperation, OpPriority)>>;
+
     /// Remove operations from the pool by hash
-    async fn remove_ops(&self, entry_point: Address, ops: Vec<H256>) -> PoolResult<()>;
+    async fn remove_ops(
+        &self,
+        entry_point: Address,
+        ops: Vec<H256>,
+        reason: RemovalReason,
+    ) -> PoolResult<()>;
+
+    /// Get the reason an operation was removed from the pool, if it was removed recently
+    async fn get_op_removal_reason(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<RemovalReason>>;
+
+    /// Get the options an operation was added to the pool with, if it is in the pool
+    async fn get_op_options(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<AddOpOptions>>;
+
+    /// Get the position of an operation in the order operations are selected for
+    /// bundles, along with the size of the pool
+    async fn get_op_rank(&self, entry_point: Address, hash: H256) -> PoolResult<OperationRank>;
 
     /// Remove operations associated with entities from the pool
     async fn remove_entities(&self, entry_point: Address, entities: Vec<Entity>) -> PoolResult<()>;
@@ -79,17 +177,49 @@ pub trait PoolServer: Send + Sync + 'static {
         entities: Vec<EntityUpdate>,
     ) -> PoolResult<()>;
 
+    /// Get the number of the last block the pool processed for an entry point
+    async fn get_last_block_number(&self, entry_point: Address) -> PoolResult<u64>;
+
+    /// Get the next usable nonce for a sender, accounting for its operations in the pool
+    async fn get_next_nonce(&self, entry_point: Address, sender: Address) -> PoolResult<U256>;
+
+    /// Get the minimum fees an operation must pay to be admitted to the pool for an entry point
+    async fn get_min_fees(&self, entry_point: Address) -> PoolResult<GasFees>;
+
+    /// Get the total maximum gas cost of the operations in the pool sponsored by a paymaster
+    async fn get_paymaster_max_cost(
+        &self,
+        entry_point: Address,
+        paymaster: Address,
+    ) -> PoolResult<U256>;
+
     /// Subscribe to new chain heads from the pool.
     ///
     /// The pool will notify the subscriber when a new chain head is received, and the pool
     /// has processed all operations up to that head.
     async fn subscribe_new_heads(&self) -> PoolResult<Pin<Box<dyn Stream<Item = NewHead> + Send>>>;
 
+    /// Subscribe to changes to the contents of the pool's mempools.
+    ///
+    /// Each event is tagged with the entry point of the mempool it occurred in. If a
+    /// subscriber falls too far behind to receive every event, its stream ends
+    /// rather than blocking the pool, and it should resubscribe and resynchronize.
+    async fn subscribe_pool_events(
+        &self,
+    ) -> PoolResult<Pin<Box<dyn Stream<Item = WithEntryPoint<MempoolEvent>> + Send>>>;
+
     /// Clear the pool state, used for debug methods
     async fn debug_clear_state(&self) -> PoolResult<()>;
 
-    /// Dump all operations in the pool, used for debug methods
-    async fn debug_dump_mempool(&self, entry_point: Address) -> PoolResult<Vec<PoolOperation>>;
+    /// Dump operations in the pool, used for debug methods
+    ///
+    /// Returns the operations in the pool that match the given filter, best first,
+    /// up to `filter.limit` of them
+    async fn debug_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: DumpMempoolFilter,
+    ) -> PoolResult<Vec<PoolOperation>>;
 
     /// Set reputations for entities, used for debug methods
     async fn debug_set_reputations(
@@ -100,4 +230,24 @@ pub trait PoolServer: Send + Sync + 'static {
 
     /// Dump reputations for entities, used for debug methods
     async fn debug_dump_reputation(&self, entry_point: Address) -> PoolResult<Vec<Reputation>>;
+
+    /// Dump a summary of each sender with operations in the pool, used for debug methods
+    async fn debug_dump_senders(&self, entry_point: Address) -> PoolResult<Vec<SenderSummary>>;
+
+    /// Export the operations and reputations of an entry point's pool, to be
+    /// imported into another pool with `import_mempool`
+    async fn export_mempool(&self, entry_point: Address) -> PoolResult<MempoolSnapshot>;
+
+    /// Import a snapshot exported by `export_mempool`
+    ///
+    /// Each operation is validated against the current chain state as if newly
+    /// submitted, and operations that fail are reported in the result. Reputations
+    /// are set before the operations are added, so that entities throttled or
+    /// banned in the snapshot stay so, and again afterwards, so they match the
+    /// snapshot.
+    async fn import_mempool(
+        &self,
+        entry_point: Address,
+        snapshot: MempoolSnapshot,
+    ) -> PoolResult<ImportMempoolResult>;
 }
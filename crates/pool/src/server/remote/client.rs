@@ -11,40 +11,80 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use std::{pin::Pin, str::FromStr};
+use std::{future::Future, pin::Pin, str::FromStr, time::Duration};
 
-use ethers::types::{Address, H256};
+use anyhow::Context;
+use ethers::types::{Address, H256, U256};
 use futures_util::Stream;
+use prost::Message;
 use rundler_task::{
-    grpc::protos::{from_bytes, ConversionError},
+    grpc::protos::{from_bytes, to_le_bytes, ConversionError},
     server::{HealthCheck, ServerStatus},
 };
-use rundler_types::{Entity, EntityUpdate, UserOperation};
-use rundler_utils::retry::{self, UnlimitedRetryOpts};
-use tokio::sync::mpsc;
+use rundler_types::{Entity, EntityUpdate, GasFees, UserOperation, UserOperationId};
+use rundler_utils::{
+    emit::WithEntryPoint,
+    retry::{self, UnlimitedRetryOpts},
+};
+use tokio::{sync::mpsc, time};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::{
     async_trait,
-    transport::{Channel, Uri},
+    transport::{Channel, Endpoint},
+    Response, Status,
 };
 use tonic_health::{
     pb::{health_client::HealthClient, HealthCheckRequest},
     ServingStatus,
 };
+use tower::discover::Change;
 
-use super::protos::{
-    self, add_op_response, debug_clear_state_response, debug_dump_mempool_response,
-    debug_dump_reputation_response, debug_set_reputation_response, get_ops_response,
-    op_pool_client::OpPoolClient, remove_entities_response, remove_ops_response,
-    update_entities_response, AddOpRequest, DebugClearStateRequest, DebugDumpMempoolRequest,
-    DebugDumpReputationRequest, DebugSetReputationRequest, GetOpsRequest, RemoveEntitiesRequest,
-    RemoveOpsRequest, SubscribeNewHeadsRequest, SubscribeNewHeadsResponse, UpdateEntitiesRequest,
+use super::{
+    error::is_unavailable,
+    protos::{
+        self, add_external_ops_response, add_op_response, debug_clear_state_response,
+        debug_dump_mempool_response, debug_dump_reputation_response, debug_dump_senders_response,
+        debug_set_reputation_response, export_mempool_response, get_last_block_number_response,
+        get_min_fees_response, get_next_nonce_response, get_op_options_response,
+        get_op_rank_response, get_op_removal_reason_response, get_ops_response,
+        get_ops_with_priority_response, get_paymaster_max_cost_response, import_mempool_response,
+        op_pool_client::OpPoolClient, remove_entities_response, remove_ops_response,
+        update_entities_response, AddExternalOpsRequest, AddOpRequest, DebugClearStateRequest,
+        DebugDumpMempoolRequest, DebugDumpReputationRequest, DebugDumpSendersRequest,
+        DebugSetReputationRequest, EntryPointOp, ExportMempoolRequest, GetLastBlockNumberRequest,
+        GetMinFeesRequest, GetNextNonceRequest, GetOpOptionsRequest, GetOpRankRequest,
+        GetOpRemovalReasonRequest, GetOpsRequest, GetOpsWithPriorityRequest,
+        GetPaymasterMaxCostRequest, ImportMempoolRequest, RemoveEntitiesRequest, RemoveOpsRequest,
+        SubscribeNewHeadsRequest, SubscribeNewHeadsResponse, SubscribePoolEventsRequest,
+        SubscribePoolEventsResponse, UpdateEntitiesRequest,
+    },
 };
 use crate::{
-    mempool::{PoolOperation, Reputation},
-    server::{error::PoolServerError, NewHead, PoolResult, PoolServer},
+    mempool::{
+        AddOpOptions, MempoolError, MempoolEvent, OpPriority, OperationRank, PoolOperation,
+        RemovalReason, Reputation, SenderSummary,
+    },
+    server::{
+        error::PoolServerError, DumpMempoolFilter, GetOpsFilter, ImportMempoolResult,
+        MempoolSnapshot, NewHead, PoolResult, PoolServer,
+    },
 };
 
+/// Maximum number of attempts for a request while the pool server is unreachable
+const RECONNECT_MAX_ATTEMPTS: u64 = 3;
+/// Wait before the first retry of a request while the pool server is unreachable
+const RECONNECT_MIN_WAIT: Duration = Duration::from_millis(100);
+/// Maximum wait between retries of a request while the pool server is unreachable
+const RECONNECT_MAX_WAIT: Duration = Duration::from_secs(1);
+/// Timeout for establishing a connection to a pool server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval of the keep-alive pings that detect broken connections to a pool server
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// How long to wait for a keep-alive ping to be acknowledged before dropping the connection
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of connections opened to a pool server
+const CONNECTION_COUNT: usize = 4;
+
 /// Remote pool client
 ///
 /// Used to submit requests to a remote pool server.
@@ -56,16 +96,56 @@ pub struct RemotePoolClient {
 
 impl RemotePoolClient {
     /// Connect to a remote pool server, returning a client for submitting requests.
+    ///
+    /// Requests are load balanced across several connections to the server, and a
+    /// connection that breaks is reconnected in the background while requests go to
+    /// the others.
     pub async fn connect(url: String) -> anyhow::Result<Self> {
-        let op_pool_client = OpPoolClient::connect(url.clone()).await?;
-        let op_pool_health =
-            HealthClient::new(Channel::builder(Uri::from_str(&url)?).connect().await?);
+        let endpoint = connection_endpoint(&url)?;
+        endpoint
+            .connect()
+            .await
+            .with_context(|| format!("pool server at {url} should be reachable"))?;
+
+        let channel = balanced_channel(endpoint);
         Ok(Self {
-            op_pool_client,
-            op_pool_health,
+            op_pool_client: OpPoolClient::new(channel.clone()),
+            op_pool_health: HealthClient::new(channel),
         })
     }
 
+    // Sends a request to the pool server, retrying with a bounded backoff while the
+    // server is unreachable, e.g. while it restarts. The channel reconnects after a
+    // connection failure, so retrying is enough to pick up a restarted server.
+    //
+    // Only used for requests that read pool state. A request that fails as
+    // unavailable may still have reached the server, so requests that change pool
+    // state are sent once and their failures returned to the caller.
+    async fn call_with_reconnect<F, Fut, T>(&self, func: F) -> Result<Response<T>, Status>
+    where
+        F: Fn(OpPoolClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
+        let mut wait = RECONNECT_MIN_WAIT;
+        let mut attempt_number = 1;
+        loop {
+            match func(self.op_pool_client.clone()).await {
+                Err(status)
+                    if attempt_number < RECONNECT_MAX_ATTEMPTS && is_unavailable(&status) =>
+                {
+                    tracing::warn!(
+                        "pool server unavailable (attempt {attempt_number}), retrying in {wait:?}: {}",
+                        status.message()
+                    );
+                    time::sleep(wait).await;
+                    wait = (2 * wait).min(RECONNECT_MAX_WAIT);
+                    attempt_number += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
     // Handler for the new block subscription. This will attempt to resubscribe if the gRPC
     // connection disconnects using exponential backoff.
     async fn new_heads_subscription_handler(
@@ -116,15 +196,71 @@ impl RemotePoolClient {
             }
         }
     }
+
+    // Handler for the pool event subscription. This will attempt to resubscribe if the gRPC
+    // connection disconnects using exponential backoff.
+    async fn pool_events_subscription_handler(
+        client: OpPoolClient<Channel>,
+        tx: mpsc::UnboundedSender<WithEntryPoint<MempoolEvent>>,
+    ) {
+        let mut stream = None;
+
+        loop {
+            if stream.is_none() {
+                stream =
+                    Some(
+                        retry::with_unlimited_retries(
+                            "subscribe pool events",
+                            || {
+                                let mut c = client.clone();
+                                async move {
+                                    c.subscribe_pool_events(SubscribePoolEventsRequest {}).await
+                                }
+                            },
+                            UnlimitedRetryOpts::default(),
+                        )
+                        .await
+                        .into_inner(),
+                    );
+            }
+
+            match stream.as_mut().unwrap().message().await {
+                Ok(Some(SubscribePoolEventsResponse { event: Some(e) })) => match e.try_into() {
+                    Ok(event) => {
+                        if tx.send(event).is_err() {
+                            // recv handle dropped
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("error parsing pool event: {:?}", e);
+                        break;
+                    }
+                },
+                Ok(Some(SubscribePoolEventsResponse { event: None })) | Ok(None) => {
+                    tracing::debug!("pool event subscription closed");
+                    stream.take();
+                    break;
+                }
+                Err(e) => {
+                    tracing::error!("error in pool event subscription: {:?}", e);
+                    stream.take();
+                    break;
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
 impl PoolServer for RemotePoolClient {
     async fn get_supported_entry_points(&self) -> PoolResult<Vec<Address>> {
         Ok(self
-            .op_pool_client
-            .clone()
-            .get_supported_entry_points(protos::GetSupportedEntryPointsRequest {})
+            .call_with_reconnect(|mut client| async move {
+                client
+                    .get_supported_entry_points(protos::GetSupportedEntryPointsRequest {})
+                    .await
+            })
             .await?
             .into_inner()
             .entry_points
@@ -133,14 +269,27 @@ impl PoolServer for RemotePoolClient {
             .collect::<Result<_, ConversionError>>()?)
     }
 
-    async fn add_op(&self, entry_point: Address, op: UserOperation) -> PoolResult<H256> {
+    async fn add_op(
+        &self,
+        entry_point: Address,
+        op: UserOperation,
+        options: AddOpOptions,
+    ) -> PoolResult<H256> {
+        let request = AddOpRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            op: Some(protos::UserOperation::from(&op)),
+            expected_block_hash: options
+                .expected_block_hash
+                .map(|h| h.as_bytes().to_vec())
+                .unwrap_or_default(),
+            metadata: options.metadata,
+            min_block: options.valid_block_range.min_block.unwrap_or_default(),
+            max_block: options.valid_block_range.max_block.unwrap_or_default(),
+        };
         let res = self
             .op_pool_client
             .clone()
-            .add_op(AddOpRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                op: Some(protos::UserOperation::from(&op)),
-            })
+            .add_op(request)
             .await?
             .into_inner()
             .result;
@@ -154,19 +303,67 @@ impl PoolServer for RemotePoolClient {
         }
     }
 
+    async fn add_external_ops(
+        &self,
+        ops: Vec<(Address, UserOperation)>,
+    ) -> PoolResult<Vec<Result<H256, MempoolError>>> {
+        let request = AddExternalOpsRequest {
+            ops: ops
+                .iter()
+                .map(|(entry_point, op)| EntryPointOp {
+                    entry_point: entry_point.as_bytes().to_vec(),
+                    op: Some(protos::UserOperation::from(op)),
+                })
+                .collect(),
+        };
+        let res = self
+            .op_pool_client
+            .clone()
+            .add_external_ops(request)
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(add_external_ops_response::Result::Success(s)) => s
+                .results
+                .into_iter()
+                .map(|r| match r.result {
+                    Some(add_op_response::Result::Success(s)) => Ok(Ok(H256::from_slice(&s.hash))),
+                    Some(add_op_response::Result::Failure(f)) => {
+                        Ok(Err(MempoolError::try_from(f)?))
+                    }
+                    None => Err(PoolServerError::Other(anyhow::anyhow!(
+                        "should have received result from op pool"
+                    ))),
+                })
+                .collect(),
+            Some(add_external_ops_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
     async fn get_ops(
         &self,
         entry_point: Address,
         max_ops: u64,
         shard_index: u64,
+        filter: GetOpsFilter,
     ) -> PoolResult<Vec<PoolOperation>> {
+        let request = GetOpsRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            max_ops,
+            shard_index,
+            paymaster: filter.paymaster.map_or(vec![], |p| p.as_bytes().to_vec()),
+            factory: filter.factory.map_or(vec![], |f| f.as_bytes().to_vec()),
+            offset: filter.offset,
+        };
         let res = self
-            .op_pool_client
-            .clone()
-            .get_ops(GetOpsRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                max_ops,
-                shard_index,
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_ops(request).await }
             })
             .await?
             .into_inner()
@@ -186,14 +383,55 @@ impl PoolServer for RemotePoolClient {
         }
     }
 
-    async fn remove_ops(&self, entry_point: Address, ops: Vec<H256>) -> PoolResult<()> {
+    async fn get_ops_with_priority(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+    ) -> PoolResult<Vec<(PoolOperation, OpPriority)>> {
+        let request = GetOpsWithPriorityRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            max_ops,
+            shard_index,
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_ops_with_priority(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_ops_with_priority_response::Result::Success(s)) => s
+                .ops
+                .into_iter()
+                .map(<(PoolOperation, OpPriority)>::try_from)
+                .map(|res| res.map_err(PoolServerError::from))
+                .collect(),
+            Some(get_ops_with_priority_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn remove_ops(
+        &self,
+        entry_point: Address,
+        ops: Vec<H256>,
+        reason: RemovalReason,
+    ) -> PoolResult<()> {
+        let request = RemoveOpsRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            hashes: ops.into_iter().map(|h| h.as_bytes().to_vec()).collect(),
+            reason: protos::RemovalReason::from(reason).into(),
+        };
         let res = self
             .op_pool_client
             .clone()
-            .remove_ops(RemoveOpsRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                hashes: ops.into_iter().map(|h| h.as_bytes().to_vec()).collect(),
-            })
+            .remove_ops(request)
             .await?
             .into_inner()
             .result;
@@ -207,14 +445,103 @@ impl PoolServer for RemotePoolClient {
         }
     }
 
+    async fn get_op_removal_reason(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<RemovalReason>> {
+        let request = GetOpRemovalReasonRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            hash: hash.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_op_removal_reason(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_op_removal_reason_response::Result::Success(s)) => {
+                if s.reason == protos::RemovalReason::Unspecified as i32 {
+                    Ok(None)
+                } else {
+                    Ok(Some(RemovalReason::try_from(s.reason)?))
+                }
+            }
+            Some(get_op_removal_reason_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_op_options(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<AddOpOptions>> {
+        let request = GetOpOptionsRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            hash: hash.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_op_options(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_op_options_response::Result::Success(s)) => {
+                Ok(s.options.map(AddOpOptions::try_from).transpose()?)
+            }
+            Some(get_op_options_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_op_rank(&self, entry_point: Address, hash: H256) -> PoolResult<OperationRank> {
+        let request = GetOpRankRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            hash: hash.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_op_rank(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_op_rank_response::Result::Success(s)) => Ok(OperationRank {
+                rank: s.ranked.then_some(s.rank),
+                pool_size: s.pool_size,
+            }),
+            Some(get_op_rank_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
     async fn remove_entities(&self, entry_point: Address, entities: Vec<Entity>) -> PoolResult<()> {
+        let request = RemoveEntitiesRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            entities: entities.iter().map(protos::Entity::from).collect(),
+        };
         let res = self
             .op_pool_client
             .clone()
-            .remove_entities(RemoveEntitiesRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                entities: entities.iter().map(protos::Entity::from).collect(),
-            })
+            .remove_entities(request)
             .await?
             .into_inner()
             .result;
@@ -233,16 +560,17 @@ impl PoolServer for RemotePoolClient {
         entry_point: Address,
         entity_updates: Vec<EntityUpdate>,
     ) -> PoolResult<()> {
+        let request = UpdateEntitiesRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            entity_updates: entity_updates
+                .iter()
+                .map(protos::EntityUpdate::from)
+                .collect(),
+        };
         let res = self
             .op_pool_client
             .clone()
-            .update_entities(UpdateEntitiesRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                entity_updates: entity_updates
-                    .iter()
-                    .map(protos::EntityUpdate::from)
-                    .collect(),
-            })
+            .update_entities(request)
             .await?
             .into_inner()
             .result;
@@ -257,10 +585,11 @@ impl PoolServer for RemotePoolClient {
     }
 
     async fn debug_clear_state(&self) -> PoolResult<()> {
+        let request = DebugClearStateRequest {};
         let res = self
             .op_pool_client
             .clone()
-            .debug_clear_state(DebugClearStateRequest {})
+            .debug_clear_state(request)
             .await?
             .into_inner()
             .result;
@@ -274,12 +603,21 @@ impl PoolServer for RemotePoolClient {
         }
     }
 
-    async fn debug_dump_mempool(&self, entry_point: Address) -> PoolResult<Vec<PoolOperation>> {
+    async fn debug_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: DumpMempoolFilter,
+    ) -> PoolResult<Vec<PoolOperation>> {
+        let request = DebugDumpMempoolRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            min_max_fee_per_gas: filter.min_max_fee_per_gas.map_or(vec![], to_le_bytes),
+            sender: filter.sender.map_or(vec![], |s| s.as_bytes().to_vec()),
+            limit: filter.limit.unwrap_or_default(),
+        };
         let res = self
-            .op_pool_client
-            .clone()
-            .debug_dump_mempool(DebugDumpMempoolRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.debug_dump_mempool(request).await }
             })
             .await?
             .into_inner()
@@ -304,16 +642,17 @@ impl PoolServer for RemotePoolClient {
         entry_point: Address,
         reputations: Vec<Reputation>,
     ) -> PoolResult<()> {
+        let request = DebugSetReputationRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            reputations: reputations
+                .into_iter()
+                .map(protos::Reputation::from)
+                .collect(),
+        };
         let res = self
             .op_pool_client
             .clone()
-            .debug_set_reputation(DebugSetReputationRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
-                reputations: reputations
-                    .into_iter()
-                    .map(protos::Reputation::from)
-                    .collect(),
-            })
+            .debug_set_reputation(request)
             .await?
             .into_inner()
             .result;
@@ -328,11 +667,13 @@ impl PoolServer for RemotePoolClient {
     }
 
     async fn debug_dump_reputation(&self, entry_point: Address) -> PoolResult<Vec<Reputation>> {
+        let request = DebugDumpReputationRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+        };
         let res = self
-            .op_pool_client
-            .clone()
-            .debug_dump_reputation(DebugDumpReputationRequest {
-                entry_point: entry_point.as_bytes().to_vec(),
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.debug_dump_reputation(request).await }
             })
             .await?
             .into_inner()
@@ -352,6 +693,206 @@ impl PoolServer for RemotePoolClient {
         }
     }
 
+    async fn debug_dump_senders(&self, entry_point: Address) -> PoolResult<Vec<SenderSummary>> {
+        let request = DebugDumpSendersRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.debug_dump_senders(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(debug_dump_senders_response::Result::Success(s)) => s
+                .senders
+                .into_iter()
+                .map(SenderSummary::try_from)
+                .map(|res| res.map_err(PoolServerError::from))
+                .collect(),
+            Some(debug_dump_senders_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn export_mempool(&self, entry_point: Address) -> PoolResult<MempoolSnapshot> {
+        let request = ExportMempoolRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.export_mempool(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(export_mempool_response::Result::Success(s)) => {
+                let snapshot = protos::MempoolSnapshot::decode(s.snapshot.as_slice())
+                    .context("should decode mempool snapshot")?;
+                Ok(snapshot.try_into()?)
+            }
+            Some(export_mempool_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn import_mempool(
+        &self,
+        entry_point: Address,
+        snapshot: MempoolSnapshot,
+    ) -> PoolResult<ImportMempoolResult> {
+        let request = ImportMempoolRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            snapshot: protos::MempoolSnapshot::from(&snapshot).encode_to_vec(),
+        };
+        let res = self
+            .op_pool_client
+            .clone()
+            .import_mempool(request)
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(import_mempool_response::Result::Success(s)) => {
+                let imported = s
+                    .imported_hashes
+                    .into_iter()
+                    .map(|hash| from_bytes(&hash))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let rejected = s
+                    .rejected
+                    .into_iter()
+                    .map(|r| {
+                        let id = UserOperationId {
+                            sender: from_bytes(&r.sender)?,
+                            nonce: from_bytes(&r.nonce)?,
+                        };
+                        let error = r
+                            .error
+                            .context("should have received error for rejected op")?
+                            .try_into()?;
+                        Ok((id, error))
+                    })
+                    .collect::<anyhow::Result<Vec<(UserOperationId, MempoolError)>>>()?;
+                Ok(ImportMempoolResult { imported, rejected })
+            }
+            Some(import_mempool_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_last_block_number(&self, entry_point: Address) -> PoolResult<u64> {
+        let request = GetLastBlockNumberRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_last_block_number(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_last_block_number_response::Result::Success(s)) => Ok(s.block_number),
+            Some(get_last_block_number_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_next_nonce(&self, entry_point: Address, sender: Address) -> PoolResult<U256> {
+        let request = GetNextNonceRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            sender: sender.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_next_nonce(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_next_nonce_response::Result::Success(s)) => Ok(from_bytes(&s.nonce)?),
+            Some(get_next_nonce_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_min_fees(&self, entry_point: Address) -> PoolResult<GasFees> {
+        let request = GetMinFeesRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_min_fees(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_min_fees_response::Result::Success(s)) => Ok(GasFees {
+                max_fee_per_gas: from_bytes(&s.max_fee_per_gas)?,
+                max_priority_fee_per_gas: from_bytes(&s.max_priority_fee_per_gas)?,
+            }),
+            Some(get_min_fees_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
+    async fn get_paymaster_max_cost(
+        &self,
+        entry_point: Address,
+        paymaster: Address,
+    ) -> PoolResult<U256> {
+        let request = GetPaymasterMaxCostRequest {
+            entry_point: entry_point.as_bytes().to_vec(),
+            paymaster: paymaster.as_bytes().to_vec(),
+        };
+        let res = self
+            .call_with_reconnect(|mut client| {
+                let request = request.clone();
+                async move { client.get_paymaster_max_cost(request).await }
+            })
+            .await?
+            .into_inner()
+            .result;
+
+        match res {
+            Some(get_paymaster_max_cost_response::Result::Success(s)) => {
+                Ok(from_bytes(&s.max_cost)?)
+            }
+            Some(get_paymaster_max_cost_response::Result::Failure(f)) => Err(f.try_into()?),
+            None => Err(PoolServerError::Other(anyhow::anyhow!(
+                "should have received result from op pool"
+            )))?,
+        }
+    }
+
     async fn subscribe_new_heads(&self) -> PoolResult<Pin<Box<dyn Stream<Item = NewHead> + Send>>> {
         let (tx, rx) = mpsc::unbounded_channel();
         let client = self.op_pool_client.clone();
@@ -359,6 +900,16 @@ impl PoolServer for RemotePoolClient {
         tokio::spawn(Self::new_heads_subscription_handler(client, tx));
         Ok(Box::pin(UnboundedReceiverStream::new(rx)))
     }
+
+    async fn subscribe_pool_events(
+        &self,
+    ) -> PoolResult<Pin<Box<dyn Stream<Item = WithEntryPoint<MempoolEvent>> + Send>>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let client = self.op_pool_client.clone();
+
+        tokio::spawn(Self::pool_events_subscription_handler(client, tx));
+        Ok(Box::pin(UnboundedReceiverStream::new(rx)))
+    }
 }
 
 #[async_trait]
@@ -378,3 +929,92 @@ impl HealthCheck for RemotePoolClient {
             .unwrap_or(ServerStatus::NotServing)
     }
 }
+
+// Endpoint for the connections to the pool server at `url`. Each pool server
+// has its own state, so balancing requests across different servers would lose
+// track of operations between requests.
+fn connection_endpoint(url: &str) -> anyhow::Result<Endpoint> {
+    if url.contains(',') {
+        anyhow::bail!("pool url {url} should be the url of a single pool server");
+    }
+    let endpoint = Endpoint::from_str(url.trim())?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .http2_keep_alive_interval(KEEP_ALIVE_INTERVAL)
+        .keep_alive_timeout(KEEP_ALIVE_TIMEOUT)
+        .keep_alive_while_idle(true);
+    Ok(endpoint)
+}
+
+// A channel balanced across `CONNECTION_COUNT` connections to the endpoint. The
+// balancer keys connections by the key they are inserted with, so each is
+// inserted under its own index rather than the shared endpoint URI, which would
+// collapse them into one. The balancer drops connections that fail and keeps
+// reconnecting them, so a restarted server is picked up without a new client.
+fn balanced_channel(endpoint: Endpoint) -> Channel {
+    let (channel, connections) = Channel::balance_channel(CONNECTION_COUNT);
+    for index in 0..CONNECTION_COUNT {
+        connections
+            .try_send(Change::Insert(index, endpoint.clone()))
+            .expect("balanced channel should have capacity for every connection");
+    }
+    channel
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn test_connection_endpoint_single_server() {
+        let endpoint = connection_endpoint("http://localhost:50051").unwrap();
+        assert_eq!(
+            endpoint.uri(),
+            &"http://localhost:50051"
+                .parse::<tonic::transport::Uri>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_connection_endpoint_rejects_multiple_servers() {
+        assert!(connection_endpoint("http://pool-a:50051,http://pool-b:50051").is_err());
+    }
+
+    #[test]
+    fn test_connection_endpoint_rejects_invalid_url() {
+        assert!(connection_endpoint("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_balanced_channel_opens_every_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                connections.push(stream);
+            }
+        });
+
+        // The server never answers, but the balancer connects each of its
+        // connections while picking one for the request
+        let channel = balanced_channel(connection_endpoint(&url).unwrap());
+        let _ = time::timeout(
+            Duration::from_secs(1),
+            HealthClient::new(channel).check(HealthCheckRequest::default()),
+        )
+        .await;
+
+        assert_eq!(accepted.load(Ordering::SeqCst), CONNECTION_COUNT);
+    }
+}
@@ -12,35 +12,50 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use anyhow::{bail, Context};
-use ethers::types::Opcode;
+use ethers::types::{Opcode, H256};
 use rundler_sim::{PrecheckViolation, SimulationViolation, ViolationOpCode};
 use rundler_task::grpc::protos::{from_bytes, to_le_bytes, ConversionError};
 use rundler_types::StorageSlot;
 
 use super::protos::{
     mempool_error, precheck_violation_error, simulation_violation_error,
-    AccessedUndeployedContract, AggregatorValidationFailed, CallGasLimitTooLow, CallHadValue,
-    CalledBannedEntryPointMethod, CodeHashChanged, DidNotRevert, DiscardedOnInsertError, Entity,
-    EntityThrottledError, EntityType, ExistingSenderWithInitCode, FactoryCalledCreate2Twice,
-    FactoryIsNotContract, InitCodeTooShort, InvalidSignature, InvalidStorageAccess,
-    MaxFeePerGasTooLow, MaxOperationsReachedError, MaxPriorityFeePerGasTooLow,
-    MempoolError as ProtoMempoolError, NotStaked, OperationAlreadyKnownError, OutOfGas,
+    AccessedUndeployedContract, AggregatorUnreachable, AggregatorValidationFailed,
+    BannedStorageSlot, BlockHashMismatchError, CallGasLimitTooLow, CallHadValue,
+    CalledBannedEntryPointMethod, CodeHashChanged, DidNotRevert, DiscardedOnInsertError,
+    DuplicateOperationError, Entity, EntityThrottledError, EntityType, ExistingSenderWithInitCode,
+    FactoryCalledCreate2Twice, FactoryIsNotContract, FeesBelowMinimumError, InitCodeTooShort,
+    InvalidMetadataError, InvalidSignature, InvalidStorageAccess, MaxFeePerGasTooLow,
+    MaxOperationsReachedError, MaxPriorityFeePerGasTooLow, MempoolError as ProtoMempoolError,
+    NonceTooLowError, NotStaked, OperationAlreadyKnownError, OperationTooLargeError, OutOfGas,
     PaymasterDepositTooLow, PaymasterIsNotContract, PaymasterTooShort, PreVerificationGasTooLow,
-    PrecheckViolationError as ProtoPrecheckViolationError, ReplacementUnderpricedError,
-    SenderFundsTooLow, SenderIsNotContractAndNoInitCode,
-    SimulationViolationError as ProtoSimulationViolationError, TotalGasLimitTooHigh,
-    UnintendedRevert, UnintendedRevertWithMessage, UnknownEntryPointError,
-    UnsupportedAggregatorError, UsedForbiddenOpcode, UsedForbiddenPrecompile,
-    VerificationGasLimitTooHigh, WrongNumberOfPhases,
+    PrecheckViolationError as ProtoPrecheckViolationError, RateLimitedError,
+    ReplacementUnderpricedError, SenderAddressMismatch, SenderFundsTooLow,
+    SenderIsNotContractAndNoInitCode, SimulationViolationError as ProtoSimulationViolationError,
+    SimulationViolationsError, StakeRequiredForGossipError, TotalGasLimitTooHigh, UnintendedRevert,
+    UnintendedRevertWithMessage, UnknownEntryPointError, UnsupportedAggregatorError,
+    UnsupportedEntryPointCall, UsedForbiddenOpcode, UsedForbiddenPrecompile,
+    VerificationGasLimitTooHigh, VerificationGasLimitTooLow, WrongNumberOfPhases,
+    WrongPoolShardError,
 };
 use crate::{mempool::MempoolError, server::error::PoolServerError};
 
 impl From<tonic::Status> for PoolServerError {
     fn from(value: tonic::Status) -> Self {
-        PoolServerError::Other(anyhow::anyhow!(value.to_string()))
+        if is_unavailable(&value) {
+            PoolServerError::Unavailable(value.message().to_string())
+        } else {
+            PoolServerError::Other(anyhow::anyhow!(value.to_string()))
+        }
     }
 }
 
+/// Returns true if the status was caused by the pool server being unreachable, rather
+/// than by the server handling the request.
+pub(crate) fn is_unavailable(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unavailable
+        || std::error::Error::source(status).is_some_and(|e| e.is::<tonic::transport::Error>())
+}
+
 impl From<ConversionError> for PoolServerError {
     fn from(value: ConversionError) -> Self {
         PoolServerError::Other(anyhow::anyhow!(value.to_string()))
@@ -64,6 +79,11 @@ impl From<PoolServerError> for ProtoMempoolError {
                     "unexpected response from pool server".to_string(),
                 )),
             },
+            PoolServerError::Unavailable(e) => ProtoMempoolError {
+                error: Some(mempool_error::Error::Internal(format!(
+                    "pool server unavailable: {e}"
+                ))),
+            },
             PoolServerError::Other(e) => ProtoMempoolError {
                 error: Some(mempool_error::Error::Internal(e.to_string())),
             },
@@ -80,12 +100,31 @@ impl TryFrom<ProtoMempoolError> for MempoolError {
             Some(mempool_error::Error::OperationAlreadyKnown(_)) => {
                 MempoolError::OperationAlreadyKnown
             }
+            Some(mempool_error::Error::DuplicateOperation(_)) => MempoolError::DuplicateOperation,
             Some(mempool_error::Error::ReplacementUnderpriced(e)) => {
                 MempoolError::ReplacementUnderpriced(
-                    from_bytes(&e.current_fee)?,
                     from_bytes(&e.current_priority_fee)?,
+                    from_bytes(&e.current_fee)?,
+                    from_bytes(&e.required_priority_fee)?,
+                    from_bytes(&e.required_fee)?,
                 )
             }
+            Some(mempool_error::Error::FeesBelowMinimum(e)) => MempoolError::FeesBelowMinimum(
+                from_bytes(&e.required_priority_fee)?,
+                from_bytes(&e.required_fee)?,
+            ),
+            Some(mempool_error::Error::NonceTooLow(e)) => {
+                MempoolError::NonceTooLow(from_bytes(&e.nonce)?, from_bytes(&e.on_chain_nonce)?)
+            }
+            Some(mempool_error::Error::WrongPoolShard(e)) => {
+                MempoolError::WrongPoolShard(e.shard_index, e.pool_shard_index)
+            }
+            Some(mempool_error::Error::OperationTooLarge(e)) => {
+                MempoolError::OperationTooLarge(e.size as usize, e.max_size as usize)
+            }
+            Some(mempool_error::Error::InvalidMetadata(e)) => {
+                MempoolError::InvalidMetadata(e.reason)
+            }
             Some(mempool_error::Error::MaxOperationsReached(e)) => {
                 MempoolError::MaxOperationsReached(
                     e.num_ops as usize,
@@ -95,19 +134,37 @@ impl TryFrom<ProtoMempoolError> for MempoolError {
             Some(mempool_error::Error::EntityThrottled(e)) => MempoolError::EntityThrottled(
                 (&e.entity.context("should have entity in error")?).try_into()?,
             ),
+            Some(mempool_error::Error::RateLimited(e)) => MempoolError::RateLimited(
+                (&e.entity.context("should have entity in error")?).try_into()?,
+            ),
             Some(mempool_error::Error::DiscardedOnInsert(_)) => MempoolError::DiscardedOnInsert,
+            Some(mempool_error::Error::StakeRequiredForGossip(_)) => {
+                MempoolError::StakeRequiredForGossip
+            }
             Some(mempool_error::Error::PrecheckViolation(e)) => {
                 MempoolError::PrecheckViolation(e.try_into()?)
             }
             Some(mempool_error::Error::SimulationViolation(e)) => {
                 MempoolError::SimulationViolation(e.try_into()?)
             }
+            Some(mempool_error::Error::SimulationViolations(e)) => {
+                MempoolError::SimulationViolations(
+                    e.violations
+                        .into_iter()
+                        .map(SimulationViolation::try_from)
+                        .collect::<anyhow::Result<Vec<_>>>()?,
+                )
+            }
             Some(mempool_error::Error::UnsupportedAggregator(e)) => {
                 MempoolError::UnsupportedAggregator(from_bytes(&e.aggregator_address)?)
             }
             Some(mempool_error::Error::UnknownEntryPoint(e)) => {
                 MempoolError::UnknownEntryPoint(from_bytes(&e.entry_point)?)
             }
+            Some(mempool_error::Error::BlockHashMismatch(e)) => MempoolError::BlockHashMismatch(
+                from_bytes(&e.expected_block_hash)?,
+                from_bytes(&e.current_block_hash)?,
+            ),
             _ => bail!("unknown proto mempool error"),
         })
     }
@@ -124,14 +181,61 @@ impl From<MempoolError> for ProtoMempoolError {
                     OperationAlreadyKnownError {},
                 )),
             },
-            MempoolError::ReplacementUnderpriced(fee, priority_fee) => ProtoMempoolError {
+            MempoolError::DuplicateOperation => ProtoMempoolError {
+                error: Some(mempool_error::Error::DuplicateOperation(
+                    DuplicateOperationError {},
+                )),
+            },
+            MempoolError::ReplacementUnderpriced(
+                priority_fee,
+                fee,
+                required_priority_fee,
+                required_fee,
+            ) => ProtoMempoolError {
                 error: Some(mempool_error::Error::ReplacementUnderpriced(
                     ReplacementUnderpricedError {
                         current_fee: to_le_bytes(fee),
                         current_priority_fee: to_le_bytes(priority_fee),
+                        required_fee: to_le_bytes(required_fee),
+                        required_priority_fee: to_le_bytes(required_priority_fee),
+                    },
+                )),
+            },
+            MempoolError::FeesBelowMinimum(required_priority_fee, required_fee) => {
+                ProtoMempoolError {
+                    error: Some(mempool_error::Error::FeesBelowMinimum(
+                        FeesBelowMinimumError {
+                            required_fee: to_le_bytes(required_fee),
+                            required_priority_fee: to_le_bytes(required_priority_fee),
+                        },
+                    )),
+                }
+            }
+            MempoolError::NonceTooLow(nonce, on_chain_nonce) => ProtoMempoolError {
+                error: Some(mempool_error::Error::NonceTooLow(NonceTooLowError {
+                    nonce: to_le_bytes(nonce),
+                    on_chain_nonce: to_le_bytes(on_chain_nonce),
+                })),
+            },
+            MempoolError::WrongPoolShard(shard_index, pool_shard_index) => ProtoMempoolError {
+                error: Some(mempool_error::Error::WrongPoolShard(WrongPoolShardError {
+                    shard_index,
+                    pool_shard_index,
+                })),
+            },
+            MempoolError::OperationTooLarge(size, max_size) => ProtoMempoolError {
+                error: Some(mempool_error::Error::OperationTooLarge(
+                    OperationTooLargeError {
+                        size: size as u64,
+                        max_size: max_size as u64,
                     },
                 )),
             },
+            MempoolError::InvalidMetadata(reason) => ProtoMempoolError {
+                error: Some(mempool_error::Error::InvalidMetadata(
+                    InvalidMetadataError { reason },
+                )),
+            },
             MempoolError::MaxOperationsReached(ops, addr) => ProtoMempoolError {
                 error: Some(mempool_error::Error::MaxOperationsReached(
                     MaxOperationsReachedError {
@@ -147,17 +251,34 @@ impl From<MempoolError> for ProtoMempoolError {
                     },
                 )),
             },
+            MempoolError::RateLimited(entity) => ProtoMempoolError {
+                error: Some(mempool_error::Error::RateLimited(RateLimitedError {
+                    entity: Some((&entity).into()),
+                })),
+            },
             MempoolError::DiscardedOnInsert => ProtoMempoolError {
                 error: Some(mempool_error::Error::DiscardedOnInsert(
                     DiscardedOnInsertError {},
                 )),
             },
+            MempoolError::StakeRequiredForGossip => ProtoMempoolError {
+                error: Some(mempool_error::Error::StakeRequiredForGossip(
+                    StakeRequiredForGossipError {},
+                )),
+            },
             MempoolError::PrecheckViolation(violation) => ProtoMempoolError {
                 error: Some(mempool_error::Error::PrecheckViolation(violation.into())),
             },
             MempoolError::SimulationViolation(violation) => ProtoMempoolError {
                 error: Some(mempool_error::Error::SimulationViolation(violation.into())),
             },
+            MempoolError::SimulationViolations(violations) => ProtoMempoolError {
+                error: Some(mempool_error::Error::SimulationViolations(
+                    SimulationViolationsError {
+                        violations: violations.into_iter().map(Into::into).collect(),
+                    },
+                )),
+            },
             MempoolError::UnsupportedAggregator(agg) => ProtoMempoolError {
                 error: Some(mempool_error::Error::UnsupportedAggregator(
                     UnsupportedAggregatorError {
@@ -172,6 +293,14 @@ impl From<MempoolError> for ProtoMempoolError {
                     },
                 )),
             },
+            MempoolError::BlockHashMismatch(expected, current) => ProtoMempoolError {
+                error: Some(mempool_error::Error::BlockHashMismatch(
+                    BlockHashMismatchError {
+                        expected_block_hash: expected.as_bytes().to_vec(),
+                        current_block_hash: current.as_bytes().to_vec(),
+                    },
+                )),
+            },
         }
     }
 }
@@ -303,6 +432,15 @@ impl From<PrecheckViolation> for ProtoPrecheckViolationError {
                     },
                 )),
             },
+            PrecheckViolation::UnsupportedEntryPointCall(selector) => ProtoPrecheckViolationError {
+                violation: Some(
+                    precheck_violation_error::Violation::UnsupportedEntryPointCall(
+                        UnsupportedEntryPointCall {
+                            selector: selector.to_vec(),
+                        },
+                    ),
+                ),
+            },
         }
     }
 }
@@ -378,6 +516,9 @@ impl TryFrom<ProtoPrecheckViolationError> for PrecheckViolation {
                     from_bytes(&e.min_gas_limit)?,
                 )
             }
+            Some(precheck_violation_error::Violation::UnsupportedEntryPointCall(e)) => {
+                PrecheckViolation::UnsupportedEntryPointCall(e.selector.into())
+            }
             None => {
                 bail!("unknown proto mempool precheck violation")
             }
@@ -455,6 +596,15 @@ impl From<SimulationViolation> for ProtoSimulationViolationError {
                     )),
                 }
             }
+            SimulationViolation::BannedStorageSlot(entity, slot) => ProtoSimulationViolationError {
+                violation: Some(simulation_violation_error::Violation::BannedStorageSlot(
+                    BannedStorageSlot {
+                        entity: Some((&entity).into()),
+                        contract_address: slot.address.as_bytes().to_vec(),
+                        slot: to_le_bytes(slot.slot),
+                    },
+                )),
+            },
             SimulationViolation::NotStaked(entity, min_stake, min_unstake_delay) => {
                 ProtoSimulationViolationError {
                     violation: Some(simulation_violation_error::Violation::NotStaked(
@@ -530,6 +680,39 @@ impl From<SimulationViolation> for ProtoSimulationViolationError {
                     ),
                 ),
             },
+            SimulationViolation::AggregatorUnreachable(addr) => ProtoSimulationViolationError {
+                violation: Some(
+                    simulation_violation_error::Violation::AggregatorUnreachable(
+                        AggregatorUnreachable {
+                            aggregator_address: addr.as_bytes().to_vec(),
+                        },
+                    ),
+                ),
+            },
+            SimulationViolation::VerificationGasLimitTooLow(actual, min) => {
+                ProtoSimulationViolationError {
+                    violation: Some(
+                        simulation_violation_error::Violation::VerificationGasLimitTooLow(
+                            VerificationGasLimitTooLow {
+                                actual_gas: to_le_bytes(actual),
+                                min_gas: to_le_bytes(min),
+                            },
+                        ),
+                    ),
+                }
+            }
+            SimulationViolation::SenderAddressMismatch(sender, created) => {
+                ProtoSimulationViolationError {
+                    violation: Some(
+                        simulation_violation_error::Violation::SenderAddressMismatch(
+                            SenderAddressMismatch {
+                                sender_address: sender.as_bytes().to_vec(),
+                                created_address: created.as_bytes().to_vec(),
+                            },
+                        ),
+                    ),
+                }
+            }
         }
     }
 }
@@ -584,6 +767,15 @@ impl TryFrom<ProtoSimulationViolationError> for SimulationViolation {
                     },
                 )
             }
+            Some(simulation_violation_error::Violation::BannedStorageSlot(e)) => {
+                SimulationViolation::BannedStorageSlot(
+                    (&e.entity.context("should have entity in error")?).try_into()?,
+                    StorageSlot {
+                        address: from_bytes(&e.contract_address)?,
+                        slot: from_bytes(&e.slot)?,
+                    },
+                )
+            }
             Some(simulation_violation_error::Violation::NotStaked(e)) => {
                 SimulationViolation::NotStaked(
                     (&e.entity.context("should have entity in error")?).try_into()?,
@@ -629,6 +821,21 @@ impl TryFrom<ProtoSimulationViolationError> for SimulationViolation {
             Some(simulation_violation_error::Violation::AggregatorValidationFailed(_)) => {
                 SimulationViolation::AggregatorValidationFailed
             }
+            Some(simulation_violation_error::Violation::AggregatorUnreachable(e)) => {
+                SimulationViolation::AggregatorUnreachable(from_bytes(&e.aggregator_address)?)
+            }
+            Some(simulation_violation_error::Violation::VerificationGasLimitTooLow(e)) => {
+                SimulationViolation::VerificationGasLimitTooLow(
+                    from_bytes(&e.actual_gas)?,
+                    from_bytes(&e.min_gas)?,
+                )
+            }
+            Some(simulation_violation_error::Violation::SenderAddressMismatch(e)) => {
+                SimulationViolation::SenderAddressMismatch(
+                    from_bytes(&e.sender_address)?,
+                    from_bytes(&e.created_address)?,
+                )
+            }
             None => {
                 bail!("unknown proto mempool simulation violation")
             }
@@ -679,6 +886,179 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_simulation_errors() {
+        let error = MempoolError::SimulationViolations(vec![
+            SimulationViolation::InvalidSignature,
+            SimulationViolation::UnintendedRevert(rundler_types::EntityType::Paymaster),
+        ]);
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::SimulationViolations(violations) => assert_eq!(
+                violations,
+                vec![
+                    SimulationViolation::InvalidSignature,
+                    SimulationViolation::UnintendedRevert(rundler_types::EntityType::Paymaster),
+                ]
+            ),
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_banned_storage_slot_error() {
+        let violation = SimulationViolation::BannedStorageSlot(
+            rundler_types::Entity::account(ethers::types::Address::random()),
+            StorageSlot {
+                address: ethers::types::Address::random(),
+                slot: 1.into(),
+            },
+        );
+        let error = MempoolError::SimulationViolation(violation.clone());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::SimulationViolation(v) => assert_eq!(v, violation),
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_sender_address_mismatch_error() {
+        let violation = SimulationViolation::SenderAddressMismatch(
+            ethers::types::Address::random(),
+            ethers::types::Address::random(),
+        );
+        let error = MempoolError::SimulationViolation(violation.clone());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::SimulationViolation(v) => assert_eq!(v, violation),
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_replacement_underpriced_error() {
+        let error = MempoolError::ReplacementUnderpriced(1.into(), 2.into(), 3.into(), 4.into());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::ReplacementUnderpriced(a, b, c, d) => {
+                assert_eq!(a, 1.into());
+                assert_eq!(b, 2.into());
+                assert_eq!(c, 3.into());
+                assert_eq!(d, 4.into());
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_fees_below_minimum_error() {
+        let error = MempoolError::FeesBelowMinimum(1.into(), 2.into());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::FeesBelowMinimum(a, b) => {
+                assert_eq!(a, 1.into());
+                assert_eq!(b, 2.into());
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_nonce_too_low_error() {
+        let error = MempoolError::NonceTooLow(1.into(), 2.into());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::NonceTooLow(a, b) => {
+                assert_eq!(a, 1.into());
+                assert_eq!(b, 2.into());
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_wrong_pool_shard_error() {
+        let error = MempoolError::WrongPoolShard(1, 2);
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::WrongPoolShard(a, b) => {
+                assert_eq!(a, 1);
+                assert_eq!(b, 2);
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_operation_too_large_error() {
+        let error = MempoolError::OperationTooLarge(2048, 1024);
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::OperationTooLarge(size, max_size) => {
+                assert_eq!(size, 2048);
+                assert_eq!(max_size, 1024);
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_metadata_error() {
+        let error = MempoolError::InvalidMetadata("too many entries".to_string());
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::InvalidMetadata(reason) => assert_eq!(reason, "too many entries"),
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_unavailable_status() {
+        let error: PoolServerError = tonic::Status::unavailable("connection refused").into();
+        assert!(matches!(error, PoolServerError::Unavailable(_)));
+
+        let error: PoolServerError = tonic::Status::internal("oops").into();
+        assert!(matches!(error, PoolServerError::Other(_)));
+    }
+
+    #[test]
+    fn test_block_hash_mismatch_error() {
+        let expected = H256::random();
+        let current = H256::random();
+        let error = MempoolError::BlockHashMismatch(expected, current);
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::BlockHashMismatch(a, b) => {
+                assert_eq!(a, expected);
+                assert_eq!(b, current);
+            }
+            _ => panic!("wrong error type"),
+        }
+    }
+
+    #[test]
+    fn test_rate_limited_error() {
+        let entity = rundler_types::Entity::paymaster(ethers::types::Address::random());
+        let error = MempoolError::RateLimited(entity);
+        let proto_error: ProtoMempoolError = error.into();
+        let error2 = proto_error.try_into().unwrap();
+        match error2 {
+            MempoolError::RateLimited(e) => assert_eq!(e, entity),
+            _ => panic!("wrong error type"),
+        }
+    }
+
     #[test]
     fn test_invalid_convert() {
         let error = ProtoMempoolError { error: None };
@@ -19,12 +19,16 @@ use rundler_types::{
     EntityUpdateType as RundlerEntityUpdateType, UserOperation as RundlerUserOperation,
     ValidTimeRange,
 };
+use rundler_utils::emit::WithEntryPoint;
 
 use crate::{
     mempool::{
-        PoolOperation, Reputation as PoolReputation, ReputationStatus as PoolReputationStatus,
+        AddOpOptions, MempoolEvent as PoolMempoolEvent, OpPriority as PoolOpPriority,
+        PoolOperation, RemovalReason as PoolRemovalReason, Reputation as PoolReputation,
+        ReputationStatus as PoolReputationStatus, SenderSummary as PoolSenderSummary,
+        ValidBlockRange,
     },
-    server::NewHead as PoolNewHead,
+    server::{MempoolSnapshot as PoolMempoolSnapshot, NewHead as PoolNewHead},
 };
 
 tonic::include_proto!("op_pool");
@@ -164,6 +168,33 @@ impl From<PoolReputationStatus> for ReputationStatus {
     }
 }
 
+impl From<PoolRemovalReason> for RemovalReason {
+    fn from(reason: PoolRemovalReason) -> Self {
+        match reason {
+            PoolRemovalReason::Mined => RemovalReason::Mined,
+            PoolRemovalReason::Expired => RemovalReason::Expired,
+            PoolRemovalReason::Replaced => RemovalReason::Replaced,
+            PoolRemovalReason::Invalidated => RemovalReason::Invalidated,
+            PoolRemovalReason::Evicted => RemovalReason::Evicted,
+        }
+    }
+}
+
+impl TryFrom<i32> for PoolRemovalReason {
+    type Error = ConversionError;
+
+    fn try_from(reason: i32) -> Result<Self, Self::Error> {
+        match reason {
+            x if x == RemovalReason::Mined as i32 => Ok(Self::Mined),
+            x if x == RemovalReason::Expired as i32 => Ok(Self::Expired),
+            x if x == RemovalReason::Replaced as i32 => Ok(Self::Replaced),
+            x if x == RemovalReason::Invalidated as i32 => Ok(Self::Invalidated),
+            x if x == RemovalReason::Evicted as i32 => Ok(Self::Evicted),
+            _ => Err(ConversionError::InvalidEnumValue(reason)),
+        }
+    }
+}
+
 impl From<PoolReputation> for Reputation {
     fn from(rep: PoolReputation) -> Self {
         Reputation {
@@ -216,6 +247,9 @@ impl From<&PoolOperation> for MempoolOp {
                 .map(|e| EntityType::from(*e).into())
                 .collect(),
             account_is_staked: op.account_is_staked,
+            metadata: op.metadata.clone(),
+            min_block: op.valid_block_range.min_block.unwrap_or_default(),
+            max_block: op.valid_block_range.max_block.unwrap_or_default(),
         }
     }
 }
@@ -255,6 +289,79 @@ impl TryFrom<MempoolOp> for PoolOperation {
             entities_needing_stake,
             sim_block_hash,
             account_is_staked: op.account_is_staked,
+            metadata: op.metadata,
+            valid_block_range: ValidBlockRange {
+                min_block: (op.min_block != 0).then_some(op.min_block),
+                max_block: (op.max_block != 0).then_some(op.max_block),
+            },
+        })
+    }
+}
+
+impl From<&PoolOpPriority> for OpPriority {
+    fn from(priority: &PoolOpPriority) -> Self {
+        OpPriority {
+            effective_gas_price: to_le_bytes(priority.effective_gas_price),
+            is_staked: priority.is_staked,
+            submission_seq: priority.submission_seq,
+            sender_nonce: to_le_bytes(priority.sender_nonce),
+        }
+    }
+}
+
+impl TryFrom<OpPriority> for PoolOpPriority {
+    type Error = ConversionError;
+
+    fn try_from(priority: OpPriority) -> Result<Self, Self::Error> {
+        Ok(PoolOpPriority {
+            effective_gas_price: from_bytes(&priority.effective_gas_price)?,
+            is_staked: priority.is_staked,
+            submission_seq: priority.submission_seq,
+            sender_nonce: from_bytes(&priority.sender_nonce)?,
+        })
+    }
+}
+
+impl From<&(PoolOperation, PoolOpPriority)> for PrioritizedOp {
+    fn from((op, priority): &(PoolOperation, PoolOpPriority)) -> Self {
+        PrioritizedOp {
+            op: Some(MempoolOp::from(op)),
+            priority: Some(OpPriority::from(priority)),
+        }
+    }
+}
+
+impl TryFrom<PrioritizedOp> for (PoolOperation, PoolOpPriority) {
+    type Error = anyhow::Error;
+
+    fn try_from(op: PrioritizedOp) -> Result<Self, Self::Error> {
+        let priority = op
+            .priority
+            .context("Prioritized op should contain priority")?
+            .try_into()?;
+        let op = op.op.context(MISSING_USER_OP_ERR_STR)?.try_into()?;
+        Ok((op, priority))
+    }
+}
+
+impl From<PoolSenderSummary> for SenderSummary {
+    fn from(summary: PoolSenderSummary) -> Self {
+        SenderSummary {
+            sender: summary.sender.as_bytes().to_vec(),
+            op_count: summary.op_count as u64,
+            max_total_cost: to_le_bytes(summary.max_total_cost),
+        }
+    }
+}
+
+impl TryFrom<SenderSummary> for PoolSenderSummary {
+    type Error = ConversionError;
+
+    fn try_from(summary: SenderSummary) -> Result<Self, Self::Error> {
+        Ok(PoolSenderSummary {
+            sender: from_bytes(&summary.sender)?,
+            op_count: summary.op_count as usize,
+            max_total_cost: from_bytes(&summary.max_total_cost)?,
         })
     }
 }
@@ -278,3 +385,135 @@ impl From<PoolNewHead> for NewHead {
         }
     }
 }
+
+impl From<&AddOpOptions> for OpOptions {
+    fn from(options: &AddOpOptions) -> Self {
+        Self {
+            expected_block_hash: options
+                .expected_block_hash
+                .map(|h| h.as_bytes().to_vec())
+                .unwrap_or_default(),
+            metadata: options.metadata.clone(),
+            min_block: options.valid_block_range.min_block.unwrap_or_default(),
+            max_block: options.valid_block_range.max_block.unwrap_or_default(),
+        }
+    }
+}
+
+impl TryFrom<OpOptions> for AddOpOptions {
+    type Error = ConversionError;
+
+    fn try_from(options: OpOptions) -> Result<Self, Self::Error> {
+        Ok(Self {
+            expected_block_hash: if options.expected_block_hash.is_empty() {
+                None
+            } else {
+                Some(from_bytes(&options.expected_block_hash)?)
+            },
+            valid_block_range: ValidBlockRange {
+                min_block: (options.min_block != 0).then_some(options.min_block),
+                max_block: (options.max_block != 0).then_some(options.max_block),
+            },
+            metadata: options.metadata,
+        })
+    }
+}
+
+impl From<WithEntryPoint<PoolMempoolEvent>> for MempoolEvent {
+    fn from(event: WithEntryPoint<PoolMempoolEvent>) -> Self {
+        let inner = match event.event {
+            PoolMempoolEvent::OpAdded { op_hash, op } => {
+                mempool_event::Event::OpAdded(OpAddedEvent {
+                    op_hash: op_hash.as_bytes().to_vec(),
+                    op: Some(UserOperation::from(&op)),
+                })
+            }
+            PoolMempoolEvent::OpRemoved { op_hash, reason } => {
+                mempool_event::Event::OpRemoved(OpRemovedEvent {
+                    op_hash: op_hash.as_bytes().to_vec(),
+                    reason: RemovalReason::from(reason).into(),
+                })
+            }
+            PoolMempoolEvent::OpReplaced {
+                op_hash,
+                replaced_by,
+            } => mempool_event::Event::OpReplaced(OpReplacedEvent {
+                op_hash: op_hash.as_bytes().to_vec(),
+                replaced_by: replaced_by.as_bytes().to_vec(),
+            }),
+            PoolMempoolEvent::BlockProcessed { block_number } => {
+                mempool_event::Event::BlockProcessed(BlockProcessedEvent { block_number })
+            }
+        };
+        Self {
+            entry_point: event.entry_point.as_bytes().to_vec(),
+            event: Some(inner),
+        }
+    }
+}
+
+impl TryFrom<MempoolEvent> for WithEntryPoint<PoolMempoolEvent> {
+    type Error = anyhow::Error;
+
+    fn try_from(event: MempoolEvent) -> Result<Self, Self::Error> {
+        let inner = match event
+            .event
+            .context("Mempool event should contain an event")?
+        {
+            mempool_event::Event::OpAdded(e) => PoolMempoolEvent::OpAdded {
+                op_hash: from_bytes(&e.op_hash)?,
+                op: e.op.context(MISSING_USER_OP_ERR_STR)?.try_into()?,
+            },
+            mempool_event::Event::OpRemoved(e) => PoolMempoolEvent::OpRemoved {
+                op_hash: from_bytes(&e.op_hash)?,
+                reason: PoolRemovalReason::try_from(e.reason)?,
+            },
+            mempool_event::Event::OpReplaced(e) => PoolMempoolEvent::OpReplaced {
+                op_hash: from_bytes(&e.op_hash)?,
+                replaced_by: from_bytes(&e.replaced_by)?,
+            },
+            mempool_event::Event::BlockProcessed(e) => PoolMempoolEvent::BlockProcessed {
+                block_number: e.block_number,
+            },
+        };
+        Ok(WithEntryPoint {
+            entry_point: from_bytes(&event.entry_point)?,
+            event: inner,
+        })
+    }
+}
+
+impl From<&PoolMempoolSnapshot> for MempoolSnapshot {
+    fn from(snapshot: &PoolMempoolSnapshot) -> Self {
+        Self {
+            ops: snapshot.operations.iter().map(MempoolOp::from).collect(),
+            reputations: snapshot
+                .reputations
+                .iter()
+                .cloned()
+                .map(Reputation::from)
+                .collect(),
+            omitted_ops: snapshot.omitted_operations,
+        }
+    }
+}
+
+impl TryFrom<MempoolSnapshot> for PoolMempoolSnapshot {
+    type Error = anyhow::Error;
+
+    fn try_from(snapshot: MempoolSnapshot) -> Result<Self, Self::Error> {
+        Ok(Self {
+            operations: snapshot
+                .ops
+                .into_iter()
+                .map(PoolOperation::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            reputations: snapshot
+                .reputations
+                .into_iter()
+                .map(PoolReputation::try_from)
+                .collect::<Result<Vec<_>, _>>()?,
+            omitted_operations: snapshot.omitted_ops,
+        })
+    }
+}
@@ -17,49 +17,82 @@ use std::{
         atomic::{AtomicUsize, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
 use ethers::types::{Address, H256};
 use futures_util::StreamExt;
-use rundler_task::grpc::{metrics::GrpcMetricsLayer, protos::from_bytes};
+use parking_lot::Mutex;
+use prost::{encoding::encoded_len_varint, Message};
+use rundler_task::grpc::{
+    metrics::GrpcMetricsLayer,
+    protos::{from_bytes, to_le_bytes},
+};
 use rundler_types::{Entity, EntityUpdate};
 use tokio::{sync::mpsc, task::JoinHandle};
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
 use tokio_util::sync::CancellationToken;
 use tonic::{transport::Server, Request, Response, Result, Status};
 
 use super::protos::{
-    add_op_response, debug_clear_state_response, debug_dump_mempool_response,
-    debug_dump_reputation_response, debug_set_reputation_response, get_ops_response,
+    add_external_ops_response, add_op_response, debug_clear_state_response,
+    debug_dump_mempool_response, debug_dump_reputation_response, debug_dump_senders_response,
+    debug_set_reputation_response, export_mempool_response, get_last_block_number_response,
+    get_min_fees_response, get_next_nonce_response, get_op_options_response, get_op_rank_response,
+    get_op_removal_reason_response, get_ops_response, get_ops_with_priority_response,
+    get_paymaster_max_cost_response, import_mempool_response,
     op_pool_server::{OpPool, OpPoolServer},
-    remove_entities_response, remove_ops_response, update_entities_response, AddOpRequest,
-    AddOpResponse, AddOpSuccess, DebugClearStateRequest, DebugClearStateResponse,
-    DebugClearStateSuccess, DebugDumpMempoolRequest, DebugDumpMempoolResponse,
-    DebugDumpMempoolSuccess, DebugDumpReputationRequest, DebugDumpReputationResponse,
-    DebugDumpReputationSuccess, DebugSetReputationRequest, DebugSetReputationResponse,
-    DebugSetReputationSuccess, GetOpsRequest, GetOpsResponse, GetOpsSuccess,
-    GetSupportedEntryPointsRequest, GetSupportedEntryPointsResponse, MempoolOp,
-    RemoveEntitiesRequest, RemoveEntitiesResponse, RemoveEntitiesSuccess, RemoveOpsRequest,
-    RemoveOpsResponse, RemoveOpsSuccess, SubscribeNewHeadsRequest, SubscribeNewHeadsResponse,
+    remove_entities_response, remove_ops_response, update_entities_response, AddExternalOpsRequest,
+    AddExternalOpsResponse, AddExternalOpsSuccess, AddOpRequest, AddOpResponse, AddOpSuccess,
+    DebugClearStateRequest, DebugClearStateResponse, DebugClearStateSuccess,
+    DebugDumpMempoolRequest, DebugDumpMempoolResponse, DebugDumpMempoolSuccess,
+    DebugDumpReputationRequest, DebugDumpReputationResponse, DebugDumpReputationSuccess,
+    DebugDumpSendersRequest, DebugDumpSendersResponse, DebugDumpSendersSuccess,
+    DebugSetReputationRequest, DebugSetReputationResponse, DebugSetReputationSuccess,
+    ExportMempoolRequest, ExportMempoolResponse, ExportMempoolSuccess, GetLastBlockNumberRequest,
+    GetLastBlockNumberResponse, GetLastBlockNumberSuccess, GetMinFeesRequest, GetMinFeesResponse,
+    GetMinFeesSuccess, GetNextNonceRequest, GetNextNonceResponse, GetNextNonceSuccess,
+    GetOpOptionsRequest, GetOpOptionsResponse, GetOpOptionsSuccess, GetOpRankRequest,
+    GetOpRankResponse, GetOpRankSuccess, GetOpRemovalReasonRequest, GetOpRemovalReasonResponse,
+    GetOpRemovalReasonSuccess, GetOpsRequest, GetOpsResponse, GetOpsSuccess,
+    GetOpsWithPriorityRequest, GetOpsWithPriorityResponse, GetOpsWithPrioritySuccess,
+    GetPaymasterMaxCostRequest, GetPaymasterMaxCostResponse, GetPaymasterMaxCostSuccess,
+    GetSupportedEntryPointsRequest, GetSupportedEntryPointsResponse, HealthCheckRequest,
+    HealthCheckResponse, HealthStatus, ImportMempoolRejection, ImportMempoolRequest,
+    ImportMempoolResponse, ImportMempoolSuccess, MempoolOp, MempoolSnapshot, PrioritizedOp,
+    RemovalReason, RemoveEntitiesRequest, RemoveEntitiesResponse, RemoveEntitiesSuccess,
+    RemoveOpsRequest, RemoveOpsResponse, RemoveOpsSuccess, SubscribeNewHeadsRequest,
+    SubscribeNewHeadsResponse, SubscribePoolEventsRequest, SubscribePoolEventsResponse,
     UpdateEntitiesRequest, UpdateEntitiesResponse, UpdateEntitiesSuccess,
     OP_POOL_FILE_DESCRIPTOR_SET,
 };
 use crate::{
-    mempool::Reputation,
-    server::{local::LocalPoolHandle, PoolServer},
+    mempool::{AddOpOptions, RemovalReason as PoolRemovalReason, Reputation, ValidBlockRange},
+    server::{local::LocalPoolHandle, DumpMempoolFilter, GetOpsFilter, PoolServer},
 };
 
 const MAX_REMOTE_BLOCK_SUBSCRIPTIONS: usize = 32;
+const MAX_REMOTE_POOL_EVENT_SUBSCRIPTIONS: usize = 32;
+// Events buffered per remote subscriber. A subscriber that falls further behind
+// holds up its local subscription until that lags and ends the stream.
+const REMOTE_POOL_EVENT_BUFFER: usize = 1024;
+// Exported mempool snapshots are kept within tonic's default 4MB message limit,
+// leaving room for the rest of the response
+const MAX_SNAPSHOT_SIZE: usize = 4 * 1024 * 1024 - 1024;
 
 pub(crate) async fn spawn_remote_mempool_server(
     chain_id: u64,
     local_pool: LocalPoolHandle,
     addr: SocketAddr,
+    max_block_staleness: Duration,
     shutdown_token: CancellationToken,
 ) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
     // gRPC server
-    let pool_impl = OpPoolImpl::new(chain_id, local_pool);
+    let pool_impl = OpPoolImpl::new(chain_id, local_pool, max_block_staleness);
+    pool_impl
+        .spawn_block_watcher(shutdown_token.clone())
+        .await?;
     let op_pool_server = OpPoolServer::new(pool_impl);
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(OP_POOL_FILE_DESCRIPTOR_SET)
@@ -90,21 +123,64 @@ struct OpPoolImpl {
     chain_id: u64,
     local_pool: LocalPoolHandle,
     num_block_subscriptions: Arc<AtomicUsize>,
+    num_pool_event_subscriptions: Arc<AtomicUsize>,
+    last_block_seen: Arc<Mutex<Option<Instant>>>,
+    // The pool is reported as not serving if it hasn't seen a new block within this window
+    max_block_staleness: Duration,
 }
 
 impl OpPoolImpl {
-    pub(crate) fn new(chain_id: u64, local_pool: LocalPoolHandle) -> Self {
+    pub(crate) fn new(
+        chain_id: u64,
+        local_pool: LocalPoolHandle,
+        max_block_staleness: Duration,
+    ) -> Self {
         Self {
             chain_id,
             local_pool,
             num_block_subscriptions: Arc::new(AtomicUsize::new(0)),
+            num_pool_event_subscriptions: Arc::new(AtomicUsize::new(0)),
+            last_block_seen: Arc::new(Mutex::new(None)),
+            max_block_staleness,
         }
     }
 
+    // Track the time of the last new block seen by the pool, used for health checks
+    async fn spawn_block_watcher(&self, shutdown_token: CancellationToken) -> anyhow::Result<()> {
+        let mut new_heads = self.local_pool.subscribe_new_heads().await?;
+        let last_block_seen = Arc::clone(&self.last_block_seen);
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    new_head = new_heads.next() => {
+                        if new_head.is_none() {
+                            tracing::warn!("health check block subscription closed");
+                            break;
+                        }
+                        *last_block_seen.lock() = Some(Instant::now());
+                    }
+                    _ = shutdown_token.cancelled() => break,
+                }
+            }
+        });
+
+        Ok(())
+    }
+
     fn get_entry_point(&self, req_entry_point: &[u8]) -> Result<Address> {
         from_bytes(req_entry_point)
             .map_err(|e| Status::invalid_argument(format!("Invalid entry point: {e}")))
     }
+
+    fn get_optional_address(&self, bytes: &[u8], name: &str) -> Result<Option<Address>> {
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        from_bytes(bytes)
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(format!("Invalid {name}: {e}")))
+    }
 }
 
 #[async_trait]
@@ -139,8 +215,31 @@ impl OpPool for OpPoolImpl {
         let uo = proto_op.try_into().map_err(|e| {
             Status::invalid_argument(format!("Failed to convert to UserOperation: {e}"))
         })?;
+        let expected_block_hash = if req.expected_block_hash.is_empty() {
+            None
+        } else {
+            Some(from_bytes(&req.expected_block_hash).map_err(|e| {
+                Status::invalid_argument(format!("Invalid expected block hash: {e}"))
+            })?)
+        };
+        let valid_block_range = ValidBlockRange {
+            min_block: (req.min_block != 0).then_some(req.min_block),
+            max_block: (req.max_block != 0).then_some(req.max_block),
+        };
 
-        let resp = match self.local_pool.add_op(ep, uo).await {
+        let resp = match self
+            .local_pool
+            .add_op(
+                ep,
+                uo,
+                AddOpOptions {
+                    expected_block_hash,
+                    valid_block_range,
+                    metadata: req.metadata,
+                },
+            )
+            .await
+        {
             Ok(hash) => AddOpResponse {
                 result: Some(add_op_response::Result::Success(AddOpSuccess {
                     hash: hash.as_bytes().to_vec(),
@@ -154,13 +253,65 @@ impl OpPool for OpPoolImpl {
         Ok(Response::new(resp))
     }
 
+    async fn add_external_ops(
+        &self,
+        request: Request<AddExternalOpsRequest>,
+    ) -> Result<Response<AddExternalOpsResponse>> {
+        let req = request.into_inner();
+        let ops = req
+            .ops
+            .into_iter()
+            .map(|op| {
+                let ep = self.get_entry_point(&op.entry_point)?;
+                let proto_op = op.op.ok_or_else(|| {
+                    Status::invalid_argument("Operation is required in AddExternalOpsRequest")
+                })?;
+                let uo = proto_op.try_into().map_err(|e| {
+                    Status::invalid_argument(format!("Failed to convert to UserOperation: {e}"))
+                })?;
+                Ok((ep, uo))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let resp = match self.local_pool.add_external_ops(ops).await {
+            Ok(results) => AddExternalOpsResponse {
+                result: Some(add_external_ops_response::Result::Success(
+                    AddExternalOpsSuccess {
+                        results: results
+                            .into_iter()
+                            .map(|r| AddOpResponse {
+                                result: Some(match r {
+                                    Ok(hash) => add_op_response::Result::Success(AddOpSuccess {
+                                        hash: hash.as_bytes().to_vec(),
+                                    }),
+                                    Err(error) => add_op_response::Result::Failure(error.into()),
+                                }),
+                            })
+                            .collect(),
+                    },
+                )),
+            },
+            Err(error) => AddExternalOpsResponse {
+                result: Some(add_external_ops_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
     async fn get_ops(&self, request: Request<GetOpsRequest>) -> Result<Response<GetOpsResponse>> {
         let req = request.into_inner();
         let ep = self.get_entry_point(&req.entry_point)?;
 
+        let filter = GetOpsFilter {
+            paymaster: self.get_optional_address(&req.paymaster, "paymaster")?,
+            factory: self.get_optional_address(&req.factory, "factory")?,
+            offset: req.offset,
+        };
+
         let resp = match self
             .local_pool
-            .get_ops(ep, req.max_ops, req.shard_index)
+            .get_ops(ep, req.max_ops, req.shard_index, filter)
             .await
         {
             Ok(ops) => GetOpsResponse {
@@ -176,6 +327,35 @@ impl OpPool for OpPoolImpl {
         Ok(Response::new(resp))
     }
 
+    async fn get_ops_with_priority(
+        &self,
+        request: Request<GetOpsWithPriorityRequest>,
+    ) -> Result<Response<GetOpsWithPriorityResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+
+        let resp = match self
+            .local_pool
+            .get_ops_with_priority(ep, req.max_ops, req.shard_index)
+            .await
+        {
+            Ok(ops) => GetOpsWithPriorityResponse {
+                result: Some(get_ops_with_priority_response::Result::Success(
+                    GetOpsWithPrioritySuccess {
+                        ops: ops.iter().map(PrioritizedOp::from).collect(),
+                    },
+                )),
+            },
+            Err(error) => GetOpsWithPriorityResponse {
+                result: Some(get_ops_with_priority_response::Result::Failure(
+                    error.into(),
+                )),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
     async fn remove_ops(
         &self,
         request: Request<RemoveOpsRequest>,
@@ -193,8 +373,10 @@ impl OpPool for OpPoolImpl {
                 Ok(H256::from_slice(&h))
             })
             .collect::<Result<Vec<_>, _>>()?;
+        let reason = PoolRemovalReason::try_from(req.reason)
+            .map_err(|e| Status::invalid_argument(format!("Invalid removal reason: {e}")))?;
 
-        let resp = match self.local_pool.remove_ops(ep, hashes).await {
+        let resp = match self.local_pool.remove_ops(ep, hashes, reason).await {
             Ok(_) => RemoveOpsResponse {
                 result: Some(remove_ops_response::Result::Success(RemoveOpsSuccess {})),
             },
@@ -287,7 +469,20 @@ impl OpPool for OpPoolImpl {
         let req = request.into_inner();
         let ep = self.get_entry_point(&req.entry_point)?;
 
-        let resp = match self.local_pool.debug_dump_mempool(ep).await {
+        let min_max_fee_per_gas = if req.min_max_fee_per_gas.is_empty() {
+            None
+        } else {
+            Some(from_bytes(&req.min_max_fee_per_gas).map_err(|e| {
+                Status::invalid_argument(format!("Invalid min max fee per gas: {e}"))
+            })?)
+        };
+        let filter = DumpMempoolFilter {
+            min_max_fee_per_gas,
+            sender: self.get_optional_address(&req.sender, "sender")?,
+            limit: (req.limit > 0).then_some(req.limit),
+        };
+
+        let resp = match self.local_pool.debug_dump_mempool(ep, filter).await {
             Ok(ops) => DebugDumpMempoolResponse {
                 result: Some(debug_dump_mempool_response::Result::Success(
                     DebugDumpMempoolSuccess {
@@ -365,6 +560,312 @@ impl OpPool for OpPoolImpl {
         Ok(Response::new(resp))
     }
 
+    async fn debug_dump_senders(
+        &self,
+        request: Request<DebugDumpSendersRequest>,
+    ) -> Result<Response<DebugDumpSendersResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+
+        let resp = match self.local_pool.debug_dump_senders(ep).await {
+            Ok(senders) => DebugDumpSendersResponse {
+                result: Some(debug_dump_senders_response::Result::Success(
+                    DebugDumpSendersSuccess {
+                        senders: senders.into_iter().map(Into::into).collect(),
+                    },
+                )),
+            },
+            Err(error) => DebugDumpSendersResponse {
+                result: Some(debug_dump_senders_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn export_mempool(
+        &self,
+        request: Request<ExportMempoolRequest>,
+    ) -> Result<Response<ExportMempoolResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+
+        let resp = match self.local_pool.export_mempool(ep).await {
+            Ok(snapshot) => {
+                let mut snapshot = MempoolSnapshot::from(&snapshot);
+                bound_snapshot_size(&mut snapshot, MAX_SNAPSHOT_SIZE);
+                if snapshot.omitted_ops > 0 {
+                    tracing::warn!(
+                        "Left {} operations out of the mempool snapshot to bound its size",
+                        snapshot.omitted_ops
+                    );
+                }
+                ExportMempoolResponse {
+                    result: Some(export_mempool_response::Result::Success(
+                        ExportMempoolSuccess {
+                            snapshot: snapshot.encode_to_vec(),
+                        },
+                    )),
+                }
+            }
+            Err(error) => ExportMempoolResponse {
+                result: Some(export_mempool_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn import_mempool(
+        &self,
+        request: Request<ImportMempoolRequest>,
+    ) -> Result<Response<ImportMempoolResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        let snapshot = MempoolSnapshot::decode(req.snapshot.as_slice())
+            .map_err(|e| Status::invalid_argument(format!("Failed to decode snapshot: {e}")))?
+            .try_into()
+            .map_err(|e| Status::invalid_argument(format!("Failed to convert snapshot: {e}")))?;
+
+        let resp = match self.local_pool.import_mempool(ep, snapshot).await {
+            Ok(result) => ImportMempoolResponse {
+                result: Some(import_mempool_response::Result::Success(
+                    ImportMempoolSuccess {
+                        imported_hashes: result
+                            .imported
+                            .into_iter()
+                            .map(|hash| hash.as_bytes().to_vec())
+                            .collect(),
+                        rejected: result
+                            .rejected
+                            .into_iter()
+                            .map(|(id, error)| ImportMempoolRejection {
+                                sender: id.sender.as_bytes().to_vec(),
+                                nonce: to_le_bytes(id.nonce),
+                                error: Some(error.into()),
+                            })
+                            .collect(),
+                    },
+                )),
+            },
+            Err(error) => ImportMempoolResponse {
+                result: Some(import_mempool_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_last_block_number(
+        &self,
+        request: Request<GetLastBlockNumberRequest>,
+    ) -> Result<Response<GetLastBlockNumberResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+
+        let resp = match self.local_pool.get_last_block_number(ep).await {
+            Ok(block_number) => GetLastBlockNumberResponse {
+                result: Some(get_last_block_number_response::Result::Success(
+                    GetLastBlockNumberSuccess { block_number },
+                )),
+            },
+            Err(error) => GetLastBlockNumberResponse {
+                result: Some(get_last_block_number_response::Result::Failure(
+                    error.into(),
+                )),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_next_nonce(
+        &self,
+        request: Request<GetNextNonceRequest>,
+    ) -> Result<Response<GetNextNonceResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        let sender = from_bytes(&req.sender)
+            .map_err(|e| Status::invalid_argument(format!("Invalid sender: {e}")))?;
+
+        let resp = match self.local_pool.get_next_nonce(ep, sender).await {
+            Ok(nonce) => GetNextNonceResponse {
+                result: Some(get_next_nonce_response::Result::Success(
+                    GetNextNonceSuccess {
+                        nonce: to_le_bytes(nonce),
+                    },
+                )),
+            },
+            Err(error) => GetNextNonceResponse {
+                result: Some(get_next_nonce_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_min_fees(
+        &self,
+        request: Request<GetMinFeesRequest>,
+    ) -> Result<Response<GetMinFeesResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+
+        let resp = match self.local_pool.get_min_fees(ep).await {
+            Ok(fees) => GetMinFeesResponse {
+                result: Some(get_min_fees_response::Result::Success(GetMinFeesSuccess {
+                    max_fee_per_gas: to_le_bytes(fees.max_fee_per_gas),
+                    max_priority_fee_per_gas: to_le_bytes(fees.max_priority_fee_per_gas),
+                })),
+            },
+            Err(error) => GetMinFeesResponse {
+                result: Some(get_min_fees_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_paymaster_max_cost(
+        &self,
+        request: Request<GetPaymasterMaxCostRequest>,
+    ) -> Result<Response<GetPaymasterMaxCostResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        let paymaster = from_bytes(&req.paymaster)
+            .map_err(|e| Status::invalid_argument(format!("Invalid paymaster: {e}")))?;
+
+        let resp = match self.local_pool.get_paymaster_max_cost(ep, paymaster).await {
+            Ok(max_cost) => GetPaymasterMaxCostResponse {
+                result: Some(get_paymaster_max_cost_response::Result::Success(
+                    GetPaymasterMaxCostSuccess {
+                        max_cost: to_le_bytes(max_cost),
+                    },
+                )),
+            },
+            Err(error) => GetPaymasterMaxCostResponse {
+                result: Some(get_paymaster_max_cost_response::Result::Failure(
+                    error.into(),
+                )),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_op_removal_reason(
+        &self,
+        request: Request<GetOpRemovalReasonRequest>,
+    ) -> Result<Response<GetOpRemovalReasonResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        if req.hash.len() != 32 {
+            return Err(Status::invalid_argument("Hash must be 32 bytes long"));
+        }
+        let hash = H256::from_slice(&req.hash);
+
+        let resp = match self.local_pool.get_op_removal_reason(ep, hash).await {
+            Ok(reason) => GetOpRemovalReasonResponse {
+                result: Some(get_op_removal_reason_response::Result::Success(
+                    GetOpRemovalReasonSuccess {
+                        reason: reason
+                            .map_or(RemovalReason::Unspecified, RemovalReason::from)
+                            .into(),
+                    },
+                )),
+            },
+            Err(error) => GetOpRemovalReasonResponse {
+                result: Some(get_op_removal_reason_response::Result::Failure(
+                    error.into(),
+                )),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_op_options(
+        &self,
+        request: Request<GetOpOptionsRequest>,
+    ) -> Result<Response<GetOpOptionsResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        if req.hash.len() != 32 {
+            return Err(Status::invalid_argument("Hash must be 32 bytes long"));
+        }
+        let hash = H256::from_slice(&req.hash);
+
+        let resp = match self.local_pool.get_op_options(ep, hash).await {
+            Ok(options) => GetOpOptionsResponse {
+                result: Some(get_op_options_response::Result::Success(
+                    GetOpOptionsSuccess {
+                        options: options.as_ref().map(Into::into),
+                    },
+                )),
+            },
+            Err(error) => GetOpOptionsResponse {
+                result: Some(get_op_options_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn get_op_rank(
+        &self,
+        request: Request<GetOpRankRequest>,
+    ) -> Result<Response<GetOpRankResponse>> {
+        let req = request.into_inner();
+        let ep = self.get_entry_point(&req.entry_point)?;
+        if req.hash.len() != 32 {
+            return Err(Status::invalid_argument("Hash must be 32 bytes long"));
+        }
+        let hash = H256::from_slice(&req.hash);
+
+        let resp = match self.local_pool.get_op_rank(ep, hash).await {
+            Ok(rank) => GetOpRankResponse {
+                result: Some(get_op_rank_response::Result::Success(GetOpRankSuccess {
+                    ranked: rank.rank.is_some(),
+                    rank: rank.rank.unwrap_or_default(),
+                    pool_size: rank.pool_size,
+                })),
+            },
+            Err(error) => GetOpRankResponse {
+                result: Some(get_op_rank_response::Result::Failure(error.into())),
+            },
+        };
+
+        Ok(Response::new(resp))
+    }
+
+    async fn health_check(
+        &self,
+        _request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>> {
+        let num_mempools = match self.local_pool.get_supported_entry_points().await {
+            Ok(entry_points) => entry_points.len() as u64,
+            Err(e) => {
+                return Err(Status::internal(format!("Failed to get entry points: {e}")));
+            }
+        };
+
+        let serving = self
+            .last_block_seen
+            .lock()
+            .is_some_and(|t| t.elapsed() <= self.max_block_staleness);
+        let status = if serving {
+            HealthStatus::Serving
+        } else {
+            HealthStatus::NotServing
+        };
+
+        Ok(Response::new(HealthCheckResponse {
+            status: status as i32,
+            chain_id: self.chain_id,
+            num_mempools,
+        }))
+    }
+
     type SubscribeNewHeadsStream = UnboundedReceiverStream<Result<SubscribeNewHeadsResponse>>;
 
     async fn subscribe_new_heads(
@@ -415,4 +916,204 @@ impl OpPool for OpPoolImpl {
 
         Ok(Response::new(UnboundedReceiverStream::new(rx)))
     }
+
+    type SubscribePoolEventsStream = ReceiverStream<Result<SubscribePoolEventsResponse>>;
+
+    async fn subscribe_pool_events(
+        &self,
+        _request: Request<SubscribePoolEventsRequest>,
+    ) -> Result<Response<Self::SubscribePoolEventsStream>> {
+        let (tx, rx) = mpsc::channel(REMOTE_POOL_EVENT_BUFFER);
+
+        if self
+            .num_pool_event_subscriptions
+            .fetch_add(1, Ordering::Relaxed)
+            >= MAX_REMOTE_POOL_EVENT_SUBSCRIPTIONS
+        {
+            self.num_pool_event_subscriptions
+                .fetch_sub(1, Ordering::Relaxed);
+            return Err(Status::resource_exhausted(
+                "Too many pool event subscriptions",
+            ));
+        }
+
+        let num_pool_event_subscriptions = Arc::clone(&self.num_pool_event_subscriptions);
+        let mut events = match self.local_pool.subscribe_pool_events().await {
+            Ok(events) => events,
+            Err(error) => {
+                self.num_pool_event_subscriptions
+                    .fetch_sub(1, Ordering::Relaxed);
+                tracing::error!("Failed to subscribe to pool events: {error}");
+                return Err(Status::internal(format!(
+                    "Failed to subscribe to pool events: {error}"
+                )));
+            }
+        };
+
+        tokio::spawn(async move {
+            loop {
+                match events.next().await {
+                    Some(event) => {
+                        if tx
+                            .send(Ok(SubscribePoolEventsResponse {
+                                event: Some(event.into()),
+                            }))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    None => {
+                        // The local subscription ends if it lagged, so the subscriber
+                        // may have missed events
+                        tracing::warn!("pool event subscription closed");
+                        let _ = tx
+                            .send(Err(Status::data_loss(
+                                "pool event subscription ended, resubscribe and resynchronize",
+                            )))
+                            .await;
+                        break;
+                    }
+                }
+            }
+            num_pool_event_subscriptions.fetch_sub(1, Ordering::Relaxed);
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+// Drops the lowest ranked operations from a snapshot, which are last, until it
+// encodes within `max_size` bytes, counting them as omitted
+fn bound_snapshot_size(snapshot: &mut MempoolSnapshot, max_size: usize) {
+    let mut size = snapshot.encoded_len();
+    while size > max_size {
+        let Some(op) = snapshot.ops.pop() else {
+            break;
+        };
+        let op_size = op.encoded_len();
+        size -= 1 + encoded_len_varint(op_size as u64) + op_size;
+        size -= omitted_ops_size(snapshot.omitted_ops);
+        snapshot.omitted_ops += 1;
+        size += omitted_ops_size(snapshot.omitted_ops);
+    }
+}
+
+// Encoded size of the snapshot's omitted_ops field, which is left out when zero
+fn omitted_ops_size(omitted_ops: u64) -> usize {
+    if omitted_ops == 0 {
+        0
+    } else {
+        1 + encoded_len_varint(omitted_ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{
+        chain::ChainUpdate,
+        mempool::MockMempool,
+        server::{
+            remote::protos::{MempoolOp, UserOperation},
+            LocalPoolBuilder,
+        },
+    };
+
+    // The chain update sender is returned to keep the pool runner's subscription open
+    fn pool_impl(
+        max_block_staleness: Duration,
+    ) -> (OpPoolImpl, broadcast::Sender<Arc<ChainUpdate>>) {
+        let builder = LocalPoolBuilder::new(10, 10, 10);
+        let handle = builder.get_handle();
+        let pools = HashMap::from([(Address::random(), Arc::new(MockMempool::new()))]);
+        let (chain_update_tx, chain_updates) = broadcast::channel(10);
+        let _run_handle = builder.run(pools, chain_updates, CancellationToken::new());
+        (
+            OpPoolImpl::new(1, handle, max_block_staleness),
+            chain_update_tx,
+        )
+    }
+
+    async fn health_status(pool_impl: &OpPoolImpl) -> i32 {
+        pool_impl
+            .health_check(Request::new(HealthCheckRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .status
+    }
+
+    #[tokio::test]
+    async fn test_health_check_fresh_block() {
+        let (pool_impl, _chain_update_tx) = pool_impl(Duration::from_secs(60));
+        *pool_impl.last_block_seen.lock() = Some(Instant::now());
+        assert_eq!(
+            health_status(&pool_impl).await,
+            HealthStatus::Serving as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_stale_block() {
+        let (pool_impl, _chain_update_tx) = pool_impl(Duration::from_millis(10));
+        *pool_impl.last_block_seen.lock() = Some(Instant::now());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            health_status(&pool_impl).await,
+            HealthStatus::NotServing as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_no_block() {
+        let (pool_impl, _chain_update_tx) = pool_impl(Duration::from_secs(60));
+        assert_eq!(
+            health_status(&pool_impl).await,
+            HealthStatus::NotServing as i32
+        );
+    }
+
+    fn snapshot_with_ops(count: usize) -> MempoolSnapshot {
+        MempoolSnapshot {
+            ops: (0..count)
+                .map(|i| MempoolOp {
+                    uo: Some(UserOperation {
+                        nonce: vec![i as u8],
+                        call_data: vec![0; 100],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bound_snapshot_size_keeps_small_snapshot() {
+        let mut snapshot = snapshot_with_ops(10);
+        let size = snapshot.encoded_len();
+        bound_snapshot_size(&mut snapshot, size);
+        assert_eq!(snapshot.ops.len(), 10);
+        assert_eq!(snapshot.omitted_ops, 0);
+    }
+
+    #[test]
+    fn test_bound_snapshot_size_drops_worst_ops() {
+        let mut snapshot = snapshot_with_ops(10);
+        let max_size = snapshot.encoded_len() / 2;
+        bound_snapshot_size(&mut snapshot, max_size);
+        assert!(snapshot.encoded_len() <= max_size);
+        assert_eq!(snapshot.ops.len() as u64 + snapshot.omitted_ops, 10);
+        // the best operations, which come first, are kept
+        for (i, op) in snapshot.ops.iter().enumerate() {
+            assert_eq!(op.uo.as_ref().unwrap().nonce, vec![i as u8]);
+        }
+    }
 }
@@ -0,0 +1,636 @@
+// This file is part of Rundler.
+//
+// Rundler is free software: you can redistribute it and/or modify it under the
+// terms of the GNU Lesser General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// Rundler is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Rundler.
+// If not, see https://www.gnu.org/licenses/.
+
+use std::{cmp::Reverse, collections::HashMap, pin::Pin};
+
+use async_trait::async_trait;
+use ethers::types::{Address, H256, U256};
+use futures::future;
+use futures_util::{stream, Stream};
+use rundler_task::server::{HealthCheck, ServerStatus};
+use rundler_types::{Entity, EntityUpdate, GasFees, UserOperation};
+use rundler_utils::emit::WithEntryPoint;
+
+use super::{
+    DumpMempoolFilter, GetOpsFilter, ImportMempoolResult, MempoolSnapshot, NewHead, PoolResult,
+    PoolServer, RemotePoolClient,
+};
+use crate::mempool::{
+    sender_shard_index, AddOpOptions, MempoolError, MempoolEvent, OpPriority, OperationRank,
+    PoolOperation, RemovalReason, Reputation, ReputationStatus, SenderSummary,
+};
+
+/// Pool client for a pool sharded by sender across several pool servers
+///
+/// Each shard is a pool server started with `--pool.shard_count` set to the
+/// number of shards and `--pool.shard_index` set to its position in the list of
+/// shards. Requests for a sender's operations are routed to the shard that owns
+/// the sender, and other requests are sent to every shard with their results
+/// merged. With a single shard, every request goes to it unchanged.
+#[derive(Debug, Clone)]
+pub struct ShardedPoolClient<P> {
+    shards: Vec<P>,
+}
+
+impl<P: PoolServer> ShardedPoolClient<P> {
+    /// Create a client for the given shards, in shard index order
+    ///
+    /// Panics if `shards` is empty.
+    pub fn new(shards: Vec<P>) -> Self {
+        assert!(!shards.is_empty(), "a sharded pool should have a shard");
+        Self { shards }
+    }
+
+    fn shard(&self, sender: Address) -> &P {
+        &self.shards[sender_shard_index(sender, self.shards.len() as u64) as usize]
+    }
+}
+
+impl ShardedPoolClient<RemotePoolClient> {
+    /// Connect to the pool servers at a comma-separated list of urls, one for
+    /// each shard in shard index order
+    pub async fn connect(urls: String) -> anyhow::Result<Self> {
+        let shards = future::try_join_all(
+            urls.split(',')
+                .map(|url| RemotePoolClient::connect(url.trim().to_string())),
+        )
+        .await?;
+        Ok(Self::new(shards))
+    }
+}
+
+#[async_trait]
+impl<P: PoolServer> PoolServer for ShardedPoolClient<P> {
+    async fn get_supported_entry_points(&self) -> PoolResult<Vec<Address>> {
+        self.shards[0].get_supported_entry_points().await
+    }
+
+    async fn add_op(
+        &self,
+        entry_point: Address,
+        op: UserOperation,
+        options: AddOpOptions,
+    ) -> PoolResult<H256> {
+        self.shard(op.sender).add_op(entry_point, op, options).await
+    }
+
+    async fn add_external_ops(
+        &self,
+        ops: Vec<(Address, UserOperation)>,
+    ) -> PoolResult<Vec<Result<H256, MempoolError>>> {
+        if self.shards.len() == 1 {
+            return self.shards[0].add_external_ops(ops).await;
+        }
+        // Send each shard its senders' operations, remembering their positions so the
+        // results can be returned in request order
+        let shard_count = self.shards.len() as u64;
+        let mut shard_ops = vec![vec![]; self.shards.len()];
+        let mut positions = vec![vec![]; self.shards.len()];
+        for (i, (entry_point, op)) in ops.into_iter().enumerate() {
+            let shard = sender_shard_index(op.sender, shard_count) as usize;
+            shard_ops[shard].push((entry_point, op));
+            positions[shard].push(i);
+        }
+        let shard_results = future::try_join_all(
+            self.shards
+                .iter()
+                .zip(shard_ops)
+                .filter(|(_, ops)| !ops.is_empty())
+                .map(|(shard, ops)| shard.add_external_ops(ops)),
+        )
+        .await?;
+
+        let mut results = positions
+            .into_iter()
+            .filter(|positions| !positions.is_empty())
+            .zip(shard_results)
+            .flat_map(|(positions, results)| positions.into_iter().zip(results))
+            .collect::<Vec<_>>();
+        results.sort_by_key(|(i, _)| *i);
+        Ok(results.into_iter().map(|(_, result)| result).collect())
+    }
+
+    // Operations are returned shard by shard rather than in a single order
+    async fn get_ops(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+        filter: GetOpsFilter,
+    ) -> PoolResult<Vec<PoolOperation>> {
+        if self.shards.len() == 1 {
+            return self.shards[0]
+                .get_ops(entry_point, max_ops, shard_index, filter)
+                .await;
+        }
+        let shard_filter = GetOpsFilter {
+            offset: 0,
+            ..filter
+        };
+        let ops = future::try_join_all(self.shards.iter().map(|shard| {
+            shard.get_ops(
+                entry_point,
+                max_ops.saturating_add(filter.offset),
+                shard_index,
+                shard_filter,
+            )
+        }))
+        .await?;
+        Ok(ops
+            .into_iter()
+            .flatten()
+            .skip(filter.offset as usize)
+            .take(max_ops as usize)
+            .collect())
+    }
+
+    async fn get_ops_with_priority(
+        &self,
+        entry_point: Address,
+        max_ops: u64,
+        shard_index: u64,
+    ) -> PoolResult<Vec<(PoolOperation, OpPriority)>> {
+        let ops = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_ops_with_priority(entry_point, max_ops, shard_index)),
+        )
+        .await?;
+        let mut ops = ops.into_iter().flatten().collect::<Vec<_>>();
+        ops.sort_by_key(|(_, priority)| {
+            (
+                Reverse(priority.effective_gas_price),
+                priority.submission_seq,
+            )
+        });
+        ops.truncate(max_ops as usize);
+        Ok(ops)
+    }
+
+    async fn remove_ops(
+        &self,
+        entry_point: Address,
+        ops: Vec<H256>,
+        reason: RemovalReason,
+    ) -> PoolResult<()> {
+        future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.remove_ops(entry_point, ops.clone(), reason)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_op_removal_reason(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<RemovalReason>> {
+        let reasons = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_op_removal_reason(entry_point, hash)),
+        )
+        .await?;
+        Ok(reasons.into_iter().flatten().next())
+    }
+
+    async fn get_op_options(
+        &self,
+        entry_point: Address,
+        hash: H256,
+    ) -> PoolResult<Option<AddOpOptions>> {
+        let options = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_op_options(entry_point, hash)),
+        )
+        .await?;
+        Ok(options.into_iter().flatten().next())
+    }
+
+    // The rank is within the shard that holds the operation, and the pool size is
+    // the total across shards
+    async fn get_op_rank(&self, entry_point: Address, hash: H256) -> PoolResult<OperationRank> {
+        let ranks = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_op_rank(entry_point, hash)),
+        )
+        .await?;
+        Ok(OperationRank {
+            rank: ranks.iter().find_map(|r| r.rank),
+            pool_size: ranks.iter().map(|r| r.pool_size).sum(),
+        })
+    }
+
+    async fn remove_entities(&self, entry_point: Address, entities: Vec<Entity>) -> PoolResult<()> {
+        future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.remove_entities(entry_point, entities.clone())),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_entities(
+        &self,
+        entry_point: Address,
+        entities: Vec<EntityUpdate>,
+    ) -> PoolResult<()> {
+        future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.update_entities(entry_point, entities.clone())),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_last_block_number(&self, entry_point: Address) -> PoolResult<u64> {
+        self.shards[0].get_last_block_number(entry_point).await
+    }
+
+    async fn get_next_nonce(&self, entry_point: Address, sender: Address) -> PoolResult<U256> {
+        self.shard(sender).get_next_nonce(entry_point, sender).await
+    }
+
+    async fn get_min_fees(&self, entry_point: Address) -> PoolResult<GasFees> {
+        self.shards[0].get_min_fees(entry_point).await
+    }
+
+    async fn get_paymaster_max_cost(
+        &self,
+        entry_point: Address,
+        paymaster: Address,
+    ) -> PoolResult<U256> {
+        let costs = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.get_paymaster_max_cost(entry_point, paymaster)),
+        )
+        .await?;
+        Ok(costs.into_iter().fold(U256::zero(), |a, b| a + b))
+    }
+
+    async fn subscribe_new_heads(&self) -> PoolResult<Pin<Box<dyn Stream<Item = NewHead> + Send>>> {
+        self.shards[0].subscribe_new_heads().await
+    }
+
+    async fn subscribe_pool_events(
+        &self,
+    ) -> PoolResult<Pin<Box<dyn Stream<Item = WithEntryPoint<MempoolEvent>> + Send>>> {
+        if self.shards.len() == 1 {
+            return self.shards[0].subscribe_pool_events().await;
+        }
+        let streams = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.subscribe_pool_events()),
+        )
+        .await?;
+        Ok(Box::pin(stream::select_all(streams)))
+    }
+
+    async fn debug_clear_state(&self) -> PoolResult<()> {
+        future::try_join_all(self.shards.iter().map(|shard| shard.debug_clear_state())).await?;
+        Ok(())
+    }
+
+    async fn debug_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: DumpMempoolFilter,
+    ) -> PoolResult<Vec<PoolOperation>> {
+        if let Some(sender) = filter.sender {
+            return self
+                .shard(sender)
+                .debug_dump_mempool(entry_point, filter)
+                .await;
+        }
+        let ops = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.debug_dump_mempool(entry_point, filter)),
+        )
+        .await?;
+        let ops = ops.into_iter().flatten();
+        Ok(match filter.limit {
+            Some(limit) => ops.take(limit as usize).collect(),
+            None => ops.collect(),
+        })
+    }
+
+    async fn debug_set_reputations(
+        &self,
+        entry_point: Address,
+        reputations: Vec<Reputation>,
+    ) -> PoolResult<()> {
+        future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.debug_set_reputations(entry_point, reputations.clone())),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn debug_dump_reputation(&self, entry_point: Address) -> PoolResult<Vec<Reputation>> {
+        let reputations = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.debug_dump_reputation(entry_point)),
+        )
+        .await?;
+        Ok(merge_reputations(reputations.into_iter().flatten()))
+    }
+
+    async fn debug_dump_senders(&self, entry_point: Address) -> PoolResult<Vec<SenderSummary>> {
+        let senders = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.debug_dump_senders(entry_point)),
+        )
+        .await?;
+        Ok(senders.into_iter().flatten().collect())
+    }
+
+    async fn export_mempool(&self, entry_point: Address) -> PoolResult<MempoolSnapshot> {
+        let snapshots = future::try_join_all(
+            self.shards
+                .iter()
+                .map(|shard| shard.export_mempool(entry_point)),
+        )
+        .await?;
+        let mut merged = MempoolSnapshot::default();
+        let mut reputations = vec![];
+        for snapshot in snapshots {
+            merged.operations.extend(snapshot.operations);
+            reputations.extend(snapshot.reputations);
+            merged.omitted_operations += snapshot.omitted_operations;
+        }
+        merged.reputations = merge_reputations(reputations);
+        Ok(merged)
+    }
+
+    async fn import_mempool(
+        &self,
+        entry_point: Address,
+        snapshot: MempoolSnapshot,
+    ) -> PoolResult<ImportMempoolResult> {
+        let mut shard_snapshots = vec![
+            MempoolSnapshot {
+                reputations: snapshot.reputations,
+                ..Default::default()
+            };
+            self.shards.len()
+        ];
+        for op in snapshot.operations {
+            let index = sender_shard_index(op.uo.sender, self.shards.len() as u64);
+            shard_snapshots[index as usize].operations.push(op);
+        }
+        let results = future::try_join_all(
+            self.shards
+                .iter()
+                .zip(shard_snapshots)
+                .map(|(shard, snapshot)| shard.import_mempool(entry_point, snapshot)),
+        )
+        .await?;
+        let mut merged = ImportMempoolResult::default();
+        for result in results {
+            merged.imported.extend(result.imported);
+            merged.rejected.extend(result.rejected);
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl<P: HealthCheck> HealthCheck for ShardedPoolClient<P> {
+    fn name(&self) -> &'static str {
+        "ShardedPoolServer"
+    }
+
+    // Serving only while every shard is, as requests for a shard's senders fail
+    // while it's down
+    async fn status(&self) -> ServerStatus {
+        let statuses = future::join_all(self.shards.iter().map(|shard| shard.status())).await;
+        if statuses
+            .iter()
+            .all(|status| matches!(status, ServerStatus::Serving))
+        {
+            ServerStatus::Serving
+        } else {
+            ServerStatus::NotServing
+        }
+    }
+}
+
+// Sums the counts each shard keeps for an entity, taking the most severe status
+// any shard has given it
+fn merge_reputations(reputations: impl IntoIterator<Item = Reputation>) -> Vec<Reputation> {
+    let mut merged: HashMap<Address, Reputation> = HashMap::new();
+    for rep in reputations {
+        match merged.get_mut(&rep.address) {
+            Some(existing) => {
+                existing.ops_seen += rep.ops_seen;
+                existing.ops_included += rep.ops_included;
+                if status_severity(rep.status) > status_severity(existing.status) {
+                    existing.status = rep.status;
+                }
+            }
+            None => {
+                merged.insert(rep.address, rep);
+            }
+        }
+    }
+    merged.into_values().collect()
+}
+
+fn status_severity(status: ReputationStatus) -> u8 {
+    match status {
+        ReputationStatus::Ok => 0,
+        ReputationStatus::Throttled => 1,
+        ReputationStatus::Banned => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::MockPoolServer;
+
+    fn sender_in_shard(index: u64, shard_count: u64) -> Address {
+        loop {
+            let sender = Address::random();
+            if sender_shard_index(sender, shard_count) == index {
+                return sender;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_op_routed_to_sender_shard() {
+        let ep = Address::random();
+        let sender = sender_in_shard(1, 3);
+        let shards = (0..3_u64)
+            .map(|i| {
+                let mut shard = MockPoolServer::new();
+                shard
+                    .expect_add_op()
+                    .times(usize::from(i == 1))
+                    .returning(|_, _, _| Ok(H256::zero()));
+                shard
+            })
+            .collect();
+
+        let client = ShardedPoolClient::new(shards);
+        client
+            .add_op(
+                ep,
+                UserOperation {
+                    sender,
+                    ..Default::default()
+                },
+                AddOpOptions::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_add_external_ops_routed_to_sender_shards() {
+        let ep = Address::random();
+        let senders = [
+            sender_in_shard(1, 2),
+            sender_in_shard(0, 2),
+            sender_in_shard(1, 2),
+        ];
+        let shards = (0..2_u64)
+            .map(|i| {
+                let mut shard = MockPoolServer::new();
+                shard
+                    .expect_add_external_ops()
+                    .times(1)
+                    .returning(move |ops| {
+                        assert!(ops
+                            .iter()
+                            .all(|(_, op)| sender_shard_index(op.sender, 2) == i));
+                        Ok(ops
+                            .into_iter()
+                            .map(|(_, op)| Ok(H256::from(op.sender)))
+                            .collect())
+                    });
+                shard
+            })
+            .collect();
+
+        let client = ShardedPoolClient::new(shards);
+        let results = client
+            .add_external_ops(
+                senders
+                    .iter()
+                    .map(|&sender| {
+                        (
+                            ep,
+                            UserOperation {
+                                sender,
+                                ..Default::default()
+                            },
+                        )
+                    })
+                    .collect(),
+            )
+            .await
+            .unwrap();
+
+        let hashes = results.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            hashes,
+            senders.iter().map(|&s| H256::from(s)).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_next_nonce_routed_to_sender_shard() {
+        let ep = Address::random();
+        let sender = sender_in_shard(0, 2);
+        let shards = (0..2_u64)
+            .map(|i| {
+                let mut shard = MockPoolServer::new();
+                shard
+                    .expect_get_next_nonce()
+                    .times(usize::from(i == 0))
+                    .returning(|_, _| Ok(U256::from(7)));
+                shard
+            })
+            .collect();
+
+        let client = ShardedPoolClient::new(shards);
+        assert_eq!(
+            client.get_next_nonce(ep, sender).await.unwrap(),
+            U256::from(7)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_op_options_checks_every_shard() {
+        let ep = Address::random();
+        let shards = (0..3)
+            .map(|i| {
+                let mut shard = MockPoolServer::new();
+                shard
+                    .expect_get_op_options()
+                    .times(1)
+                    .returning(move |_, _| Ok((i == 2).then(AddOpOptions::default)));
+                shard
+            })
+            .collect();
+
+        let client = ShardedPoolClient::new(shards);
+        assert_eq!(
+            client.get_op_options(ep, H256::random()).await.unwrap(),
+            Some(AddOpOptions::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_debug_dump_reputation_sums_shards() {
+        let ep = Address::random();
+        let address = Address::random();
+        let shards = [ReputationStatus::Ok, ReputationStatus::Throttled]
+            .into_iter()
+            .map(|status| {
+                let mut shard = MockPoolServer::new();
+                shard.expect_debug_dump_reputation().returning(move |_| {
+                    Ok(vec![Reputation {
+                        address,
+                        status,
+                        ops_seen: 10,
+                        ops_included: 1,
+                    }])
+                });
+                shard
+            })
+            .collect();
+
+        let client = ShardedPoolClient::new(shards);
+        let reputations = client.debug_dump_reputation(ep).await.unwrap();
+        assert_eq!(reputations.len(), 1);
+        assert_eq!(reputations[0].ops_seen, 20);
+        assert_eq!(reputations[0].ops_included, 2);
+        assert_eq!(reputations[0].status, ReputationStatus::Throttled);
+    }
+}
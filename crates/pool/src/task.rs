@@ -15,17 +15,21 @@ use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::{bail, Context};
 use async_trait::async_trait;
-use ethers::providers::{JsonRpcClient, Provider};
+use ethers::{
+    providers::{JsonRpcClient, Provider},
+    types::Address,
+};
+use rundler_provider::EntryPoint;
 use rundler_sim::{
     Prechecker, PrecheckerImpl, SimulateValidationTracerImpl, Simulator, SimulatorImpl,
 };
 use rundler_task::Task;
 use rundler_types::contracts::i_entry_point::IEntryPoint;
 use rundler_utils::{emit::WithEntryPoint, eth, handle};
-use tokio::{sync::broadcast, try_join};
+use tokio::{sync::broadcast, task::JoinHandle, try_join};
 use tokio_util::sync::CancellationToken;
 
-use super::mempool::{HourlyMovingAverageReputation, PoolConfig, ReputationParams};
+use super::mempool::{HourlyMovingAverageReputation, MempoolEvent, PoolConfig, ReputationManager};
 use crate::{
     chain::{self, Chain},
     emit::OpPoolEvent,
@@ -49,6 +53,9 @@ pub struct Args {
     /// Address to bind the remote mempool server to, if any.
     /// If not provided, a server will not be started.
     pub remote_address: Option<SocketAddr>,
+    /// How long the remote mempool server reports itself healthy after the
+    /// last new block it has seen.
+    pub max_block_staleness: Duration,
     /// Channel capacity for the chain update channel.
     pub chain_update_channel_capacity: usize,
 }
@@ -59,6 +66,7 @@ pub struct PoolTask {
     args: Args,
     event_sender: broadcast::Sender<WithEntryPoint<OpPoolEvent>>,
     pool_builder: LocalPoolBuilder,
+    reputation_managers: HashMap<Address, Arc<dyn ReputationManager>>,
 }
 
 #[async_trait]
@@ -79,6 +87,12 @@ impl Task for PoolTask {
                 .map(|config| config.entry_point)
                 .collect(),
         };
+        eth::check_chain(
+            &self.args.http_url,
+            self.args.chain_id,
+            &chain_settings.entry_point_addresses,
+        )
+        .await?;
         let provider = eth::new_provider(&self.args.http_url, Some(self.args.http_poll_interval))?;
         let chain = Chain::new(provider.clone(), chain_settings);
         let (update_sender, _) = broadcast::channel(self.args.chain_update_channel_capacity);
@@ -86,13 +100,23 @@ impl Task for PoolTask {
 
         // create mempools
         let mut mempools = HashMap::new();
+        let mut reputation_handles = vec![];
         for pool_config in &self.args.pool_configs {
-            let pool =
-                PoolTask::create_mempool(pool_config, self.event_sender.clone(), provider.clone())
-                    .await
-                    .context("should have created mempool")?;
+            let (pool, reputation_handle) = PoolTask::create_mempool(
+                pool_config,
+                self.reputation_managers
+                    .get(&pool_config.entry_point)
+                    .cloned(),
+                self.event_sender.clone(),
+                self.pool_builder.mempool_event_sender(),
+                provider.clone(),
+                shutdown_token.clone(),
+            )
+            .await
+            .context("should have created mempool")?;
 
             mempools.insert(pool_config.entry_point, Arc::new(pool));
+            reputation_handles.extend(reputation_handle);
         }
 
         let pool_handle = self.pool_builder.get_handle();
@@ -102,8 +126,14 @@ impl Task for PoolTask {
 
         let remote_handle = match self.args.remote_address {
             Some(addr) => {
-                spawn_remote_mempool_server(self.args.chain_id, pool_handle, addr, shutdown_token)
-                    .await?
+                spawn_remote_mempool_server(
+                    self.args.chain_id,
+                    pool_handle,
+                    addr,
+                    self.args.max_block_staleness,
+                    shutdown_token,
+                )
+                .await?
             }
             None => tokio::spawn(async { Ok(()) }),
         };
@@ -116,6 +146,12 @@ impl Task for PoolTask {
             handle::as_anyhow_handle(chain_handle),
         ) {
             Ok(_) => {
+                // wait for the reputation managers to persist their reputation
+                for handle in reputation_handles {
+                    if let Err(e) = handle.await {
+                        tracing::error!("Reputation manager failed: {e:?}");
+                    }
+                }
                 tracing::info!("Pool server shutdown");
                 Ok(())
             }
@@ -138,9 +174,25 @@ impl PoolTask {
             args,
             event_sender,
             pool_builder,
+            reputation_managers: HashMap::new(),
         }
     }
 
+    /// Use the given reputation manager for the pool of an entry point instead of
+    /// the default hourly moving average reputation.
+    ///
+    /// The manager is used as is, so it's responsible for applying the pool's
+    /// reputation parameters, blocklist and allowlist if it needs them.
+    pub fn with_reputation_manager(
+        mut self,
+        entry_point: Address,
+        reputation_manager: Arc<dyn ReputationManager>,
+    ) -> Self {
+        self.reputation_managers
+            .insert(entry_point, reputation_manager);
+        self
+    }
+
     /// Convert this task into a boxed task.
     pub fn boxed(self) -> Box<dyn Task> {
         Box::new(self)
@@ -148,19 +200,33 @@ impl PoolTask {
 
     async fn create_mempool<C: JsonRpcClient + 'static>(
         pool_config: &PoolConfig,
+        reputation: Option<Arc<dyn ReputationManager>>,
         event_sender: broadcast::Sender<WithEntryPoint<OpPoolEvent>>,
+        mempool_event_sender: broadcast::Sender<WithEntryPoint<MempoolEvent>>,
         provider: Arc<Provider<C>>,
-    ) -> anyhow::Result<UoPool<HourlyMovingAverageReputation, impl Prechecker, impl Simulator>>
-    {
+        shutdown_token: CancellationToken,
+    ) -> anyhow::Result<(
+        UoPool<dyn ReputationManager, impl Prechecker, impl Simulator, impl EntryPoint>,
+        Option<JoinHandle<()>>,
+    )> {
         // Reputation manager
-        let reputation = Arc::new(HourlyMovingAverageReputation::new(
-            ReputationParams::bundler_default(),
-            pool_config.blocklist.clone(),
-            pool_config.allowlist.clone(),
-        ));
-        // Start reputation manager
-        let reputation_runner = Arc::clone(&reputation);
-        tokio::spawn(async move { reputation_runner.run().await });
+        let (reputation, reputation_handle): (Arc<dyn ReputationManager>, _) = match reputation {
+            Some(reputation) => (reputation, None),
+            None => {
+                let reputation = Arc::new(HourlyMovingAverageReputation::new(
+                    pool_config.reputation_params,
+                    pool_config.blocklist.clone(),
+                    pool_config.allowlist.clone(),
+                    pool_config.reputation_persistence_path.clone(),
+                ));
+                reputation.load_persisted().await;
+                // Start reputation manager
+                let reputation_runner = Arc::clone(&reputation);
+                let handle =
+                    tokio::spawn(async move { reputation_runner.run(shutdown_token).await });
+                (reputation, Some(handle))
+            }
+        };
 
         let i_entry_point = IEntryPoint::new(pool_config.entry_point, Arc::clone(&provider));
         let simulate_validation_tracer =
@@ -174,16 +240,138 @@ impl PoolTask {
             Arc::clone(&provider),
             i_entry_point.address(),
             simulate_validation_tracer,
-            pool_config.sim_settings,
+            pool_config.sim_settings.clone(),
             pool_config.mempool_channel_configs.clone(),
         );
 
-        Ok(UoPool::new(
+        let pool = UoPool::new(
             pool_config.clone(),
             Arc::clone(&reputation),
             event_sender,
+            mempool_event_sender,
             prechecker,
             simulator,
-        ))
+            i_entry_point,
+        );
+        Ok((pool, reputation_handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{providers::Http, types::U256};
+    use rundler_sim::{PrecheckSettings, SimulationSettings};
+
+    use super::*;
+    use crate::mempool::{Mempool, Reputation, ReputationParams, ReputationStatus};
+
+    #[derive(Debug)]
+    struct FixedReputation(Reputation);
+
+    impl ReputationManager for FixedReputation {
+        fn status(&self, _address: Address) -> ReputationStatus {
+            self.0.status
+        }
+
+        fn add_seen(&self, _address: Address) {}
+
+        fn handle_urep_030_penalty(&self, _address: Address) {}
+
+        fn handle_srep_050_penalty(&self, _address: Address) {}
+
+        fn add_included(&self, _address: Address) {}
+
+        fn remove_included(&self, _address: Address) {}
+
+        fn update_included(&self, _included: &[Address], _unincluded: &[Address]) {}
+
+        fn dump_reputation(&self) -> Vec<Reputation> {
+            vec![self.0.clone()]
+        }
+
+        fn set_reputation(&self, _address: Address, _ops_seen: u64, _ops_included: u64) {}
+
+        fn get_ops_allowed(&self, _address: Address) -> u64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_mempool_with_reputation_manager() {
+        let reputation = Reputation {
+            address: Address::random(),
+            status: ReputationStatus::Banned,
+            ops_seen: 7,
+            ops_included: 1,
+        };
+        let pool_config = PoolConfig {
+            entry_point: Address::random(),
+            chain_id: 1,
+            max_userops_per_sender: 16,
+            min_replacement_fee_increase_percentage: 10,
+            max_size_of_pool_bytes: 10000,
+            max_pool_size: 100,
+            min_priority_fee_per_gas: U256::zero(),
+            min_max_fee_per_gas: U256::zero(),
+            max_op_size_bytes: 131_072,
+            max_bundle_gas: 25_000_000,
+            blocklist: None,
+            allowlist: None,
+            precheck_settings: PrecheckSettings::default(),
+            sim_settings: SimulationSettings::default(),
+            mempool_channel_configs: HashMap::new(),
+            num_shards: 1,
+            pool_shard_count: 1,
+            pool_shard_index: 0,
+            throttled_entity_mempool_count: 4,
+            throttled_entity_live_blocks: 10,
+            reputation_params: ReputationParams::bundler_default(),
+            reputation_persistence_path: None,
+            rate_limits: HashMap::new(),
+            revalidate_before_bundle: false,
+            require_stake_for_gossip: false,
+            revalidate_reorged_ops: false,
+        };
+        let task = PoolTask::new(
+            Args {
+                http_url: "http://localhost:8545".to_string(),
+                http_poll_interval: Duration::from_millis(100),
+                chain_id: 1,
+                chain_history_size: 10,
+                pool_configs: vec![pool_config.clone()],
+                remote_address: None,
+                max_block_staleness: Duration::from_secs(60),
+                chain_update_channel_capacity: 10,
+            },
+            broadcast::channel(10).0,
+            LocalPoolBuilder::new(10, 10, 10),
+        )
+        .with_reputation_manager(
+            pool_config.entry_point,
+            Arc::new(FixedReputation(reputation.clone())),
+        );
+
+        // The provider is never called while creating the mempool
+        let provider = Arc::new(Provider::<Http>::try_from("http://localhost:8545").unwrap());
+        let (pool, reputation_handle) = PoolTask::create_mempool(
+            &pool_config,
+            task.reputation_managers
+                .get(&pool_config.entry_point)
+                .cloned(),
+            task.event_sender.clone(),
+            task.pool_builder.mempool_event_sender(),
+            provider,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+
+        // The default reputation manager, and its persistence task, are not created
+        assert!(reputation_handle.is_none());
+        let dumped = pool.dump_reputation();
+        assert_eq!(dumped.len(), 1);
+        assert_eq!(dumped[0].address, reputation.address);
+        assert_eq!(dumped[0].status, ReputationStatus::Banned);
+        assert_eq!(dumped[0].ops_seen, 7);
     }
 }
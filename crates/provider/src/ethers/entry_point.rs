@@ -26,7 +26,7 @@ use ethers::{
 use rundler_types::{
     contracts::{
         i_entry_point::{ExecutionResult, FailedOp, IEntryPoint, SignatureValidationFailed},
-        shared_types::UserOpsPerAggregator,
+        shared_types::{DepositInfo, UserOpsPerAggregator},
     },
     GasFees, UserOperation,
 };
@@ -72,14 +72,8 @@ where
             Err(error) => error,
         };
         if let ContractError::Revert(revert_data) = &error {
-            if let Ok(FailedOp { op_index, reason }) = FailedOp::decode(revert_data) {
-                match &reason[..4] {
-                    "AA95" => anyhow::bail!("Handle ops called with insufficient gas"),
-                    _ => return Ok(HandleOpsOut::FailedOp(op_index.as_usize(), reason)),
-                }
-            }
-            if let Ok(failure) = SignatureValidationFailed::decode(revert_data) {
-                return Ok(HandleOpsOut::SignatureValidationFailed(failure.aggregator));
+            if let Some(out) = decode_handle_ops_revert(revert_data)? {
+                return Ok(out);
             }
         }
         Err(error)?
@@ -99,6 +93,20 @@ where
             .context("entry point should return balance")
     }
 
+    async fn get_deposit_info(&self, address: Address) -> anyhow::Result<DepositInfo> {
+        self.get_deposit_info(address)
+            .call()
+            .await
+            .context("entry point should return deposit info")
+    }
+
+    async fn get_nonce(&self, address: Address, key: U256) -> anyhow::Result<U256> {
+        self.get_nonce(address, key)
+            .call()
+            .await
+            .context("entry point should return nonce")
+    }
+
     async fn call_spoofed_simulate_op(
         &self,
         op: UserOperation,
@@ -154,6 +162,27 @@ where
     }
 }
 
+/// Decodes the revert data of a `handleOps` call, returning the index of the
+/// failed operation along with its decoded revert reason if the bundle failed
+/// due to a single operation or aggregator.
+fn decode_handle_ops_revert(revert_data: &Bytes) -> anyhow::Result<Option<HandleOpsOut>> {
+    if let Ok(FailedOp { op_index, reason }) = FailedOp::decode(revert_data) {
+        if reason.starts_with("AA95") {
+            anyhow::bail!("Handle ops called with insufficient gas");
+        }
+        return Ok(Some(HandleOpsOut::FailedOp(op_index.as_usize(), reason)));
+    }
+    if let Ok(failure) = SignatureValidationFailed::decode(revert_data) {
+        return Ok(Some(HandleOpsOut::SignatureValidationFailed(
+            failure.aggregator,
+        )));
+    }
+    if let Ok(err) = ContractRevertError::decode(revert_data) {
+        anyhow::bail!("Handle ops reverted: {}", err.reason);
+    }
+    Ok(None)
+}
+
 fn get_handle_ops_call<M: Middleware>(
     entry_point: &IEntryPoint<M>,
     mut ops_per_aggregator: Vec<UserOpsPerAggregator>,
@@ -168,3 +197,50 @@ fn get_handle_ops_call<M: Middleware>(
         };
     call.gas(gas)
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_handle_ops_revert_failed_op() {
+        let revert_data: Bytes = FailedOp {
+            op_index: 1.into(),
+            reason: "AA23 reverted: invalid signature".to_string(),
+        }
+        .encode()
+        .into();
+
+        let out = decode_handle_ops_revert(&revert_data).unwrap();
+        assert_eq!(
+            out,
+            Some(HandleOpsOut::FailedOp(
+                1,
+                "AA23 reverted: invalid signature".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_decode_handle_ops_revert_error_string() {
+        let revert_data: Bytes = ContractRevertError {
+            reason: "AA92 internal call only".to_string(),
+        }
+        .encode()
+        .into();
+
+        let err = decode_handle_ops_revert(&revert_data).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Handle ops reverted: AA92 internal call only"
+        );
+    }
+
+    #[test]
+    fn test_decode_handle_ops_revert_unknown() {
+        let revert_data = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(decode_handle_ops_revert(&revert_data).unwrap(), None);
+    }
+}
@@ -15,6 +15,7 @@ use std::{fmt::Debug, sync::Arc};
 
 use anyhow::Context;
 use ethers::{
+    abi::AbiDecode,
     contract::ContractError,
     prelude::ContractError as EthersContractError,
     providers::{
@@ -29,7 +30,9 @@ use ethers::{
 };
 use rundler_types::{
     contracts::{
-        gas_price_oracle::GasPriceOracle, i_aggregator::IAggregator, i_entry_point::IEntryPoint,
+        gas_price_oracle::GasPriceOracle,
+        i_aggregator::IAggregator,
+        i_entry_point::{IEntryPoint, SenderAddressResult},
         node_interface::NodeInterface,
     },
     UserOperation,
@@ -133,6 +136,21 @@ impl<C: JsonRpcClient + 'static> Provider for EthersProvider<C> {
         )
     }
 
+    async fn get_latest_block_hash_and_number(&self) -> ProviderResult<(H256, U64)> {
+        let latest_block = Middleware::get_block(self, BlockId::Number(BlockNumber::Latest))
+            .await
+            .context("should load block to get hash and number")?
+            .context("block should exist to get latest hash and number")?;
+        Ok((
+            latest_block
+                .hash
+                .context("hash should be present on block")?,
+            latest_block
+                .number
+                .context("number should be present on block")?,
+        ))
+    }
+
     async fn get_base_fee(&self) -> ProviderResult<U256> {
         Ok(Middleware::get_block(self, BlockNumber::Pending)
             .await
@@ -188,6 +206,33 @@ impl<C: JsonRpcClient + 'static> Provider for EthersProvider<C> {
         }
     }
 
+    async fn get_sender_address(
+        self: Arc<Self>,
+        entry_point_address: Address,
+        init_code: Bytes,
+        block_id: BlockId,
+        gas_cap: u64,
+    ) -> ProviderResult<Address> {
+        let entry_point = IEntryPoint::new(entry_point_address, self);
+        let result = entry_point
+            .get_sender_address(init_code)
+            .block(block_id)
+            .gas(gas_cap)
+            .call()
+            .await;
+
+        // getSenderAddress always reverts, returning the address in the revert data
+        match result {
+            Err(ContractError::Revert(revert_data)) => {
+                Ok(SenderAddressResult::decode(&revert_data)
+                    .context("getSenderAddress should revert with the sender address")?
+                    .sender)
+            }
+            Ok(()) => Err(anyhow::anyhow!("getSenderAddress should revert"))?,
+            Err(error) => Err(error).context("should call entry point to get sender address")?,
+        }
+    }
+
     async fn get_code(&self, address: Address, block_hash: Option<H256>) -> ProviderResult<Bytes> {
         Ok(Middleware::get_code(self, address, block_hash.map(|b| b.into())).await?)
     }
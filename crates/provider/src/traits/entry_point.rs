@@ -17,12 +17,15 @@ use ethers::types::{
 #[cfg(feature = "test-utils")]
 use mockall::automock;
 use rundler_types::{
-    contracts::{i_entry_point::ExecutionResult, shared_types::UserOpsPerAggregator},
+    contracts::{
+        i_entry_point::ExecutionResult,
+        shared_types::{DepositInfo, UserOpsPerAggregator},
+    },
     GasFees, UserOperation,
 };
 
 /// Result of an entry point handle ops call
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum HandleOpsOut {
     /// Call succeeded
     Success,
@@ -53,6 +56,12 @@ pub trait EntryPoint: Send + Sync + 'static {
     async fn balance_of(&self, address: Address, block_id: Option<BlockId>)
         -> anyhow::Result<U256>;
 
+    /// Get the deposit and stake info of an address
+    async fn get_deposit_info(&self, address: Address) -> anyhow::Result<DepositInfo>;
+
+    /// Get the next nonce of a sender for the given nonce key
+    async fn get_nonce(&self, address: Address, key: U256) -> anyhow::Result<U256>;
+
     /// Call the entry point contract's `simulateValidation` function
     async fn simulate_validation(
         &self,
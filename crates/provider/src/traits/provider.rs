@@ -18,7 +18,7 @@ use std::{fmt::Debug, sync::Arc};
 use ethers::types::{
     transaction::eip2718::TypedTransaction, Address, Block, BlockId, BlockNumber, Bytes,
     FeeHistory, Filter, GethDebugTracingCallOptions, GethDebugTracingOptions, GethTrace, Log,
-    Transaction, TransactionReceipt, TxHash, H256, U256,
+    Transaction, TransactionReceipt, TxHash, H256, U256, U64,
 };
 #[cfg(feature = "test-utils")]
 use mockall::automock;
@@ -45,6 +45,8 @@ pub enum AggregatorOut {
     SuccessWithInfo(AggregatorSimOut),
     /// Aggregator validation function reverted
     ValidationReverted,
+    /// Aggregator could not be called, e.g. there is no contract deployed at its address
+    Unreachable,
 }
 
 /// Result of a provider method call
@@ -113,6 +115,9 @@ pub trait Provider: Send + Sync + 'static {
     /// Get the latest block hash
     async fn get_latest_block_hash(&self) -> ProviderResult<H256>;
 
+    /// Get the latest block hash along with its number
+    async fn get_latest_block_hash_and_number(&self) -> ProviderResult<(H256, U64)>;
+
     /// Get the base fee per gas of the pending block
     async fn get_base_fee(&self) -> ProviderResult<U256>;
 
@@ -143,6 +148,16 @@ pub trait Provider: Send + Sync + 'static {
         gas_cap: u64,
     ) -> ProviderResult<AggregatorOut>;
 
+    /// Get the sender address that an entry point deploys for the given init code,
+    /// limiting the call to `gas_cap` gas
+    async fn get_sender_address(
+        self: Arc<Self>,
+        entry_point_address: Address,
+        init_code: Bytes,
+        block_id: BlockId,
+        gas_cap: u64,
+    ) -> ProviderResult<Address>;
+
     /// Calculate the L1 portion of the gas for a user operation on Arbitrum
     async fn calc_arbitrum_l1_gas(
         self: Arc<Self>,
@@ -14,13 +14,17 @@
 use async_trait::async_trait;
 use ethers::types::{Address, H256};
 use futures_util::StreamExt;
-use jsonrpsee::{core::RpcResult, proc_macros::rpc, types::error::INTERNAL_ERROR_CODE};
+use jsonrpsee::{
+    core::RpcResult,
+    proc_macros::rpc,
+    types::error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
+};
 use rundler_builder::{BuilderServer, BundlingMode};
-use rundler_pool::PoolServer;
+use rundler_pool::{PoolServer, Reputation};
 
 use crate::{
     error::rpc_err,
-    types::{RpcReputation, RpcUserOperation},
+    types::{RpcDumpMempoolFilter, RpcReputation, RpcUserOperation},
 };
 
 /// Debug API
@@ -30,21 +34,32 @@ pub trait DebugApi {
     #[method(name = "bundler_clearState")]
     async fn bundler_clear_state(&self) -> RpcResult<String>;
 
-    /// Dumps the mempool.
+    /// Dumps the mempool, optionally only the operations matching a filter.
     #[method(name = "bundler_dumpMempool")]
-    async fn bundler_dump_mempool(&self, entry_point: Address) -> RpcResult<Vec<RpcUserOperation>>;
+    async fn bundler_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: Option<RpcDumpMempoolFilter>,
+    ) -> RpcResult<Vec<RpcUserOperation>>;
 
     /// Triggers the builder to send a bundle now
-    ///
-    /// Note that the bundling mode must be set to `Manual` else this will fail.
     #[method(name = "bundler_sendBundleNow")]
     async fn bundler_send_bundle_now(&self) -> RpcResult<H256>;
 
+    /// Triggers the builder to build and send a bundle immediately instead of
+    /// waiting for the next block, in either bundling mode. Returns the bundle
+    /// transaction hash once it has landed.
+    #[method(name = "bundleNow")]
+    async fn bundle_now(&self) -> RpcResult<H256>;
+
     /// Sets the bundling mode.
     #[method(name = "bundler_setBundlingMode")]
     async fn bundler_set_bundling_mode(&self, mode: BundlingMode) -> RpcResult<String>;
 
     /// Sets the reputations of entities on the given entry point.
+    ///
+    /// Every entry is validated before any is applied, so either all of the
+    /// reputations are set or, if any entry is invalid, none are.
     #[method(name = "bundler_setReputation")]
     async fn bundler_set_reputation(
         &self,
@@ -68,45 +83,24 @@ impl<P, B> DebugApi<P, B> {
     }
 }
 
-#[async_trait]
-impl<P, B> DebugApiServer for DebugApi<P, B>
+impl<P, B> DebugApi<P, B>
 where
     P: PoolServer,
     B: BuilderServer,
 {
-    async fn bundler_clear_state(&self) -> RpcResult<String> {
-        let _ = self
-            .pool
-            .debug_clear_state()
-            .await
-            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
-
-        Ok("ok".to_string())
-    }
-
-    async fn bundler_dump_mempool(&self, entry_point: Address) -> RpcResult<Vec<RpcUserOperation>> {
-        Ok(self
-            .pool
-            .debug_dump_mempool(entry_point)
-            .await
-            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?
-            .into_iter()
-            .map(|pop| pop.uo.into())
-            .collect::<Vec<RpcUserOperation>>())
-    }
-
-    async fn bundler_send_bundle_now(&self) -> RpcResult<H256> {
+    async fn send_bundle_now(&self, require_manual: bool) -> RpcResult<H256> {
         let mut new_heads = self
             .pool
             .subscribe_new_heads()
             .await
             .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
 
-        let (tx, block_number) = self
-            .builder
-            .debug_send_bundle_now()
-            .await
-            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
+        let result = if require_manual {
+            self.builder.debug_send_bundle_now().await
+        } else {
+            self.builder.debug_bundle_now().await
+        };
+        let (tx, block_number) = result.map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
 
         // After the bundle is sent, we need to make sure that the mempool
         // has processes the same block that the transaction was mined on.
@@ -127,6 +121,46 @@ where
 
         Ok(tx)
     }
+}
+
+#[async_trait]
+impl<P, B> DebugApiServer for DebugApi<P, B>
+where
+    P: PoolServer,
+    B: BuilderServer,
+{
+    async fn bundler_clear_state(&self) -> RpcResult<String> {
+        let _ = self
+            .pool
+            .debug_clear_state()
+            .await
+            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
+
+        Ok("ok".to_string())
+    }
+
+    async fn bundler_dump_mempool(
+        &self,
+        entry_point: Address,
+        filter: Option<RpcDumpMempoolFilter>,
+    ) -> RpcResult<Vec<RpcUserOperation>> {
+        Ok(self
+            .pool
+            .debug_dump_mempool(entry_point, filter.unwrap_or_default().into())
+            .await
+            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?
+            .into_iter()
+            .map(|pop| pop.uo.into())
+            .collect::<Vec<RpcUserOperation>>())
+    }
+
+    async fn bundler_send_bundle_now(&self) -> RpcResult<H256> {
+        self.send_bundle_now(true).await
+    }
+
+    async fn bundle_now(&self) -> RpcResult<H256> {
+        self.send_bundle_now(false).await
+    }
 
     async fn bundler_set_bundling_mode(&self, mode: BundlingMode) -> RpcResult<String> {
         self.builder
@@ -142,13 +176,26 @@ where
         reputations: Vec<RpcReputation>,
         entry_point: Address,
     ) -> RpcResult<String> {
-        let _ = self
-            .pool
-            .debug_set_reputations(
-                entry_point,
-                reputations.into_iter().map(Into::into).collect(),
-            )
-            .await;
+        let mut valid = Vec::with_capacity(reputations.len());
+        let mut errors = vec![];
+        for (i, rep) in reputations.into_iter().enumerate() {
+            let address = rep.address;
+            match Reputation::try_from(rep) {
+                Ok(rep) => valid.push(rep),
+                Err(e) => errors.push(format!("reputation {i} ({address:?}): {e}")),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(rpc_err(
+                INVALID_PARAMS_CODE,
+                format!("no reputations were set: {}", errors.join("; ")),
+            ));
+        }
+
+        self.pool
+            .debug_set_reputations(entry_point, valid)
+            .await
+            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
 
         Ok("ok".to_string())
     }
@@ -167,3 +214,113 @@ where
             .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+    use futures_util::stream;
+    use rundler_builder::MockBuilderServer;
+    use rundler_pool::{MockPoolServer, NewHead, ReputationStatus};
+
+    use super::*;
+
+    fn rpc_reputation(address: Address, ops_seen: U256) -> RpcReputation {
+        RpcReputation {
+            address,
+            ops_seen,
+            ops_included: U256::zero(),
+            status: ReputationStatus::Ok,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_reputation_applies_all() {
+        let ep = Address::random();
+        let reputations = vec![
+            rpc_reputation(Address::random(), 10.into()),
+            rpc_reputation(Address::random(), 20.into()),
+        ];
+        let expected = reputations.clone();
+
+        let mut pool = MockPoolServer::default();
+        pool.expect_debug_set_reputations()
+            .withf(move |e, reps| {
+                *e == ep
+                    && reps.len() == expected.len()
+                    && reps
+                        .iter()
+                        .zip(&expected)
+                        .all(|(r, e)| r.address == e.address && r.ops_seen == e.ops_seen.as_u64())
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let api = DebugApi::new(pool, MockBuilderServer::default());
+        let res = api.bundler_set_reputation(reputations, ep).await.unwrap();
+        assert_eq!(res, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_set_reputation_applies_none_on_invalid_entry() {
+        let ep = Address::random();
+        let reputations = vec![
+            rpc_reputation(Address::random(), 10.into()),
+            rpc_reputation(Address::zero(), 10.into()),
+            rpc_reputation(Address::random(), 20.into()),
+            rpc_reputation(Address::random(), U256::MAX),
+        ];
+
+        let mut pool = MockPoolServer::default();
+        pool.expect_debug_set_reputations().times(0);
+
+        let api = DebugApi::new(pool, MockBuilderServer::default());
+        let err = api
+            .bundler_set_reputation(reputations, ep)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), INVALID_PARAMS_CODE);
+        assert!(err.message().contains("reputation 1"));
+        assert!(err.message().contains("reputation 3"));
+        assert!(!err.message().contains("reputation 0"));
+        assert!(!err.message().contains("reputation 2"));
+    }
+
+    fn pool_with_head(block_number: u64) -> MockPoolServer {
+        let mut pool = MockPoolServer::default();
+        pool.expect_subscribe_new_heads().returning(move || {
+            Ok(Box::pin(stream::iter(vec![NewHead {
+                block_hash: H256::random(),
+                block_number,
+            }])))
+        });
+        pool
+    }
+
+    #[tokio::test]
+    async fn test_send_bundle_now_requires_manual_mode() {
+        let hash = H256::random();
+        let mut builder = MockBuilderServer::default();
+        builder
+            .expect_debug_send_bundle_now()
+            .times(1)
+            .returning(move || Ok((hash, 1)));
+        builder.expect_debug_bundle_now().times(0);
+
+        let api = DebugApi::new(pool_with_head(1), builder);
+        assert_eq!(api.bundler_send_bundle_now().await.unwrap(), hash);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_now_in_any_mode() {
+        let hash = H256::random();
+        let mut builder = MockBuilderServer::default();
+        builder.expect_debug_send_bundle_now().times(0);
+        builder
+            .expect_debug_bundle_now()
+            .times(1)
+            .returning(move || Ok((hash, 1)));
+
+        let api = DebugApi::new(pool_with_head(1), builder);
+        assert_eq!(api.bundle_now().await.unwrap(), hash);
+    }
+}
@@ -11,8 +11,15 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+use std::future::Future;
+
+use jsonrpsee::types::{ErrorObject, ErrorObjectOwned, Id};
 use serde::Serialize;
+use serde_json::{json, Value};
+
+tokio::task_local! {
+    static REQUEST_ID: Value;
+}
 
 pub(crate) fn rpc_err(code: i32, msg: impl Into<String>) -> ErrorObjectOwned {
     create_rpc_err(code, msg, None::<()>)
@@ -33,3 +40,125 @@ fn create_rpc_err<S: Serialize>(
 ) -> ErrorObjectOwned {
     ErrorObject::owned(code, msg.into(), data)
 }
+
+/// Runs `f`, the handler of the request with the JSON-RPC id `id`, so that the
+/// errors it returns are given that id by [`with_request_id`].
+pub(crate) async fn with_request_id_scope<F: Future>(id: &Id<'_>, f: F) -> F::Output {
+    let id = serde_json::to_value(id).unwrap_or(Value::Null);
+    REQUEST_ID.scope(id, f).await
+}
+
+/// Attaches the JSON-RPC id of the request being served to the error's data and
+/// logs the failure with the same id, so that client-side errors can be
+/// correlated with server logs. Outside of [`with_request_id_scope`], the error
+/// is only logged.
+///
+/// Object data gains a `requestId` field, any other data is nested under `data`.
+pub(crate) fn with_request_id(error: ErrorObjectOwned) -> ErrorObjectOwned {
+    let Ok(request_id) = REQUEST_ID.try_with(Value::clone) else {
+        tracing::debug!(
+            "rpc request failed with code {}: {}",
+            error.code(),
+            error.message()
+        );
+        return error;
+    };
+    tracing::debug!(
+        "rpc request {request_id} failed with code {}: {}",
+        error.code(),
+        error.message()
+    );
+
+    let mut data = match error
+        .data()
+        .and_then(|data| serde_json::from_str::<Value>(data.get()).ok())
+    {
+        Some(Value::Object(data)) => Value::Object(data),
+        Some(Value::Null) | None => json!({}),
+        Some(data) => json!({ "data": data }),
+    };
+    data["requestId"] = Value::String(request_id);
+
+    create_rpc_err(error.code(), error.message(), Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn error_data(error: &ErrorObjectOwned) -> Value {
+        serde_json::from_str(error.data().unwrap().get()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_with_request_id() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let error = with_request_id_scope(&Id::Number(42), async {
+            with_request_id(rpc_err(-32602, "invalid params"))
+        })
+        .await;
+        assert_eq!(error_data(&error)["requestId"], 42);
+        assert_eq!(error.code(), -32602);
+        assert_eq!(error.message(), "invalid params");
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("rpc request 42 failed"));
+        assert!(logs.contains("invalid params"));
+    }
+
+    #[tokio::test]
+    async fn test_with_request_id_preserves_data() {
+        let id = Id::Str("request-1".into());
+        let error = with_request_id_scope(&id, async {
+            with_request_id(rpc_err_with_data(
+                -32501,
+                "paymaster rejected",
+                json!({ "paymaster": "0x01" }),
+            ))
+        })
+        .await;
+        let data = error_data(&error);
+        assert_eq!(data["paymaster"], "0x01");
+        assert_eq!(data["requestId"], "request-1");
+
+        let error = with_request_id_scope(&id, async {
+            with_request_id(rpc_err_with_data(-32602, "bad", vec![1, 2]))
+        })
+        .await;
+        let data = error_data(&error);
+        assert_eq!(data["data"], json!([1, 2]));
+        assert_eq!(data["requestId"], "request-1");
+    }
+
+    #[test]
+    fn test_with_request_id_outside_request() {
+        let error = with_request_id(rpc_err_with_data(-32602, "bad", vec![1, 2]));
+        assert_eq!(error_data(&error), json!([1, 2]));
+    }
+}
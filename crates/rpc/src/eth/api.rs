@@ -12,57 +12,127 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::Context;
 use ethers::{
-    abi::{AbiDecode, RawLog},
+    abi::{AbiDecode, RawLog, Selector},
+    contract::EthCall,
     prelude::EthEvent,
     types::{
-        Address, Bytes, Filter, GethDebugBuiltInTracerType, GethDebugTracerType,
-        GethDebugTracingOptions, GethTrace, GethTraceFrame, Log, TransactionReceipt, H256, U256,
-        U64,
+        transaction::eip2718::TypedTransaction, Address, BlockId, Bytes, Filter,
+        GethDebugBuiltInTracerType, GethDebugTracerType, GethDebugTracingOptions, GethTrace,
+        GethTraceFrame, Log, Transaction, TransactionReceipt, H256, U256, U64,
     },
     utils::to_checksum,
 };
-use rundler_pool::PoolServer;
-use rundler_provider::{EntryPoint, Provider};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use rundler_pool::{check_op_metadata, AddOpOptions, PoolServer, ValidBlockRange};
+use rundler_provider::{EntryPoint, Provider, ProviderError};
 use rundler_sim::{
-    EstimationSettings, GasEstimate, GasEstimationError, GasEstimator, GasEstimatorImpl,
-    UserOperationOptionalGas,
+    EstimationSettings, GasEstimate, GasEstimateRange, GasEstimationError, GasEstimator,
+    GasEstimatorImpl, UserOperationOptionalGas,
 };
 use rundler_types::{
     contracts::i_entry_point::{
-        IEntryPointCalls, UserOperationEventFilter, UserOperationRevertReasonFilter,
+        BeforeExecutionFilter, FailedOp, HandleAggregatedOpsCall, HandleOpsCall, IEntryPointCalls,
+        SignatureAggregatorChangedFilter, UserOperationEventFilter,
+        UserOperationRevertReasonFilter,
     },
     UserOperation,
 };
-use rundler_utils::{eth::log_to_raw_log, log::LogOnError};
-use tracing::Level;
+use rundler_utils::{
+    eth::{log_to_raw_log, parse_revert_message},
+    log::LogOnError,
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use url::Url;
+
+use super::{
+    cache::UserOperationCache,
+    error::{rejection_violation, EthResult, EthRpcError},
+    indexer::IndexerClient,
+};
+use crate::types::{
+    RichUserOperation, RpcDepositInfo, RpcPaymasterBalanceStatus, RpcSimulateValidationResult,
+    RpcUserOperation, UserOperationReceipt,
+};
 
-use super::error::{EthResult, EthRpcError};
-use crate::types::{RichUserOperation, RpcUserOperation, UserOperationReceipt};
+/// Maximum number of hashes accepted by a single `eth_getUserOperationReceipts` call
+const MAX_USER_OPERATION_RECEIPTS: usize = 100;
+
+/// Maximum number of provider requests in flight while fetching receipts in bulk
+const MAX_CONCURRENT_RECEIPT_REQUESTS: usize = 10;
+
+/// Maximum number of nested bundle wrapper calls searched for entry point calls
+const MAX_BUNDLE_WRAPPER_DEPTH: usize = 4;
 
 /// Settings for the `eth_` API
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Settings {
     /// The number of blocks to look back for user operation events
     pub user_operation_event_block_distance: Option<u64>,
+    /// The maximum number of entry point addresses to include in a single log query
+    pub user_operation_event_address_batch_size: Option<usize>,
+    /// The maximum number of blocks to include in a single log query
+    pub user_operation_event_block_page_size: Option<u64>,
+    /// Whether to search blocks older than the block distance when a user
+    /// operation event is not found within it. The search is split into pages of
+    /// `user_operation_event_block_page_size` blocks, which should be set with it.
+    pub user_operation_event_historical_fallback: bool,
+    /// The maximum number of blocks older than the block distance that the
+    /// historical fallback searches, or all blocks back to genesis if unset
+    pub user_operation_event_historical_max_depth: Option<u64>,
+    /// URL of an external service that indexes user operations by hash. If set,
+    /// user operation events are found through the transaction it returns instead
+    /// of by searching logs, and the other `user_operation_event` settings are unused.
+    pub user_operation_indexer_url: Option<Url>,
+    /// The maximum number of `eth_getUserOperationByHash` results to cache, 0 disables caching
+    pub user_operation_cache_size: usize,
+    /// How long to cache a user operation that was not found, as it may be mined at any time.
+    /// Found operations are cached until evicted.
+    pub user_operation_cache_pending_ttl: Duration,
+    /// Function selectors of contracts, such as multicalls, known to wrap calls to the
+    /// entry point's `handleOps`. The calldata of transactions calling these is searched
+    /// for the wrapped `handleOps` calldata when looking up a user operation by hash.
+    pub bundle_wrapper_selectors: HashSet<Selector>,
+    /// The maximum number of operations simulated or estimated at once. Further
+    /// requests wait for one to finish.
+    pub max_concurrent_simulations: usize,
+    /// How long a request waits to start simulating before failing as unavailable
+    pub simulation_wait_timeout: Duration,
+    /// The maximum time `eth_sendUserOperation` spends validating an operation and
+    /// adding it to the pool before failing with a timeout
+    pub send_user_operation_deadline: Duration,
 }
 
-impl Settings {
-    /// Create new settings for the `eth_` API
-    pub fn new(block_distance: Option<u64>) -> Self {
+impl Default for Settings {
+    /// Defaults match the CLI's, except that user operation caching is disabled
+    fn default() -> Self {
         Self {
-            user_operation_event_block_distance: block_distance,
+            user_operation_event_block_distance: None,
+            user_operation_event_address_batch_size: None,
+            user_operation_event_block_page_size: None,
+            user_operation_event_historical_fallback: false,
+            user_operation_event_historical_max_depth: None,
+            user_operation_indexer_url: None,
+            user_operation_cache_size: 0,
+            user_operation_cache_pending_ttl: Duration::ZERO,
+            bundle_wrapper_selectors: HashSet::new(),
+            max_concurrent_simulations: 64,
+            simulation_wait_timeout: Duration::from_secs(5),
+            send_user_operation_deadline: Duration::from_secs(10),
         }
     }
 }
 
 #[derive(Debug)]
 struct EntryPointContext<P, E> {
+    entry_point: E,
+    max_verification_gas: u64,
     gas_estimator: GasEstimatorImpl<P, E>,
 }
 
@@ -80,9 +150,14 @@ where
     where
         E: Clone, // Add Clone trait bound for E
     {
+        let max_verification_gas = estimation_settings.max_verification_gas;
         let gas_estimator =
             GasEstimatorImpl::new(chain_id, provider, entry_point.clone(), estimation_settings);
-        Self { gas_estimator }
+        Self {
+            entry_point,
+            max_verification_gas,
+            gas_estimator,
+        }
     }
 }
 
@@ -93,6 +168,9 @@ pub(crate) struct EthApi<P, E, PS> {
     chain_id: u64,
     pool: PS,
     settings: Settings,
+    user_operation_cache: UserOperationCache,
+    indexer: Option<IndexerClient>,
+    simulation_permits: Semaphore,
 }
 
 impl<P, E, PS> EthApi<P, E, PS>
@@ -128,6 +206,15 @@ where
             .collect();
 
         Self {
+            user_operation_cache: UserOperationCache::new(
+                settings.user_operation_cache_size,
+                settings.user_operation_cache_pending_ttl,
+            ),
+            indexer: settings
+                .user_operation_indexer_url
+                .clone()
+                .map(IndexerClient::new),
+            simulation_permits: Semaphore::new(settings.max_concurrent_simulations),
             settings,
             contexts_by_entry_point,
             provider,
@@ -140,23 +227,101 @@ where
         &self,
         op: RpcUserOperation,
         entry_point: Address,
+        options: AddOpOptions,
     ) -> EthResult<H256> {
-        if !self.contexts_by_entry_point.contains_key(&entry_point) {
+        check_op_metadata(&options.metadata)?;
+        check_valid_block_range(&options.valid_block_range)?;
+        if let Some(max_block) = options.valid_block_range.max_block {
+            // A bundle can land in the next block at the earliest
+            let block_number = self.provider.get_block_number().await?;
+            if max_block <= block_number {
+                return Err(EthRpcError::InvalidParams(format!(
+                    "block range max block {max_block} has passed, the next block is {}",
+                    block_number + 1
+                )));
+            }
+        }
+        let op: UserOperation = op.into();
+        let op_hash = op.op_hash(entry_point, self.chain_id);
+        // The pool keeps handling an add that has timed out here, so the op may
+        // still enter the pool. Its hash is returned with the error so that clients
+        // can look it up, and a retry of the same op with the same options returns
+        // that hash once the op is in the pool.
+        tokio::time::timeout(
+            self.settings.send_user_operation_deadline,
+            self.add_user_operation(op, op_hash, entry_point, options),
+        )
+        .await
+        .map_err(|_| {
+            tracing::debug!(
+                op_hash = ?op_hash,
+                entry_point = ?entry_point,
+                "timed out adding op to the mempool"
+            );
+            EthRpcError::Timeout(op_hash)
+        })?
+    }
+
+    async fn add_user_operation(
+        &self,
+        op: UserOperation,
+        op_hash: H256,
+        entry_point: Address,
+        options: AddOpOptions,
+    ) -> EthResult<H256> {
+        let context = self
+            .contexts_by_entry_point
+            .get(&entry_point)
+            .ok_or_else(|| {
+                EthRpcError::InvalidParams(
+                    "supplied entry point addr is not a known entry point".to_string(),
+                )
+            })?;
+        // The op is added to the pool for the requested entry point, so a
+        // misconfigured context must not let it be submitted to another pool
+        if context.entry_point.address() != entry_point {
+            return Err(EthRpcError::Internal(anyhow::anyhow!(
+                "entry point context for {entry_point:?} is configured with entry point {:?}",
+                context.entry_point.address()
+            )));
+        }
+        // Clients often retry sends on flaky networks, so an op that is already in
+        // the pool is acknowledged without simulating it again. A send with other
+        // options would not have them applied, so it is rejected instead.
+        if let Some(pooled_options) = self.pool.get_op_options(entry_point, op_hash).await? {
+            if pooled_options == options {
+                return Ok(op_hash);
+            }
             return Err(EthRpcError::InvalidParams(
-                "supplied entry point addr is not a known entry point".to_string(),
+                "operation is already in the mempool with a different expectedBlockHash, block range or metadata".to_string(),
             ));
         }
+        let sender = op.sender;
+        // The pool simulates the op before adding it
+        let _permit = self.acquire_simulation_permit().await?;
         self.pool
-            .add_op(entry_point, op.into())
+            .add_op(entry_point, op, options)
             .await
-            .map_err(EthRpcError::from)
-            .log_on_error_level(Level::DEBUG, "failed to add op to the mempool")
+            .map_err(|error| {
+                let violation = rejection_violation(&error);
+                let error = EthRpcError::from(error);
+                tracing::debug!(
+                    op_hash = ?op_hash,
+                    sender = ?sender,
+                    entry_point = ?entry_point,
+                    category = error.category(),
+                    violation,
+                    "failed to add op to the mempool: {error:?}"
+                );
+                error
+            })
     }
 
     pub(crate) async fn estimate_user_operation_gas(
         &self,
         op: UserOperationOptionalGas,
         entry_point: Address,
+        trace_on_failure: bool,
     ) -> EthResult<GasEstimate> {
         let context = self
             .contexts_by_entry_point
@@ -167,22 +332,135 @@ where
                 )
             })?;
 
-        let result = context.gas_estimator.estimate_op_gas(op).await;
-        match result {
-            Ok(estimate) => Ok(estimate),
+        let result = {
+            let _permit = self.acquire_simulation_permit().await?;
+            context.gas_estimator.estimate_op_gas(op.clone()).await
+        };
+        self.handle_estimation_result(context, op, result, trace_on_failure)
+            .await
+    }
+
+    pub(crate) async fn estimate_user_operation_gas_range(
+        &self,
+        op: UserOperationOptionalGas,
+        entry_point: Address,
+        trace_on_failure: bool,
+    ) -> EthResult<GasEstimateRange> {
+        let context = self
+            .contexts_by_entry_point
+            .get(&entry_point)
+            .ok_or_else(|| {
+                EthRpcError::InvalidParams(
+                    "supplied entry_point address is not a known entry point".to_string(),
+                )
+            })?;
+
+        let result = {
+            let _permit = self.acquire_simulation_permit().await?;
+            context
+                .gas_estimator
+                .estimate_op_gas_range(op.clone())
+                .await
+        };
+        self.handle_estimation_result(context, op, result, trace_on_failure)
+            .await
+    }
+
+    /// Converts a gas estimation failure into an RPC error, tracing the failed
+    /// operation's simulation if requested.
+    async fn handle_estimation_result<T>(
+        &self,
+        context: &EntryPointContext<P, E>,
+        op: UserOperationOptionalGas,
+        result: Result<T, GasEstimationError>,
+        trace_on_failure: bool,
+    ) -> EthResult<T> {
+        let error = match result {
+            Ok(estimate) => return Ok(estimate),
             Err(GasEstimationError::RevertInValidation(message)) => {
-                Err(EthRpcError::EntryPointValidationRejected(message))?
+                EthRpcError::EntryPointValidationRejected(message)
             }
             Err(GasEstimationError::RevertInCallWithMessage(message)) => {
-                Err(EthRpcError::ExecutionReverted(message))?
+                EthRpcError::ExecutionReverted(message)
             }
             Err(error @ GasEstimationError::RevertInCallWithBytes(_)) => {
-                Err(EthRpcError::ExecutionReverted(error.to_string()))?
+                EthRpcError::ExecutionReverted(error.to_string())
+            }
+            Err(GasEstimationError::RevertInPostOp(message)) => {
+                EthRpcError::PostOpReverted(message)
+            }
+            Err(error @ GasEstimationError::RevertInAccountDeployment(_)) => {
+                EthRpcError::EntryPointValidationRejected(error.to_string())
             }
-            Err(GasEstimationError::Other(error)) => Err(error)?,
+            Err(GasEstimationError::Other(error)) => return Err(error.into()),
+        };
+        if !trace_on_failure {
+            return Err(error);
+        }
+
+        // The trace is best effort, so report the estimation error either way
+        let trace = {
+            let _permit = self.acquire_simulation_permit().await?;
+            context.gas_estimator.trace_op_simulation(op).await
+        };
+        match trace {
+            Ok(GethTrace::Known(GethTraceFrame::CallTracer(frame))) => Err(
+                EthRpcError::EstimationFailedWithTrace(Box::new(error), Box::new(frame.into())),
+            ),
+            Ok(trace) => {
+                tracing::warn!("unexpected trace of failed estimation: {trace:?}");
+                Err(error)
+            }
+            Err(trace_error) => {
+                tracing::warn!("failed to trace failed estimation: {trace_error:?}");
+                Err(error)
+            }
+        }
+    }
+
+    pub(crate) async fn simulate_validation(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> EthResult<RpcSimulateValidationResult> {
+        let context = self
+            .contexts_by_entry_point
+            .get(&entry_point)
+            .ok_or_else(|| {
+                EthRpcError::InvalidParams(
+                    "supplied entry_point address is not a known entry point".to_string(),
+                )
+            })?;
+
+        let _permit = self.acquire_simulation_permit().await?;
+        let result = rundler_sim::simulate_validation_only(
+            &*self.provider,
+            &context.entry_point,
+            op.into(),
+            context.max_verification_gas,
+        )
+        .await?;
+        match result {
+            Ok(result) => Ok(result.into()),
+            Err(reason) => Err(EthRpcError::EntryPointValidationRejected(reason)),
         }
     }
 
+    // Waits for a permit to simulate, so that bursts of requests queue rather than
+    // flood the provider
+    async fn acquire_simulation_permit(&self) -> EthResult<SemaphorePermit<'_>> {
+        tokio::time::timeout(
+            self.settings.simulation_wait_timeout,
+            self.simulation_permits.acquire(),
+        )
+        .await
+        .map_err(|_| {
+            EthRpcError::ServiceUnavailable("too many concurrent simulations".to_string())
+        })?
+        .context("simulation semaphore should not be closed")
+        .map_err(EthRpcError::from)
+    }
+
     pub(crate) async fn get_user_operation_by_hash(
         &self,
         hash: H256,
@@ -193,6 +471,34 @@ where
             ));
         }
 
+        if let Some(op) = self.user_operation_cache.get(hash) {
+            if self.is_cached_result_canonical(&op).await? {
+                return Ok(op);
+            }
+        }
+        let op = self.fetch_user_operation_by_hash(hash).await?;
+        self.user_operation_cache.insert(hash, op.clone());
+        Ok(op)
+    }
+
+    // A cached operation is only returned while the block it was found in is still
+    // canonical, as a reorg may have dropped or moved its transaction
+    async fn is_cached_result_canonical(&self, op: &Option<RichUserOperation>) -> EthResult<bool> {
+        let Some(op) = op else {
+            return Ok(true);
+        };
+        let block = self
+            .provider
+            .get_block(op.block_number.as_u64())
+            .await
+            .context("should load block of cached user operation")?;
+        Ok(block.and_then(|block| block.hash) == Some(op.block_hash))
+    }
+
+    async fn fetch_user_operation_by_hash(
+        &self,
+        hash: H256,
+    ) -> EthResult<Option<RichUserOperation>> {
         // Get event associated with hash (need to check all entry point addresses associated with this API)
         let event = self
             .get_user_operation_event_by_hash(hash)
@@ -213,20 +519,35 @@ where
             .context("should have fetched tx from provider")?
             .context("should have found tx")?;
 
-        // We should return null if the tx isn't included in the block yet
+        // We should return null if the tx isn't included in the block yet, or if it
+        // was reorged into a different block than the one the event was found in
         if tx.block_hash.is_none() && tx.block_number.is_none() {
             return Ok(None);
         }
+        if tx.block_hash != event.block_hash {
+            return Ok(None);
+        }
         let to = tx
             .to
             .context("tx.to should be present on transaction containing user operation event")?;
 
-        // Find first op matching the hash
-        let user_operation = if self.contexts_by_entry_point.contains_key(&to) {
-            self.get_user_operations_from_tx_data(tx.input)
-                .into_iter()
-                .find(|op| op.op_hash(to, self.chain_id) == hash)
-                .context("matching user operation should be found in tx data")?
+        // Find the op matching the hash
+        let user_operation_event = self.decode_user_operation_event(event.clone())?;
+        let (user_operation, aggregator) = if self.contexts_by_entry_point.contains_key(&to) {
+            self.find_user_operation_by_hash(
+                self.get_user_operations_from_tx_data(tx.input)?,
+                to,
+                hash,
+                &user_operation_event,
+            )?
+            .context("matching user operation should be found in tx data")?
+        } else if let Some(found) = self.find_user_operation_by_hash(
+            self.get_wrapped_user_operations(&tx.input),
+            event.address,
+            hash,
+            &user_operation_event,
+        )? {
+            found
         } else {
             self.trace_find_user_operation(transaction_hash, hash)
                 .await
@@ -243,6 +564,7 @@ where
                 .unwrap_or_default(),
             block_hash: tx.block_hash.unwrap_or_default(),
             transaction_hash,
+            aggregator: aggregator.map(Into::into),
         }))
     }
 
@@ -256,6 +578,24 @@ where
             ));
         }
 
+        // The indexer knows the transaction even if it reverted and emitted no
+        // events, so only it can find operations in a reverted bundle
+        if let Some(indexer) = &self.indexer {
+            let Some(tx_hash) = indexer.get_transaction_hash(hash).await? else {
+                return Ok(None);
+            };
+            let tx_receipt = self.fetch_transaction_receipt(tx_hash).await?;
+            if tx_receipt.status == Some(U64::zero()) {
+                return self
+                    .build_reverted_user_operation_receipt(hash, tx_receipt)
+                    .await;
+            }
+            let Some(log) = self.find_user_operation_event_in_receipt(hash, &tx_receipt)? else {
+                return Ok(None);
+            };
+            return self.build_user_operation_receipt(hash, log, tx_receipt);
+        }
+
         // Get event associated with hash (need to check all entry point addresses associated with this API)
         let log = self
             .get_user_operation_event_by_hash(hash)
@@ -263,21 +603,93 @@ where
             .context("should have fetched user ops by hash")?;
 
         let Some(log) = log else { return Ok(None) };
-        let entry_point = log.address;
 
         // If the event is found, get the TX receipt
         let tx_hash = log.transaction_hash.context("tx_hash should be present")?;
-        let tx_receipt = self
+        let tx_receipt = self.fetch_transaction_receipt(tx_hash).await?;
+        self.build_user_operation_receipt(hash, log, tx_receipt)
+    }
+
+    /// Returns the receipts of many user operations, in the order of the given
+    /// hashes. Operations mined in the same transaction share a single receipt fetch.
+    pub(crate) async fn get_user_operation_receipts(
+        &self,
+        hashes: Vec<H256>,
+    ) -> EthResult<Vec<Option<UserOperationReceipt>>> {
+        if hashes.len() > MAX_USER_OPERATION_RECEIPTS {
+            return Err(EthRpcError::InvalidParams(format!(
+                "at most {MAX_USER_OPERATION_RECEIPTS} userOpHashes may be requested at once"
+            )));
+        }
+        if hashes.contains(&H256::zero()) {
+            return Err(EthRpcError::InvalidParams(
+                "Missing/invalid userOpHash".to_string(),
+            ));
+        }
+
+        let logs: Vec<Option<Log>> = stream::iter(hashes.iter().copied())
+            .map(|hash| self.get_user_operation_event_by_hash(hash))
+            .buffered(MAX_CONCURRENT_RECEIPT_REQUESTS)
+            .try_collect()
+            .await
+            .context("should have fetched user ops by hash")?;
+
+        let tx_hashes = logs
+            .iter()
+            .flatten()
+            .map(|log| log.transaction_hash.context("tx_hash should be present"))
+            .collect::<anyhow::Result<HashSet<_>>>()?;
+        let tx_receipts: HashMap<H256, TransactionReceipt> = stream::iter(tx_hashes)
+            .map(|tx_hash| async move {
+                let tx_receipt = self.fetch_transaction_receipt(tx_hash).await?;
+                Ok::<_, EthRpcError>((tx_hash, tx_receipt))
+            })
+            .buffer_unordered(MAX_CONCURRENT_RECEIPT_REQUESTS)
+            .try_collect()
+            .await?;
+
+        hashes
+            .into_iter()
+            .zip(logs)
+            .map(|(hash, log)| {
+                let Some(log) = log else { return Ok(None) };
+                let tx_receipt = log
+                    .transaction_hash
+                    .and_then(|tx_hash| tx_receipts.get(&tx_hash))
+                    .context("should have fetched tx receipt")?
+                    .clone();
+                self.build_user_operation_receipt(hash, log, tx_receipt)
+            })
+            .collect()
+    }
+
+    async fn fetch_transaction_receipt(&self, tx_hash: H256) -> EthResult<TransactionReceipt> {
+        Ok(self
             .provider
             .get_transaction_receipt(tx_hash)
             .await
             .context("should have fetched tx receipt")?
-            .context("Failed to fetch tx receipt")?;
+            .context("Failed to fetch tx receipt")?)
+    }
+
+    /// Builds the receipt of a user operation from its event and the receipt of
+    /// the transaction that emitted it.
+    fn build_user_operation_receipt(
+        &self,
+        hash: H256,
+        log: Log,
+        tx_receipt: TransactionReceipt,
+    ) -> EthResult<Option<UserOperationReceipt>> {
+        let entry_point = log.address;
 
-        // Return null if the tx isn't included in the block yet
+        // Return null if the tx isn't included in the block yet, or if it was
+        // reorged into a different block than the one the event was found in
         if tx_receipt.block_hash.is_none() && tx_receipt.block_number.is_none() {
             return Ok(None);
         }
+        if tx_receipt.block_hash != log.block_hash {
+            return Ok(None);
+        }
 
         // Filter receipt logs to match just those belonging to the user op
         let filtered_logs =
@@ -304,6 +716,11 @@ where
             paymaster: uo_event.paymaster.into(),
             actual_gas_cost: uo_event.actual_gas_cost,
             actual_gas_used: uo_event.actual_gas_used,
+            effective_gas_price: user_operation_effective_gas_price(
+                uo_event.actual_gas_cost,
+                uo_event.actual_gas_used,
+                &tx_receipt,
+            ),
             success: uo_event.success,
             logs: filtered_logs,
             receipt: tx_receipt,
@@ -311,6 +728,151 @@ where
         }))
     }
 
+    /// Builds a failed receipt for a user operation whose bundle transaction
+    /// reverted. A reverted transaction emits no events, so the operation is found
+    /// in the transaction's calldata instead. Only used with an indexer, as the
+    /// transaction can't be found through the operation's event otherwise.
+    async fn build_reverted_user_operation_receipt(
+        &self,
+        hash: H256,
+        tx_receipt: TransactionReceipt,
+    ) -> EthResult<Option<UserOperationReceipt>> {
+        let Some(block_number) = tx_receipt.block_number else {
+            return Ok(None);
+        };
+        let tx = self
+            .provider
+            .get_transaction(tx_receipt.transaction_hash)
+            .await
+            .context("should have fetched tx from provider")?
+            .context("should have found tx")?;
+        let to = tx
+            .to
+            .context("tx.to should be present on bundle transaction")?;
+
+        // Ops in a direct entry point call are hashed with that entry point, while
+        // ops in a wrapped call may belong to any known entry point
+        let (ops, entry_points) = if self.contexts_by_entry_point.contains_key(&to) {
+            (
+                self.get_user_operations_from_tx_data(tx.input.clone())?,
+                vec![to],
+            )
+        } else {
+            (
+                self.get_wrapped_user_operations(&tx.input),
+                self.contexts_by_entry_point.keys().copied().collect(),
+            )
+        };
+        let Some((entry_point, user_operation)) = entry_points.into_iter().find_map(|ep| {
+            ops.iter()
+                .find(|(op, _)| op.op_hash(ep, self.chain_id) == hash)
+                .map(|(op, _)| (ep, op))
+        }) else {
+            return Ok(None);
+        };
+
+        let reason = self.get_transaction_revert_reason(&tx, block_number).await;
+
+        Ok(Some(UserOperationReceipt {
+            user_op_hash: hash,
+            entry_point: entry_point.into(),
+            sender: user_operation.sender.into(),
+            nonce: user_operation.nonce,
+            paymaster: user_operation.paymaster().unwrap_or_default().into(),
+            actual_gas_cost: U256::zero(),
+            actual_gas_used: U256::zero(),
+            effective_gas_price: tx_receipt.effective_gas_price.unwrap_or_default(),
+            success: false,
+            logs: vec![],
+            receipt: tx_receipt,
+            reason,
+        }))
+    }
+
+    /// Recovers the revert reason of a reverted transaction by replaying it on top
+    /// of its parent block. The replay doesn't include the transactions before it
+    /// in the same block, so the reason is a best effort.
+    async fn get_transaction_revert_reason(&self, tx: &Transaction, block_number: U64) -> String {
+        let block = BlockId::from(block_number.saturating_sub(U64::one()));
+        let revert_data = match self
+            .provider
+            .call(&TypedTransaction::from(tx), Some(block))
+            .await
+        {
+            Err(ProviderError::JsonRpcError(error)) => error.as_revert_data(),
+            _ => None,
+        };
+        let Some(revert_data) = revert_data else {
+            return "bundle transaction reverted".to_owned();
+        };
+        if let Ok(failed_op) = FailedOp::decode(&revert_data) {
+            return failed_op.reason;
+        }
+        parse_revert_message(&revert_data)
+            .unwrap_or_else(|| format!("bundle transaction reverted with data {revert_data}"))
+    }
+
+    pub(crate) async fn get_pending_user_operation_nonce(
+        &self,
+        sender: Address,
+        entry_point: Address,
+    ) -> EthResult<U256> {
+        if !self.contexts_by_entry_point.contains_key(&entry_point) {
+            return Err(EthRpcError::InvalidParams(
+                "supplied entry point addr is not a known entry point".to_string(),
+            ));
+        }
+        Ok(self.pool.get_next_nonce(entry_point, sender).await?)
+    }
+
+    pub(crate) async fn get_deposit_info(
+        &self,
+        address: Address,
+        entry_point: Address,
+    ) -> EthResult<RpcDepositInfo> {
+        let context = self
+            .contexts_by_entry_point
+            .get(&entry_point)
+            .ok_or_else(|| {
+                EthRpcError::InvalidParams(
+                    "supplied entry point addr is not a known entry point".to_string(),
+                )
+            })?;
+        Ok(context.entry_point.get_deposit_info(address).await?.into())
+    }
+
+    pub(crate) async fn get_paymaster_balance_status(
+        &self,
+        paymaster: Address,
+        entry_point: Address,
+    ) -> EthResult<RpcPaymasterBalanceStatus> {
+        let (deposit_info, pending_max_cost) =
+            tokio::try_join!(self.get_deposit_info(paymaster, entry_point), async {
+                self.pool
+                    .get_paymaster_max_cost(entry_point, paymaster)
+                    .await
+                    .map_err(EthRpcError::from)
+            },)?;
+        Ok(RpcPaymasterBalanceStatus {
+            deposit_info,
+            pending_max_cost,
+            shortfall: pending_max_cost.saturating_sub(deposit_info.deposit),
+        })
+    }
+
+    pub(crate) async fn get_user_operation_hash(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> EthResult<H256> {
+        if !self.contexts_by_entry_point.contains_key(&entry_point) {
+            return Err(EthRpcError::InvalidParams(
+                "supplied entry point addr is not a known entry point".to_string(),
+            ));
+        }
+        Ok(UserOperation::from(op).op_hash(entry_point, self.chain_id))
+    }
+
     pub(crate) async fn supported_entry_points(&self) -> EthResult<Vec<String>> {
         Ok(self
             .contexts_by_entry_point
@@ -324,6 +886,13 @@ where
     }
 
     async fn get_user_operation_event_by_hash(&self, hash: H256) -> EthResult<Option<Log>> {
+        if let Some(indexer) = &self.indexer {
+            return match indexer.get_transaction_hash(hash).await? {
+                Some(tx_hash) => self.get_user_operation_event_in_tx(hash, tx_hash).await,
+                None => Ok(None),
+            };
+        }
+
         let to_block = self.provider.get_block_number().await?;
 
         let from_block = match self.settings.user_operation_event_block_distance {
@@ -331,49 +900,224 @@ where
             None => 0,
         };
 
+        let event = self
+            .find_user_operation_event(hash, from_block, to_block)
+            .await?;
+        if event.is_some() || !self.settings.user_operation_event_historical_fallback {
+            return Ok(event);
+        }
+
+        let historical_from_block = match self.settings.user_operation_event_historical_max_depth {
+            Some(depth) => from_block.saturating_sub(depth),
+            None => 0,
+        };
+        if historical_from_block == from_block {
+            return Ok(None);
+        }
+        self.find_user_operation_event(hash, historical_from_block, from_block - 1)
+            .await
+    }
+
+    /// Searches for the user operation event in the given block range, newest
+    /// blocks first, splitting the range into pages to stay under provider limits.
+    async fn find_user_operation_event(
+        &self,
+        hash: H256,
+        from_block: u64,
+        to_block: u64,
+    ) -> EthResult<Option<Log>> {
+        let page_size = match self.settings.user_operation_event_block_page_size {
+            Some(page_size) => page_size.max(1),
+            None => to_block - from_block + 1,
+        };
+
+        let mut page_to_block = to_block;
+        loop {
+            let page_from_block = page_to_block.saturating_sub(page_size - 1).max(from_block);
+            let event = self
+                .get_user_operation_event_in_range(hash, page_from_block, page_to_block)
+                .await?;
+            if event.is_some() || page_from_block == from_block {
+                return Ok(event);
+            }
+            page_to_block = page_from_block - 1;
+        }
+    }
+
+    async fn get_user_operation_event_in_range(
+        &self,
+        hash: H256,
+        from_block: u64,
+        to_block: u64,
+    ) -> EthResult<Option<Log>> {
         let filter = Filter::new()
-            .address::<Vec<Address>>(
-                self.contexts_by_entry_point
-                    .iter()
-                    .map(|ep| *ep.0)
-                    .collect(),
-            )
             .event(&UserOperationEventFilter::abi_signature())
             .from_block(from_block)
             .to_block(to_block)
             .topic1(hash);
 
-        let logs = self.provider.get_logs(&filter).await?;
-        Ok(logs.into_iter().next())
+        // Some providers cap the number of addresses in a single filter, so split
+        // the entry points into batches and merge the results
+        let entry_points = self
+            .contexts_by_entry_point
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        let batch_size = self
+            .settings
+            .user_operation_event_address_batch_size
+            .unwrap_or(entry_points.len())
+            .max(1);
+
+        let mut logs = vec![];
+        for batch in entry_points.chunks(batch_size) {
+            let filter = filter.clone().address(batch.to_vec());
+            logs.extend(self.provider.get_logs(&filter).await?);
+        }
+
+        // Logs from blocks that have since been reorged out must not be reported,
+        // the operation is considered pending again in that case
+        let mut events = vec![];
+        for log in logs.into_iter().filter(|log| log.removed != Some(true)) {
+            if self.is_log_canonical(&log).await? {
+                events.push(log);
+            }
+        }
+        if events.len() <= 1 {
+            return Ok(events.pop());
+        }
+
+        let mut events_with_tx_to = Vec::with_capacity(events.len());
+        for log in events {
+            let tx_to = match log.transaction_hash {
+                Some(tx_hash) => self
+                    .provider
+                    .get_transaction(tx_hash)
+                    .await?
+                    .and_then(|tx| tx.to),
+                None => None,
+            };
+            events_with_tx_to.push((log, tx_to));
+        }
+        select_user_operation_event(hash, events_with_tx_to).map(Some)
     }
 
-    fn get_user_operations_from_tx_data(&self, tx_data: Bytes) -> Vec<UserOperation> {
-        let entry_point_calls = match IEntryPointCalls::decode(tx_data) {
-            Ok(entry_point_calls) => entry_point_calls,
-            Err(_) => return vec![],
+    /// Finds the user operation event in the receipt of the given transaction
+    async fn get_user_operation_event_in_tx(
+        &self,
+        hash: H256,
+        tx_hash: H256,
+    ) -> EthResult<Option<Log>> {
+        let Some(receipt) = self.provider.get_transaction_receipt(tx_hash).await? else {
+            return Ok(None);
         };
+        self.find_user_operation_event_in_receipt(hash, &receipt)
+    }
 
-        match entry_point_calls {
-            IEntryPointCalls::HandleOps(handle_ops_call) => handle_ops_call.ops,
-            IEntryPointCalls::HandleAggregatedOps(handle_aggregated_ops_call) => {
-                handle_aggregated_ops_call
-                    .ops_per_aggregator
-                    .into_iter()
-                    .flat_map(|ops| ops.user_ops)
-                    .collect()
+    fn find_user_operation_event_in_receipt(
+        &self,
+        hash: H256,
+        receipt: &TransactionReceipt,
+    ) -> EthResult<Option<Log>> {
+        let event_signature = UserOperationEventFilter::signature();
+        let mut events = receipt
+            .logs
+            .iter()
+            .filter(|log| {
+                self.contexts_by_entry_point.contains_key(&log.address)
+                    && log.topics.first() == Some(&event_signature)
+                    && log.topics.get(1) == Some(&hash)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if events.len() <= 1 {
+            return Ok(events.pop());
+        }
+
+        let events_with_tx_to = events.into_iter().map(|log| (log, receipt.to)).collect();
+        select_user_operation_event(hash, events_with_tx_to).map(Some)
+    }
+
+    async fn is_log_canonical(&self, log: &Log) -> EthResult<bool> {
+        let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) else {
+            return Ok(false);
+        };
+
+        let block = self.provider.get_block(block_number).await?;
+        Ok(block.and_then(|b| b.hash) == Some(block_hash))
+    }
+
+    /// Decodes the user operations in an entry point call, each paired with the
+    /// aggregator that validated it, if any.
+    fn get_user_operations_from_tx_data(
+        &self,
+        tx_data: Bytes,
+    ) -> anyhow::Result<Vec<(UserOperation, Option<Address>)>> {
+        if let Ok(entry_point_call) = IEntryPointCalls::decode(&tx_data) {
+            if let Some(ops) = user_operations_from_entry_point_call(entry_point_call) {
+                return Ok(ops);
             }
-            _ => vec![],
         }
+        let selector = tx_data.get(..4).unwrap_or_default();
+        anyhow::bail!(
+            "tx data with selector {} is not a handleOps or handleAggregatedOps call",
+            Bytes::from(selector.to_vec())
+        )
     }
 
-    fn decode_user_operation_event(&self, log: Log) -> EthResult<UserOperationEventFilter> {
-        Ok(UserOperationEventFilter::decode_log(&log_to_raw_log(log))
-            .context("log should be a user operation event")?)
+    // Searches the calldata of a call to a known bundle wrapper for the user operations
+    // of the entry point calls it wraps.
+    fn get_wrapped_user_operations(&self, tx_data: &[u8]) -> Vec<(UserOperation, Option<Address>)> {
+        if !is_wrapper_call(tx_data, &self.settings.bundle_wrapper_selectors) {
+            return vec![];
+        }
+        find_wrapped_entry_point_calls(
+            &tx_data[4..],
+            &self.settings.bundle_wrapper_selectors,
+            MAX_BUNDLE_WRAPPER_DEPTH,
+        )
+        .into_iter()
+        .filter_map(user_operations_from_entry_point_call)
+        .flatten()
+        .collect()
     }
 
-    /// This method takes a user operation event and a transaction receipt and filters out all the logs
-    /// relevant to the user operation. Since there are potentially many user operations in a transaction,
-    /// we want to find all the logs (including the user operation event itself) that are sandwiched between
+    /// Finds the user operation with the given hash among operations sent to the
+    /// entry point. An operation with the event's sender and nonce but a different
+    /// hash means the event and the calldata disagree, so it is reported as an
+    /// error rather than returning the wrong operation or none at all.
+    fn find_user_operation_by_hash(
+        &self,
+        ops: Vec<(UserOperation, Option<Address>)>,
+        entry_point: Address,
+        hash: H256,
+        event: &UserOperationEventFilter,
+    ) -> EthResult<Option<(UserOperation, Option<Address>)>> {
+        let mut sender_nonce_match = false;
+        for (op, aggregator) in ops {
+            if op.op_hash(entry_point, self.chain_id) == hash {
+                return Ok(Some((op, aggregator)));
+            }
+            sender_nonce_match |= op.sender == event.sender && op.nonce == event.nonce;
+        }
+        if sender_nonce_match {
+            return Err(EthRpcError::Internal(anyhow::anyhow!(
+                "user operation from sender {:?} with nonce {} does not match hash {hash:?}",
+                event.sender,
+                event.nonce
+            )));
+        }
+        Ok(None)
+    }
+
+    fn decode_user_operation_event(&self, log: Log) -> EthResult<UserOperationEventFilter> {
+        Ok(UserOperationEventFilter::decode_log(&log_to_raw_log(log))
+            .context("log should be a user operation event")?)
+    }
+
+    /// This method takes a user operation event and a transaction receipt and filters out all the logs
+    /// relevant to the user operation. Since there are potentially many user operations in a transaction,
+    /// we want to find all the logs (including the user operation event itself) that are sandwiched between
     /// ours and the one before it that wasn't ours.
     /// eg. reference_log: UserOp(hash_moldy) logs: \[...OtherLogs, UserOp(hash1), ...OtherLogs, UserOp(hash_moldy), ...OtherLogs\]
     /// -> logs: logs\[(idx_of_UserOp(hash1) + 1)..=idx_of_UserOp(hash_moldy)\]
@@ -387,17 +1131,33 @@ where
         reference_log: &Log,
         tx_receipt: &TransactionReceipt,
     ) -> EthResult<Vec<Log>> {
-        let mut start_idx = 0;
-        let mut end_idx = tx_receipt.logs.len() - 1;
         let logs = &tx_receipt.logs;
+        if logs.is_empty() {
+            return Err(EthRpcError::Internal(anyhow::anyhow!(
+                "fatal: receipt has no logs"
+            )));
+        }
+        let mut start_idx = 0;
+        let mut end_idx = logs.len() - 1;
 
+        // Logs may be anonymous, so topics can't be indexed directly
         let is_ref_user_op = |log: &Log| {
-            log.topics[0] == reference_log.topics[0]
-                && log.topics[1] == reference_log.topics[1]
+            log.topics.first() == reference_log.topics.first()
+                && log.topics.get(1) == reference_log.topics.get(1)
                 && log.address == reference_log.address
         };
 
-        let is_user_op_event = |log: &Log| log.topics[0] == reference_log.topics[0];
+        let is_user_op_event = |log: &Log| log.topics.first() == reference_log.topics.first();
+
+        // Events the entry point emits once per bundle, or once per aggregator
+        // group in handleAggregatedOps, don't belong to any single user op
+        let is_bundle_event = |log: &Log| {
+            log.address == reference_log.address
+                && log.topics.first().is_some_and(|topic| {
+                    *topic == BeforeExecutionFilter::signature()
+                        || *topic == SignatureAggregatorChangedFilter::signature()
+                })
+        };
 
         let mut i = 0;
         while i < logs.len() {
@@ -417,7 +1177,11 @@ where
         }
 
         let start_idx = if start_idx == 0 { 0 } else { start_idx + 1 };
-        Ok(logs[start_idx..=end_idx].to_vec())
+        Ok(logs[start_idx..=end_idx]
+            .iter()
+            .filter(|log| !is_bundle_event(log))
+            .cloned()
+            .collect())
     }
 
     fn get_user_operation_failure_reason(
@@ -447,7 +1211,7 @@ where
         &self,
         tx_hash: H256,
         user_op_hash: H256,
-    ) -> EthResult<Option<UserOperation>> {
+    ) -> EthResult<Option<(UserOperation, Option<Address>)>> {
         // initial call wasn't to an entrypoint, so we need to trace the transaction to find the user operation
         let trace_options = GethDebugTracingOptions {
             tracer: Some(GethDebugTracerType::BuiltInTracer(
@@ -477,12 +1241,13 @@ where
                 .filter(|to| self.contexts_by_entry_point.contains_key(to))
             {
                 // check if the user operation is in the call frame
-                if let Some(uo) = self
+                if let Some(found) = self
                     .get_user_operations_from_tx_data(call_frame.input)
+                    .unwrap_or_default()
                     .into_iter()
-                    .find(|op| op.op_hash(*to, self.chain_id) == user_op_hash)
+                    .find(|(op, _)| op.op_hash(*to, self.chain_id) == user_op_hash)
                 {
-                    return Ok(Some(uo));
+                    return Ok(Some(found));
                 }
             } else if let Some(calls) = call_frame.calls {
                 frame_queue.extend(calls)
@@ -493,14 +1258,152 @@ where
     }
 }
 
+/// Returns the gas price an operation paid per unit of gas. The entry point charges
+/// each operation at its own gas price, which can be below the bundle transaction's,
+/// so this is derived from the operation's cost. An operation that used no gas falls
+/// back to the transaction's gas price.
+fn user_operation_effective_gas_price(
+    actual_gas_cost: U256,
+    actual_gas_used: U256,
+    tx_receipt: &TransactionReceipt,
+) -> U256 {
+    if actual_gas_used.is_zero() {
+        tx_receipt.effective_gas_price.unwrap_or_default()
+    } else {
+        actual_gas_cost / actual_gas_used
+    }
+}
+
+/// Picks the event for an operation hash that several entry points emitted an event
+/// for, each paired with the address its transaction was sent to. Operation hashes
+/// commit to the entry point, so this only happens with colliding deployments. The
+/// event of the entry point the transaction was sent to is kept, and if that does not
+/// single out one event an error is returned rather than picking one arbitrarily.
+fn select_user_operation_event(hash: H256, events: Vec<(Log, Option<Address>)>) -> EthResult<Log> {
+    let mut matching = events
+        .iter()
+        .filter(|(log, tx_to)| *tx_to == Some(log.address))
+        .map(|(log, _)| log);
+    if let (Some(log), None) = (matching.next(), matching.next()) {
+        return Ok(log.clone());
+    }
+
+    let mut entry_points = events
+        .iter()
+        .map(|(log, _)| log.address)
+        .collect::<Vec<_>>();
+    entry_points.sort();
+    entry_points.dedup();
+    Err(EthRpcError::Internal(anyhow::anyhow!(
+        "user operation hash {hash:?} matches events from entry points {entry_points:?}"
+    )))
+}
+
+/// Checks that an operation's block range can contain at least one block
+fn check_valid_block_range(range: &ValidBlockRange) -> EthResult<()> {
+    if let (Some(min_block), Some(max_block)) = (range.min_block, range.max_block) {
+        if min_block > max_block {
+            return Err(EthRpcError::InvalidParams(format!(
+                "block range min block {min_block} is after max block {max_block}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the user operations of a `handleOps` or `handleAggregatedOps` call, each
+/// paired with its aggregator. Ops in a group with the zero aggregator address are
+/// not aggregated.
+fn user_operations_from_entry_point_call(
+    call: IEntryPointCalls,
+) -> Option<Vec<(UserOperation, Option<Address>)>> {
+    match call {
+        IEntryPointCalls::HandleOps(handle_ops_call) => Some(
+            handle_ops_call
+                .ops
+                .into_iter()
+                .map(|op| (op, None))
+                .collect(),
+        ),
+        IEntryPointCalls::HandleAggregatedOps(handle_aggregated_ops_call) => Some(
+            handle_aggregated_ops_call
+                .ops_per_aggregator
+                .into_iter()
+                .flat_map(|ops| {
+                    let aggregator = Some(ops.aggregator).filter(|a| !a.is_zero());
+                    ops.user_ops.into_iter().map(move |op| (op, aggregator))
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn is_wrapper_call(data: &[u8], wrapper_selectors: &HashSet<Selector>) -> bool {
+    data.get(..4)
+        .and_then(|s| Selector::try_from(s).ok())
+        .is_some_and(|s| wrapper_selectors.contains(&s))
+}
+
+/// Finds the `handleOps` and `handleAggregatedOps` calls passed as `bytes` anywhere in
+/// ABI encoded call arguments, including inside calls to other known wrappers nested
+/// up to `depth` levels deep.
+///
+/// Dynamic `bytes` are encoded as a length word followed by the data, both aligned to
+/// 32 bytes, so each word is checked for being the length of entry point calldata that
+/// follows it. A nested wrapper call's arguments start after its 4-byte selector, so
+/// they are searched separately rather than by the same word scan.
+fn find_wrapped_entry_point_calls(
+    args: &[u8],
+    wrapper_selectors: &HashSet<Selector>,
+    depth: usize,
+) -> Vec<IEntryPointCalls> {
+    let selectors = [
+        HandleOpsCall::selector(),
+        HandleAggregatedOpsCall::selector(),
+    ];
+    let mut calls = vec![];
+    for start in (32..args.len()).step_by(32) {
+        let length = U256::from_big_endian(&args[start - 32..start]);
+        if length < U256::from(4) || length > U256::from(args.len() - start) {
+            continue;
+        }
+        let data = &args[start..start + length.as_usize()];
+        if selectors.iter().any(|s| data.starts_with(s)) {
+            if let Ok(call) = IEntryPointCalls::decode(data) {
+                calls.push(call);
+            }
+        } else if depth > 1 && is_wrapper_call(data, wrapper_selectors) {
+            calls.extend(find_wrapped_entry_point_calls(
+                &data[4..],
+                wrapper_selectors,
+                depth - 1,
+            ));
+        }
+    }
+    calls
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::{Read, Write},
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
     use ethers::{
-        types::{Log, TransactionReceipt},
+        abi::{self, AbiEncode, Token},
+        providers::JsonRpcError,
+        types::{Block, Opcode, ValueOrArray},
         utils::keccak256,
     };
-    use rundler_pool::MockPoolServer;
+    use rundler_pool::{MempoolError, MockPoolServer, OpMetadata, PoolServerError};
     use rundler_provider::{MockEntryPoint, MockProvider};
+    use rundler_sim::{SimulationViolation, ViolationOpCode};
+    use rundler_types::{
+        contracts::{entry_point::ValidationResult, shared_types::DepositInfo},
+        Entity, UserOpsPerAggregator,
+    };
 
     use super::*;
 
@@ -646,6 +1549,1268 @@ mod tests {
         assert!(result.is_err(), "{:?}", result.unwrap());
     }
 
+    #[test]
+    fn test_filter_receipt_logs_empty_receipt() {
+        let reference_log = given_log(UO_OP_TOPIC, "moldy-hash");
+        let receipt = given_receipt(vec![]);
+
+        let result =
+            EthApi::<MockProvider, MockEntryPoint, MockPoolServer>::filter_receipt_logs_matching_user_op(
+                &reference_log,
+                &receipt,
+            );
+
+        let err = result.unwrap_err();
+        assert!(
+            err.to_string().contains("receipt has no logs"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_filter_receipt_logs_with_short_topic_logs() {
+        let reference_log = given_log(UO_OP_TOPIC, "moldy-hash");
+        let single_topic_log = Log {
+            topics: vec![keccak256(UO_OP_TOPIC.as_bytes()).into()],
+            ..Default::default()
+        };
+        let receipt = given_receipt(vec![
+            Log::default(),
+            single_topic_log,
+            given_log("other-topic", "some-hash"),
+            reference_log.clone(),
+        ]);
+
+        let result =
+            EthApi::<MockProvider, MockEntryPoint, MockPoolServer>::filter_receipt_logs_matching_user_op(
+                &reference_log,
+                &receipt,
+            );
+
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+        assert_eq!(result.unwrap(), receipt.logs[2..=3]);
+
+        // a receipt made only of short topic logs doesn't match
+        let receipt = given_receipt(vec![Log::default(), receipt.logs[1].clone()]);
+        let result =
+            EthApi::<MockProvider, MockEntryPoint, MockPoolServer>::filter_receipt_logs_matching_user_op(
+                &reference_log,
+                &receipt,
+            );
+
+        assert!(result.is_err(), "{:?}", result.unwrap());
+    }
+
+    #[test]
+    fn test_filter_receipt_logs_aggregated_ops() {
+        let ep = Address::random();
+        let entry_point_log = |topic_0: H256, topic_1: Option<H256>| Log {
+            address: ep,
+            topics: [Some(topic_0), topic_1].into_iter().flatten().collect(),
+            ..Default::default()
+        };
+        let uo_event = |hash: &str| {
+            entry_point_log(
+                UserOperationEventFilter::signature(),
+                Some(keccak256(hash.as_bytes()).into()),
+            )
+        };
+        let aggregator_changed = |aggregator: Address| {
+            entry_point_log(
+                SignatureAggregatorChangedFilter::signature(),
+                Some(aggregator.into()),
+            )
+        };
+        let anonymous_log = Log {
+            address: Address::random(),
+            ..Default::default()
+        };
+
+        // handleAggregatedOps with two aggregators, one op each
+        let reference_log = uo_event("moldy-hash");
+        let receipt = given_receipt(vec![
+            entry_point_log(BeforeExecutionFilter::signature(), None),
+            aggregator_changed(Address::random()),
+            given_log("other-topic", "some-hash"),
+            uo_event("other-hash"),
+            aggregator_changed(Address::random()),
+            given_log("another-topic", "some-hash"),
+            anonymous_log,
+            reference_log.clone(),
+            aggregator_changed(Address::zero()),
+        ]);
+
+        let result =
+            EthApi::<MockProvider, MockEntryPoint, MockPoolServer>::filter_receipt_logs_matching_user_op(
+                &reference_log,
+                &receipt,
+            );
+
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+        let result = result.unwrap();
+        assert_eq!(result, receipt.logs[5..=7]);
+
+        // the first op in the bundle doesn't pick up the bundle level events
+        let result =
+            EthApi::<MockProvider, MockEntryPoint, MockPoolServer>::filter_receipt_logs_matching_user_op(
+                &receipt.logs[3],
+                &receipt,
+            );
+
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+        let result = result.unwrap();
+        assert_eq!(result, receipt.logs[2..=3]);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_receipt_reorged_event() {
+        let ep = Address::random();
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+
+        let mut log = given_log(UO_OP_TOPIC, "moldy-hash");
+        log.address = ep;
+        log.block_number = Some(U64::from(9));
+        log.block_hash = Some(H256::random());
+        provider
+            .expect_get_logs()
+            .returning(move |_| Ok(vec![log.clone()]));
+
+        // a reorg replaced the block the event was emitted in
+        provider.expect_get_block::<U64>().returning(|_| {
+            Ok(Some(Block {
+                hash: Some(H256::random()),
+                ..Default::default()
+            }))
+        });
+
+        let api = create_api(provider, &[ep], Settings::default());
+        let hash = H256::random();
+        assert!(api
+            .get_user_operation_receipt(hash)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(api
+            .get_user_operation_by_hash(hash)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    // Serves a single response from an indexer that knows the operation was
+    // included in `tx_hash`, returning the indexer's url
+    fn serve_indexer_response(tx_hash: H256) -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let body = format!(r#"{{"transactionHash":"{tx_hash:?}"}}"#);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_receipt_reverted_bundle() {
+        let ep = Address::random();
+        let tx_hash = H256::random();
+        let block_hash = H256::random();
+        let paymaster = Address::random();
+        let op = UserOperation {
+            sender: Address::random(),
+            nonce: 5.into(),
+            paymaster_and_data: paymaster.as_bytes().to_vec().into(),
+            ..Default::default()
+        };
+        let hash = op.op_hash(ep, 1);
+
+        // the reverted transaction emits no events, so it's only found through the
+        // indexer, and the provider mock has no log expectations
+        let mut provider = MockProvider::new();
+        provider
+            .expect_get_transaction_receipt::<H256>()
+            .returning(move |_| {
+                Ok(Some(TransactionReceipt {
+                    transaction_hash: tx_hash,
+                    block_hash: Some(block_hash),
+                    block_number: Some(U64::from(9)),
+                    status: Some(U64::zero()),
+                    ..Default::default()
+                }))
+            });
+        let input = HandleOpsCall {
+            ops: vec![op.clone()],
+            beneficiary: Address::random(),
+        }
+        .encode();
+        provider
+            .expect_get_transaction::<H256>()
+            .returning(move |_| {
+                Ok(Some(Transaction {
+                    hash: tx_hash,
+                    to: Some(ep),
+                    input: input.clone().into(),
+                    block_hash: Some(block_hash),
+                    block_number: Some(U64::from(9)),
+                    ..Default::default()
+                }))
+            });
+        let revert_data = FailedOp {
+            op_index: U256::zero(),
+            reason: "AA23 reverted (or OOG)".to_string(),
+        }
+        .encode();
+        provider
+            .expect_call()
+            .withf(|_, block| *block == Some(BlockId::from(U64::from(8))))
+            .returning(move |_, _| {
+                Err(ProviderError::JsonRpcError(JsonRpcError {
+                    code: 3,
+                    message: "execution reverted".to_string(),
+                    data: Some(serde_json::Value::String(
+                        Bytes::from(revert_data.clone()).to_string(),
+                    )),
+                }))
+            });
+
+        let api = create_api(
+            provider,
+            &[ep],
+            Settings {
+                user_operation_indexer_url: Some(serve_indexer_response(tx_hash)),
+                ..Default::default()
+            },
+        );
+        let receipt = api.get_user_operation_receipt(hash).await.unwrap().unwrap();
+        assert!(!receipt.success);
+        assert_eq!(receipt.reason, "AA23 reverted (or OOG)");
+        assert_eq!(receipt.sender, op.sender.into());
+        assert_eq!(receipt.nonce, op.nonce);
+        assert_eq!(receipt.paymaster, paymaster.into());
+        assert!(receipt.logs.is_empty());
+        assert_eq!(receipt.receipt.transaction_hash, tx_hash);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_by_hash_rejects_sender_nonce_match() {
+        let ep = Address::random();
+        let tx_hash = H256::random();
+        let block_hash = H256::random();
+        // The event has a zero sender and nonce, as does the op in the transaction,
+        // but the op's hash doesn't match the event's
+        let op = UserOperation {
+            call_gas_limit: 100_000.into(),
+            ..Default::default()
+        };
+        let hash = H256::random();
+        let event = given_user_op_event(ep, hash, tx_hash, block_hash);
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+        provider
+            .expect_get_logs()
+            .returning(move |_| Ok(vec![event.clone()]));
+        provider.expect_get_block::<U64>().returning(move |_| {
+            Ok(Some(Block {
+                hash: Some(block_hash),
+                ..Default::default()
+            }))
+        });
+        provider
+            .expect_get_transaction::<H256>()
+            .returning(move |_| {
+                Ok(Some(Transaction {
+                    hash: tx_hash,
+                    to: Some(ep),
+                    input: HandleOpsCall {
+                        ops: vec![op.clone()],
+                        beneficiary: Address::random(),
+                    }
+                    .encode()
+                    .into(),
+                    block_hash: Some(block_hash),
+                    block_number: Some(U64::from(9)),
+                    ..Default::default()
+                }))
+            });
+
+        let api = create_api(provider, &[ep], Settings::default());
+        let err = api.get_user_operation_by_hash(hash).await.unwrap_err();
+        assert!(matches!(err, EthRpcError::Internal(_)));
+        assert!(err.to_string().contains("does not match hash"));
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_receipts_shares_tx_receipt() {
+        let ep = Address::random();
+        let tx_hash = H256::random();
+        let block_hash = H256::random();
+        let hashes = [H256::random(), H256::random(), H256::random()];
+        let logs = hashes[..2]
+            .iter()
+            .map(|hash| given_user_op_event(ep, *hash, tx_hash, block_hash))
+            .collect::<Vec<_>>();
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+        let event_logs = logs.clone();
+        provider.expect_get_logs().returning(move |filter| {
+            Ok(event_logs
+                .iter()
+                .filter(|log| filter.topics[1] == Some(ValueOrArray::Value(Some(log.topics[1]))))
+                .cloned()
+                .collect())
+        });
+        provider.expect_get_block::<U64>().returning(move |_| {
+            Ok(Some(Block {
+                hash: Some(block_hash),
+                ..Default::default()
+            }))
+        });
+        let mut receipt = given_receipt(
+            [given_log("other-topic", "some-hash")]
+                .into_iter()
+                .chain(logs.clone())
+                .collect(),
+        );
+        receipt.transaction_hash = tx_hash;
+        receipt.block_hash = Some(block_hash);
+        receipt.block_number = Some(U64::from(9));
+        // both operations were mined in the same bundle, so one fetch serves both
+        provider
+            .expect_get_transaction_receipt::<H256>()
+            .times(1)
+            .returning(move |_| Ok(Some(receipt.clone())));
+
+        let api = create_api(provider, &[ep], Settings::default());
+        let receipts = api
+            .get_user_operation_receipts(hashes.to_vec())
+            .await
+            .unwrap();
+
+        assert_eq!(receipts.len(), 3);
+        for (i, log) in logs.iter().enumerate() {
+            let receipt = receipts[i].as_ref().unwrap();
+            assert_eq!(receipt.user_op_hash, hashes[i]);
+            assert_eq!(receipt.logs.last(), Some(log));
+        }
+        assert!(receipts[2].is_none());
+
+        let too_many = vec![H256::random(); MAX_USER_OPERATION_RECEIPTS + 1];
+        assert!(matches!(
+            api.get_user_operation_receipts(too_many).await,
+            Err(EthRpcError::InvalidParams(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_entry_point_mismatch() {
+        let ep = Address::random();
+        let misconfigured_ep = Address::random();
+        let mut api = create_api(MockProvider::new(), &[ep], Settings::default());
+        let context = api.contexts_by_entry_point.remove(&ep).unwrap();
+        api.contexts_by_entry_point
+            .insert(misconfigured_ep, context);
+
+        // the pool mock has no add_op expectation, so reaching it would panic
+        let result = api
+            .send_user_operation(
+                UserOperation::default().into(),
+                misconfigured_ep,
+                AddOpOptions::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(EthRpcError::Internal(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_by_hash_cached() {
+        let ep = Address::random();
+        let mut provider = MockProvider::new();
+        provider
+            .expect_get_block_number()
+            .times(1)
+            .returning(|| Ok(10));
+        provider
+            .expect_get_logs()
+            .times(1)
+            .returning(|_| Ok(vec![]));
+
+        let api = create_api(
+            provider,
+            &[ep],
+            Settings {
+                user_operation_cache_size: 10,
+                user_operation_cache_pending_ttl: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+        let hash = H256::random();
+        for _ in 0..3 {
+            assert!(api
+                .get_user_operation_by_hash(hash)
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_by_hash_cached_revalidated() {
+        let ep = Address::random();
+        let block_hash = H256::random();
+        let mut provider = MockProvider::new();
+        let mut seq = mockall::Sequence::new();
+        // the block is still canonical on the first lookup, then reorged out
+        provider
+            .expect_get_block::<u64>()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(move |_| {
+                Ok(Some(Block {
+                    hash: Some(block_hash),
+                    ..Default::default()
+                }))
+            });
+        provider
+            .expect_get_block::<u64>()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| {
+                Ok(Some(Block {
+                    hash: Some(H256::random()),
+                    ..Default::default()
+                }))
+            });
+        provider
+            .expect_get_block_number()
+            .times(1)
+            .returning(|| Ok(10));
+        provider
+            .expect_get_logs()
+            .times(1)
+            .returning(|_| Ok(vec![]));
+
+        let api = create_api(
+            provider,
+            &[ep],
+            Settings {
+                user_operation_cache_size: 10,
+                ..Default::default()
+            },
+        );
+        let hash = H256::random();
+        let op = RichUserOperation {
+            user_operation: UserOperation::default().into(),
+            entry_point: ep.into(),
+            block_number: 9.into(),
+            block_hash,
+            transaction_hash: H256::random(),
+            aggregator: None,
+        };
+        api.user_operation_cache.insert(hash, Some(op));
+
+        let found = api.get_user_operation_by_hash(hash).await.unwrap().unwrap();
+        assert_eq!(found.block_hash, block_hash);
+        assert!(api
+            .get_user_operation_by_hash(hash)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_event_batched_entry_points() {
+        let eps = (0..5).map(|_| Address::random()).collect::<Vec<_>>();
+        let target_ep = eps[3];
+        let block_hash = H256::random();
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+
+        let mut log = given_log(UO_OP_TOPIC, "moldy-hash");
+        log.address = target_ep;
+        log.block_number = Some(U64::from(9));
+        log.block_hash = Some(block_hash);
+        provider
+            .expect_get_logs()
+            .withf(|filter| {
+                matches!(&filter.address, Some(ValueOrArray::Array(addrs)) if addrs.len() <= 2)
+            })
+            .times(3)
+            .returning(move |filter| match &filter.address {
+                Some(ValueOrArray::Array(addrs)) if addrs.contains(&target_ep) => {
+                    Ok(vec![log.clone()])
+                }
+                _ => Ok(vec![]),
+            });
+        provider.expect_get_block::<U64>().returning(move |_| {
+            Ok(Some(Block {
+                hash: Some(block_hash),
+                ..Default::default()
+            }))
+        });
+
+        let api = create_api(
+            provider,
+            &eps,
+            Settings {
+                user_operation_event_address_batch_size: Some(2),
+                ..Default::default()
+            },
+        );
+        let event = api
+            .get_user_operation_event_by_hash(H256::random())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.address, target_ep);
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_event_paged_with_historical_fallback() {
+        let ep = Address::random();
+        let block_hash = H256::random();
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(100));
+
+        let mut log = given_log(UO_OP_TOPIC, "moldy-hash");
+        log.address = ep;
+        log.block_number = Some(U64::from(65));
+        log.block_hash = Some(block_hash);
+
+        // recent blocks are searched newest page first, then the historical
+        // fallback continues paging below the block distance
+        for (from, to) in [(91, 100), (81, 90), (71, 80)] {
+            provider
+                .expect_get_logs()
+                .withf(move |filter| {
+                    filter.get_from_block() == Some(U64::from(from))
+                        && filter.get_to_block() == Some(U64::from(to))
+                })
+                .times(1)
+                .returning(|_| Ok(vec![]));
+        }
+        provider
+            .expect_get_logs()
+            .withf(|filter| {
+                filter.get_from_block() == Some(U64::from(61))
+                    && filter.get_to_block() == Some(U64::from(70))
+            })
+            .times(1)
+            .returning(move |_| Ok(vec![log.clone()]));
+        provider.expect_get_block::<U64>().returning(move |_| {
+            Ok(Some(Block {
+                hash: Some(block_hash),
+                ..Default::default()
+            }))
+        });
+
+        let api = create_api(
+            provider,
+            &[ep],
+            Settings {
+                user_operation_event_block_distance: Some(29),
+                user_operation_event_block_page_size: Some(10),
+                user_operation_event_historical_fallback: true,
+                ..Default::default()
+            },
+        );
+        let event = api
+            .get_user_operation_event_by_hash(H256::random())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.block_number, Some(U64::from(65)));
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_event_historical_fallback_max_depth() {
+        let ep = Address::random();
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(100));
+
+        // the fallback searches only the 21 blocks below the block distance
+        for (from, to) in [(91, 100), (81, 90), (71, 80), (61, 70), (51, 60), (50, 50)] {
+            provider
+                .expect_get_logs()
+                .withf(move |filter| {
+                    filter.get_from_block() == Some(U64::from(from))
+                        && filter.get_to_block() == Some(U64::from(to))
+                })
+                .times(1)
+                .returning(|_| Ok(vec![]));
+        }
+
+        let api = create_api(
+            provider,
+            &[ep],
+            Settings {
+                user_operation_event_block_distance: Some(29),
+                user_operation_event_block_page_size: Some(10),
+                user_operation_event_historical_fallback: true,
+                user_operation_event_historical_max_depth: Some(21),
+                ..Default::default()
+            },
+        );
+        let event = api
+            .get_user_operation_event_by_hash(H256::random())
+            .await
+            .unwrap();
+        assert!(event.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_event_in_tx() {
+        let ep = Address::random();
+        let hash = H256::random();
+        let tx_hash = H256::random();
+        let block_hash = H256::random();
+        let event = given_user_op_event(ep, hash, tx_hash, block_hash);
+        let receipt = given_receipt(vec![
+            given_user_op_event(ep, H256::random(), tx_hash, block_hash),
+            given_user_op_event(Address::random(), hash, tx_hash, block_hash),
+            event.clone(),
+        ]);
+
+        let mut provider = MockProvider::new();
+        provider
+            .expect_get_transaction_receipt::<H256>()
+            .withf(move |h| *h == tx_hash)
+            .returning(move |_| Ok(Some(receipt.clone())));
+
+        let api = create_api(provider, &[ep], Settings::default());
+        assert_eq!(
+            api.get_user_operation_event_in_tx(hash, tx_hash)
+                .await
+                .unwrap(),
+            Some(event)
+        );
+        assert_eq!(
+            api.get_user_operation_event_in_tx(H256::random(), tx_hash)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simulate_validation() {
+        let ep = Address::random();
+        let mut provider = MockProvider::new();
+        let revert_data = ValidationResult {
+            return_info: (
+                50_000.into(),
+                1_000_000.into(),
+                false,
+                0,
+                100,
+                Bytes::from(vec![1]),
+            ),
+            sender_info: (0.into(), 0.into()),
+            factory_info: (0.into(), 0.into()),
+            paymaster_info: (0.into(), 0.into()),
+        }
+        .encode();
+        provider.expect_call().returning(move |_, _| {
+            Err(ProviderError::JsonRpcError(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(serde_json::Value::String(
+                    Bytes::from(revert_data.clone()).to_string(),
+                )),
+            }))
+        });
+
+        let api = create_api(provider, &[ep], Settings::default());
+        let result = api
+            .simulate_validation(UserOperation::default().into(), ep)
+            .await
+            .unwrap();
+        assert_eq!(result.pre_op_gas, 50_000.into());
+        assert_eq!(result.prefund, 1_000_000.into());
+        assert!(!result.sig_failed);
+        assert_eq!(result.valid_until, 100.into());
+        assert!(result.has_paymaster_context);
+    }
+
+    #[tokio::test]
+    async fn test_simulation_permit_timeout() {
+        let ep = Address::random();
+        let api = create_api(
+            MockProvider::new(),
+            &[ep],
+            Settings {
+                max_concurrent_simulations: 1,
+                simulation_wait_timeout: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+
+        let permit = api.acquire_simulation_permit().await.unwrap();
+        let result = api
+            .simulate_validation(UserOperation::default().into(), ep)
+            .await;
+        assert!(matches!(result, Err(EthRpcError::ServiceUnavailable(_))));
+
+        drop(permit);
+        assert!(api.acquire_simulation_permit().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_deadline() {
+        let ep = Address::random();
+        let mut api = create_api(
+            MockProvider::new(),
+            &[ep],
+            Settings {
+                max_concurrent_simulations: 1,
+                simulation_wait_timeout: Duration::from_secs(10),
+                send_user_operation_deadline: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+
+        api.pool.expect_get_op_options().returning(|_, _| Ok(None));
+
+        // waiting for the permit outlasts the deadline, and the pool mock has no
+        // add_op expectation, so reaching it would panic
+        let _permit = api.acquire_simulation_permit().await.unwrap();
+        let op = UserOperation::default();
+        let op_hash = op.op_hash(ep, api.chain_id);
+        let result = api
+            .send_user_operation(op.into(), ep, AddOpOptions::default())
+            .await;
+        assert!(matches!(result, Err(EthRpcError::Timeout(hash)) if hash == op_hash));
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_retry_after_deadline() {
+        let ep = Address::random();
+        let mut api = create_api(
+            MockProvider::new(),
+            &[ep],
+            Settings {
+                max_concurrent_simulations: 1,
+                simulation_wait_timeout: Duration::from_secs(10),
+                send_user_operation_deadline: Duration::from_millis(10),
+                ..Default::default()
+            },
+        );
+        let op = UserOperation::default();
+        let op_hash = op.op_hash(ep, api.chain_id);
+        let added = Arc::new(AtomicBool::new(false));
+        let pooled = Arc::clone(&added);
+        api.pool
+            .expect_get_op_options()
+            .withf(move |e, h| *e == ep && *h == op_hash)
+            .returning(move |_, _| Ok(pooled.load(Ordering::SeqCst).then(AddOpOptions::default)));
+
+        let permit = api.acquire_simulation_permit().await.unwrap();
+        let result = api
+            .send_user_operation(op.clone().into(), ep, AddOpOptions::default())
+            .await;
+        assert!(matches!(result, Err(EthRpcError::Timeout(hash)) if hash == op_hash));
+        drop(permit);
+
+        // the pool finished adding the op after the deadline, so the retry is
+        // acknowledged without adding it again
+        added.store(true, Ordering::SeqCst);
+        let hash = api
+            .send_user_operation(op.into(), ep, AddOpOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(hash, op_hash);
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_already_in_pool() {
+        let ep = Address::random();
+        let mut api = create_api(MockProvider::new(), &[ep], Settings::default());
+        let op = UserOperation::default();
+        let op_hash = op.op_hash(ep, api.chain_id);
+        let metadata = OpMetadata::from([("source".to_string(), "wallet".to_string())]);
+        let pooled_options = AddOpOptions {
+            metadata: metadata.clone(),
+            ..Default::default()
+        };
+        api.pool
+            .expect_get_op_options()
+            .withf(move |e, h| *e == ep && *h == op_hash)
+            .returning(move |_, _| Ok(Some(pooled_options.clone())));
+
+        // the pool mock has no add_op expectation, so simulating the op again
+        // would panic
+        let hash = api
+            .send_user_operation(
+                op.clone().into(),
+                ep,
+                AddOpOptions {
+                    metadata,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(hash, op_hash);
+
+        // a retry with different options would not have them applied
+        let result = api
+            .send_user_operation(
+                op.into(),
+                ep,
+                AddOpOptions {
+                    expected_block_hash: Some(H256::random()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(EthRpcError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_simulation_violations() {
+        let ep = Address::random();
+        let mut api = create_api(MockProvider::new(), &[ep], Settings::default());
+        let paymaster = Address::random();
+        api.pool.expect_get_op_options().returning(|_, _| Ok(None));
+        api.pool.expect_add_op().returning(move |_, _, _| {
+            Err(PoolServerError::MempoolError(
+                MempoolError::SimulationViolations(vec![
+                    SimulationViolation::UsedForbiddenOpcode(
+                        Entity::account(Address::zero()),
+                        Address::zero(),
+                        ViolationOpCode(Opcode::GASPRICE),
+                    ),
+                    SimulationViolation::NotStaked(
+                        Entity::paymaster(paymaster),
+                        U256::from(100),
+                        U256::from(10),
+                    ),
+                ]),
+            ))
+        });
+
+        let error = api
+            .send_user_operation(UserOperation::default().into(), ep, AddOpOptions::default())
+            .await
+            .unwrap_err();
+        let object = jsonrpsee::types::ErrorObjectOwned::from(error);
+        assert_eq!(object.code(), -32502);
+        let data: serde_json::Value = serde_json::from_str(object.data().unwrap().get()).unwrap();
+        let violations = data["violations"].as_array().unwrap();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0]["violation"], "used_forbidden_opcode");
+        assert_eq!(violations[0]["code"], -32502);
+        assert_eq!(violations[1]["violation"], "not_staked");
+        assert_eq!(violations[1]["code"], -32505);
+        assert_eq!(violations[1]["data"]["minimumStake"], "0x64");
+        assert_eq!(violations[1]["data"]["minimumUnstakeDelay"], "0xa");
+    }
+
+    #[tokio::test]
+    async fn test_send_user_op_block_range_passed() {
+        let ep = Address::random();
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+        let api = create_api(provider, &[ep], Settings::default());
+
+        // the pool mock has no expectations, so reaching it would panic
+        let result = api
+            .send_user_operation(
+                UserOperation::default().into(),
+                ep,
+                AddOpOptions {
+                    valid_block_range: ValidBlockRange {
+                        min_block: None,
+                        max_block: Some(10),
+                    },
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(matches!(result, Err(EthRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn test_user_operation_effective_gas_price() {
+        let tx_receipt = TransactionReceipt {
+            effective_gas_price: Some(U256::from(30)),
+            ..Default::default()
+        };
+        assert_eq!(
+            user_operation_effective_gas_price(U256::from(2000), U256::from(100), &tx_receipt),
+            U256::from(20)
+        );
+        assert_eq!(
+            user_operation_effective_gas_price(U256::zero(), U256::zero(), &tx_receipt),
+            U256::from(30)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_op_event_multiple_entry_points() {
+        let eps = [Address::random(), Address::random()];
+        let hash = H256::random();
+        let tx_hashes = [H256::random(), H256::random()];
+        let block_hash = H256::random();
+        let logs = vec![
+            given_user_op_event(eps[0], hash, tx_hashes[0], block_hash),
+            given_user_op_event(eps[1], hash, tx_hashes[1], block_hash),
+        ];
+
+        let mut provider = MockProvider::new();
+        provider.expect_get_block_number().returning(|| Ok(10));
+        provider
+            .expect_get_logs()
+            .returning(move |_| Ok(logs.clone()));
+        provider.expect_get_block::<U64>().returning(move |_| {
+            Ok(Some(Block {
+                hash: Some(block_hash),
+                ..Default::default()
+            }))
+        });
+        // only the second transaction was sent to the entry point that emitted its event
+        provider
+            .expect_get_transaction::<H256>()
+            .returning(move |tx_hash| {
+                let to = if tx_hash == tx_hashes[1] {
+                    eps[1]
+                } else {
+                    Address::random()
+                };
+                Ok(Some(Transaction {
+                    hash: tx_hash,
+                    to: Some(to),
+                    ..Default::default()
+                }))
+            });
+
+        let api = create_api(provider, &eps, Settings::default());
+        let event = api
+            .get_user_operation_event_by_hash(hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.address, eps[1]);
+        assert_eq!(event.transaction_hash, Some(tx_hashes[1]));
+    }
+
+    #[test]
+    fn test_select_user_operation_event() {
+        let eps = [Address::random(), Address::random()];
+        let hash = H256::random();
+        let tx_hash = H256::random();
+        let block_hash = H256::random();
+        let events = [
+            given_user_op_event(eps[0], hash, tx_hash, block_hash),
+            given_user_op_event(eps[1], hash, tx_hash, block_hash),
+        ];
+
+        let event = select_user_operation_event(
+            hash,
+            vec![
+                (events[0].clone(), Some(eps[1])),
+                (events[1].clone(), Some(eps[1])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(event.address, eps[1]);
+
+        // neither transaction was sent to an entry point, e.g. a bundle wrapper
+        let wrapper = Some(Address::random());
+        assert!(select_user_operation_event(
+            hash,
+            vec![(events[0].clone(), wrapper), (events[1].clone(), wrapper)],
+        )
+        .is_err());
+
+        // both events' transactions were sent to the entry point that emitted them
+        assert!(select_user_operation_event(
+            hash,
+            vec![
+                (events[0].clone(), Some(eps[0])),
+                (events[1].clone(), Some(eps[1])),
+            ],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_valid_block_range() {
+        assert!(check_valid_block_range(&ValidBlockRange::default()).is_ok());
+        assert!(check_valid_block_range(&ValidBlockRange {
+            min_block: Some(5),
+            max_block: Some(5),
+        })
+        .is_ok());
+        assert!(matches!(
+            check_valid_block_range(&ValidBlockRange {
+                min_block: Some(6),
+                max_block: Some(5),
+            }),
+            Err(EthRpcError::InvalidParams(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_pending_user_operation_nonce() {
+        let ep = Address::random();
+        let sender = Address::random();
+        let mut api = create_api(MockProvider::new(), &[ep], Settings::default());
+        api.pool
+            .expect_get_next_nonce()
+            .withf(move |e, s| *e == ep && *s == sender)
+            .returning(|_, _| Ok(U256::from(4)));
+
+        let nonce = api
+            .get_pending_user_operation_nonce(sender, ep)
+            .await
+            .unwrap();
+        assert_eq!(nonce, U256::from(4));
+
+        let result = api
+            .get_pending_user_operation_nonce(sender, Address::random())
+            .await;
+        assert!(matches!(result, Err(EthRpcError::InvalidParams(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_paymaster_balance_status() {
+        let ep = Address::random();
+        let paymaster = Address::random();
+        let mut api = create_api(MockProvider::new(), &[ep], Settings::default());
+        api.contexts_by_entry_point
+            .get_mut(&ep)
+            .unwrap()
+            .entry_point
+            .expect_get_deposit_info()
+            .withf(move |a| *a == paymaster)
+            .returning(|_| {
+                Ok(DepositInfo {
+                    deposit: 1_000,
+                    staked: true,
+                    stake: 500,
+                    unstake_delay_sec: 86_400,
+                    withdraw_time: 0,
+                })
+            });
+        api.pool
+            .expect_get_paymaster_max_cost()
+            .withf(move |e, p| *e == ep && *p == paymaster)
+            .returning(|_, _| Ok(U256::from(1_500)));
+
+        let status = api
+            .get_paymaster_balance_status(paymaster, ep)
+            .await
+            .unwrap();
+        assert_eq!(status.deposit_info.deposit, U256::from(1_000));
+        assert_eq!(status.deposit_info.unstake_delay_sec, U64::from(86_400));
+        assert_eq!(status.pending_max_cost, U256::from(1_500));
+        assert_eq!(status.shortfall, U256::from(500));
+
+        let result = api
+            .get_paymaster_balance_status(paymaster, Address::random())
+            .await;
+        assert!(matches!(result, Err(EthRpcError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn test_get_user_operations_from_tx_data_unknown_selector() {
+        let api = create_api(
+            MockProvider::new(),
+            &[Address::random()],
+            Settings::default(),
+        );
+        let err = api
+            .get_user_operations_from_tx_data(Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef, 0, 0]))
+            .unwrap_err();
+        assert!(err.to_string().contains("0xdeadbeef"));
+    }
+
+    #[test]
+    fn test_get_wrapped_user_operations() {
+        let ep = Address::random();
+        let uo = UserOperation {
+            sender: Address::random(),
+            nonce: 1.into(),
+            ..Default::default()
+        };
+        let handle_ops = HandleOpsCall {
+            ops: vec![uo.clone()],
+            beneficiary: Address::random(),
+        }
+        .encode();
+        // Multicall3 aggregate3((address,bool,bytes)[])
+        let selector = [0x82, 0xad, 0x56, 0xcb];
+        let mut tx_data = selector.to_vec();
+        tx_data.extend(abi::encode(&[Token::Array(vec![
+            Token::Tuple(vec![
+                Token::Address(Address::random()),
+                Token::Bool(false),
+                Token::Bytes(vec![1, 2, 3]),
+            ]),
+            Token::Tuple(vec![
+                Token::Address(ep),
+                Token::Bool(false),
+                Token::Bytes(handle_ops),
+            ]),
+        ])]));
+
+        let api = create_api(
+            MockProvider::new(),
+            &[ep],
+            Settings {
+                bundle_wrapper_selectors: HashSet::from([selector]),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            api.get_wrapped_user_operations(&tx_data),
+            vec![(uo.clone(), None)]
+        );
+
+        // a wrapper call nested inside another is searched after its selector
+        let mut outer_tx_data = selector.to_vec();
+        outer_tx_data.extend(abi::encode(&[Token::Array(vec![Token::Tuple(vec![
+            Token::Address(Address::random()),
+            Token::Bool(false),
+            Token::Bytes(tx_data.clone()),
+        ])])]));
+        assert_eq!(
+            api.get_wrapped_user_operations(&outer_tx_data),
+            vec![(uo, None)]
+        );
+
+        // calls to unknown wrappers are not searched
+        let api = create_api(MockProvider::new(), &[ep], Settings::default());
+        assert!(api.get_wrapped_user_operations(&tx_data).is_empty());
+    }
+
+    #[test]
+    fn test_user_operations_from_aggregated_call() {
+        let aggregator = Address::random();
+        let aggregated_op = UserOperation {
+            sender: Address::random(),
+            ..Default::default()
+        };
+        let unaggregated_op = UserOperation {
+            sender: Address::random(),
+            ..Default::default()
+        };
+        let call = IEntryPointCalls::HandleAggregatedOps(HandleAggregatedOpsCall {
+            ops_per_aggregator: vec![
+                UserOpsPerAggregator {
+                    user_ops: vec![aggregated_op.clone()],
+                    aggregator,
+                    signature: Bytes::default(),
+                },
+                UserOpsPerAggregator {
+                    user_ops: vec![unaggregated_op.clone()],
+                    aggregator: Address::zero(),
+                    signature: Bytes::default(),
+                },
+            ],
+            beneficiary: Address::random(),
+        });
+
+        assert_eq!(
+            user_operations_from_entry_point_call(call),
+            Some(vec![
+                (aggregated_op, Some(aggregator)),
+                (unaggregated_op, None)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_operation_hash() {
+        let ep = Address::random();
+        let api = create_api(MockProvider::new(), &[ep], Settings::default());
+        let uo = UserOperation {
+            sender: Address::random(),
+            nonce: 3.into(),
+            call_data: vec![1, 2, 3].into(),
+            ..Default::default()
+        };
+
+        let hash = api
+            .get_user_operation_hash(uo.clone().into(), ep)
+            .await
+            .unwrap();
+        assert_eq!(hash, uo.op_hash(ep, api.chain_id));
+
+        let result = api
+            .get_user_operation_hash(uo.into(), Address::random())
+            .await;
+        assert!(matches!(result, Err(EthRpcError::InvalidParams(_))));
+    }
+
+    fn create_api(
+        provider: MockProvider,
+        eps: &[Address],
+        settings: Settings,
+    ) -> EthApi<MockProvider, MockEntryPoint, MockPoolServer> {
+        let provider = Arc::new(provider);
+        let contexts_by_entry_point = eps
+            .iter()
+            .map(|ep| {
+                let mut entry_point = MockEntryPoint::new();
+                entry_point.expect_address().return_const(*ep);
+                let mut context_entry_point = MockEntryPoint::new();
+                context_entry_point.expect_address().return_const(*ep);
+                context_entry_point
+                    .expect_simulate_validation()
+                    .returning(|_, _| Ok(TypedTransaction::default()));
+
+                let context = EntryPointContext {
+                    entry_point: context_entry_point,
+                    max_verification_gas: 1_000_000,
+                    gas_estimator: GasEstimatorImpl::new(
+                        1,
+                        Arc::clone(&provider),
+                        entry_point,
+                        EstimationSettings {
+                            max_verification_gas: 1_000_000,
+                            max_call_gas: 1_000_000,
+                            max_simulate_handle_ops_gas: 1_000_000,
+                            margins: rundler_sim::GasEstimateMargins::NONE,
+                            gas_overheads: rundler_sim::gas::GasOverheads::default(),
+                        },
+                    ),
+                };
+                (*ep, context)
+            })
+            .collect();
+
+        EthApi {
+            contexts_by_entry_point,
+            provider,
+            chain_id: 1,
+            pool: MockPoolServer::new(),
+            user_operation_cache: UserOperationCache::new(
+                settings.user_operation_cache_size,
+                settings.user_operation_cache_pending_ttl,
+            ),
+            indexer: settings
+                .user_operation_indexer_url
+                .clone()
+                .map(IndexerClient::new),
+            simulation_permits: Semaphore::new(settings.max_concurrent_simulations),
+            settings,
+        }
+    }
+
     fn given_log(topic_0: &str, topic_1: &str) -> Log {
         Log {
             topics: vec![
@@ -656,6 +2821,25 @@ mod tests {
         }
     }
 
+    fn given_user_op_event(ep: Address, hash: H256, tx_hash: H256, block_hash: H256) -> Log {
+        Log {
+            address: ep,
+            topics: vec![
+                UserOperationEventFilter::signature(),
+                hash,
+                H256::zero(),
+                H256::zero(),
+            ],
+            data: (U256::zero(), true, U256::zero(), U256::zero())
+                .encode()
+                .into(),
+            block_number: Some(U64::from(9)),
+            block_hash: Some(block_hash),
+            transaction_hash: Some(tx_hash),
+            ..Default::default()
+        }
+    }
+
     fn given_receipt(logs: Vec<Log>) -> TransactionReceipt {
         TransactionReceipt {
             logs,
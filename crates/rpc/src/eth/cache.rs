@@ -0,0 +1,194 @@
+// This file is part of Rundler.
+//
+// Rundler is free software: you can redistribute it and/or modify it under the
+// terms of the GNU Lesser General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// Rundler is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Rundler.
+// If not, see https://www.gnu.org/licenses/.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use ethers::types::H256;
+use parking_lot::Mutex;
+
+use crate::types::RichUserOperation;
+
+/// A bounded, least recently used cache of `eth_getUserOperationByHash` results.
+///
+/// Results for included operations are kept until evicted, but callers must
+/// check that the block they were found in is still canonical before using one,
+/// as a reorg may drop or move the operation's transaction. Missing results are
+/// kept for `pending_ttl`, as the operation may be mined at any moment.
+#[derive(Debug)]
+pub(crate) struct UserOperationCache {
+    capacity: usize,
+    pending_ttl: Duration,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    entries: HashMap<H256, CacheEntry>,
+    // Hashes by the tick they were last used at, oldest first
+    by_last_used: BTreeMap<u64, H256>,
+    next_tick: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    value: Option<RichUserOperation>,
+    expires_at: Option<Instant>,
+    last_used: u64,
+}
+
+impl UserOperationCache {
+    /// Create a cache holding at most `capacity` results. A capacity of 0 disables caching.
+    pub(crate) fn new(capacity: usize, pending_ttl: Duration) -> Self {
+        Self {
+            capacity,
+            pending_ttl,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Returns the cached result for `hash`, or `None` if there is no live entry.
+    pub(crate) fn get(&self, hash: H256) -> Option<Option<RichUserOperation>> {
+        let mut inner = self.inner.lock();
+        let entry = inner.entries.get(&hash)?;
+        let expired = entry.expires_at.is_some_and(|t| t <= Instant::now());
+        let value = entry.value.clone();
+        if expired {
+            inner.remove(hash);
+            return None;
+        }
+        inner.touch(hash);
+        Some(value)
+    }
+
+    pub(crate) fn insert(&self, hash: H256, value: Option<RichUserOperation>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let expires_at = if value.is_some() {
+            None
+        } else {
+            Some(Instant::now() + self.pending_ttl)
+        };
+
+        let mut inner = self.inner.lock();
+        inner.remove(hash);
+        while inner.entries.len() >= self.capacity {
+            let Some((_, oldest)) = inner.by_last_used.pop_first() else {
+                break;
+            };
+            inner.entries.remove(&oldest);
+        }
+
+        let tick = inner.next_tick();
+        inner.by_last_used.insert(tick, hash);
+        inner.entries.insert(
+            hash,
+            CacheEntry {
+                value,
+                expires_at,
+                last_used: tick,
+            },
+        );
+    }
+}
+
+impl CacheInner {
+    fn next_tick(&mut self) -> u64 {
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        tick
+    }
+
+    fn touch(&mut self, hash: H256) {
+        let tick = self.next_tick();
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            self.by_last_used.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.by_last_used.insert(tick, hash);
+        }
+    }
+
+    fn remove(&mut self, hash: H256) {
+        if let Some(entry) = self.entries.remove(&hash) {
+            self.by_last_used.remove(&entry.last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Address;
+    use rundler_types::UserOperation;
+
+    use super::*;
+
+    fn rich_op() -> RichUserOperation {
+        RichUserOperation {
+            user_operation: UserOperation::default().into(),
+            entry_point: Address::random().into(),
+            block_number: 1.into(),
+            block_hash: H256::random(),
+            transaction_hash: H256::random(),
+            aggregator: None,
+        }
+    }
+
+    #[test]
+    fn test_included_op_is_cached() {
+        let cache = UserOperationCache::new(10, Duration::ZERO);
+        let hash = H256::random();
+        let op = rich_op();
+        cache.insert(hash, Some(op.clone()));
+
+        let cached = cache.get(hash).unwrap().unwrap();
+        assert_eq!(cached.transaction_hash, op.transaction_hash);
+    }
+
+    #[test]
+    fn test_missing_op_expires() {
+        let cache = UserOperationCache::new(10, Duration::ZERO);
+        let hash = H256::random();
+        cache.insert(hash, None);
+        assert!(cache.get(hash).is_none());
+
+        let cache = UserOperationCache::new(10, Duration::from_secs(60));
+        cache.insert(hash, None);
+        assert!(matches!(cache.get(hash), Some(None)));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = UserOperationCache::new(2, Duration::ZERO);
+        let hashes = [H256::random(), H256::random(), H256::random()];
+        cache.insert(hashes[0], Some(rich_op()));
+        cache.insert(hashes[1], Some(rich_op()));
+        // use the first entry so that the second is the least recently used
+        assert!(cache.get(hashes[0]).is_some());
+        cache.insert(hashes[2], Some(rich_op()));
+
+        assert!(cache.get(hashes[0]).is_some());
+        assert!(cache.get(hashes[1]).is_none());
+        assert!(cache.get(hashes[2]).is_some());
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let cache = UserOperationCache::new(0, Duration::from_secs(60));
+        let hash = H256::random();
+        cache.insert(hash, Some(rich_op()));
+        assert!(cache.get(hash).is_none());
+    }
+}
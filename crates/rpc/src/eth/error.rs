@@ -11,24 +11,27 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use ethers::types::{Address, Opcode, U256};
+use ethers::types::{Address, Bytes, CallFrame, Opcode, H256, U256};
 use jsonrpsee::types::{
     error::{CALL_EXECUTION_FAILED_CODE, INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE},
     ErrorObjectOwned,
 };
 use rundler_pool::{MempoolError, PoolServerError};
 use rundler_provider::ProviderError;
-use rundler_sim::{PrecheckViolation, SimulationViolation};
-use rundler_types::{Entity, EntityType, Timestamp};
+use rundler_sim::{DisplayViolations, PrecheckViolation, SimulationViolation};
+use rundler_types::{Entity, EntityType, FailedOpCode, Timestamp};
 use serde::Serialize;
 
-use crate::error::{rpc_err, rpc_err_with_data};
+use crate::error::{rpc_err, rpc_err_with_data, with_request_id};
 
 // Error codes borrowed from jsonrpsee
 // INVALID_REQUEST_CODE = -32600
 // INVALID_PARAMS_CODE = -32602
 // INTERNAL_ERROR_CODE = -32603
 
+// EIP-1474 "Resource unavailable" error code
+const RESOURCE_UNAVAILABLE_CODE: i32 = -32002;
+
 // Custom ERC-4337 error codes
 const ENTRYPOINT_VALIDATION_REJECTED_CODE: i32 = -32500;
 const PAYMASTER_VALIDATION_REJECTED_CODE: i32 = -32501;
@@ -40,10 +43,18 @@ const UNSUPORTED_AGGREGATOR_CODE: i32 = -32506;
 const SIGNATURE_CHECK_FAILED_CODE: i32 = -32507;
 const EXECUTION_REVERTED: i32 = -32521;
 
+// Rundler specific error code for an unreachable aggregator, which the ERC doesn't distinguish
+const AGGREGATOR_UNREACHABLE_CODE: i32 = -32511;
+
+/// Number of call frames, counted back from the last one entered, included with
+/// a traced estimation failure
+const ESTIMATION_TRACE_FRAMES: usize = 10;
+
 pub(crate) type EthResult<T> = Result<T, EthRpcError>;
 
 /// Error returned by the RPC server eth namespace
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum EthRpcError {
     #[error(transparent)]
     Internal(#[from] anyhow::Error),
@@ -72,15 +83,31 @@ pub enum EthRpcError {
     /// Entity throttled or banned
     #[error("entity throttled or banned")]
     ThrottledOrBanned(Entity),
+    /// Entity submitted too many operations within its rate limit window
+    #[error("entity rate limited")]
+    RateLimited(Entity),
     /// Entity stake/unstake delay too low
     #[error("entity stake/unstake delay too low")]
     StakeTooLow(StakeTooLowData),
     /// Unsupported aggregator
     #[error("unsupported aggregator")]
     UnsupportedAggregator(UnsupportedAggregatorData),
+    /// Aggregator reverted when validating the signature
+    #[error("aggregator signature validation failed")]
+    AggregatorValidationFailed,
+    /// Aggregator could not be called to validate the signature
+    #[error("aggregator unreachable")]
+    AggregatorUnreachable(UnsupportedAggregatorData),
     /// Replacement underpriced
     #[error("replacement underpriced")]
     ReplacementUnderpriced(ReplacementUnderpricedData),
+    /// Operation fees are below the pool's minimums
+    #[error(
+        "maxPriorityFeePerGas must be at least {} and maxFeePerGas must be at least {} to enter the pool",
+        .0.min_max_priority_fee_per_gas,
+        .0.min_max_fee_per_gas
+    )]
+    FeesBelowMinimum(FeesBelowMinimumData),
     /// Operation already known
     #[error("already known")]
     OperationAlreadyKnown,
@@ -89,19 +116,60 @@ pub enum EthRpcError {
     SignatureCheckFailed,
     #[error("precheck failed: {0}")]
     PrecheckFailed(PrecheckViolation),
-    #[error("validation simulation failed: {0}")]
-    SimulationFailed(SimulationViolation),
+    /// Simulation failed with one or more violations not covered by a more
+    /// specific error, or with multiple violations. Violations are ordered by
+    /// importance.
+    #[error("validation simulation failed: {}", DisplayViolations(.0))]
+    SimulationFailed(Vec<SimulationViolation>),
     #[error("{0}")]
     ExecutionReverted(String),
+    /// The paymaster's postOp reverted when executing the full operation
+    #[error("{0}")]
+    PostOpReverted(String),
     #[error("operation rejected by mempool: {0}")]
     OperationRejected(String),
+    /// A backing service, such as the pool, is temporarily unreachable and the
+    /// request can be retried
+    #[error("service unavailable: {0}")]
+    ServiceUnavailable(String),
+    /// The request didn't complete within its deadline. The operation with the
+    /// given hash may still be added to the mempool.
+    #[error("request timed out, operation {0:?} may still be added to the mempool")]
+    Timeout(H256),
+    /// Gas estimation failed, and the caller asked for a trace of the operation's
+    /// simulation. Reported with the code and message of the underlying error.
+    #[error("{0}")]
+    EstimationFailedWithTrace(Box<EthRpcError>, Box<EstimationTraceData>),
 }
 
+/// Error data for a rejection with an entry point `FailedOp` reason, e.g.
+/// "AA21 didn't pay prefund", that starts with a known error code
 #[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedOpData {
+    pub reason: String,
+    pub failed_op_code: FailedOpCode,
+    pub description: &'static str,
+}
+
+impl FailedOpData {
+    fn from_reason(reason: &str) -> Option<Self> {
+        FailedOpCode::from_reason(reason).map(|code| Self {
+            reason: reason.to_string(),
+            failed_op_code: code,
+            description: code.description(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PaymasterValidationRejectedData {
     pub paymaster: Address,
     #[serde(skip_serializing)] // this is included in the message
     pub reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_op_code: Option<FailedOpCode>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -135,22 +203,218 @@ impl StakeTooLowData {
 pub struct ReplacementUnderpricedData {
     pub current_max_priority_fee: U256,
     pub current_max_fee: U256,
+    pub required_max_priority_fee: U256,
+    pub required_max_fee: U256,
 }
 
 impl ReplacementUnderpricedData {
-    pub fn new(current_max_priority_fee: U256, current_max_fee: U256) -> Self {
+    pub fn new(
+        current_max_priority_fee: U256,
+        current_max_fee: U256,
+        required_max_priority_fee: U256,
+        required_max_fee: U256,
+    ) -> Self {
         Self {
             current_max_priority_fee,
             current_max_fee,
+            required_max_priority_fee,
+            required_max_fee,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeesBelowMinimumData {
+    pub min_max_priority_fee_per_gas: U256,
+    pub min_max_fee_per_gas: U256,
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct UnsupportedAggregatorData {
     pub aggregator: Address,
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeoutData {
+    pub user_op_hash: H256,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationFailedData {
+    pub violations: Vec<SimulationViolationData>,
+}
+
+/// A single simulation violation, with the code and data it would be reported
+/// with if it were the only violation
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationViolationData {
+    pub violation: &'static str,
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+impl From<&[SimulationViolation]> for SimulationFailedData {
+    fn from(violations: &[SimulationViolation]) -> Self {
+        Self {
+            violations: violations.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&SimulationViolation> for SimulationViolationData {
+    fn from(violation: &SimulationViolation) -> Self {
+        let name = violation.into();
+        match EthRpcError::from(violation.clone()) {
+            // Reported on its own as a generic simulation failure, which has no
+            // data beyond the violation itself
+            EthRpcError::SimulationFailed(_) => Self {
+                violation: name,
+                code: CALL_EXECUTION_FAILED_CODE,
+                message: violation.to_string(),
+                data: None,
+            },
+            error => {
+                let object = to_error_object(error);
+                Self {
+                    violation: name,
+                    code: object.code(),
+                    message: object.message().to_string(),
+                    data: object
+                        .data()
+                        .and_then(|data| serde_json::from_str(data.get()).ok()),
+                }
+            }
+        }
+    }
+}
+
+/// Error data for a traced gas estimation failure: the revert data of the
+/// operation's simulation and the last call frames entered before it reverted
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimationTraceData {
+    pub revert_data: Bytes,
+    pub call_frames: Vec<TracedCallFrame>,
+}
+
+/// A call frame from the call tracer, without its child calls
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TracedCallFrame {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub depth: usize,
+    pub from: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Address>,
+    pub input: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Bytes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub gas_used: U256,
+}
+
+impl From<CallFrame> for EstimationTraceData {
+    fn from(root: CallFrame) -> Self {
+        let revert_data = root.output.clone().unwrap_or_default();
+
+        // Flatten the call tree in the order the calls were entered
+        let mut call_frames = vec![];
+        let mut stack = vec![(root, 0)];
+        while let Some((frame, depth)) = stack.pop() {
+            if let Some(calls) = frame.calls {
+                stack.extend(calls.into_iter().rev().map(|call| (call, depth + 1)));
+            }
+            call_frames.push(TracedCallFrame {
+                typ: frame.typ,
+                depth,
+                from: frame.from,
+                to: frame.to.as_ref().and_then(|to| to.as_address()).copied(),
+                input: frame.input,
+                output: frame.output,
+                error: frame.error,
+                gas_used: frame.gas_used,
+            });
+        }
+        let skip = call_frames.len().saturating_sub(ESTIMATION_TRACE_FRAMES);
+        call_frames.drain(..skip);
+
+        Self {
+            revert_data,
+            call_frames,
+        }
+    }
+}
+
+impl EthRpcError {
+    /// A stable, machine readable name for the kind of this error, for use in logs and metrics
+    pub(crate) fn category(&self) -> &'static str {
+        self.into()
+    }
+
+    /// The JSON-RPC error code reported to clients, using the ERC-4337 codes for
+    /// rejections the spec standardizes
+    pub(crate) fn code(&self) -> i32 {
+        match self {
+            EthRpcError::Internal(_) => INTERNAL_ERROR_CODE,
+            EthRpcError::InvalidParams(_) => INVALID_PARAMS_CODE,
+            EthRpcError::EntryPointValidationRejected(_) => ENTRYPOINT_VALIDATION_REJECTED_CODE,
+            EthRpcError::PaymasterValidationRejected(_) => PAYMASTER_VALIDATION_REJECTED_CODE,
+            EthRpcError::OpcodeViolation(_, _)
+            | EthRpcError::OpcodeViolationMap(_)
+            | EthRpcError::InvalidStorageAccess(_, _, _) => OPCODE_VIOLATION_CODE,
+            EthRpcError::OutOfTimeRange(_) => OUT_OF_TIME_RANGE_CODE,
+            EthRpcError::ThrottledOrBanned(_) | EthRpcError::RateLimited(_) => {
+                THROTTLED_OR_BANNED_CODE
+            }
+            EthRpcError::StakeTooLow(_) => STAKE_TOO_LOW_CODE,
+            EthRpcError::UnsupportedAggregator(_) => UNSUPORTED_AGGREGATOR_CODE,
+            EthRpcError::AggregatorValidationFailed => SIGNATURE_CHECK_FAILED_CODE,
+            EthRpcError::AggregatorUnreachable(_) => AGGREGATOR_UNREACHABLE_CODE,
+            EthRpcError::ReplacementUnderpriced(_)
+            | EthRpcError::FeesBelowMinimum(_)
+            | EthRpcError::OperationAlreadyKnown
+            | EthRpcError::OperationRejected(_) => INVALID_PARAMS_CODE,
+            EthRpcError::SignatureCheckFailed => SIGNATURE_CHECK_FAILED_CODE,
+            EthRpcError::PrecheckFailed(_) => CALL_EXECUTION_FAILED_CODE,
+            // With multiple violations, report the code of the most important one
+            EthRpcError::SimulationFailed(violations) => match violations.as_slice() {
+                [first, _, ..] => EthRpcError::from(first.clone()).code(),
+                _ => CALL_EXECUTION_FAILED_CODE,
+            },
+            EthRpcError::ExecutionReverted(_) | EthRpcError::PostOpReverted(_) => {
+                EXECUTION_REVERTED
+            }
+            EthRpcError::ServiceUnavailable(_) | EthRpcError::Timeout(_) => {
+                RESOURCE_UNAVAILABLE_CODE
+            }
+            EthRpcError::EstimationFailedWithTrace(error, _) => error.code(),
+        }
+    }
+}
+
+/// Returns a stable, machine readable name for the most important violation that
+/// caused the pool to reject an operation, if it was rejected for a violation
+pub(crate) fn rejection_violation(error: &PoolServerError) -> Option<&'static str> {
+    match error {
+        PoolServerError::MempoolError(MempoolError::PrecheckViolation(violation)) => {
+            Some(violation.into())
+        }
+        PoolServerError::MempoolError(MempoolError::SimulationViolation(violation)) => {
+            Some(violation.into())
+        }
+        PoolServerError::MempoolError(MempoolError::SimulationViolations(violations)) => {
+            violations.first().map(Into::into)
+        }
+        _ => None,
+    }
+}
+
 impl From<PoolServerError> for EthRpcError {
     fn from(value: PoolServerError) -> Self {
         match value {
@@ -158,6 +422,7 @@ impl From<PoolServerError> for EthRpcError {
             PoolServerError::UnexpectedResponse => {
                 EthRpcError::Internal(anyhow::anyhow!("unexpected response from pool server"))
             }
+            PoolServerError::Unavailable(e) => EthRpcError::ServiceUnavailable(e),
             PoolServerError::Other(e) => EthRpcError::Internal(e),
         }
     }
@@ -167,28 +432,65 @@ impl From<MempoolError> for EthRpcError {
     fn from(value: MempoolError) -> Self {
         match value {
             MempoolError::Other(e) => EthRpcError::Internal(e),
-            MempoolError::OperationAlreadyKnown => EthRpcError::OperationAlreadyKnown,
-            MempoolError::ReplacementUnderpriced(priority_fee, fee) => {
-                EthRpcError::ReplacementUnderpriced(ReplacementUnderpricedData {
-                    current_max_priority_fee: priority_fee,
-                    current_max_fee: fee,
+            MempoolError::OperationAlreadyKnown | MempoolError::DuplicateOperation => {
+                EthRpcError::OperationAlreadyKnown
+            }
+            MempoolError::ReplacementUnderpriced(
+                priority_fee,
+                fee,
+                required_priority_fee,
+                required_fee,
+            ) => EthRpcError::ReplacementUnderpriced(ReplacementUnderpricedData::new(
+                priority_fee,
+                fee,
+                required_priority_fee,
+                required_fee,
+            )),
+            MempoolError::FeesBelowMinimum(priority_fee, fee) => {
+                EthRpcError::FeesBelowMinimum(FeesBelowMinimumData {
+                    min_max_priority_fee_per_gas: priority_fee,
+                    min_max_fee_per_gas: fee,
                 })
             }
+            MempoolError::NonceTooLow(nonce, on_chain_nonce) => EthRpcError::InvalidParams(
+                format!("nonce {nonce} is below the sender's on-chain nonce {on_chain_nonce}"),
+            ),
+            MempoolError::WrongPoolShard(shard_index, pool_shard_index) => {
+                EthRpcError::OperationRejected(format!(
+                    "operation belongs to pool shard {shard_index}, not shard {pool_shard_index}; resubmit to the pool serving shard {shard_index}"
+                ))
+            }
+            MempoolError::InvalidMetadata(reason) => EthRpcError::InvalidParams(reason),
+            MempoolError::OperationTooLarge(size, max_size) => EthRpcError::OperationRejected(
+                format!("operation size {size} bytes exceeds maximum of {max_size} bytes"),
+            ),
             MempoolError::MaxOperationsReached(count, _) => EthRpcError::OperationRejected(
                 format!("max operations reached for sender {count} already in pool"),
             ),
             MempoolError::EntityThrottled(entity) => EthRpcError::ThrottledOrBanned(entity),
+            MempoolError::RateLimited(entity) => EthRpcError::RateLimited(entity),
             MempoolError::DiscardedOnInsert => {
                 EthRpcError::OperationRejected("discarded on insert".to_owned())
             }
+            MempoolError::StakeRequiredForGossip => {
+                EthRpcError::OperationRejected("staked account or paymaster required".to_owned())
+            }
             MempoolError::PrecheckViolation(violation) => violation.into(),
             MempoolError::SimulationViolation(violation) => violation.into(),
+            MempoolError::SimulationViolations(violations) => {
+                EthRpcError::SimulationFailed(violations)
+            }
             MempoolError::UnsupportedAggregator(a) => {
                 EthRpcError::UnsupportedAggregator(UnsupportedAggregatorData { aggregator: a })
             }
             MempoolError::UnknownEntryPoint(a) => {
                 EthRpcError::EntryPointValidationRejected(format!("unknown entry point: {}", a))
             }
+            MempoolError::BlockHashMismatch(expected, current) => {
+                EthRpcError::OperationRejected(format!(
+                    "expected block hash {expected:?} does not match current block hash {current:?}"
+                ))
+            }
         }
     }
 }
@@ -209,6 +511,7 @@ impl From<SimulationViolation> for EthRpcError {
                 Some(paymaster),
             ) => Self::PaymasterValidationRejected(PaymasterValidationRejectedData {
                 paymaster,
+                failed_op_code: FailedOpCode::from_reason(&reason),
                 reason,
             }),
             SimulationViolation::UnintendedRevertWithMessage(_, reason, _) => {
@@ -224,54 +527,63 @@ impl From<SimulationViolation> for EthRpcError {
             SimulationViolation::FactoryCalledCreate2Twice(_) => {
                 Self::OpcodeViolation(EntityType::Factory, Opcode::CREATE2)
             }
-            SimulationViolation::InvalidStorageAccess(entity, slot) => {
+            SimulationViolation::InvalidStorageAccess(entity, slot)
+            | SimulationViolation::BannedStorageSlot(entity, slot) => {
                 Self::InvalidStorageAccess(entity.kind, slot.address, slot.slot)
             }
             SimulationViolation::NotStaked(entity, min_stake, min_unstake_delay) => {
                 Self::StakeTooLow(StakeTooLowData::new(entity, min_stake, min_unstake_delay))
             }
-            SimulationViolation::AggregatorValidationFailed => Self::SignatureCheckFailed,
-            _ => Self::SimulationFailed(value),
+            SimulationViolation::AggregatorValidationFailed => Self::AggregatorValidationFailed,
+            SimulationViolation::AggregatorUnreachable(aggregator) => {
+                Self::AggregatorUnreachable(UnsupportedAggregatorData { aggregator })
+            }
+            SimulationViolation::VerificationGasLimitTooLow(_, _) => {
+                Self::InvalidParams(value.to_string())
+            }
+            SimulationViolation::SenderAddressMismatch(_, _) => {
+                Self::EntryPointValidationRejected(value.to_string())
+            }
+            _ => Self::SimulationFailed(vec![value]),
         }
     }
 }
 
 impl From<EthRpcError> for ErrorObjectOwned {
     fn from(error: EthRpcError) -> Self {
-        let msg = error.to_string();
+        with_request_id(to_error_object(error))
+    }
+}
 
-        match error {
-            EthRpcError::Internal(_) => rpc_err(INTERNAL_ERROR_CODE, msg),
-            EthRpcError::InvalidParams(_) => rpc_err(INVALID_PARAMS_CODE, msg),
-            EthRpcError::EntryPointValidationRejected(_) => {
-                rpc_err(ENTRYPOINT_VALIDATION_REJECTED_CODE, msg)
-            }
-            EthRpcError::PaymasterValidationRejected(data) => {
-                rpc_err_with_data(PAYMASTER_VALIDATION_REJECTED_CODE, msg, data)
-            }
-            EthRpcError::OpcodeViolation(_, _)
-            | EthRpcError::OpcodeViolationMap(_)
-            | EthRpcError::InvalidStorageAccess(_, _, _) => rpc_err(OPCODE_VIOLATION_CODE, msg),
-            EthRpcError::OutOfTimeRange(data) => {
-                rpc_err_with_data(OUT_OF_TIME_RANGE_CODE, msg, data)
-            }
-            EthRpcError::ThrottledOrBanned(data) => {
-                rpc_err_with_data(THROTTLED_OR_BANNED_CODE, msg, data)
-            }
-            EthRpcError::StakeTooLow(data) => rpc_err_with_data(STAKE_TOO_LOW_CODE, msg, data),
-            EthRpcError::UnsupportedAggregator(data) => {
-                rpc_err_with_data(UNSUPORTED_AGGREGATOR_CODE, msg, data)
-            }
-            EthRpcError::ReplacementUnderpriced(data) => {
-                rpc_err_with_data(INVALID_PARAMS_CODE, msg, data)
+fn to_error_object(error: EthRpcError) -> ErrorObjectOwned {
+    let code = error.code();
+    let msg = error.to_string();
+
+    match error {
+        EthRpcError::EntryPointValidationRejected(reason) => {
+            match FailedOpData::from_reason(&reason) {
+                Some(data) => rpc_err_with_data(code, msg, data),
+                None => rpc_err(code, msg),
             }
-            EthRpcError::OperationAlreadyKnown => rpc_err(INVALID_PARAMS_CODE, msg),
-            EthRpcError::SignatureCheckFailed => rpc_err(SIGNATURE_CHECK_FAILED_CODE, msg),
-            EthRpcError::PrecheckFailed(_) => rpc_err(CALL_EXECUTION_FAILED_CODE, msg),
-            EthRpcError::SimulationFailed(_) => rpc_err(CALL_EXECUTION_FAILED_CODE, msg),
-            EthRpcError::ExecutionReverted(_) => rpc_err(EXECUTION_REVERTED, msg),
-            EthRpcError::OperationRejected(_) => rpc_err(INVALID_PARAMS_CODE, msg),
         }
+        EthRpcError::PaymasterValidationRejected(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::OutOfTimeRange(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::ThrottledOrBanned(data) | EthRpcError::RateLimited(data) => {
+            rpc_err_with_data(code, msg, data)
+        }
+        EthRpcError::StakeTooLow(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::UnsupportedAggregator(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::AggregatorUnreachable(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::ReplacementUnderpriced(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::FeesBelowMinimum(data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::SimulationFailed(violations) => {
+            rpc_err_with_data(code, msg, SimulationFailedData::from(violations.as_slice()))
+        }
+        EthRpcError::EstimationFailedWithTrace(_, data) => rpc_err_with_data(code, msg, data),
+        EthRpcError::Timeout(user_op_hash) => {
+            rpc_err_with_data(code, msg, TimeoutData { user_op_hash })
+        }
+        _ => rpc_err(code, msg),
     }
 }
 
@@ -290,3 +602,198 @@ impl From<ProviderError> for EthRpcError {
         EthRpcError::Internal(anyhow::anyhow!("provider error: {e:?}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category() {
+        let error: EthRpcError = MempoolError::ReplacementUnderpriced(
+            U256::zero(),
+            U256::zero(),
+            U256::zero(),
+            U256::zero(),
+        )
+        .into();
+        assert_eq!(error.category(), "replacement_underpriced");
+
+        let error: EthRpcError = SimulationViolation::InvalidSignature.into();
+        assert_eq!(error.category(), "signature_check_failed");
+
+        let error: EthRpcError = SimulationViolation::AggregatorValidationFailed.into();
+        assert_eq!(error.category(), "aggregator_validation_failed");
+
+        let error: EthRpcError = SimulationViolation::AggregatorUnreachable(Address::zero()).into();
+        assert_eq!(error.category(), "aggregator_unreachable");
+    }
+
+    #[test]
+    fn test_codes() {
+        let entity = Entity::paymaster(Address::zero());
+        let aggregator = UnsupportedAggregatorData {
+            aggregator: Address::zero(),
+        };
+        let cases = [
+            (
+                EthRpcError::EntryPointValidationRejected("AA13".to_string()),
+                -32500,
+            ),
+            (
+                EthRpcError::PaymasterValidationRejected(PaymasterValidationRejectedData {
+                    paymaster: Address::zero(),
+                    reason: "rejected".to_string(),
+                    failed_op_code: None,
+                }),
+                -32501,
+            ),
+            (
+                EthRpcError::OpcodeViolation(EntityType::Account, Opcode::GASPRICE),
+                -32502,
+            ),
+            (
+                EthRpcError::InvalidStorageAccess(
+                    EntityType::Account,
+                    Address::zero(),
+                    U256::zero(),
+                ),
+                -32502,
+            ),
+            (
+                EthRpcError::OutOfTimeRange(OutOfTimeRangeData {
+                    valid_until: Timestamp::default(),
+                    valid_after: Timestamp::default(),
+                    paymaster: None,
+                }),
+                -32503,
+            ),
+            (EthRpcError::ThrottledOrBanned(entity), -32504),
+            (EthRpcError::RateLimited(entity), -32504),
+            (
+                EthRpcError::StakeTooLow(StakeTooLowData::new(entity, U256::zero(), U256::zero())),
+                -32505,
+            ),
+            (EthRpcError::UnsupportedAggregator(aggregator), -32506),
+            (EthRpcError::AggregatorValidationFailed, -32507),
+            (EthRpcError::AggregatorUnreachable(aggregator), -32511),
+            (EthRpcError::SignatureCheckFailed, -32507),
+            (
+                EthRpcError::ExecutionReverted("reverted".to_string()),
+                -32521,
+            ),
+            (EthRpcError::PostOpReverted("AA50".to_string()), -32521),
+            (EthRpcError::InvalidParams("invalid".to_string()), -32602),
+            (EthRpcError::OperationAlreadyKnown, -32602),
+            (EthRpcError::Internal(anyhow::anyhow!("internal")), -32603),
+            (
+                EthRpcError::PrecheckFailed(PrecheckViolation::InitCodeTooShort(1)),
+                -32000,
+            ),
+            (
+                EthRpcError::SimulationFailed(vec![
+                    SimulationViolation::InvalidSignature,
+                    SimulationViolation::AggregatorValidationFailed,
+                ]),
+                -32507,
+            ),
+            (
+                EthRpcError::ServiceUnavailable("unavailable".to_string()),
+                -32002,
+            ),
+            (EthRpcError::Timeout(H256::zero()), -32002),
+        ];
+
+        for (error, code) in cases {
+            assert_eq!(error.code(), code, "{error:?}");
+            assert_eq!(to_error_object(error).code(), code);
+        }
+    }
+
+    #[test]
+    fn test_failed_op_data() {
+        let error = EthRpcError::EntryPointValidationRejected("AA21 didn't pay prefund".into());
+        let object = to_error_object(error);
+        assert_eq!(object.code(), -32500);
+        assert_eq!(object.message(), "AA21 didn't pay prefund");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(object.data().unwrap().get()).unwrap(),
+            serde_json::json!({
+                "reason": "AA21 didn't pay prefund",
+                "failedOpCode": "AA21",
+                "description": "account did not pay the required prefund",
+            })
+        );
+
+        let error = EthRpcError::EntryPointValidationRejected("unknown entry point".into());
+        assert!(to_error_object(error).data().is_none());
+    }
+
+    #[test]
+    fn test_estimation_failed_with_trace() {
+        let frame = |typ: &str, calls: Vec<CallFrame>| CallFrame {
+            typ: typ.to_string(),
+            calls: (!calls.is_empty()).then_some(calls),
+            ..Default::default()
+        };
+        let mut root = frame(
+            "CALL",
+            (0..ESTIMATION_TRACE_FRAMES)
+                .map(|_| frame("STATICCALL", vec![frame("STATICCALL", vec![])]))
+                .chain([frame("CALL", vec![])])
+                .collect(),
+        );
+        root.output = Some(vec![0xde, 0xad].into());
+
+        let data = EstimationTraceData::from(root);
+        assert_eq!(data.revert_data, Bytes::from(vec![0xde, 0xad]));
+        assert_eq!(data.call_frames.len(), ESTIMATION_TRACE_FRAMES);
+        // frames are in the order they were entered, ending with the last call
+        let depths = data
+            .call_frames
+            .iter()
+            .map(|frame| frame.depth)
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [2, 1, 2, 1, 2, 1, 2, 1, 2, 1]);
+        assert_eq!(data.call_frames.last().unwrap().typ, "CALL");
+
+        let error = EthRpcError::EstimationFailedWithTrace(
+            Box::new(EthRpcError::EntryPointValidationRejected(
+                "AA23 reverted".into(),
+            )),
+            Box::new(data),
+        );
+        let object = to_error_object(error);
+        assert_eq!(object.code(), ENTRYPOINT_VALIDATION_REJECTED_CODE);
+        assert_eq!(object.message(), "AA23 reverted");
+        let data = serde_json::from_str::<serde_json::Value>(object.data().unwrap().get()).unwrap();
+        assert_eq!(data["revertData"], "0xdead");
+        assert_eq!(data["callFrames"][9]["depth"], 1);
+    }
+
+    #[test]
+    fn test_pool_unavailable() {
+        let error: EthRpcError = PoolServerError::Unavailable("connection refused".into()).into();
+        assert!(matches!(error, EthRpcError::ServiceUnavailable(_)));
+        assert_eq!(to_error_object(error).code(), RESOURCE_UNAVAILABLE_CODE);
+    }
+
+    #[test]
+    fn test_rejection_violation() {
+        let error = PoolServerError::MempoolError(MempoolError::SimulationViolations(vec![
+            SimulationViolation::InvalidSignature,
+            SimulationViolation::AggregatorValidationFailed,
+        ]));
+        assert_eq!(rejection_violation(&error), Some("invalid_signature"));
+
+        let error = PoolServerError::MempoolError(MempoolError::PrecheckViolation(
+            PrecheckViolation::SenderIsNotContractAndNoInitCode(Address::zero()),
+        ));
+        assert_eq!(
+            rejection_violation(&error),
+            Some("sender_is_not_contract_and_no_init_code")
+        );
+
+        let error = PoolServerError::MempoolError(MempoolError::OperationAlreadyKnown);
+        assert_eq!(rejection_violation(&error), None);
+    }
+}
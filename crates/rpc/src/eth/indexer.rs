@@ -0,0 +1,71 @@
+// This file is part of Rundler.
+//
+// Rundler is free software: you can redistribute it and/or modify it under the
+// terms of the GNU Lesser General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// Rundler is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Rundler.
+// If not, see https://www.gnu.org/licenses/.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use ethers::types::H256;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use url::Url;
+
+const INDEXER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for an external service that indexes user operations by hash.
+///
+/// The service is queried with `GET <url>?userOpHash=<hash>` and responds with
+/// `{"transactionHash": <hash or null>}`, or with a 404 if it has not seen the
+/// operation.
+#[derive(Debug)]
+pub(crate) struct IndexerClient {
+    client: reqwest::Client,
+    url: Url,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexerResponse {
+    transaction_hash: Option<H256>,
+}
+
+impl IndexerClient {
+    pub(crate) fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    /// Returns the hash of the transaction that included the user operation, or
+    /// `None` if the indexer has not seen it
+    pub(crate) async fn get_transaction_hash(&self, hash: H256) -> anyhow::Result<Option<H256>> {
+        let resp = self
+            .client
+            .get(self.url.clone())
+            .query(&[("userOpHash", format!("{hash:?}"))])
+            .timeout(INDEXER_REQUEST_TIMEOUT)
+            .send()
+            .await
+            .context("indexer request should succeed")?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let resp = resp
+            .error_for_status()
+            .context("indexer should return a successful status")?
+            .json::<IndexerResponse>()
+            .await
+            .context("should deserialize indexer response")?;
+        Ok(resp.transaction_hash)
+    }
+}
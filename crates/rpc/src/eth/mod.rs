@@ -15,35 +15,79 @@ mod api;
 pub(crate) use api::EthApi;
 pub use api::Settings as EthApiSettings;
 
+mod cache;
+
+mod indexer;
+
 mod error;
 mod server;
 
-use ethers::types::{Address, H256, U64};
+use ethers::types::{Address, H256, U256, U64};
 use jsonrpsee::{core::RpcResult, proc_macros::rpc};
-use rundler_sim::{GasEstimate, UserOperationOptionalGas};
+use rundler_pool::OpMetadata;
+use rundler_sim::{GasEstimate, GasEstimateRange, UserOperationOptionalGas};
 
-use crate::types::{RichUserOperation, RpcUserOperation, UserOperationReceipt};
+use crate::types::{
+    RichUserOperation, RpcDepositInfo, RpcPaymasterBalanceStatus, RpcSimulateValidationResult,
+    RpcUserOperation, RpcValidBlockRange, UserOperationReceipt,
+};
 
 /// Eth API
 #[rpc(client, server, namespace = "eth")]
 #[cfg_attr(test, automock)]
 pub trait EthApi {
     /// Sends a user operation to the pool.
+    ///
+    /// If `expected_block_hash` is given, the operation is only accepted if it
+    /// validates at that block, and is dropped if the chain head moves on before
+    /// it is included. `metadata` tags the operation in the pool, e.g. with its
+    /// source, and has no effect on how it is handled. If `block_range` is given,
+    /// the operation is only bundled within those block numbers, and is dropped
+    /// once the range has passed.
     #[method(name = "sendUserOperation")]
     async fn send_user_operation(
         &self,
         op: RpcUserOperation,
         entry_point: Address,
+        expected_block_hash: Option<H256>,
+        metadata: Option<OpMetadata>,
+        block_range: Option<RpcValidBlockRange>,
     ) -> RpcResult<H256>;
 
     /// Estimates the gas fields for a user operation.
+    ///
+    /// If `trace_on_failure` is true and estimation fails because the operation
+    /// reverts, the operation's simulation is traced and the last call frames
+    /// and revert data are returned as the error's data.
     #[method(name = "estimateUserOperationGas")]
     async fn estimate_user_operation_gas(
         &self,
         op: UserOperationOptionalGas,
         entry_point: Address,
+        trace_on_failure: Option<bool>,
     ) -> RpcResult<GasEstimate>;
 
+    /// Estimates the gas fields for a user operation as a range: the tightest
+    /// limits found, the limits with the configured margins, and a safe upper bound.
+    ///
+    /// Takes the same parameters as `estimateUserOperationGas`.
+    #[method(name = "estimateUserOperationGasRange")]
+    async fn estimate_user_operation_gas_range(
+        &self,
+        op: UserOperationOptionalGas,
+        entry_point: Address,
+        trace_on_failure: Option<bool>,
+    ) -> RpcResult<GasEstimateRange>;
+
+    /// Runs only the entry point's `simulateValidation` for a user operation, without
+    /// the bundler's opcode and storage rules, and returns the decoded validation result.
+    #[method(name = "simulateValidation")]
+    async fn simulate_validation(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> RpcResult<RpcSimulateValidationResult>;
+
     /// Returns the user operation with the given hash.
     #[method(name = "getUserOperationByHash")]
     async fn get_user_operation_by_hash(&self, hash: H256) -> RpcResult<Option<RichUserOperation>>;
@@ -55,6 +99,49 @@ pub trait EthApi {
         hash: H256,
     ) -> RpcResult<Option<UserOperationReceipt>>;
 
+    /// Returns the receipts of the user operations with the given hashes, in order,
+    /// with null for any operation that has not been mined.
+    #[method(name = "getUserOperationReceipts")]
+    async fn get_user_operation_receipts(
+        &self,
+        hashes: Vec<H256>,
+    ) -> RpcResult<Vec<Option<UserOperationReceipt>>>;
+
+    /// Returns the next usable nonce for a sender, accounting for its operations
+    /// pending in the mempool.
+    #[method(name = "getPendingUserOperationNonce")]
+    async fn get_pending_user_operation_nonce(
+        &self,
+        sender: Address,
+        entry_point: Address,
+    ) -> RpcResult<U256>;
+
+    /// Returns the deposit and stake info of an address on an entry point.
+    #[method(name = "getDepositInfo")]
+    async fn get_deposit_info(
+        &self,
+        address: Address,
+        entry_point: Address,
+    ) -> RpcResult<RpcDepositInfo>;
+
+    /// Returns a paymaster's deposit on an entry point along with the total maximum
+    /// gas cost of its operations pending in the mempool.
+    #[method(name = "getPaymasterBalanceStatus")]
+    async fn get_paymaster_balance_status(
+        &self,
+        paymaster: Address,
+        entry_point: Address,
+    ) -> RpcResult<RpcPaymasterBalanceStatus>;
+
+    /// Returns the hash of a user operation for the given entry point on this chain,
+    /// matching the entry point's `getUserOpHash()`.
+    #[method(name = "getUserOperationHash")]
+    async fn get_user_operation_hash(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> RpcResult<H256>;
+
     /// Returns the supported entry points addresses
     #[method(name = "supportedEntryPoints")]
     async fn supported_entry_points(&self) -> RpcResult<Vec<String>>;
@@ -12,14 +12,17 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use async_trait::async_trait;
-use ethers::types::{Address, H256, U64};
+use ethers::types::{Address, H256, U256, U64};
 use jsonrpsee::core::RpcResult;
-use rundler_pool::PoolServer;
+use rundler_pool::{AddOpOptions, OpMetadata, PoolServer};
 use rundler_provider::{EntryPoint, Provider};
-use rundler_sim::{GasEstimate, UserOperationOptionalGas};
+use rundler_sim::{GasEstimate, GasEstimateRange, UserOperationOptionalGas};
 
 use super::{api::EthApi, EthApiServer};
-use crate::types::{RichUserOperation, RpcUserOperation, UserOperationReceipt};
+use crate::types::{
+    RichUserOperation, RpcDepositInfo, RpcPaymasterBalanceStatus, RpcSimulateValidationResult,
+    RpcUserOperation, RpcValidBlockRange, UserOperationReceipt,
+};
 
 #[async_trait]
 impl<P, E, PS> EthApiServer for EthApi<P, E, PS>
@@ -32,16 +35,59 @@ where
         &self,
         op: RpcUserOperation,
         entry_point: Address,
+        expected_block_hash: Option<H256>,
+        metadata: Option<OpMetadata>,
+        block_range: Option<RpcValidBlockRange>,
     ) -> RpcResult<H256> {
-        Ok(EthApi::send_user_operation(self, op, entry_point).await?)
+        Ok(EthApi::send_user_operation(
+            self,
+            op,
+            entry_point,
+            AddOpOptions {
+                expected_block_hash,
+                valid_block_range: block_range.unwrap_or_default().into(),
+                metadata: metadata.unwrap_or_default(),
+            },
+        )
+        .await?)
     }
 
     async fn estimate_user_operation_gas(
         &self,
         op: UserOperationOptionalGas,
         entry_point: Address,
+        trace_on_failure: Option<bool>,
     ) -> RpcResult<GasEstimate> {
-        Ok(EthApi::estimate_user_operation_gas(self, op, entry_point).await?)
+        Ok(EthApi::estimate_user_operation_gas(
+            self,
+            op,
+            entry_point,
+            trace_on_failure.unwrap_or_default(),
+        )
+        .await?)
+    }
+
+    async fn estimate_user_operation_gas_range(
+        &self,
+        op: UserOperationOptionalGas,
+        entry_point: Address,
+        trace_on_failure: Option<bool>,
+    ) -> RpcResult<GasEstimateRange> {
+        Ok(EthApi::estimate_user_operation_gas_range(
+            self,
+            op,
+            entry_point,
+            trace_on_failure.unwrap_or_default(),
+        )
+        .await?)
+    }
+
+    async fn simulate_validation(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> RpcResult<RpcSimulateValidationResult> {
+        Ok(EthApi::simulate_validation(self, op, entry_point).await?)
     }
 
     async fn get_user_operation_by_hash(&self, hash: H256) -> RpcResult<Option<RichUserOperation>> {
@@ -55,6 +101,45 @@ where
         Ok(EthApi::get_user_operation_receipt(self, hash).await?)
     }
 
+    async fn get_user_operation_receipts(
+        &self,
+        hashes: Vec<H256>,
+    ) -> RpcResult<Vec<Option<UserOperationReceipt>>> {
+        Ok(EthApi::get_user_operation_receipts(self, hashes).await?)
+    }
+
+    async fn get_pending_user_operation_nonce(
+        &self,
+        sender: Address,
+        entry_point: Address,
+    ) -> RpcResult<U256> {
+        Ok(EthApi::get_pending_user_operation_nonce(self, sender, entry_point).await?)
+    }
+
+    async fn get_deposit_info(
+        &self,
+        address: Address,
+        entry_point: Address,
+    ) -> RpcResult<RpcDepositInfo> {
+        Ok(EthApi::get_deposit_info(self, address, entry_point).await?)
+    }
+
+    async fn get_paymaster_balance_status(
+        &self,
+        paymaster: Address,
+        entry_point: Address,
+    ) -> RpcResult<RpcPaymasterBalanceStatus> {
+        Ok(EthApi::get_paymaster_balance_status(self, paymaster, entry_point).await?)
+    }
+
+    async fn get_user_operation_hash(
+        &self,
+        op: RpcUserOperation,
+        entry_point: Address,
+    ) -> RpcResult<H256> {
+        Ok(EthApi::get_user_operation_hash(self, op, entry_point).await?)
+    }
+
     async fn supported_entry_points(&self) -> RpcResult<Vec<String>> {
         Ok(EthApi::supported_entry_points(self).await?)
     }
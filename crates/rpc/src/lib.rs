@@ -37,4 +37,4 @@ mod task;
 pub use task::{Args as RpcTaskArgs, RpcTask};
 
 mod types;
-pub use types::{RichUserOperation, RpcUserOperation, UserOperationReceipt};
+pub use types::{RichUserOperation, RpcGasFees, RpcUserOperation, UserOperationReceipt};
@@ -11,9 +11,48 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use std::time::{Duration, Instant};
-
-use jsonrpsee::{helpers::MethodResponseResult, server::logger::Logger};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::FutureExt;
+use jsonrpsee::{
+    helpers::MethodResponseResult,
+    server::{logger::Logger, ConnectionId, MaxResponseSize, MethodCallback, Methods},
+    types::{Id, Params},
+    RpcModule,
+};
+use rundler_utils::eth;
+
+use crate::error::with_request_id_scope;
+
+/// Wraps the module's methods so that the provider requests each one makes are
+/// labeled with its name in provider metrics, see [`eth::with_rpc_method`], and
+/// the errors it returns carry the request's id, see [`with_request_id_scope`]
+pub(crate) fn instrument_methods(module: RpcModule<()>) -> anyhow::Result<Methods> {
+    let mut methods = Methods::new();
+    for name in module.method_names() {
+        let callback = match module.method(name).cloned() {
+            Some(MethodCallback::Async(callback)) => MethodCallback::Async(Arc::new(
+                move |id: Id<'static>,
+                      params: Params<'static>,
+                      conn_id: ConnectionId,
+                      max_response_size: MaxResponseSize| {
+                    let response = callback(id.clone(), params, conn_id, max_response_size);
+                    async move {
+                        eth::with_rpc_method(name, with_request_id_scope(&id, response)).await
+                    }
+                    .boxed()
+                },
+            )),
+            Some(callback) => callback,
+            None => continue,
+        };
+        methods.verify_and_insert(name, callback)?;
+    }
+    Ok(methods)
+}
 
 #[derive(Clone)]
 pub(crate) struct RpcMetricsLogger;
@@ -101,3 +140,48 @@ impl RpcMetrics {
         metrics::histogram!("rpc_request_latency", latency, "method_name" => method_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use jsonrpsee::{core::RpcResult, rpc_params};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::error::{rpc_err, with_request_id};
+
+    #[tokio::test]
+    async fn test_instrument_methods() {
+        let mut module = RpcModule::new(());
+        module
+            .register_async_method("eth_chainId", |_, _| async {
+                RpcResult::Ok(eth::current_rpc_method().to_string())
+            })
+            .unwrap();
+
+        let methods = instrument_methods(module).unwrap();
+        let rpc_method: String = methods.call("eth_chainId", rpc_params![]).await.unwrap();
+        assert_eq!(rpc_method, "eth_chainId");
+    }
+
+    #[tokio::test]
+    async fn test_instrument_methods_error_request_id() {
+        let mut module = RpcModule::new(());
+        module
+            .register_async_method("eth_chainId", |_, _| async {
+                RpcResult::<()>::Err(with_request_id(rpc_err(-32602, "invalid params")).into())
+            })
+            .unwrap();
+
+        let methods = instrument_methods(module).unwrap();
+        let (response, _) = methods
+            .raw_json_request(
+                r#"{"jsonrpc":"2.0","id":7,"method":"eth_chainId","params":[]}"#,
+                1,
+            )
+            .await
+            .unwrap();
+        let response: Value = serde_json::from_str(&response.result).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["error"]["data"]["requestId"], 7);
+    }
+}
@@ -13,31 +13,90 @@
 
 use std::sync::Arc;
 
+use anyhow::Context;
 use async_trait::async_trait;
-use ethers::types::U256;
+use ethers::types::{Address, BlockNumber, H256, U256, U64};
 use jsonrpsee::{core::RpcResult, proc_macros::rpc, types::error::INTERNAL_ERROR_CODE};
+use rundler_pool::PoolServer;
 use rundler_provider::Provider;
-use rundler_sim::{FeeEstimator, PrecheckSettings};
+use rundler_sim::{EstimationSettings, FeeEstimator, PrecheckSettings, SimulationSettings};
+use rundler_types::GasFees;
+use rundler_utils::math;
 
-use crate::error::rpc_err;
+use crate::{
+    error::rpc_err,
+    types::{RpcGasFees, RpcSimulationConfig, RpcSupportedEntryPoint, RpcUserOperationRank},
+};
+
+/// Number of recent blocks sampled when suggesting user operation fees
+const FEE_HISTORY_BLOCKS: u64 = 10;
+/// Priority fee percentile sampled from each block when suggesting user operation fees
+const FEE_HISTORY_PERCENTILE: f64 = 50.0;
+/// Headroom added to the current base fee when suggesting `maxFeePerGas`, enough to
+/// absorb the base fee increase of two consecutive full blocks
+const BASE_FEE_HEADROOM_PERCENT: u64 = 27;
+/// ERC-4337 version of the entry point contracts Rundler supports
+const ENTRY_POINT_VERSION: &str = "0.6.0";
 
 #[rpc(client, server, namespace = "rundler")]
 pub trait RundlerApi {
     /// Returns the maximum priority fee per gas required by Rundler
     #[method(name = "maxPriorityFeePerGas")]
     async fn max_priority_fee_per_gas(&self) -> RpcResult<U256>;
+
+    /// Returns recommended `maxFeePerGas` and `maxPriorityFeePerGas` values for a user
+    /// operation sent to an entry point
+    #[method(name = "suggestUserOperationFees")]
+    async fn suggest_user_operation_fees(&self, entry_point: Address) -> RpcResult<RpcGasFees>;
+
+    /// Returns the number of the last block the mempool for an entry point processed
+    #[method(name = "lastProcessedBlockNumber")]
+    async fn last_processed_block_number(&self, entry_point: Address) -> RpcResult<U64>;
+
+    /// Returns the supported entry points along with their ERC-4337 versions
+    #[method(name = "supportedEntryPoints")]
+    async fn supported_entry_points(&self) -> RpcResult<Vec<RpcSupportedEntryPoint>>;
+
+    /// Returns the position of a user operation in the order the builder selects
+    /// operations for bundles, along with the size of the mempool
+    #[method(name = "getUserOperationRank")]
+    async fn get_user_operation_rank(&self, hash: H256) -> RpcResult<RpcUserOperationRank>;
+
+    /// Returns the validation rules enforced when simulating user operations, such as
+    /// the minimum stake and the opcodes and storage slots allowed or banned
+    #[method(name = "getSimulationConfig")]
+    async fn get_simulation_config(&self) -> RpcResult<RpcSimulationConfig>;
 }
 
-pub(crate) struct RundlerApi<P: Provider> {
+pub(crate) struct RundlerApi<P: Provider, PS> {
+    entry_points: Vec<Address>,
+    provider: Arc<P>,
     fee_estimator: FeeEstimator<P>,
+    pool: PS,
+    sim_settings: SimulationSettings,
+    estimation_settings: EstimationSettings,
 }
 
-impl<P> RundlerApi<P>
+impl<P, PS> RundlerApi<P, PS>
 where
     P: Provider,
+    PS: PoolServer,
 {
-    pub(crate) fn new(provider: Arc<P>, chain_id: u64, settings: PrecheckSettings) -> Self {
+    pub(crate) fn new(
+        provider: Arc<P>,
+        entry_points: Vec<Address>,
+        pool: PS,
+        chain_id: u64,
+        settings: PrecheckSettings,
+        sim_settings: SimulationSettings,
+        estimation_settings: EstimationSettings,
+    ) -> Self {
         Self {
+            entry_points,
+            pool,
+            sim_settings,
+            estimation_settings,
+            provider: Arc::clone(&provider),
             fee_estimator: FeeEstimator::new(
                 provider,
                 chain_id,
@@ -47,12 +106,68 @@ where
             ),
         }
     }
+
+    async fn suggest_fees(&self, entry_point: Address) -> anyhow::Result<GasFees> {
+        let (bundle_fees, sampled_priority_fee, min_fees) = tokio::try_join!(
+            self.fee_estimator.required_bundle_fees(None),
+            self.sample_priority_fee(),
+            async {
+                self.pool
+                    .get_min_fees(entry_point)
+                    .await
+                    .context("should get min fees from pool")
+            },
+        )?;
+
+        // Pay at least what the bundler requires, what recent blocks paid, and the pool's floor
+        let required_fees = self.fee_estimator.required_op_fees(bundle_fees);
+        let max_priority_fee_per_gas = required_fees
+            .max_priority_fee_per_gas
+            .max(sampled_priority_fee)
+            .max(min_fees.max_priority_fee_per_gas);
+
+        let base_fee = bundle_fees.max_fee_per_gas - bundle_fees.max_priority_fee_per_gas;
+        let max_fee_per_gas = (math::increase_by_percent(base_fee, BASE_FEE_HEADROOM_PERCENT)
+            + max_priority_fee_per_gas)
+            .max(min_fees.max_fee_per_gas);
+
+        Ok(GasFees {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        })
+    }
+
+    // Average of the sampled priority fee percentile over recent non-empty blocks
+    async fn sample_priority_fee(&self) -> anyhow::Result<U256> {
+        let fee_history = self
+            .provider
+            .fee_history(
+                FEE_HISTORY_BLOCKS,
+                BlockNumber::Latest,
+                &[FEE_HISTORY_PERCENTILE],
+            )
+            .await?;
+        let rewards = fee_history
+            .reward
+            .iter()
+            .filter_map(|r| r.first())
+            .filter(|r| !r.is_zero())
+            .collect::<Vec<_>>();
+        if rewards.is_empty() {
+            return Ok(U256::zero());
+        }
+        let sum = rewards
+            .iter()
+            .fold(U256::zero(), |sum, r| sum.saturating_add(**r));
+        Ok(sum / rewards.len())
+    }
 }
 
 #[async_trait]
-impl<P> RundlerApiServer for RundlerApi<P>
+impl<P, PS> RundlerApiServer for RundlerApi<P, PS>
 where
     P: Provider,
+    PS: PoolServer,
 {
     async fn max_priority_fee_per_gas(&self) -> RpcResult<U256> {
         let bundle_fees = self
@@ -65,4 +180,282 @@ where
             .required_op_fees(bundle_fees)
             .max_priority_fee_per_gas)
     }
+
+    async fn suggest_user_operation_fees(&self, entry_point: Address) -> RpcResult<RpcGasFees> {
+        Ok(self
+            .suggest_fees(entry_point)
+            .await
+            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?
+            .into())
+    }
+
+    async fn last_processed_block_number(&self, entry_point: Address) -> RpcResult<U64> {
+        let block_number = self
+            .pool
+            .get_last_block_number(entry_point)
+            .await
+            .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
+        Ok(block_number.into())
+    }
+
+    async fn supported_entry_points(&self) -> RpcResult<Vec<RpcSupportedEntryPoint>> {
+        Ok(self
+            .entry_points
+            .iter()
+            .map(|&address| RpcSupportedEntryPoint {
+                address: address.into(),
+                version: ENTRY_POINT_VERSION.to_string(),
+            })
+            .collect())
+    }
+
+    async fn get_user_operation_rank(&self, hash: H256) -> RpcResult<RpcUserOperationRank> {
+        let mut pool_size = 0;
+        for &entry_point in &self.entry_points {
+            let rank = self
+                .pool
+                .get_op_rank(entry_point, hash)
+                .await
+                .map_err(|e| rpc_err(INTERNAL_ERROR_CODE, e.to_string()))?;
+            if let Some(r) = rank.rank {
+                return Ok(RpcUserOperationRank {
+                    rank: Some(r.into()),
+                    pool_size: rank.pool_size.into(),
+                });
+            }
+            pool_size += rank.pool_size;
+        }
+
+        // Not ranked in any mempool, report the size of all of them
+        Ok(RpcUserOperationRank {
+            rank: None,
+            pool_size: pool_size.into(),
+        })
+    }
+
+    async fn get_simulation_config(&self) -> RpcResult<RpcSimulationConfig> {
+        Ok(RpcSimulationConfig::new(
+            &self.sim_settings,
+            &self.estimation_settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ethers::types::{FeeHistory, Opcode};
+    use rundler_pool::{MockPoolServer, OperationRank};
+    use rundler_provider::MockProvider;
+    use rundler_sim::{gas::GasOverheads, GasEstimateMargins};
+
+    use super::*;
+    use crate::types::{RpcAllowedOpcode, RpcBannedStorageSlot};
+
+    fn create_api(min_fees: GasFees) -> RundlerApi<MockProvider, MockPoolServer> {
+        create_api_with_entry_points(min_fees, vec![])
+    }
+
+    fn create_api_with_entry_points(
+        min_fees: GasFees,
+        entry_points: Vec<Address>,
+    ) -> RundlerApi<MockProvider, MockPoolServer> {
+        let mut pool = MockPoolServer::new();
+        pool.expect_get_min_fees().returning(move |_| Ok(min_fees));
+        create_api_with_pool(entry_points, pool)
+    }
+
+    fn create_api_with_pool(
+        entry_points: Vec<Address>,
+        pool: MockPoolServer,
+    ) -> RundlerApi<MockProvider, MockPoolServer> {
+        create_api_with_sim_settings(entry_points, pool, sim_settings(HashSet::new()))
+    }
+
+    fn create_api_with_sim_settings(
+        entry_points: Vec<Address>,
+        pool: MockPoolServer,
+        sim_settings: SimulationSettings,
+    ) -> RundlerApi<MockProvider, MockPoolServer> {
+        let mut provider = MockProvider::new();
+        provider
+            .expect_get_base_fee()
+            .returning(|| Ok(U256::from(100)));
+        provider
+            .expect_get_max_priority_fee()
+            .returning(|| Ok(U256::from(5)));
+        provider.expect_fee_history::<u64>().returning(|_, _, _| {
+            Ok(FeeHistory {
+                base_fee_per_gas: vec![],
+                gas_used_ratio: vec![],
+                oldest_block: U256::zero(),
+                // empty blocks are skipped
+                reward: vec![vec![0.into()], vec![8.into()], vec![12.into()]],
+            })
+        });
+        RundlerApi::new(
+            Arc::new(provider),
+            entry_points,
+            pool,
+            1,
+            PrecheckSettings::default(),
+            sim_settings,
+            EstimationSettings {
+                max_verification_gas: 5_000_000,
+                max_call_gas: 20_000_000,
+                max_simulate_handle_ops_gas: 550_000_000,
+                margins: GasEstimateMargins {
+                    call_gas_limit_percent: 110,
+                    verification_gas_limit_percent: 120,
+                    gas_limit_buffer: 1000,
+                },
+                gas_overheads: GasOverheads::default(),
+            },
+        )
+    }
+
+    fn sim_settings(allowed_opcodes: HashSet<(Address, Opcode)>) -> SimulationSettings {
+        SimulationSettings::new(
+            86400,
+            1_000_000_000_000_000_000,
+            550_000_000,
+            5_000_000,
+            allowed_opcodes,
+            HashSet::from([(Address::zero(), U256::one())]),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_suggest_fees_applies_pool_floor() {
+        let api = create_api(GasFees {
+            max_fee_per_gas: 50.into(),
+            max_priority_fee_per_gas: 20.into(),
+        });
+
+        let fees = api.suggest_fees(Address::random()).await.unwrap();
+        assert_eq!(fees.max_priority_fee_per_gas, 20.into());
+        // base fee of 100 plus 27% headroom, plus the priority fee
+        assert_eq!(fees.max_fee_per_gas, 147.into());
+    }
+
+    #[tokio::test]
+    async fn test_suggest_fees_uses_sampled_priority_fee() {
+        let api = create_api(GasFees {
+            max_fee_per_gas: 1000.into(),
+            max_priority_fee_per_gas: 1.into(),
+        });
+
+        let fees = api.suggest_fees(Address::random()).await.unwrap();
+        assert_eq!(fees.max_priority_fee_per_gas, 10.into());
+        assert_eq!(fees.max_fee_per_gas, 1000.into());
+    }
+
+    #[tokio::test]
+    async fn test_supported_entry_points() {
+        let entry_point = Address::random();
+        let api = create_api_with_entry_points(GasFees::default(), vec![entry_point]);
+
+        let entry_points = RundlerApiServer::supported_entry_points(&api)
+            .await
+            .unwrap();
+        assert_eq!(
+            entry_points,
+            vec![RpcSupportedEntryPoint {
+                address: entry_point.into(),
+                version: "0.6.0".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_operation_rank() {
+        let ep0 = Address::random();
+        let ep1 = Address::random();
+        let hash = H256::random();
+        let mut pool = MockPoolServer::new();
+        pool.expect_get_op_rank().returning(move |ep, _| {
+            Ok(if ep == ep1 {
+                OperationRank {
+                    rank: Some(3),
+                    pool_size: 7,
+                }
+            } else {
+                OperationRank {
+                    rank: None,
+                    pool_size: 2,
+                }
+            })
+        });
+        let api = create_api_with_pool(vec![ep0, ep1], pool);
+
+        let rank = RundlerApiServer::get_user_operation_rank(&api, hash)
+            .await
+            .unwrap();
+        assert_eq!(
+            rank,
+            RpcUserOperationRank {
+                rank: Some(3.into()),
+                pool_size: 7.into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_user_operation_rank_not_found() {
+        let mut pool = MockPoolServer::new();
+        pool.expect_get_op_rank().returning(|_, _| {
+            Ok(OperationRank {
+                rank: None,
+                pool_size: 2,
+            })
+        });
+        let api = create_api_with_pool(vec![Address::random(), Address::random()], pool);
+
+        let rank = RundlerApiServer::get_user_operation_rank(&api, H256::random())
+            .await
+            .unwrap();
+        assert_eq!(
+            rank,
+            RpcUserOperationRank {
+                rank: None,
+                pool_size: 4.into(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_simulation_config() {
+        let entity = Address::random();
+        let api = create_api_with_sim_settings(
+            vec![],
+            MockPoolServer::new(),
+            sim_settings(HashSet::from([(entity, Opcode::GASPRICE)])),
+        );
+
+        let config = RundlerApiServer::get_simulation_config(&api).await.unwrap();
+        assert_eq!(config.min_unstake_delay, 86400);
+        assert_eq!(config.max_verification_gas, 5_000_000.into());
+        assert_eq!(
+            config.allowed_opcodes,
+            vec![RpcAllowedOpcode {
+                entity: entity.into(),
+                opcode: "GASPRICE".to_string(),
+            }]
+        );
+        assert_eq!(
+            config.banned_storage_slots,
+            vec![RpcBannedStorageSlot {
+                address: Address::zero().into(),
+                slot: U256::one(),
+            }]
+        );
+        assert_eq!(config.gas_estimate_margins.call_gas_limit_percent, 110);
+        assert_eq!(
+            config.gas_estimate_margins.verification_gas_limit_percent,
+            120
+        );
+        assert_eq!(config.gas_estimate_margins.gas_limit_buffer, 1000);
+        assert!(config.allowed_aggregators.is_empty());
+    }
 }
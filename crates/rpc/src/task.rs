@@ -26,13 +26,13 @@ use jsonrpsee::{
 use rundler_builder::BuilderServer;
 use rundler_pool::PoolServer;
 use rundler_provider::EntryPoint;
-use rundler_sim::{EstimationSettings, PrecheckSettings};
+use rundler_sim::{EstimationSettings, PrecheckSettings, SimulationSettings};
 use rundler_task::{
     server::{format_socket_addr, HealthCheck},
     Task,
 };
 use rundler_types::contracts::i_entry_point::IEntryPoint;
-use rundler_utils::eth;
+use rundler_utils::eth::{self, FailoverClient, MetricsClient};
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
@@ -40,7 +40,7 @@ use crate::{
     debug::{DebugApi, DebugApiServer},
     eth::{EthApi, EthApiServer, EthApiSettings},
     health::{HealthChecker, SystemApiServer},
-    metrics::RpcMetricsLogger,
+    metrics::{instrument_methods, RpcMetricsLogger},
     rundler::{RundlerApi, RundlerApiServer},
     types::ApiNamespace,
 };
@@ -62,6 +62,8 @@ pub struct Args {
     pub rpc_url: String,
     /// Precheck settings.
     pub precheck_settings: PrecheckSettings,
+    /// Simulation settings, reported by `rundler_getSimulationConfig`.
+    pub sim_settings: SimulationSettings,
     /// eth_ API settings.
     pub eth_api_settings: EthApiSettings,
     /// Estimation settings.
@@ -94,6 +96,14 @@ where
             bail!("No entry points provided");
         }
 
+        // Operation hashes depend on the chain id and the entry points, so a
+        // mismatch would otherwise surface as confusing failures for every operation
+        eth::check_chain(
+            &self.args.rpc_url,
+            self.args.chain_id,
+            &self.args.entry_points,
+        )
+        .await?;
         let provider = eth::new_provider(&self.args.rpc_url, None)?;
         let entry_points = self
             .args
@@ -123,7 +133,7 @@ where
             .http_only()
             .build(addr)
             .await?;
-        let handle = server.start(module);
+        let handle = server.start(instrument_methods(module)?);
 
         info!("Started RPC server");
 
@@ -161,7 +171,7 @@ where
 
     fn attach_namespaces<E: EntryPoint + Clone>(
         &self,
-        provider: Arc<Provider<RetryClient<Http>>>,
+        provider: Arc<Provider<MetricsClient<RetryClient<FailoverClient<Http>>>>>,
         entry_points: Vec<E>,
         module: &mut RpcModule<()>,
     ) -> anyhow::Result<()> {
@@ -173,7 +183,7 @@ where
                         entry_points.clone(),
                         self.args.chain_id,
                         self.pool.clone(),
-                        self.args.eth_api_settings,
+                        self.args.eth_api_settings.clone(),
                         self.args.estimation_settings,
                     )
                     .into_rpc(),
@@ -183,8 +193,12 @@ where
                 ApiNamespace::Rundler => module.merge(
                     RundlerApi::new(
                         provider.clone(),
+                        self.args.entry_points.clone(),
+                        self.pool.clone(),
                         self.args.chain_id,
                         self.args.precheck_settings,
+                        self.args.sim_settings.clone(),
+                        self.args.estimation_settings,
                     )
                     .into_rpc(),
                 )?,
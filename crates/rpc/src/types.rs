@@ -12,11 +12,14 @@
 // If not, see https://www.gnu.org/licenses/.
 
 use ethers::{
-    types::{Address, Bytes, Log, TransactionReceipt, H160, H256, U256},
+    types::{Address, Bytes, Log, TransactionReceipt, H160, H256, U256, U64},
     utils::to_checksum,
 };
-use rundler_pool::{Reputation, ReputationStatus};
-use rundler_types::UserOperation;
+use rundler_pool::{DumpMempoolFilter, Reputation, ReputationStatus, ValidBlockRange};
+use rundler_sim::{
+    EstimationSettings, GasEstimateMargins, SimulateValidationResult, SimulationSettings,
+};
+use rundler_types::{contracts::shared_types::DepositInfo, GasFees, UserOperation};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// API namespace
@@ -129,6 +132,133 @@ pub struct RichUserOperation {
     pub block_hash: H256,
     /// The hash of the transaction this operation was included in
     pub transaction_hash: H256,
+    /// The aggregator that validated this operation, if it was included in an
+    /// aggregated bundle
+    pub aggregator: Option<RpcAddress>,
+}
+
+/// Gas fees for a user operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcGasFees {
+    /// EIP-1559 max fee per gas
+    pub max_fee_per_gas: U256,
+    /// EIP-1559 max priority fee per gas
+    pub max_priority_fee_per_gas: U256,
+}
+
+impl From<GasFees> for RpcGasFees {
+    fn from(fees: GasFees) -> Self {
+        Self {
+            max_fee_per_gas: fees.max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+        }
+    }
+}
+
+/// An entry point supported by the bundler, with its ERC-4337 version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSupportedEntryPoint {
+    /// Entry point address
+    pub address: RpcAddress,
+    /// ERC-4337 version of the entry point, e.g. "0.6.0"
+    pub version: String,
+}
+
+/// The validation rules the bundler enforces when simulating user operations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSimulationConfig {
+    /// Minimum unstake delay, in seconds, for an entity to be considered staked
+    pub min_unstake_delay: u32,
+    /// Minimum stake, in wei, for an entity to be considered staked
+    pub min_stake_value: U256,
+    /// Maximum gas used by the simulation call
+    pub max_simulate_handle_ops_gas: U64,
+    /// Maximum `verificationGasLimit` an operation may have
+    pub max_verification_gas: U64,
+    /// Forbidden opcodes that specific entities are allowed to use
+    pub allowed_opcodes: Vec<RpcAllowedOpcode>,
+    /// Storage slots that operations may not access during validation
+    pub banned_storage_slots: Vec<RpcBannedStorageSlot>,
+    /// Safety margins added to the gas limits returned by `eth_estimateUserOperationGas`
+    pub gas_estimate_margins: GasEstimateMargins,
+    /// Aggregators whose operations are accepted. Operations that use an aggregator
+    /// are not supported yet, so this is always empty.
+    pub allowed_aggregators: Vec<RpcAddress>,
+}
+
+/// A forbidden opcode that an entity is allowed to use
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAllowedOpcode {
+    /// The entity address
+    pub entity: RpcAddress,
+    /// The opcode name, e.g. "GASPRICE"
+    pub opcode: String,
+}
+
+/// A storage slot that operations may not access during validation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBannedStorageSlot {
+    /// The contract address
+    pub address: RpcAddress,
+    /// The storage slot
+    pub slot: U256,
+}
+
+impl RpcSimulationConfig {
+    /// Reports the validation rules from the simulation settings, and the gas
+    /// margins from the estimation settings
+    pub(crate) fn new(settings: &SimulationSettings, estimation: &EstimationSettings) -> Self {
+        let mut allowed_opcodes = settings
+            .allowed_opcodes
+            .iter()
+            .map(|(entity, opcode)| (*entity, format!("{opcode:?}")))
+            .collect::<Vec<_>>();
+        allowed_opcodes.sort();
+        let mut banned_storage_slots = settings
+            .banned_storage_slots
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        banned_storage_slots.sort();
+        Self {
+            min_unstake_delay: settings.min_unstake_delay,
+            min_stake_value: settings.min_stake_value.into(),
+            max_simulate_handle_ops_gas: settings.max_simulate_handle_ops_gas.into(),
+            max_verification_gas: settings.max_verification_gas.into(),
+            allowed_opcodes: allowed_opcodes
+                .into_iter()
+                .map(|(entity, opcode)| RpcAllowedOpcode {
+                    entity: entity.into(),
+                    opcode,
+                })
+                .collect(),
+            banned_storage_slots: banned_storage_slots
+                .into_iter()
+                .map(|(address, slot)| RpcBannedStorageSlot {
+                    address: address.into(),
+                    slot,
+                })
+                .collect(),
+            gas_estimate_margins: estimation.margins,
+            allowed_aggregators: vec![],
+        }
+    }
+}
+
+/// Position of a user operation in the order the builder selects operations for bundles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcUserOperationRank {
+    /// Number of operations that would be bundled before this one, or `None` if the
+    /// operation is not in the mempool or would not currently be bundled
+    pub rank: Option<U64>,
+    /// Total number of operations in the mempool
+    pub pool_size: U64,
 }
 
 /// User operation receipt
@@ -149,6 +279,8 @@ pub struct UserOperationReceipt {
     pub actual_gas_cost: U256,
     /// The gas used by this operation
     pub actual_gas_used: U256,
+    /// The gas price this operation paid per unit of gas
+    pub effective_gas_price: U256,
     /// Whether this operation's execution was successful
     pub success: bool,
     /// If not successful, the revert reason string
@@ -159,6 +291,119 @@ pub struct UserOperationReceipt {
     pub receipt: TransactionReceipt,
 }
 
+/// Result of running only the entry point's `simulateValidation` for an operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSimulateValidationResult {
+    /// Gas used by validation, including pre-verification gas
+    pub pre_op_gas: U256,
+    /// Amount the entry point will charge the payer up front for the operation
+    pub prefund: U256,
+    /// Whether the signature check failed
+    pub sig_failed: bool,
+    /// Time after which the operation is valid
+    pub valid_after: U64,
+    /// Time until which the operation is valid
+    pub valid_until: U64,
+    /// Whether the paymaster returned a context, i.e. `postOp` will be called
+    pub has_paymaster_context: bool,
+}
+
+impl From<SimulateValidationResult> for RpcSimulateValidationResult {
+    fn from(result: SimulateValidationResult) -> Self {
+        Self {
+            pre_op_gas: result.pre_op_gas,
+            prefund: result.prefund,
+            sig_failed: result.sig_failed,
+            valid_after: result.valid_after.seconds_since_epoch().into(),
+            valid_until: result.valid_until.seconds_since_epoch().into(),
+            has_paymaster_context: result.has_paymaster_context,
+        }
+    }
+}
+
+/// Deposit and stake info of an address on an entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcDepositInfo {
+    /// Amount deposited to pay for gas
+    pub deposit: U256,
+    /// Whether the address is currently staked
+    pub staked: bool,
+    /// Amount staked
+    pub stake: U256,
+    /// Delay, in seconds, before the stake can be withdrawn after unlocking
+    pub unstake_delay_sec: U64,
+    /// Time after which an unlocked stake can be withdrawn, zero if not unlocked
+    pub withdraw_time: U64,
+}
+
+impl From<DepositInfo> for RpcDepositInfo {
+    fn from(info: DepositInfo) -> Self {
+        Self {
+            deposit: info.deposit.into(),
+            staked: info.staked,
+            stake: info.stake.into(),
+            unstake_delay_sec: info.unstake_delay_sec.into(),
+            withdraw_time: info.withdraw_time.into(),
+        }
+    }
+}
+
+/// Deposit of a paymaster compared to the maximum cost of its pooled operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcPaymasterBalanceStatus {
+    /// Deposit and stake info of the paymaster on the entry point
+    pub deposit_info: RpcDepositInfo,
+    /// Total maximum gas cost of the paymaster's operations in the mempool
+    pub pending_max_cost: U256,
+    /// Amount by which the pending maximum cost exceeds the deposit, zero if
+    /// the deposit covers it
+    pub shortfall: U256,
+}
+
+/// Filters for `debug_bundler_dumpMempool`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcDumpMempoolFilter {
+    /// Only return operations with at least this `maxFeePerGas`
+    pub min_max_fee_per_gas: Option<U256>,
+    /// Only return operations from this sender
+    pub sender: Option<Address>,
+    /// Maximum number of operations to return
+    pub limit: Option<U64>,
+}
+
+impl From<RpcDumpMempoolFilter> for DumpMempoolFilter {
+    fn from(filter: RpcDumpMempoolFilter) -> Self {
+        Self {
+            min_max_fee_per_gas: filter.min_max_fee_per_gas,
+            sender: filter.sender,
+            limit: filter.limit.map(|l| l.as_u64()),
+        }
+    }
+}
+
+/// Block numbers a user operation may be bundled in, for `eth_sendUserOperation`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcValidBlockRange {
+    /// Don't bundle the operation before this block
+    pub min_block: Option<U64>,
+    /// Don't bundle the operation after this block
+    pub max_block: Option<U64>,
+}
+
+impl From<RpcValidBlockRange> for ValidBlockRange {
+    fn from(range: RpcValidBlockRange) -> Self {
+        Self {
+            min_block: range.min_block.map(|b| b.as_u64()),
+            max_block: range.max_block.map(|b| b.as_u64()),
+        }
+    }
+}
+
 /// Reputation of an entity
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RpcReputation {
@@ -172,14 +417,26 @@ pub struct RpcReputation {
     pub status: ReputationStatus,
 }
 
-impl From<RpcReputation> for Reputation {
-    fn from(rpc_reputation: RpcReputation) -> Self {
-        Reputation {
+impl TryFrom<RpcReputation> for Reputation {
+    type Error = anyhow::Error;
+
+    fn try_from(rpc_reputation: RpcReputation) -> Result<Self, Self::Error> {
+        if rpc_reputation.address.is_zero() {
+            anyhow::bail!("address must not be zero");
+        }
+        if rpc_reputation.ops_seen > u64::MAX.into() {
+            anyhow::bail!("opsSeen must fit in a u64");
+        }
+        if rpc_reputation.ops_included > u64::MAX.into() {
+            anyhow::bail!("opsIncluded must fit in a u64");
+        }
+
+        Ok(Reputation {
             address: rpc_reputation.address,
             ops_seen: rpc_reputation.ops_seen.as_u64(),
             ops_included: rpc_reputation.ops_included.as_u64(),
             status: rpc_reputation.status,
-        }
+        })
     }
 }
 
@@ -18,7 +18,10 @@ use ethers::{
     abi::AbiDecode,
     contract::EthCall,
     providers::spoof,
-    types::{Address, Bytes, H256, U256},
+    types::{
+        Address, Bytes, Eip1559TransactionRequest, GethDebugBuiltInTracerType, GethDebugTracerType,
+        GethDebugTracingCallOptions, GethDebugTracingOptions, GethTrace, H256, U256,
+    },
 };
 #[cfg(feature = "test-utils")]
 use mockall::automock;
@@ -38,8 +41,14 @@ use rundler_types::{
 use rundler_utils::{eth, math};
 use tokio::join;
 
-use super::types::{GasEstimate, Settings, UserOperationOptionalGas};
-use crate::{gas, precheck::MIN_CALL_GAS_LIMIT, utils};
+use super::types::{
+    GasEstimate, GasEstimateMargins, GasEstimateRange, Settings, UserOperationOptionalGas,
+};
+use crate::{
+    gas::{self, PreVerificationGasComponents},
+    precheck::MIN_CALL_GAS_LIMIT,
+    utils,
+};
 
 /// Gas estimates will be rounded up to the next multiple of this. Increasing
 /// this value reduces the number of rounds of `eth_call` needed in binary
@@ -53,6 +62,10 @@ const GAS_ESTIMATION_ERROR_MARGIN: f64 = 0.1;
 
 const VERIFICATION_GAS_BUFFER_PERCENT: u64 = 10;
 
+/// Percentage points added to the configured margins for the upper bound of a
+/// gas estimate range, e.g. a configured `120` becomes `170`.
+const MAX_ESTIMATE_EXTRA_PERCENT: u64 = 50;
+
 /// This accounts for the gas used during a transfer to the entrypoint contract
 /// As well as the cost to initialize a previously 0 storage slot for an account
 /// that has yet to deposit.
@@ -65,6 +78,14 @@ const GAS_FEE_TRANSFER_COST: u64 = 30000;
 /// failure will tell you the new value.
 const PROXY_TARGET_OFFSET: usize = 137;
 
+/// Prefix of the entry point's revert reason when a paymaster's `postOp`
+/// reverts in both the normal and the `postOpReverted` modes.
+const POST_OP_REVERTED_PREFIX: &str = "AA50";
+
+/// Prefix of the entry point's revert reasons for failures while deploying the
+/// sender from its `initCode`, e.g. `AA13 initCode failed or OOG`.
+const ACCOUNT_DEPLOYMENT_FAILED_PREFIX: &str = "AA1";
+
 /// Error type for gas estimation
 #[derive(Debug, thiserror::Error)]
 pub enum GasEstimationError {
@@ -77,6 +98,13 @@ pub enum GasEstimationError {
     /// Call reverted with bytes
     #[error("user operation's call reverted: {0:#x}")]
     RevertInCallWithBytes(Bytes),
+    /// Paymaster's postOp reverted when executing the full operation
+    #[error("paymaster's postOp reverted: {0}")]
+    RevertInPostOp(String),
+    /// Deploying the sender from the operation's initCode failed, so neither
+    /// verification nor call gas can be estimated
+    #[error("account deployment from initCode failed: {0}")]
+    RevertInAccountDeployment(String),
     /// Other error
     #[error(transparent)]
     Other(#[from] anyhow::Error),
@@ -92,6 +120,14 @@ pub trait GasEstimator: Send + Sync + 'static {
         &self,
         op: UserOperationOptionalGas,
     ) -> Result<GasEstimate, GasEstimationError>;
+
+    /// Returns a range of gas estimates: the tightest limits found by the search,
+    /// the limits with the configured margins (as returned by `estimate_op_gas`),
+    /// and a safe upper bound with larger margins.
+    async fn estimate_op_gas_range(
+        &self,
+        op: UserOperationOptionalGas,
+    ) -> Result<GasEstimateRange, GasEstimationError>;
 }
 
 /// Gas estimator implementation
@@ -109,18 +145,33 @@ impl<P: Provider, E: EntryPoint> GasEstimator for GasEstimatorImpl<P, E> {
         &self,
         op: UserOperationOptionalGas,
     ) -> Result<GasEstimate, GasEstimationError> {
+        self.estimate_op_gas_range(op)
+            .await
+            .map(|range| range.recommended)
+    }
+
+    async fn estimate_op_gas_range(
+        &self,
+        op: UserOperationOptionalGas,
+    ) -> Result<GasEstimateRange, GasEstimationError> {
         let Self {
             provider, settings, ..
         } = self;
 
-        let block_hash = provider
-            .get_latest_block_hash()
+        // Pin all estimation calls to the same block so that they see consistent
+        // state, and report that block back to the caller
+        let (block_hash, block_number) = provider
+            .get_latest_block_hash_and_number()
             .await
             .map_err(anyhow::Error::from)?;
 
         // Estimate pre verification gas
         let pre_verification_gas = self.calc_pre_verification_gas(&op).await?;
 
+        // Keep the caller's fees for the full execution check below
+        let (max_fee_per_gas, max_priority_fee_per_gas) =
+            (op.max_fee_per_gas, op.max_priority_fee_per_gas);
+
         // We deviate from the spec here always ignoring `max_fee_per_gas` and setting to zero.
         // If not using a paymaster, the bundler will automatically add 21K to the verification
         // gas limit to account for the gas fee transfer.
@@ -150,14 +201,67 @@ impl<P: Provider, E: EntryPoint> GasEstimator for GasEstimatorImpl<P, E> {
             return Err(GasEstimationError::RevertInValidation(err));
         }
 
-        Ok(GasEstimate {
-            pre_verification_gas,
-            verification_gas_limit: math::increase_by_percent(
+        let estimate =
+            |(verification_gas_limit, call_gas_limit): (U256, U256), margins| GasEstimate {
+                pre_verification_gas,
+                verification_gas_limit,
+                call_gas_limit,
+                block_number,
+                block_hash,
+                margins,
+            };
+
+        // The minimum is the success boundary of the searches, without any margins
+        let min = estimate(
+            self.apply_margins(
                 verification_gas_limit,
-                VERIFICATION_GAS_BUFFER_PERCENT,
+                call_gas_limit,
+                GasEstimateMargins::NONE,
+            ),
+            GasEstimateMargins::NONE,
+        );
+
+        let verification_gas_limit =
+            math::increase_by_percent(verification_gas_limit, VERIFICATION_GAS_BUFFER_PERCENT);
+        let recommended = estimate(
+            self.apply_margins(verification_gas_limit, call_gas_limit, settings.margins),
+            settings.margins,
+        );
+
+        // Verification and call gas are estimated separately, so check that the
+        // whole operation, including any paymaster postOp, executes with them.
+        let (max_fee_per_gas, max_priority_fee_per_gas) =
+            full_execution_fees(max_fee_per_gas, max_priority_fee_per_gas);
+        let gas_used = self
+            .simulate_full_execution(
+                &UserOperation {
+                    verification_gas_limit: recommended.verification_gas_limit,
+                    call_gas_limit: recommended.call_gas_limit,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    ..op
+                },
+                block_hash,
             )
-            .min(settings.max_verification_gas.into()),
-            call_gas_limit: call_gas_limit.clamp(MIN_CALL_GAS_LIMIT, settings.max_call_gas.into()),
+            .await?;
+        tracing::debug!("full execution of estimated op used {gas_used} gas");
+
+        let max_margins = GasEstimateMargins {
+            call_gas_limit_percent: settings.margins.call_gas_limit_percent
+                + MAX_ESTIMATE_EXTRA_PERCENT,
+            verification_gas_limit_percent: settings.margins.verification_gas_limit_percent
+                + MAX_ESTIMATE_EXTRA_PERCENT,
+            gas_limit_buffer: settings.margins.gas_limit_buffer,
+        };
+        let max = estimate(
+            self.apply_margins(verification_gas_limit, call_gas_limit, max_margins),
+            max_margins,
+        );
+
+        Ok(GasEstimateRange {
+            min,
+            recommended,
+            max,
         })
     }
 }
@@ -173,6 +277,26 @@ impl<P: Provider, E: EntryPoint> GasEstimatorImpl<P, E> {
         }
     }
 
+    /// Applies the margins to the estimated verification and call gas limits,
+    /// keeping them within the configured bounds.
+    fn apply_margins(
+        &self,
+        verification_gas_limit: U256,
+        call_gas_limit: U256,
+        margins: GasEstimateMargins,
+    ) -> (U256, U256) {
+        let verification_gas_limit = math::percent(
+            verification_gas_limit,
+            margins.verification_gas_limit_percent,
+        ) + margins.gas_limit_buffer;
+        let call_gas_limit = math::percent(call_gas_limit, margins.call_gas_limit_percent)
+            + margins.gas_limit_buffer;
+        (
+            verification_gas_limit.min(self.settings.max_verification_gas.into()),
+            call_gas_limit.clamp(MIN_CALL_GAS_LIMIT, self.settings.max_call_gas.into()),
+        )
+    }
+
     async fn binary_search_verification_gas(
         &self,
         op: &UserOperation,
@@ -196,6 +320,7 @@ impl<P: Provider, E: EntryPoint> GasEstimatorImpl<P, E> {
                 i_entry_point::SimulateHandleOpCall::selector(),
                 (initial_op, Address::zero(), Bytes::new()),
             ),
+            Some(block_hash.into()),
         )
         .await
         .context("failed to run initial guess")?;
@@ -209,7 +334,7 @@ impl<P: Provider, E: EntryPoint> GasEstimatorImpl<P, E> {
             .decode_simulate_handle_ops_revert(gas_used.result)
             .err()
         {
-            return Err(GasEstimationError::RevertInValidation(message));
+            return Err(validation_error(op, message));
         }
 
         let run_attempt_returning_error = |gas: u64| async move {
@@ -324,7 +449,16 @@ impl<P: Provider, E: EntryPoint> GasEstimatorImpl<P, E> {
                     &spoofed_state,
                 )
                 .await?
-                .map_err(GasEstimationError::RevertInCallWithMessage)?
+                .map_err(|message| {
+                    // The target call is only made once the sender is
+                    // deployed, so report a failed deployment as such
+                    // instead of as a reverted call
+                    if is_account_deployment_failure(op, &message) {
+                        GasEstimationError::RevertInAccountDeployment(message)
+                    } else {
+                        GasEstimationError::RevertInCallWithMessage(message)
+                    }
+                })?
                 .target_result;
             if let Ok(result) = EstimateCallGasResult::decode(&target_revert_data) {
                 num_rounds += result.num_rounds;
@@ -365,21 +499,138 @@ impl<P: Provider, E: EntryPoint> GasEstimatorImpl<P, E> {
         }
     }
 
+    /// Runs the entry point's `simulateHandleOp` on the operation with its gas
+    /// limits as given, executing validation, the call and any paymaster
+    /// `postOp` together, and returns the total gas used.
+    pub async fn simulate_full_execution(
+        &self,
+        op: &UserOperation,
+        block_hash: H256,
+    ) -> Result<U256, GasEstimationError> {
+        let gas_used = utils::get_gas_used(
+            self.provider.deref(),
+            self.entry_point.address(),
+            U256::zero(),
+            utils::call_data_of(
+                i_entry_point::SimulateHandleOpCall::selector(),
+                (op.clone(), Address::zero(), Bytes::new()),
+            ),
+            Some(block_hash.into()),
+        )
+        .await
+        .context("failed to simulate full execution")?;
+        if gas_used.success {
+            Err(anyhow!(
+                "simulateHandleOp succeeded, but should always revert"
+            ))?;
+        }
+        match self
+            .entry_point
+            .decode_simulate_handle_ops_revert(gas_used.result)
+        {
+            Ok(_) => Ok(gas_used.gas_used),
+            Err(message) if message.starts_with(POST_OP_REVERTED_PREFIX) => {
+                Err(GasEstimationError::RevertInPostOp(message))
+            }
+            Err(message) => Err(validation_error(op, message)),
+        }
+    }
+
+    /// Runs the entry point's `simulateHandleOp` on the operation with the gas
+    /// limits estimation starts from, under the call tracer, so that a failed
+    /// estimation can be debugged from the call frames leading up to the revert.
+    pub async fn trace_op_simulation(
+        &self,
+        op: UserOperationOptionalGas,
+    ) -> anyhow::Result<GethTrace> {
+        let op = UserOperation {
+            verification_gas_limit: self.settings.max_verification_gas.into(),
+            call_gas_limit: self.settings.max_call_gas.into(),
+            max_fee_per_gas: 0.into(),
+            max_priority_fee_per_gas: 0.into(),
+            ..op.into_user_operation(&self.settings)
+        };
+        let tx = Eip1559TransactionRequest::new()
+            .to(self.entry_point.address())
+            .gas(self.settings.max_simulate_handle_ops_gas)
+            .data(utils::call_data_of(
+                i_entry_point::SimulateHandleOpCall::selector(),
+                (op, Address::zero(), Bytes::new()),
+            ));
+
+        Ok(self
+            .provider
+            .debug_trace_call(
+                tx.into(),
+                None,
+                GethDebugTracingCallOptions {
+                    tracing_options: GethDebugTracingOptions {
+                        tracer: Some(GethDebugTracerType::BuiltInTracer(
+                            GethDebugBuiltInTracerType::CallTracer,
+                        )),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .await
+            .context("failed to trace operation simulation")?)
+    }
+
     async fn calc_pre_verification_gas(
         &self,
         op: &UserOperationOptionalGas,
     ) -> Result<U256, GasEstimationError> {
-        Ok(gas::calc_pre_verification_gas(
+        Ok(self.calc_pre_verification_gas_components(op).await?.total())
+    }
+
+    /// Returns the pre_verification_gas for the given user operation with the L1
+    /// data fee portion broken out from the L2 portion.
+    pub async fn calc_pre_verification_gas_components(
+        &self,
+        op: &UserOperationOptionalGas,
+    ) -> Result<PreVerificationGasComponents, GasEstimationError> {
+        Ok(gas::calc_pre_verification_gas_components(
             &op.max_fill(&self.settings),
             &op.random_fill(&self.settings),
             self.entry_point.address(),
             self.provider.clone(),
             self.chain_id,
+            &self.settings.gas_overheads,
         )
         .await?)
     }
 }
 
+/// Whether a `FailedOp` reason is the entry point failing to deploy an
+/// undeployed sender from its `initCode`.
+/// Returns the fees to use for the full execution check: the caller's fees where
+/// given, so that a paymaster's postOp sees the gas cost the op would pay, and
+/// otherwise zero like the searches, so that a sender that hasn't been funded
+/// yet can still be estimated.
+fn full_execution_fees(
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+) -> (U256, U256) {
+    let max_fee_per_gas = max_fee_per_gas.unwrap_or_default();
+    let max_priority_fee_per_gas = max_priority_fee_per_gas
+        .unwrap_or(max_fee_per_gas)
+        .min(max_fee_per_gas);
+    (max_fee_per_gas, max_priority_fee_per_gas)
+}
+
+fn is_account_deployment_failure(op: &UserOperation, message: &str) -> bool {
+    !op.init_code.is_empty() && message.starts_with(ACCOUNT_DEPLOYMENT_FAILED_PREFIX)
+}
+
+fn validation_error(op: &UserOperation, message: String) -> GasEstimationError {
+    if is_account_deployment_failure(op, &message) {
+        GasEstimationError::RevertInAccountDeployment(message)
+    } else {
+        GasEstimationError::RevertInValidation(message)
+    }
+}
+
 /// Replaces the address of the proxy target where it appears in the proxy
 /// bytecode so we don't need the same fixed address every time.
 fn estimation_proxy_bytecode_with_target(target: Address) -> Bytes {
@@ -393,7 +644,7 @@ mod tests {
     use ethers::{
         abi::{AbiEncode, Address},
         providers::JsonRpcError,
-        types::Chain,
+        types::{Chain, U64},
         utils::hex,
     };
     use rundler_provider::{MockEntryPoint, MockProvider, ProviderError};
@@ -425,6 +676,8 @@ mod tests {
             max_verification_gas: 10000000000,
             max_call_gas: 10000000000,
             max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
         };
 
         let estimator: GasEstimatorImpl<MockProvider, MockEntryPoint> =
@@ -506,6 +759,45 @@ mod tests {
         assert_eq!(result + dynamic_gas, estimation);
     }
 
+    #[tokio::test]
+    async fn test_calc_pre_verification_input_custom_overheads() {
+        let (mut entry, provider) = create_base_config();
+        entry.expect_address().return_const(Address::zero());
+
+        let settings = Settings {
+            max_verification_gas: 10000000000,
+            max_call_gas: 10000000000,
+            max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads {
+                transaction_gas_overhead: 42_000.into(),
+                per_user_op: 20_000.into(),
+                zero_byte: 8.into(),
+                ..Default::default()
+            },
+        };
+        let estimator: GasEstimatorImpl<MockProvider, MockEntryPoint> =
+            GasEstimatorImpl::new(0, Arc::new(provider), entry, settings);
+
+        let user_op = demo_user_op_optional_gas();
+        let estimation = estimator.calc_pre_verification_gas(&user_op).await.unwrap();
+
+        let u_o = user_op.max_fill(&settings);
+
+        let u_o_encoded = u_o.encode();
+        let length_in_words = (u_o_encoded.len() + 31) / 32;
+        let zero_bytes = u_o_encoded.iter().filter(|&&b| b == 0).count();
+        let non_zero_bytes = u_o_encoded.len() - zero_bytes;
+        let call_data_cost = 8 * zero_bytes + 16 * non_zero_bytes;
+
+        let result = U256::from(42_000) / U256::from(BUNDLE_SIZE)
+            + call_data_cost
+            + U256::from(20_000)
+            + U256::from(PER_USER_OP_WORD) * length_in_words;
+
+        assert_eq!(result, estimation);
+    }
+
     #[tokio::test]
     async fn test_calc_pre_verification_input_arbitrum() {
         let (mut entry, mut provider) = create_base_config();
@@ -518,6 +810,8 @@ mod tests {
             max_verification_gas: 10000000000,
             max_call_gas: 10000000000,
             max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
         };
 
         // Chose arbitrum
@@ -548,6 +842,45 @@ mod tests {
         assert_eq!(result + dynamic_gas, estimation);
     }
 
+    #[tokio::test]
+    async fn test_calc_pre_verification_components_arbitrum() {
+        let (mut entry, mut provider) = create_base_config();
+        entry.expect_address().return_const(Address::zero());
+        provider
+            .expect_calc_arbitrum_l1_gas()
+            .returning(|_a, _b| Ok(U256::from(1000)));
+
+        let settings = Settings {
+            max_verification_gas: 10000000000,
+            max_call_gas: 10000000000,
+            max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
+        };
+
+        let estimator: GasEstimatorImpl<MockProvider, MockEntryPoint> =
+            GasEstimatorImpl::new(Chain::Arbitrum as u64, Arc::new(provider), entry, settings);
+
+        let user_op = demo_user_op_optional_gas();
+        let components = estimator
+            .calc_pre_verification_gas_components(&user_op)
+            .await
+            .unwrap();
+        let total = estimator.calc_pre_verification_gas(&user_op).await.unwrap();
+
+        let u_o = user_op.max_fill(&settings);
+        let length_in_words = (u_o.encode().len() + 31) / 32;
+        let call_data_cost = 3936;
+        let static_gas = U256::from(FIXED) / U256::from(BUNDLE_SIZE)
+            + call_data_cost
+            + U256::from(PER_USER_OP)
+            + U256::from(PER_USER_OP_WORD) * length_in_words;
+
+        assert_eq!(components.l1_gas, U256::from(1000));
+        assert_eq!(components.l2_gas, static_gas);
+        assert_eq!(components.total(), total);
+    }
+
     #[tokio::test]
     async fn test_calc_pre_verification_input_op() {
         let (mut entry, mut provider) = create_base_config();
@@ -561,6 +894,8 @@ mod tests {
             max_verification_gas: 10000000000,
             max_call_gas: 10000000000,
             max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
         };
 
         // Chose OP
@@ -829,6 +1164,46 @@ mod tests {
         assert!(estimation.is_err());
     }
 
+    #[tokio::test]
+    async fn test_binary_search_verification_gas_deployment_failed() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| Err(String::from("AA13 initCode failed or OOG")));
+        expect_gas_used_call(&mut provider, 20000);
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let user_op = UserOperation {
+            init_code: Bytes::from(vec![1; 20]),
+            ..demo_user_op()
+        };
+        let estimation = estimator
+            .binary_search_verification_gas(&user_op, H256::zero())
+            .await
+            .err()
+            .unwrap();
+
+        assert!(matches!(
+            estimation,
+            GasEstimationError::RevertInAccountDeployment(message) if message.starts_with("AA13")
+        ));
+
+        // Without initCode the same reason is a plain validation failure
+        let user_op = demo_user_op();
+        let estimation = estimator
+            .binary_search_verification_gas(&user_op, H256::zero())
+            .await
+            .err()
+            .unwrap();
+
+        assert!(matches!(
+            estimation,
+            GasEstimationError::RevertInValidation(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_binary_search_verification_gas_invalid_spoof() {
         let (mut entry, mut provider) = create_base_config();
@@ -999,6 +1374,38 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_estimate_call_gas_deployment_failed() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_call_spoofed_simulate_op()
+            .returning(|_a, _b, _c, _d, _e, _f| {
+                Ok(Err(String::from("AA13 initCode failed or OOG")))
+            });
+
+        provider
+            .expect_get_code()
+            .returning(|_a, _b| Ok(Bytes::new()));
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let user_op = UserOperation {
+            init_code: Bytes::from(vec![1; 20]),
+            ..demo_user_op()
+        };
+        let estimation = estimator
+            .estimate_call_gas(&user_op, H256::zero())
+            .await
+            .err()
+            .unwrap();
+
+        assert!(matches!(
+            estimation,
+            GasEstimationError::RevertInAccountDeployment(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_estimate_call_gas_continuation() {
         let (mut entry, mut provider) = create_base_config();
@@ -1089,8 +1496,8 @@ mod tests {
             .expect_get_code()
             .returning(|_a, _b| Ok(Bytes::new()));
         provider
-            .expect_get_latest_block_hash()
-            .returning(|| Ok(H256::zero()));
+            .expect_get_latest_block_hash_and_number()
+            .returning(|| Ok((H256::zero(), U64::from(10))));
         provider.expect_call().returning(|_a, _b| {
             let result_data: Bytes = GasUsedResult {
                 gas_used: U256::from(100000),
@@ -1122,6 +1529,401 @@ mod tests {
 
         // input gas limit clamped with the set limit in settings and constant MIN
         assert_eq!(estimation.call_gas_limit, U256::from(10000));
+
+        // the block the estimate was pinned to is reported
+        assert_eq!(estimation.block_number, U64::from(10));
+        assert_eq!(estimation.block_hash, H256::zero());
+    }
+
+    #[tokio::test]
+    async fn test_estimation_applies_margins() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_call_spoofed_simulate_op()
+            .returning(|_a, _b, _c, _d, _e, _f| {
+                Ok(Ok(ExecutionResult {
+                    target_result: EstimateCallGasResult {
+                        gas_estimate: U256::from(10000),
+                        num_rounds: U256::from(10),
+                    }
+                    .encode()
+                    .into(),
+                    target_success: true,
+                    ..Default::default()
+                }))
+            });
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| {
+                Ok(ExecutionResult {
+                    pre_op_gas: U256::from(10000),
+                    paid: U256::from(100000),
+                    valid_after: 100000000000,
+                    valid_until: 100000000001,
+                    target_success: true,
+                    target_result: Bytes::new(),
+                })
+            });
+
+        provider
+            .expect_get_code()
+            .returning(|_a, _b| Ok(Bytes::new()));
+        provider
+            .expect_get_latest_block_hash_and_number()
+            .returning(|| Ok((H256::zero(), U64::from(10))));
+        provider.expect_call().returning(|_a, _b| {
+            let result_data: Bytes = GasUsedResult {
+                gas_used: U256::from(100000),
+                success: false,
+                result: Bytes::new(),
+            }
+            .encode()
+            .into();
+
+            let json_rpc_error = JsonRpcError {
+                code: -32000,
+                message: "execution reverted".to_string(),
+                data: Some(serde_json::Value::String(result_data.to_string())),
+            };
+            Err(ProviderError::JsonRpcError(json_rpc_error))
+        });
+
+        let settings = Settings {
+            max_verification_gas: 10000000000,
+            max_call_gas: 10000000000,
+            max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins {
+                call_gas_limit_percent: 150,
+                verification_gas_limit_percent: 120,
+                gas_limit_buffer: 1000,
+            },
+            gas_overheads: gas::GasOverheads::default(),
+        };
+        let estimator: GasEstimatorImpl<MockProvider, MockEntryPoint> =
+            GasEstimatorImpl::new(0, Arc::new(provider), entry, settings);
+
+        let estimation = estimator
+            .estimate_op_gas(demo_user_op_optional_gas())
+            .await
+            .unwrap();
+
+        // 33000 from the default estimate, scaled by 120% plus the buffer
+        assert_eq!(estimation.verification_gas_limit, U256::from(40600));
+
+        // 10000 from the default estimate, scaled by 150% plus the buffer
+        assert_eq!(estimation.call_gas_limit, U256::from(16000));
+
+        assert_eq!(estimation.margins, settings.margins);
+
+        let range = estimator
+            .estimate_op_gas_range(demo_user_op_optional_gas())
+            .await
+            .unwrap();
+
+        // the minimum is the search result without the verification buffer or margins
+        assert_eq!(range.min.verification_gas_limit, U256::from(30000));
+        assert_eq!(range.min.call_gas_limit, U256::from(10000));
+        assert_eq!(range.min.margins, GasEstimateMargins::NONE);
+
+        assert_eq!(range.recommended.verification_gas_limit, U256::from(40600));
+        assert_eq!(range.recommended.call_gas_limit, U256::from(16000));
+        assert_eq!(range.recommended.margins, settings.margins);
+
+        // 33000 scaled by 170% plus the buffer
+        assert_eq!(range.max.verification_gas_limit, U256::from(57100));
+        // 10000 scaled by 200% plus the buffer
+        assert_eq!(range.max.call_gas_limit, U256::from(21000));
+        assert_eq!(range.max.margins.call_gas_limit_percent, 200);
+        assert_eq!(range.max.margins.verification_gas_limit_percent, 170);
+
+        // all estimates share the pre verification gas and block
+        assert_eq!(
+            range.min.pre_verification_gas,
+            range.max.pre_verification_gas
+        );
+        assert_eq!(range.min.block_hash, range.max.block_hash);
+    }
+
+    #[tokio::test]
+    async fn test_estimation_pins_calls_to_one_block() {
+        let (mut entry, mut provider) = create_base_config();
+        let block_hash = H256::random();
+
+        entry.expect_address().return_const(Address::zero());
+        // both the verification and call gas searches run against the pinned block
+        entry
+            .expect_call_spoofed_simulate_op()
+            .withf(move |_, _, _, hash, _, _| *hash == block_hash)
+            .returning(|_a, _b, _c, _d, _e, _f| {
+                Ok(Ok(ExecutionResult {
+                    target_result: EstimateCallGasResult {
+                        gas_estimate: U256::from(10000),
+                        num_rounds: U256::from(10),
+                    }
+                    .encode()
+                    .into(),
+                    target_success: true,
+                    ..Default::default()
+                }))
+            });
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| Ok(ExecutionResult::default()));
+
+        provider
+            .expect_get_code()
+            .withf(move |_, hash| *hash == Some(block_hash))
+            .returning(|_a, _b| Ok(Bytes::new()));
+        provider
+            .expect_get_latest_block_hash_and_number()
+            .times(1)
+            .returning(move || Ok((block_hash, U64::from(10))));
+        // the initial verification gas guess runs against the pinned block
+        provider
+            .expect_call()
+            .withf(move |_, block| *block == Some(block_hash.into()))
+            .returning(|_a, _b| {
+                let result_data: Bytes = GasUsedResult {
+                    gas_used: U256::from(100000),
+                    success: false,
+                    result: Bytes::new(),
+                }
+                .encode()
+                .into();
+
+                let json_rpc_error = JsonRpcError {
+                    code: -32000,
+                    message: "execution reverted".to_string(),
+                    data: Some(serde_json::Value::String(result_data.to_string())),
+                };
+                Err(ProviderError::JsonRpcError(json_rpc_error))
+            });
+
+        let (estimator, _) = create_estimator(entry, provider);
+
+        let estimation = estimator
+            .estimate_op_gas(demo_user_op_optional_gas())
+            .await
+            .unwrap();
+        assert_eq!(estimation.block_hash, block_hash);
+        assert_eq!(estimation.block_number, U64::from(10));
+    }
+
+    fn expect_gas_used_call(provider: &mut MockProvider, gas_used: u64) {
+        provider.expect_call().returning(move |_a, _b| {
+            let result_data: Bytes = GasUsedResult {
+                gas_used: U256::from(gas_used),
+                success: false,
+                result: Bytes::new(),
+            }
+            .encode()
+            .into();
+
+            let json_rpc_error = JsonRpcError {
+                code: -32000,
+                message: "execution reverted".to_string(),
+                data: Some(serde_json::Value::String(result_data.to_string())),
+            };
+            Err(ProviderError::JsonRpcError(json_rpc_error))
+        });
+    }
+
+    #[tokio::test]
+    async fn test_trace_op_simulation() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        provider
+            .expect_debug_trace_call()
+            .withf(|tx, block_id, options| {
+                tx.to_addr() == Some(&Address::zero())
+                    && tx.data().is_some_and(|data| {
+                        data.starts_with(&i_entry_point::SimulateHandleOpCall::selector())
+                    })
+                    && block_id.is_none()
+                    && matches!(
+                        options.tracing_options.tracer,
+                        Some(GethDebugTracerType::BuiltInTracer(
+                            GethDebugBuiltInTracerType::CallTracer
+                        ))
+                    )
+            })
+            .returning(|_, _, _| Ok(GethTrace::Unknown(serde_json::Value::Null)));
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let trace = estimator
+            .trace_op_simulation(demo_user_op_optional_gas())
+            .await
+            .unwrap();
+
+        assert_eq!(trace, GethTrace::Unknown(serde_json::Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_full_execution() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| Ok(ExecutionResult::default()));
+        expect_gas_used_call(&mut provider, 123456);
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let gas_used = estimator
+            .simulate_full_execution(&demo_user_op(), H256::zero())
+            .await
+            .unwrap();
+
+        assert_eq!(gas_used, U256::from(123456));
+    }
+
+    #[test]
+    fn test_full_execution_fees() {
+        assert_eq!(
+            full_execution_fees(Some(U256::from(1000)), Some(U256::from(100))),
+            (U256::from(1000), U256::from(100))
+        );
+        // a caller's max fee caps the priority fee
+        assert_eq!(
+            full_execution_fees(Some(U256::from(20)), None),
+            (U256::from(20), U256::from(20))
+        );
+        assert_eq!(
+            full_execution_fees(None, None),
+            (U256::zero(), U256::zero())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_estimation_unfunded_sender_without_fees() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_call_spoofed_simulate_op()
+            .returning(|_a, _b, _c, _d, _e, _f| {
+                Ok(Ok(ExecutionResult {
+                    target_result: EstimateCallGasResult {
+                        gas_estimate: U256::from(10000),
+                        num_rounds: U256::from(10),
+                    }
+                    .encode()
+                    .into(),
+                    target_success: true,
+                    ..Default::default()
+                }))
+            });
+        // the sender has no deposit and there is no paymaster, so the entry point
+        // rejects any op that has to pay for gas
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|result| {
+                if result.is_empty() {
+                    Ok(ExecutionResult::default())
+                } else {
+                    Err("AA21 didn't pay prefund".to_string())
+                }
+            });
+
+        provider
+            .expect_get_code()
+            .returning(|_a, _b| Ok(Bytes::new()));
+        provider
+            .expect_get_latest_block_hash_and_number()
+            .returning(|| Ok((H256::zero(), U64::from(10))));
+        provider.expect_call().returning(|tx, _b| {
+            let pays = tx
+                .data()
+                .and_then(|data| i_entry_point::SimulateHandleOpCall::decode(data).ok())
+                .is_some_and(|call| !call.op.max_fee_per_gas.is_zero());
+            let result_data: Bytes = GasUsedResult {
+                gas_used: U256::from(100000),
+                success: false,
+                result: if pays {
+                    Bytes::from_static(b"prefund")
+                } else {
+                    Bytes::new()
+                },
+            }
+            .encode()
+            .into();
+
+            let json_rpc_error = JsonRpcError {
+                code: -32000,
+                message: "execution reverted".to_string(),
+                data: Some(serde_json::Value::String(result_data.to_string())),
+            };
+            Err(ProviderError::JsonRpcError(json_rpc_error))
+        });
+
+        let (estimator, _) = create_estimator(entry, provider);
+
+        // without fees, the full execution check runs with zero fees and passes
+        let user_op = UserOperationOptionalGas {
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            ..demo_user_op_optional_gas()
+        };
+        estimator.estimate_op_gas(user_op).await.unwrap();
+
+        // with fees, the check pays for gas like a bundled op would
+        let estimation = estimator
+            .estimate_op_gas(demo_user_op_optional_gas())
+            .await
+            .err();
+        assert!(matches!(
+            estimation,
+            Some(GasEstimationError::RevertInValidation(message))
+                if message == "AA21 didn't pay prefund"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_full_execution_post_op_revert() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| Err("AA50 postOp reverted: out of tokens".to_string()));
+        expect_gas_used_call(&mut provider, 123456);
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let estimation = estimator
+            .simulate_full_execution(&demo_user_op(), H256::zero())
+            .await
+            .err();
+
+        assert!(matches!(
+            estimation,
+            Some(GasEstimationError::RevertInPostOp(message))
+                if message == "AA50 postOp reverted: out of tokens"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_simulate_full_execution_validation_revert() {
+        let (mut entry, mut provider) = create_base_config();
+
+        entry.expect_address().return_const(Address::zero());
+        entry
+            .expect_decode_simulate_handle_ops_revert()
+            .returning(|_a| Err("AA23 reverted".to_string()));
+        expect_gas_used_call(&mut provider, 123456);
+
+        let (estimator, _) = create_estimator(entry, provider);
+        let estimation = estimator
+            .simulate_full_execution(&demo_user_op(), H256::zero())
+            .await
+            .err();
+
+        assert!(matches!(
+            estimation,
+            Some(GasEstimationError::RevertInValidation(message)) if message == "AA23 reverted"
+        ));
     }
 
     #[tokio::test]
@@ -1160,8 +1962,8 @@ mod tests {
             .expect_get_code()
             .returning(|_a, _b| Ok(Bytes::new()));
         provider
-            .expect_get_latest_block_hash()
-            .returning(|| Ok(H256::zero()));
+            .expect_get_latest_block_hash_and_number()
+            .returning(|| Ok((H256::zero(), U64::from(10))));
         provider.expect_call().returning(|_a, _b| {
             let result_data: Bytes = GasUsedResult {
                 gas_used: U256::from(100000),
@@ -1185,6 +1987,8 @@ mod tests {
             max_verification_gas: 10,
             max_call_gas: 10,
             max_simulate_handle_ops_gas: 10,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
         };
 
         let estimator: GasEstimatorImpl<MockProvider, MockEntryPoint> =
@@ -1197,4 +2001,23 @@ mod tests {
             Some(GasEstimationError::RevertInValidation(..))
         ));
     }
+
+    #[test]
+    fn test_settings_reject_margins_below_100_percent() {
+        let mut settings = Settings {
+            max_verification_gas: 10000000000,
+            max_call_gas: 10000000000,
+            max_simulate_handle_ops_gas: 100000000,
+            margins: GasEstimateMargins::NONE,
+            gas_overheads: gas::GasOverheads::default(),
+        };
+        assert!(settings.validate().is_none());
+
+        settings.margins.call_gas_limit_percent = 99;
+        assert!(settings.validate().is_some());
+
+        settings.margins.call_gas_limit_percent = 100;
+        settings.margins.verification_gas_limit_percent = 90;
+        assert!(settings.validate().is_some());
+    }
 }
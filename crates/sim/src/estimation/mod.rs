@@ -16,4 +16,6 @@ mod estimation;
 pub use estimation::*;
 
 mod types;
-pub use types::{GasEstimate, Settings, UserOperationOptionalGas};
+pub use types::{
+    GasEstimate, GasEstimateMargins, GasEstimateRange, Settings, UserOperationOptionalGas,
+};
@@ -11,12 +11,12 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
-use ethers::types::{Address, Bytes, U256};
+use ethers::types::{Address, Bytes, H256, U256, U64};
 use rand::RngCore;
 use rundler_types::UserOperation;
 use serde::{Deserialize, Serialize};
 
-use crate::precheck::MIN_CALL_GAS_LIMIT;
+use crate::{gas::GasOverheads, precheck::MIN_CALL_GAS_LIMIT};
 
 /// Settings for gas estimation
 #[derive(Clone, Copy, Debug)]
@@ -27,6 +27,10 @@ pub struct Settings {
     pub max_call_gas: u64,
     /// The maximum amount of gas that can be used in a call to `simulateHandleOps`
     pub max_simulate_handle_ops_gas: u64,
+    /// Safety margins applied to the recommended call and verification gas limits
+    pub margins: GasEstimateMargins,
+    /// Gas overheads used to calculate the pre-verification gas
+    pub gas_overheads: GasOverheads,
 }
 
 impl Settings {
@@ -38,6 +42,11 @@ impl Settings {
         {
             return Some("max_call_gas field cannot be lower than MIN_CALL_GAS_LIMIT".to_string());
         }
+        if self.margins.call_gas_limit_percent < 100
+            || self.margins.verification_gas_limit_percent < 100
+        {
+            return Some("gas limit percent margins cannot be lower than 100".to_string());
+        }
         None
     }
 }
@@ -221,4 +230,48 @@ pub struct GasEstimate {
     pub verification_gas_limit: U256,
     /// Call gas limit estimate
     pub call_gas_limit: U256,
+    /// Number of the block the estimate was performed against
+    pub block_number: U64,
+    /// Hash of the block the estimate was performed against
+    pub block_hash: H256,
+    /// Safety margins already applied to the call and verification gas limits
+    pub margins: GasEstimateMargins,
+}
+
+/// Range of gas estimates for a user operation, from the tightest limits found
+/// by estimation to a safe upper bound
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasEstimateRange {
+    /// Tightest estimate, without any safety margins
+    pub min: GasEstimate,
+    /// Estimate with the configured safety margins
+    pub recommended: GasEstimate,
+    /// Estimate with larger safety margins than the configured ones
+    pub max: GasEstimate,
+}
+
+/// Safety margins applied on top of the raw gas estimates, reported so that
+/// clients know whether to add their own
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GasEstimateMargins {
+    /// Percentage of the estimated call gas limit that was returned, e.g. `110`
+    /// adds a 10% margin
+    pub call_gas_limit_percent: u64,
+    /// Percentage of the estimated verification gas limit that was returned, e.g.
+    /// `110` adds a 10% margin
+    pub verification_gas_limit_percent: u64,
+    /// Fixed amount of gas added to both the call and verification gas limits,
+    /// after the percentage margins are applied
+    pub gas_limit_buffer: u64,
+}
+
+impl GasEstimateMargins {
+    /// No margins, the estimates are returned as found
+    pub const NONE: Self = Self {
+        call_gas_limit_percent: 100,
+        verification_gas_limit_percent: 100,
+        gas_limit_buffer: 0,
+    };
 }
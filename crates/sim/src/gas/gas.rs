@@ -29,16 +29,23 @@ use tokio::try_join;
 use super::polygon::Polygon;
 
 /// Gas overheads for user operations used in calculating the pre-verification gas. See: https://github.com/eth-infinitism/bundler/blob/main/packages/sdk/src/calcPreVerificationGas.ts
-#[derive(Clone, Copy, Debug)]
+///
+/// Defaults come from [`GasOverheads::for_chain`], and individual values can be overridden
+/// for chains with non-standard intrinsic gas or calldata pricing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct GasOverheads {
     /// The Entrypoint requires a gas buffer for the bundle to account for the gas spent outside of the major steps in the processing of UOs
     pub bundle_transaction_gas_buffer: U256,
     /// The fixed gas overhead for any EVM transaction
     pub transaction_gas_overhead: U256,
-    per_user_op: U256,
-    per_user_op_word: U256,
-    zero_byte: U256,
-    non_zero_byte: U256,
+    /// The fixed gas overhead of each user operation in a bundle
+    pub per_user_op: U256,
+    /// The gas overhead of each 32 byte word of an encoded user operation
+    pub per_user_op_word: U256,
+    /// The cost of a zero byte of calldata
+    pub zero_byte: U256,
+    /// The cost of a non-zero byte of calldata
+    pub non_zero_byte: U256,
 }
 
 impl Default for GasOverheads {
@@ -54,6 +61,27 @@ impl Default for GasOverheads {
     }
 }
 
+impl GasOverheads {
+    /// Returns the gas overheads used to calculate the pre-verification gas on the given chain.
+    ///
+    /// Every known chain charges the same intrinsic transaction and calldata costs as
+    /// Ethereum mainnet, so `CHAIN_GAS_OVERHEADS` has no entries yet and this returns the
+    /// default overheads. Operators of other chains can override the values through
+    /// configuration instead.
+    pub fn for_chain(chain_id: u64) -> Self {
+        CHAIN_GAS_OVERHEADS
+            .iter()
+            .find(|(id, _)| *id == chain_id)
+            .map(|(_, overheads)| overheads())
+            .unwrap_or_default()
+    }
+}
+
+/// Per-chain overrides of the default gas overheads, keyed by chain ID.
+///
+/// Add an entry here for chains whose intrinsic calldata pricing differs from Ethereum mainnet.
+const CHAIN_GAS_OVERHEADS: &[(u64, fn() -> GasOverheads)] = &[];
+
 /// Returns the required pre_verification_gas for the given user operation
 ///
 /// `full_op` is either the user operation submitted via `sendUserOperation`
@@ -73,8 +101,51 @@ pub async fn calc_pre_verification_gas<P: Provider>(
     entry_point: Address,
     provider: Arc<P>,
     chain_id: u64,
+    ov: &GasOverheads,
 ) -> anyhow::Result<U256> {
-    let static_gas = calc_static_pre_verification_gas(full_op, true);
+    Ok(calc_pre_verification_gas_components(
+        full_op,
+        random_op,
+        entry_point,
+        provider,
+        chain_id,
+        ov,
+    )
+    .await?
+    .total())
+}
+
+/// The components of the pre_verification_gas of a user operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PreVerificationGasComponents {
+    /// The static portion of the pre_verification_gas, charged for execution on the
+    /// chain the bundle is submitted to
+    pub l2_gas: U256,
+    /// The dynamic portion of the pre_verification_gas, charged to cover the L1 data
+    /// fee on L2 networks. Zero on networks without an L1 data fee.
+    pub l1_gas: U256,
+}
+
+impl PreVerificationGasComponents {
+    /// Returns the total pre_verification_gas
+    pub fn total(&self) -> U256 {
+        self.l2_gas + self.l1_gas
+    }
+}
+
+/// Returns the required pre_verification_gas for the given user operation,
+/// with the L1 data fee portion broken out from the L2 portion.
+///
+/// See `calc_pre_verification_gas` for the meaning of `full_op` and `random_op`.
+pub async fn calc_pre_verification_gas_components<P: Provider>(
+    full_op: &UserOperation,
+    random_op: &UserOperation,
+    entry_point: Address,
+    provider: Arc<P>,
+    chain_id: u64,
+    ov: &GasOverheads,
+) -> anyhow::Result<PreVerificationGasComponents> {
+    let static_gas = calc_static_pre_verification_gas(full_op, ov, true);
     let dynamic_gas = match chain_id {
         _ if ARBITRUM_CHAIN_IDS.contains(&chain_id) => {
             provider
@@ -91,7 +162,10 @@ pub async fn calc_pre_verification_gas<P: Provider>(
         _ => U256::zero(),
     };
 
-    Ok(static_gas + dynamic_gas)
+    Ok(PreVerificationGasComponents {
+        l2_gas: static_gas,
+        l1_gas: dynamic_gas,
+    })
 }
 
 /// Gas limit functions
@@ -114,10 +188,11 @@ pub async fn calc_pre_verification_gas<P: Provider>(
 pub fn user_operation_gas_limit(
     uo: &UserOperation,
     chain_id: u64,
+    ov: &GasOverheads,
     assume_single_op_bundle: bool,
     paymaster_post_op: bool,
 ) -> U256 {
-    user_operation_pre_verification_gas_limit(uo, chain_id, assume_single_op_bundle)
+    user_operation_pre_verification_gas_limit(uo, chain_id, ov, assume_single_op_bundle)
         + uo.call_gas_limit
         + uo.verification_gas_limit
             * verification_gas_limit_multiplier(assume_single_op_bundle, paymaster_post_op)
@@ -127,10 +202,11 @@ pub fn user_operation_gas_limit(
 pub fn user_operation_execution_gas_limit(
     uo: &UserOperation,
     chain_id: u64,
+    ov: &GasOverheads,
     assume_single_op_bundle: bool,
     paymaster_post_op: bool,
 ) -> U256 {
-    user_operation_pre_verification_execution_gas_limit(uo, chain_id, assume_single_op_bundle)
+    user_operation_pre_verification_execution_gas_limit(uo, chain_id, ov, assume_single_op_bundle)
         + uo.call_gas_limit
         + uo.verification_gas_limit
             * verification_gas_limit_multiplier(assume_single_op_bundle, paymaster_post_op)
@@ -140,13 +216,14 @@ pub fn user_operation_execution_gas_limit(
 pub fn user_operation_pre_verification_execution_gas_limit(
     uo: &UserOperation,
     chain_id: u64,
+    ov: &GasOverheads,
     include_fixed_gas_overhead: bool,
 ) -> U256 {
     // On some chains (OP bedrock, Arbitrum) the L1 gas fee is charged via pre_verification_gas
     // but this not part of the EXECUTION gas limit of the transaction.
     // In such cases we only consider the static portion of the pre_verification_gas in the gas limit.
     if OP_BEDROCK_CHAIN_IDS.contains(&chain_id) | ARBITRUM_CHAIN_IDS.contains(&chain_id) {
-        calc_static_pre_verification_gas(uo, include_fixed_gas_overhead)
+        calc_static_pre_verification_gas(uo, ov, include_fixed_gas_overhead)
     } else {
         uo.pre_verification_gas
     }
@@ -156,13 +233,14 @@ pub fn user_operation_pre_verification_execution_gas_limit(
 pub fn user_operation_pre_verification_gas_limit(
     uo: &UserOperation,
     chain_id: u64,
+    ov: &GasOverheads,
     include_fixed_gas_overhead: bool,
 ) -> U256 {
     // On some chains (OP bedrock) the L1 gas fee is charged via pre_verification_gas
     // but this not part of the execution TOTAL limit of the transaction.
     // In such cases we only consider the static portion of the pre_verification_gas in the gas limit.
     if OP_BEDROCK_CHAIN_IDS.contains(&chain_id) {
-        calc_static_pre_verification_gas(uo, include_fixed_gas_overhead)
+        calc_static_pre_verification_gas(uo, ov, include_fixed_gas_overhead)
     } else {
         uo.pre_verification_gas
     }
@@ -175,8 +253,11 @@ pub fn user_operation_max_gas_cost(uo: &UserOperation) -> U256 {
         * (uo.pre_verification_gas + uo.call_gas_limit + uo.verification_gas_limit * mul)
 }
 
-fn calc_static_pre_verification_gas(op: &UserOperation, include_fixed_gas_overhead: bool) -> U256 {
-    let ov = GasOverheads::default();
+fn calc_static_pre_verification_gas(
+    op: &UserOperation,
+    ov: &GasOverheads,
+    include_fixed_gas_overhead: bool,
+) -> U256 {
     let encoded_op = op.clone().encode();
     let length_in_words = encoded_op.len() / 32; // size of packed user op is always a multiple of 32 bytes
     let call_data_cost: U256 = encoded_op
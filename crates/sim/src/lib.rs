@@ -32,8 +32,8 @@
 
 mod estimation;
 pub use estimation::{
-    GasEstimate, GasEstimationError, GasEstimator, GasEstimatorImpl,
-    Settings as EstimationSettings, UserOperationOptionalGas,
+    GasEstimate, GasEstimateMargins, GasEstimateRange, GasEstimationError, GasEstimator,
+    GasEstimatorImpl, Settings as EstimationSettings, UserOperationOptionalGas,
 };
 
 pub mod gas;
@@ -51,12 +51,13 @@ mod simulation;
 #[cfg(feature = "test-utils")]
 pub use simulation::MockSimulator;
 pub use simulation::{
-    MempoolConfig, Settings as SimulationSettings, SimulateValidationTracer,
-    SimulateValidationTracerImpl, SimulationError, SimulationSuccess, SimulationViolation,
-    Simulator, SimulatorImpl, ViolationOpCode,
+    simulate_validation_only, MempoolConfig, Settings as SimulationSettings,
+    SimulateValidationResult, SimulateValidationTracer, SimulateValidationTracerImpl,
+    SimulationError, SimulationSuccess, SimulationViolation, Simulator, SimulatorImpl,
+    ViolationOpCode,
 };
 
 mod types;
-pub use types::ExpectedStorage;
+pub use types::{DisplayViolations, ExpectedStorage};
 
 mod utils;
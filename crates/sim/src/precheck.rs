@@ -15,11 +15,25 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use arrayvec::ArrayVec;
-use ethers::types::{Address, U256};
+use ethers::{
+    abi::AbiDecode,
+    contract::EthCall,
+    types::{Address, Bytes, U256},
+};
 #[cfg(feature = "test-utils")]
 use mockall::automock;
 use rundler_provider::{EntryPoint, Provider};
-use rundler_types::{chain, GasFees, UserOperation};
+use rundler_types::{
+    chain,
+    contracts::{
+        i_entry_point::{
+            AddStakeCall, DepositToCall, IncrementNonceCall, UnlockStakeCall, WithdrawStakeCall,
+            WithdrawToCall,
+        },
+        simple_account::SimpleAccountCalls,
+    },
+    GasFees, UserOperation,
+};
 use rundler_utils::math;
 
 use crate::{gas, types::ViolationError};
@@ -53,6 +67,8 @@ pub struct PrecheckerImpl<P: Provider, E: EntryPoint> {
 pub struct Settings {
     /// Chain ID
     pub chain_id: u64,
+    /// Gas overheads used to calculate the pre-verification gas
+    pub gas_overheads: gas::GasOverheads,
     /// Maximum verification gas allowed for a user operation
     pub max_verification_gas: U256,
     /// Maximum total execution gas allowed for a user operation
@@ -68,6 +84,12 @@ pub struct Settings {
     pub priority_fee_mode: gas::PriorityFeeMode,
     /// Percentage of the current network fees that a user operation must have to be accepted into the mempool.
     pub fee_accept_percent: u64,
+    /// Whether to check that the sender of an operation without a paymaster
+    /// can cover the required prefund before simulation.
+    pub check_sender_funds: bool,
+    /// Whether to reject operations whose callData calls the entry point with
+    /// a method other than those used to manage the account's deposit or stake.
+    pub reject_unsupported_entry_point_calls: bool,
 }
 
 #[cfg(any(test, feature = "test-utils"))]
@@ -80,7 +102,10 @@ impl Default for Settings {
             priority_fee_mode: gas::PriorityFeeMode::BaseFeePercent(0),
             max_total_execution_gas: 10_000_000.into(),
             chain_id: 1,
+            gas_overheads: gas::GasOverheads::default(),
             fee_accept_percent: 100,
+            check_sender_funds: true,
+            reject_unsupported_entry_point_calls: true,
         }
     }
 }
@@ -103,6 +128,7 @@ impl<P: Provider, E: EntryPoint> Prechecker for PrecheckerImpl<P, E> {
         violations.extend(self.check_init_code(op, async_data));
         violations.extend(self.check_gas(op, async_data));
         violations.extend(self.check_payer(op, async_data));
+        violations.extend(self.check_call_data(op));
         if !violations.is_empty() {
             Err(violations)?
         }
@@ -167,6 +193,7 @@ impl<P: Provider, E: EntryPoint> PrecheckerImpl<P, E> {
     ) -> ArrayVec<PrecheckViolation, 6> {
         let Settings {
             chain_id,
+            gas_overheads,
             max_verification_gas,
             max_total_execution_gas,
             ..
@@ -187,7 +214,8 @@ impl<P: Provider, E: EntryPoint> PrecheckerImpl<P, E> {
 
         // compute the worst case total gas limit by assuming the UO is in its own bundle and has a postOp call.
         // This is conservative and potentially may invalidate some very large UOs that would otherwise be valid.
-        let gas_limit = gas::user_operation_execution_gas_limit(op, chain_id, true, true);
+        let gas_limit =
+            gas::user_operation_execution_gas_limit(op, chain_id, &gas_overheads, true, true);
         if gas_limit > max_total_execution_gas {
             violations.push(PrecheckViolation::TotalGasLimitTooHigh(
                 gas_limit,
@@ -260,10 +288,12 @@ impl<P: Provider, E: EntryPoint> PrecheckerImpl<P, E> {
         let max_gas_cost = gas::user_operation_max_gas_cost(op);
         if payer_funds < max_gas_cost {
             if op.paymaster_and_data.is_empty() {
-                return Some(PrecheckViolation::SenderFundsTooLow(
-                    payer_funds,
-                    max_gas_cost,
-                ));
+                if self.settings.check_sender_funds {
+                    return Some(PrecheckViolation::SenderFundsTooLow(
+                        payer_funds,
+                        max_gas_cost,
+                    ));
+                }
             } else {
                 return Some(PrecheckViolation::PaymasterDepositTooLow(
                     payer_funds,
@@ -274,6 +304,31 @@ impl<P: Provider, E: EntryPoint> PrecheckerImpl<P, E> {
         None
     }
 
+    fn check_call_data(&self, op: &UserOperation) -> Option<PrecheckViolation> {
+        if !self.settings.reject_unsupported_entry_point_calls {
+            return None;
+        }
+        // Only calls made through the standard account execute methods can be decoded
+        let calls: Vec<(Address, Bytes)> = match SimpleAccountCalls::decode(&op.call_data) {
+            Ok(SimpleAccountCalls::Execute(call)) => vec![(call.dest, call.func)],
+            Ok(SimpleAccountCalls::ExecuteBatch(call)) => {
+                call.dest.into_iter().zip(call.func).collect()
+            }
+            _ => return None,
+        };
+        let entry_point = self.entry_point.address();
+        calls
+            .into_iter()
+            .filter(|(dest, _)| *dest == entry_point)
+            .find_map(|(_, func)| {
+                // Calls without a selector are plain deposits to the entry point
+                let selector: [u8; 4] = func.get(..4)?.try_into().ok()?;
+                (!is_supported_entry_point_selector(selector)).then(|| {
+                    PrecheckViolation::UnsupportedEntryPointCall(Bytes::from(selector.to_vec()))
+                })
+            })
+    }
+
     async fn load_async_data(&self, op: &UserOperation) -> anyhow::Result<AsyncData> {
         let (
             factory_exists,
@@ -354,16 +409,34 @@ impl<P: Provider, E: EntryPoint> PrecheckerImpl<P, E> {
             self.entry_point.address(),
             self.provider.clone(),
             self.settings.chain_id,
+            &self.settings.gas_overheads,
         )
         .await
         .context("should calculate pre-verification gas")
     }
 }
 
+/// Entry point methods an account may call from its callData, all of which
+/// manage the account's own deposit, stake or nonces.
+fn is_supported_entry_point_selector(selector: [u8; 4]) -> bool {
+    [
+        DepositToCall::selector(),
+        IncrementNonceCall::selector(),
+        AddStakeCall::selector(),
+        UnlockStakeCall::selector(),
+        WithdrawStakeCall::selector(),
+        WithdrawToCall::selector(),
+    ]
+    .contains(&selector)
+}
+
 /// Precheck violation enumeration
 ///
 /// All possible errors that can be returned from a precheck.
-#[derive(Clone, Debug, parse_display::Display, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(
+    Clone, Debug, parse_display::Display, Eq, PartialEq, Ord, PartialOrd, strum::IntoStaticStr,
+)]
+#[strum(serialize_all = "snake_case")]
 pub enum PrecheckViolation {
     /// The init code is too short to contain a factory address.
     #[display("initCode must start with a 20-byte factory address, but was only {0} bytes")]
@@ -409,14 +482,18 @@ pub enum PrecheckViolation {
     /// The call gas limit is too low to account for any possible call.
     #[display("callGasLimit is {0} but must be at least {1}")]
     CallGasLimitTooLow(U256, U256),
+    /// The call data calls an entry point method that is not supported.
+    #[display("callData calls unsupported entry point method with selector {0}")]
+    UnsupportedEntryPointCall(Bytes),
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use ethers::types::Bytes;
+    use ethers::{abi::AbiEncode, types::Bytes};
     use rundler_provider::{MockEntryPoint, MockProvider};
+    use rundler_types::contracts::{i_entry_point::HandleOpsCall, simple_account::ExecuteCall};
 
     use super::*;
 
@@ -473,12 +550,15 @@ mod tests {
         let (provider, entry_point) = create_base_config();
         let test_settings = Settings {
             chain_id: 1,
+            gas_overheads: gas::GasOverheads::default(),
             max_verification_gas: 5_000_000.into(),
             max_total_execution_gas: 10_000_000.into(),
             use_bundle_priority_fee: None,
             bundle_priority_fee_overhead_percent: 0,
             priority_fee_mode: gas::PriorityFeeMode::BaseFeePercent(100),
             fee_accept_percent: 100,
+            check_sender_funds: true,
+            reject_unsupported_entry_point_calls: true,
         };
         let prechecker = PrecheckerImpl::new(Arc::new(provider), entry_point, test_settings);
         let op = UserOperation {
@@ -541,6 +621,101 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_check_payer_sender_funds_too_low() {
+        let (provider, entry_point) = create_base_config();
+        let op = UserOperation {
+            sender: Address::from_str("0x3f8a2b6c4d5e1079286fa1b3c0d4e5f6902b7c8d").unwrap(),
+            nonce: 100.into(),
+            init_code: Bytes::default(),
+            call_data: Bytes::default(),
+            call_gas_limit: 500_000.into(),
+            verification_gas_limit: 500_000.into(),
+            pre_verification_gas: 0.into(),
+            max_fee_per_gas: 1_000.into(),
+            max_priority_fee_per_gas: 0.into(),
+            paymaster_and_data: Bytes::default(),
+            signature: Bytes::default(),
+        };
+
+        let prechecker = PrecheckerImpl::new(Arc::new(provider), entry_point, Settings::default());
+        let res = prechecker.check_payer(&op, get_test_async_data());
+        assert_eq!(
+            res,
+            Some(PrecheckViolation::SenderFundsTooLow(
+                5_000_000.into(),
+                1_000_000_000.into(),
+            ))
+        );
+
+        let (provider, entry_point) = create_base_config();
+        let settings = Settings {
+            check_sender_funds: false,
+            ..Default::default()
+        };
+        let prechecker = PrecheckerImpl::new(Arc::new(provider), entry_point, settings);
+        let res = prechecker.check_payer(&op, get_test_async_data());
+        assert_eq!(res, None);
+    }
+
+    #[tokio::test]
+    async fn test_check_call_data_unsupported_entry_point_call() {
+        let (provider, mut entry_point) = create_base_config();
+        let entry_point_address = Address::random();
+        entry_point
+            .expect_address()
+            .return_const(entry_point_address);
+        let prechecker = PrecheckerImpl::new(Arc::new(provider), entry_point, Settings::default());
+
+        let handle_ops = HandleOpsCall {
+            ops: vec![],
+            beneficiary: Address::random(),
+        }
+        .encode();
+        let op = UserOperation {
+            call_data: ExecuteCall {
+                dest: entry_point_address,
+                value: 0.into(),
+                func: handle_ops.into(),
+            }
+            .encode()
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            prechecker.check_call_data(&op),
+            Some(PrecheckViolation::UnsupportedEntryPointCall(
+                HandleOpsCall::selector().to_vec().into()
+            ))
+        );
+
+        let deposit = DepositToCall { account: op.sender }.encode();
+        let op = UserOperation {
+            call_data: ExecuteCall {
+                dest: entry_point_address,
+                value: 1.into(),
+                func: deposit.into(),
+            }
+            .encode()
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(prechecker.check_call_data(&op), None);
+
+        let increment_nonce = IncrementNonceCall { key: 1.into() }.encode();
+        let op = UserOperation {
+            call_data: ExecuteCall {
+                dest: entry_point_address,
+                value: 0.into(),
+                func: increment_nonce.into(),
+            }
+            .encode()
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(prechecker.check_call_data(&op), None);
+    }
+
     #[tokio::test]
     async fn test_check_fees() {
         let settings = Settings {
@@ -27,3 +27,4 @@ mod tracer;
 pub use tracer::{SimulateValidationTracer, SimulateValidationTracerImpl};
 
 mod validation_results;
+pub use validation_results::{simulate_validation_only, SimulateValidationResult};
@@ -67,6 +67,9 @@ pub struct SimulationSuccess {
     pub entities_needing_stake: Vec<EntityType>,
     /// Whether the sender account is staked
     pub account_is_staked: bool,
+    /// Whether the sender is an EOA with EIP-7702 delegated code rather than
+    /// a contract account
+    pub account_is_delegated_eoa: bool,
     /// List of all addresses accessed during validation
     pub accessed_addresses: HashSet<Address>,
     /// Expected storage values for all accessed slots during validation
@@ -82,6 +85,14 @@ impl SimulationSuccess {
     }
 }
 
+/// Reason code of the entry point's `FailedOp` when the factory deploys a
+/// different sender than the user operation's sender
+const SENDER_MISMATCH_REASON_CODE: &str = "AA14";
+
+/// Reason code of the entry point's `FailedOp` when validation uses more gas
+/// than the user operation's verification gas limit
+const VERIFICATION_GAS_LIMIT_REASON_CODE: &str = "AA40";
+
 /// The result of a failed simulation
 pub type SimulationError = ViolationError<SimulationViolation>;
 
@@ -179,6 +190,47 @@ where
         let last_entity = entity_type_from_simulation_phase(tracer_out.phases.len() - 1).unwrap();
 
         if let Ok(failed_op) = FailedOp::decode_hex(revert_data) {
+            // The entry point rejects ops whose factory deploys a different sender
+            // than the op declares, so report the address it would have deployed.
+            // If that lookup fails, the entry point's own reason is reported instead.
+            if failed_op.reason.starts_with(SENDER_MISMATCH_REASON_CODE) {
+                match self
+                    .provider
+                    .clone()
+                    .get_sender_address(
+                        self.entry_point_address,
+                        op.init_code.clone(),
+                        block_id,
+                        self.sim_settings.max_verification_gas,
+                    )
+                    .await
+                {
+                    Ok(created_sender) => Err(vec![SimulationViolation::SenderAddressMismatch(
+                        sender_address,
+                        created_sender,
+                    )])?,
+                    Err(error) => {
+                        tracing::debug!(
+                            "failed to get the sender address deployed by the factory: {error:?}"
+                        );
+                    }
+                }
+            }
+            // The entry point rejects ops whose validation, including the factory
+            // and paymaster, uses more than the verification gas limit, so report
+            // the gas validation needs. If it can't be measured, the entry point's
+            // own reason is reported instead.
+            if failed_op
+                .reason
+                .starts_with(VERIFICATION_GAS_LIMIT_REASON_CODE)
+            {
+                if let Some(min_gas) = self.measure_verification_gas(&op, block_id).await {
+                    Err(vec![SimulationViolation::VerificationGasLimitTooLow(
+                        op.verification_gas_limit,
+                        min_gas,
+                    )])?
+                }
+            }
             let entity_addr = match last_entity {
                 EntityType::Factory => factory_address,
                 EntityType::Paymaster => paymaster_address,
@@ -199,7 +251,7 @@ where
             sender_address,
             paymaster_address,
             &entry_point_out,
-            self.sim_settings,
+            &self.sim_settings,
         );
         let is_unstaked_wallet_creation = entity_infos
             .get(EntityType::Factory)
@@ -219,6 +271,39 @@ where
         })
     }
 
+    // Measure the verification gas the op needs by simulating it again with the
+    // maximum verification gas limit. The entry point measures the pre op gas as
+    // the gas used by validation plus the pre verification gas.
+    async fn measure_verification_gas(
+        &self,
+        op: &UserOperation,
+        block_id: BlockId,
+    ) -> Option<U256> {
+        let op = UserOperation {
+            verification_gas_limit: self.sim_settings.max_verification_gas.into(),
+            ..op.clone()
+        };
+        let pre_verification_gas = op.pre_verification_gas;
+        let tracer_out = match self
+            .simulate_validation_tracer
+            .trace_simulate_validation(op, block_id, self.sim_settings.max_verification_gas)
+            .await
+        {
+            Ok(tracer_out) => tracer_out,
+            Err(error) => {
+                tracing::debug!("failed to measure the verification gas: {error:?}");
+                return None;
+            }
+        };
+        let entry_point_out = ValidationOutput::decode_hex(tracer_out.revert_data?).ok()?;
+        Some(
+            entry_point_out
+                .return_info
+                .pre_op_gas
+                .saturating_sub(pre_verification_gas),
+        )
+    }
+
     async fn validate_aggregator_signature(
         &self,
         op: UserOperation,
@@ -229,6 +314,17 @@ where
             return Ok(AggregatorOut::NotNeeded);
         };
 
+        // Calls to an address without code succeed with empty return data, so
+        // check for the contract explicitly to report a meaningful error
+        if self
+            .provider
+            .get_code(aggregator_address, None)
+            .await?
+            .is_empty()
+        {
+            return Ok(AggregatorOut::Unreachable);
+        }
+
         Ok(self
             .provider
             .clone()
@@ -272,6 +368,13 @@ where
             };
             for opcode in &phase.forbidden_opcodes_used {
                 let (contract, opcode) = parse_combined_tracer_str(opcode)?;
+                if self
+                    .sim_settings
+                    .allowed_opcodes
+                    .contains(&(entity.address, opcode))
+                {
+                    continue;
+                }
                 violations.push(SimulationViolation::UsedForbiddenOpcode(
                     entity,
                     contract,
@@ -311,6 +414,19 @@ where
                         accessed_address: address,
                         slot: *slot,
                     });
+                    if self
+                        .sim_settings
+                        .banned_storage_slots
+                        .contains(&(address, *slot))
+                    {
+                        violations.push(SimulationViolation::BannedStorageSlot(
+                            entity,
+                            StorageSlot {
+                                address,
+                                slot: *slot,
+                            },
+                        ));
+                    }
                     match restriction {
                         StorageRestriction::Allowed => {}
                         StorageRestriction::NeedsStake => needs_stake = true,
@@ -360,7 +476,7 @@ where
 
         if let Some(aggregator_info) = entry_point_out.aggregator_info {
             entities_needing_stake.push(EntityType::Aggregator);
-            if !is_staked(aggregator_info.stake_info, self.sim_settings) {
+            if !is_staked(aggregator_info.stake_info, &self.sim_settings) {
                 violations.push(SimulationViolation::NotStaked(
                     Entity::aggregator(aggregator_info.address),
                     self.sim_settings.min_stake_value.into(),
@@ -397,6 +513,7 @@ where
         op: UserOperation,
         context: &mut ValidationContext,
         expected_code_hash: Option<H256>,
+        sender_delegate: Option<Address>,
     ) -> Result<(H256, Option<AggregatorSimOut>), SimulationError> {
         let &mut ValidationContext {
             block_id,
@@ -408,12 +525,20 @@ where
         // collect a vector of violations to ensure a deterministic error message
         let mut violations = vec![];
 
+        // An EIP-7702 delegated sender runs its delegate's code at its own
+        // address, so the tracer never reports the delegate as accessed. Include
+        // it in the code hash so that validation is rerun if the delegate's code
+        // changes, the same as for any other contract used during validation.
+        let mut code_addresses = mem::take(&mut tracer_out.accessed_contract_addresses);
+        if let Some(delegate) = sender_delegate {
+            if !code_addresses.contains(&delegate) {
+                code_addresses.push(delegate);
+            }
+        }
+
         let aggregator_address = entry_point_out.aggregator_info.map(|info| info.address);
-        let code_hash_future = utils::get_code_hash(
-            self.provider.deref(),
-            mem::take(&mut tracer_out.accessed_contract_addresses),
-            Some(block_id),
-        );
+        let code_hash_future =
+            utils::get_code_hash(self.provider.deref(), code_addresses, Some(block_id));
         let aggregator_signature_future = self.validate_aggregator_signature(
             op,
             aggregator_address,
@@ -435,6 +560,14 @@ where
                 violations.push(SimulationViolation::AggregatorValidationFailed);
                 None
             }
+            AggregatorOut::Unreachable => {
+                if let Some(aggregator_address) = aggregator_address {
+                    violations.push(SimulationViolation::AggregatorUnreachable(
+                        aggregator_address,
+                    ));
+                }
+                None
+            }
         };
 
         if !violations.is_empty() {
@@ -481,9 +614,18 @@ where
             MempoolMatchResult::NoMatch(i) => return Err(vec![violations[i].clone()].into()),
         };
 
+        // An EOA with EIP-7702 delegated code acts as a contract account, but its
+        // code is a designator pointing at the contract whose code it runs
+        let sender_code = self
+            .provider
+            .get_code(op.sender, Some(block_hash))
+            .await
+            .map_err(anyhow::Error::from)?;
+        let sender_delegate = utils::delegation_target(&sender_code);
+
         // Check code hash and aggregator signature, these can't fail
         let (code_hash, aggregator) = self
-            .check_contracts(op, &mut context, expected_code_hash)
+            .check_contracts(op, &mut context, expected_code_hash, sender_delegate)
             .await?;
 
         // Transform outputs into success struct
@@ -500,7 +642,7 @@ where
             sender_info,
             ..
         } = entry_point_out;
-        let account_is_staked = is_staked(sender_info, self.sim_settings);
+        let account_is_staked = is_staked(sender_info, &self.sim_settings);
         let ValidationReturnInfo {
             pre_op_gas,
             valid_after,
@@ -517,6 +659,7 @@ where
             code_hash,
             entities_needing_stake,
             account_is_staked,
+            account_is_delegated_eoa: sender_delegate.is_some(),
             accessed_addresses,
             expected_storage: tracer_out.expected_storage,
             requires_post_op: !paymaster_context.is_empty(),
@@ -525,7 +668,10 @@ where
 }
 
 /// All possible simulation violations
-#[derive(Clone, Debug, parse_display::Display, Ord, Eq, PartialOrd, PartialEq)]
+#[derive(
+    Clone, Debug, parse_display::Display, Ord, Eq, PartialOrd, PartialEq, strum::IntoStaticStr,
+)]
+#[strum(serialize_all = "snake_case")]
 pub enum SimulationViolation {
     // Make sure to maintain the order here based on the importance
     // of the violation for converting to an JSON RPC error
@@ -549,6 +695,11 @@ pub enum SimulationViolation {
     /// The user operation accessed a storage slot that is not allowed
     #[display("{0.kind} accessed forbidden storage at address {1:?} during validation")]
     InvalidStorageAccess(Entity, StorageSlot),
+    /// The user operation accessed a storage slot that the bundler operator has banned
+    #[display(
+        "{0.kind} accessed storage banned by the bundler at address {1:?} during validation"
+    )]
+    BannedStorageSlot(Entity, StorageSlot),
     /// The user operation called an entry point method that is not allowed
     #[display("{0.kind} called entry point method other than depositTo")]
     CalledBannedEntryPointMethod(Entity),
@@ -562,6 +713,10 @@ pub enum SimulationViolation {
     /// The user operation contained an entity that accessed storage without being staked
     #[display("{0.kind} must be staked")]
     NotStaked(Entity, U256, U256),
+    /// The factory deployed a different sender than the user operation's sender,
+    /// containing the user operation's sender and the deployed address
+    #[display("factory deployed sender {1:?} instead of the user operation's sender {0:?}")]
+    SenderAddressMismatch(Address, Address),
     /// Simulation reverted with an unintended reason, containing a message
     #[display("reverted while simulating {0} validation: {1}")]
     UnintendedRevertWithMessage(EntityType, String, Option<Address>),
@@ -577,9 +732,15 @@ pub enum SimulationViolation {
     /// The user operation ran out of gas during validation
     #[display("ran out of gas during {0.kind} validation")]
     OutOfGas(Entity),
+    /// The verification gas limit is below the verification gas measured during simulation
+    #[display("verification gas limit {0} is too low, validation needs {1} gas")]
+    VerificationGasLimitTooLow(U256, U256),
     /// The user operation aggregator signature validation failed
     #[display("aggregator signature validation failed")]
     AggregatorValidationFailed,
+    /// The user operation aggregator could not be called to validate the signature
+    #[display("aggregator {0:?} is unreachable, it may not be deployed")]
+    AggregatorUnreachable(Address),
 }
 
 /// A wrapper around Opcode that implements extra traits
@@ -641,7 +802,7 @@ impl EntityInfos {
         sender_address: Address,
         paymaster_address: Option<Address>,
         entry_point_out: &ValidationOutput,
-        sim_settings: Settings,
+        sim_settings: &Settings,
     ) -> Self {
         let factory = factory_address.map(|address| EntityInfo {
             address,
@@ -676,7 +837,7 @@ impl EntityInfos {
     }
 }
 
-fn is_staked(info: StakeInfo, sim_settings: Settings) -> bool {
+fn is_staked(info: StakeInfo, sim_settings: &Settings) -> bool {
     info.stake >= sim_settings.min_stake_value.into()
         && info.unstake_delay_sec >= sim_settings.min_unstake_delay.into()
 }
@@ -730,7 +891,7 @@ fn get_storage_restriction(args: GetStorageRestrictionArgs<'_>) -> StorageRestri
 }
 
 /// Simulation Settings
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Settings {
     /// The minimum amount of time that a staked entity must have configured as
     /// their unstake delay on the entry point contract in order to be considered staked.
@@ -742,6 +903,12 @@ pub struct Settings {
     pub max_simulate_handle_ops_gas: u64,
     /// The maximum amount of verification gas that can be used during the simulation call
     pub max_verification_gas: u64,
+    /// Forbidden opcodes that specific entities are allowed to use, by entity address.
+    /// Uses of these opcodes by these entities are not reported as violations.
+    pub allowed_opcodes: HashSet<(Address, Opcode)>,
+    /// Storage slots, by contract address, that operations may not access during
+    /// validation, regardless of the storage access rules.
+    pub banned_storage_slots: HashSet<(Address, U256)>,
 }
 
 impl Settings {
@@ -751,12 +918,16 @@ impl Settings {
         min_stake_value: u128,
         max_simulate_handle_ops_gas: u64,
         max_verification_gas: u64,
+        allowed_opcodes: HashSet<(Address, Opcode)>,
+        banned_storage_slots: HashSet<(Address, U256)>,
     ) -> Self {
         Self {
             min_unstake_delay,
             min_stake_value,
             max_simulate_handle_ops_gas,
             max_verification_gas,
+            allowed_opcodes,
+            banned_storage_slots,
         }
     }
 }
@@ -772,6 +943,8 @@ impl Default for Settings {
             // 550 million gas: currently the defaults for Alchemy eth_call
             max_simulate_handle_ops_gas: 550_000_000,
             max_verification_gas: 5_000_000,
+            allowed_opcodes: HashSet::new(),
+            banned_storage_slots: HashSet::new(),
         }
     }
 }
@@ -881,8 +1054,14 @@ mod tests {
         provider: MockProvider,
         simulate_validation_tracer: MockSimulateValidationTracer,
     ) -> SimulatorImpl<MockProvider, MockSimulateValidationTracer> {
-        let settings = Settings::default();
+        create_simulator_with_settings(provider, simulate_validation_tracer, Settings::default())
+    }
 
+    fn create_simulator_with_settings(
+        provider: MockProvider,
+        simulate_validation_tracer: MockSimulateValidationTracer,
+        settings: Settings,
+    ) -> SimulatorImpl<MockProvider, MockSimulateValidationTracer> {
         let mut mempool_configs = HashMap::new();
         mempool_configs.insert(H256::zero(), MempoolConfig::default());
 
@@ -900,10 +1079,19 @@ mod tests {
         simulator
     }
 
-    #[tokio::test]
-    async fn test_simulate_validation() {
+    fn create_simulate_validation_mocks() -> (MockProvider, MockSimulateValidationTracer) {
+        create_simulate_validation_mocks_with_sender_code(Bytes::from_static(&[0x60, 0x80]))
+    }
+
+    fn create_simulate_validation_mocks_with_sender_code(
+        sender_code: Bytes,
+    ) -> (MockProvider, MockSimulateValidationTracer) {
         let (mut provider, mut tracer) = create_base_config();
 
+        provider
+            .expect_get_code()
+            .returning(move |_, _| Ok(sender_code.clone()));
+
         provider.expect_get_latest_block_hash().returning(|| {
             Ok(
                 H256::from_str(
@@ -934,7 +1122,11 @@ mod tests {
             .expect_validate_user_op_signature()
             .returning(|_, _, _| Ok(AggregatorOut::NotNeeded));
 
-        let user_operation = UserOperation {
+        (provider, tracer)
+    }
+
+    fn get_test_user_operation() -> UserOperation {
+        UserOperation {
             sender: Address::from_str("b856dbd4fa1a79a46d426f537455e7d3e79ab7c4").unwrap(),
             nonce: U256::from(264),
             init_code: Bytes::from_str("0x").unwrap(),
@@ -946,13 +1138,49 @@ mod tests {
             max_priority_fee_per_gas: U256::from(105000000),
             paymaster_and_data: Bytes::from_str("0x").unwrap(),
             signature: Bytes::from_str("0x98f89993ce573172635b44ef3b0741bd0c19dd06909d3539159f6d66bef8c0945550cc858b1cf5921dfce0986605097ba34c2cf3fc279154dd25e161ea7b3d0f1c").unwrap(),
-        };
+        }
+    }
 
+    #[tokio::test]
+    async fn test_simulate_validation() {
+        let (provider, tracer) = create_simulate_validation_mocks();
         let simulator = create_simulator(provider, tracer);
         let res = simulator
-            .simulate_validation(user_operation, None, None)
+            .simulate_validation(get_test_user_operation(), None, None)
+            .await;
+        assert!(!res.unwrap().account_is_delegated_eoa);
+    }
+
+    #[tokio::test]
+    async fn test_simulate_validation_delegated_eoa_sender() {
+        let delegate = Address::random();
+        let mut designator = vec![0xef, 0x01, 0x00];
+        designator.extend_from_slice(delegate.as_bytes());
+        let (provider, tracer) =
+            create_simulate_validation_mocks_with_sender_code(designator.into());
+        let simulator = create_simulator(provider, tracer);
+
+        let res = simulator
+            .simulate_validation(get_test_user_operation(), None, None)
             .await;
-        assert!(res.is_ok());
+        assert!(res.unwrap().account_is_delegated_eoa);
+    }
+
+    #[tokio::test]
+    async fn test_validate_aggregator_signature_unreachable() {
+        let (mut provider, tracer) = create_base_config();
+        // no code at the aggregator address
+        provider
+            .expect_get_code()
+            .returning(|_, _| Ok(Bytes::default()));
+        provider.expect_validate_user_op_signature().never();
+
+        let simulator = create_simulator(provider, tracer);
+        let res = simulator
+            .validate_aggregator_signature(UserOperation::default(), Some(Address::random()), 0)
+            .await;
+
+        assert!(matches!(res, Ok(AggregatorOut::Unreachable)));
     }
 
     #[tokio::test]
@@ -1005,6 +1233,134 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_create_context_sender_address_mismatch() {
+        let (mut provider, mut tracer) = create_base_config();
+        let created_sender = Address::random();
+
+        tracer
+            .expect_trace_simulate_validation()
+            .returning(|_, _, _| {
+                let mut tracer_output = get_test_tracer_output();
+                tracer_output.revert_data = Some(hex::encode(
+                    FailedOp {
+                        op_index: U256::zero(),
+                        reason: "AA14 initCode must return sender".to_string(),
+                    }
+                    .encode(),
+                ));
+                Ok(tracer_output)
+            });
+        provider
+            .expect_get_sender_address()
+            .returning(move |_, _, _, _| Ok(created_sender));
+
+        let user_operation = UserOperation {
+            sender: Address::random(),
+            init_code: Bytes::from(Address::random().as_bytes().to_vec()),
+            ..Default::default()
+        };
+        let sender = user_operation.sender;
+
+        let simulator = create_simulator(provider, tracer);
+        let res = simulator
+            .create_context(user_operation, BlockId::Number(BlockNumber::Latest))
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ViolationError::Violations(violations)) if violations
+                == vec![SimulationViolation::SenderAddressMismatch(sender, created_sender)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_context_sender_address_mismatch_lookup_failed() {
+        let (mut provider, mut tracer) = create_base_config();
+
+        tracer
+            .expect_trace_simulate_validation()
+            .returning(|_, _, _| {
+                let mut tracer_output = get_test_tracer_output();
+                tracer_output.revert_data = Some(hex::encode(
+                    FailedOp {
+                        op_index: U256::zero(),
+                        reason: "AA14 initCode must return sender".to_string(),
+                    }
+                    .encode(),
+                ));
+                Ok(tracer_output)
+            });
+        provider
+            .expect_get_sender_address()
+            .returning(|_, _, _, _| Err(anyhow::anyhow!("provider unavailable").into()));
+
+        let user_operation = UserOperation {
+            sender: Address::random(),
+            init_code: Bytes::from(Address::random().as_bytes().to_vec()),
+            ..Default::default()
+        };
+
+        let simulator = create_simulator(provider, tracer);
+        let res = simulator
+            .create_context(user_operation, BlockId::Number(BlockNumber::Latest))
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ViolationError::Violations(violations)) if matches!(
+                violations.get(0),
+                Some(SimulationViolation::UnintendedRevertWithMessage(
+                    _,
+                    reason,
+                    _
+                )) if reason == "AA14 initCode must return sender"
+            )
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_context_verification_gas_limit_too_low() {
+        let (provider, mut tracer) = create_base_config();
+
+        // The op fails with its own limit, and passes with the maximum limit,
+        // where the entry point measures a pre op gas of 85759
+        tracer
+            .expect_trace_simulate_validation()
+            .returning(|op, _, max_validation_gas| {
+                let mut tracer_output = get_test_tracer_output();
+                if op.verification_gas_limit < max_validation_gas.into() {
+                    tracer_output.revert_data = Some(hex::encode(
+                        FailedOp {
+                            op_index: U256::zero(),
+                            reason: "AA40 over verificationGasLimit".to_string(),
+                        }
+                        .encode(),
+                    ));
+                }
+                Ok(tracer_output)
+            });
+
+        let user_operation = UserOperation {
+            verification_gas_limit: U256::from(30000),
+            ..get_test_user_operation()
+        };
+
+        let simulator = create_simulator(provider, tracer);
+        let res = simulator
+            .create_context(user_operation, BlockId::Number(BlockNumber::Latest))
+            .await;
+
+        assert!(matches!(
+            res,
+            Err(ViolationError::Violations(violations)) if violations
+                == vec![SimulationViolation::VerificationGasLimitTooLow(
+                    U256::from(30000),
+                    U256::from(39631)
+                )]
+        ));
+    }
+
     #[tokio::test]
     async fn test_gather_context_violations() {
         let (provider, tracer) = create_base_config();
@@ -1051,7 +1407,7 @@ mod tests {
                     paymaster_info: StakeInfo::from((U256::default(), U256::default())),
                     aggregator_info: None,
                 },
-                Settings::default(),
+                &Settings::default(),
             ),
             tracer_out: tracer_output,
             entry_point_out: ValidationOutput {
@@ -1126,4 +1482,96 @@ mod tests {
             ]
         );
     }
+
+    // Gathers the violations in the tracer output of an op from an existing,
+    // unstaked sender, with no factory or paymaster
+    fn gather_unstaked_sender_violations(
+        sender: Address,
+        tracer_out: SimulationTracerOutput,
+        settings: Settings,
+    ) -> anyhow::Result<Vec<SimulationViolation>> {
+        let (provider, tracer) = create_base_config();
+        let entry_point_out = ValidationOutput {
+            return_info: ValidationReturnInfo::from((
+                U256::default(),
+                U256::default(),
+                false,
+                0,
+                0,
+                Bytes::default(),
+            )),
+            sender_info: StakeInfo::from((U256::default(), U256::default())),
+            factory_info: StakeInfo::from((U256::default(), U256::default())),
+            paymaster_info: StakeInfo::from((U256::default(), U256::default())),
+            aggregator_info: None,
+        };
+        let mut validation_context = ValidationContext {
+            block_id: BlockId::Number(BlockNumber::Latest),
+            entity_infos: EntityInfos::new(None, sender, None, &entry_point_out, &settings),
+            tracer_out,
+            entry_point_out,
+            is_unstaked_wallet_creation: false,
+            entities_needing_stake: vec![],
+            accessed_addresses: HashSet::new(),
+        };
+
+        let simulator = create_simulator_with_settings(provider, tracer, settings);
+        simulator.gather_context_violations(&mut validation_context)
+    }
+
+    #[tokio::test]
+    async fn test_gather_context_violations_allowed_opcodes() {
+        let sender = Address::from_str("0xb856dbd4fa1a79a46d426f537455e7d3e79ab7c4").unwrap();
+
+        let mut tracer_output = get_test_tracer_output();
+        tracer_output.phases[1].forbidden_opcodes_used = vec![
+            format!("{sender:?}:GASPRICE"),
+            format!("{sender:?}:COINBASE"),
+        ];
+
+        let settings = Settings {
+            allowed_opcodes: HashSet::from([(sender, Opcode::GASPRICE)]),
+            ..Settings::default()
+        };
+        let res = gather_unstaked_sender_violations(sender, tracer_output, settings);
+
+        assert_eq!(
+            res.unwrap(),
+            vec![SimulationViolation::UsedForbiddenOpcode(
+                Entity::account(sender),
+                sender,
+                ViolationOpCode(Opcode::COINBASE),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gather_context_violations_banned_storage_slots() {
+        let sender = Address::from_str("0xb856dbd4fa1a79a46d426f537455e7d3e79ab7c4").unwrap();
+        let slot = U256::from(1);
+
+        // the sender's own storage is otherwise always accessible
+        let mut tracer_output = get_test_tracer_output();
+        tracer_output.phases[1].storage_accesses = vec![StorageAccess {
+            address: sender,
+            slots: vec![U256::zero(), slot],
+        }];
+
+        let settings = Settings {
+            banned_storage_slots: HashSet::from([(sender, slot)]),
+            ..Settings::default()
+        };
+        let res = gather_unstaked_sender_violations(sender, tracer_output, settings);
+
+        assert_eq!(
+            res.unwrap(),
+            vec![SimulationViolation::BannedStorageSlot(
+                Entity::account(sender),
+                StorageSlot {
+                    address: sender,
+                    slot,
+                },
+            )]
+        );
+    }
 }
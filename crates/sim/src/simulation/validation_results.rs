@@ -11,16 +11,84 @@
 // You should have received a copy of the GNU General Public License along with Rundler.
 // If not, see https://www.gnu.org/licenses/.
 
+use anyhow::Context;
 use ethers::{
     abi,
     abi::{AbiDecode, AbiError},
     types::{Address, Bytes, U256},
 };
+use rundler_provider::{EntryPoint, Provider, ProviderError};
 use rundler_types::{
-    contracts::entry_point::{ValidationResult, ValidationResultWithAggregation},
-    Timestamp,
+    contracts::{
+        entry_point::{ValidationResult, ValidationResultWithAggregation},
+        i_entry_point::FailedOp,
+    },
+    Timestamp, UserOperation,
 };
 
+/// The decoded result of the entry point's `simulateValidation` call, without
+/// any of the bundler's opcode or storage access rules applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulateValidationResult {
+    /// Gas used by validation, including pre-verification gas
+    pub pre_op_gas: U256,
+    /// Amount the entry point will charge the payer up front for the operation
+    pub prefund: U256,
+    /// Whether the signature check failed
+    pub sig_failed: bool,
+    /// Time after which the operation is valid
+    pub valid_after: Timestamp,
+    /// Time until which the operation is valid
+    pub valid_until: Timestamp,
+    /// Whether the paymaster returned a context, i.e. `postOp` will be called
+    pub has_paymaster_context: bool,
+}
+
+/// Runs only the entry point's `simulateValidation` for the operation and
+/// returns its decoded result.
+///
+/// Returns the revert reason as the inner error if validation itself failed.
+pub async fn simulate_validation_only<P: Provider, E: EntryPoint>(
+    provider: &P,
+    entry_point: &E,
+    op: UserOperation,
+    max_validation_gas: u64,
+) -> anyhow::Result<Result<SimulateValidationResult, String>> {
+    let tx = entry_point
+        .simulate_validation(op, max_validation_gas)
+        .await?;
+    let error = provider
+        .call(&tx, None)
+        .await
+        .err()
+        .context("simulateValidation should always revert")?;
+    let ProviderError::JsonRpcError(jsonrpc_error) = &error else {
+        return Err(error.into());
+    };
+    if let Some(output) = jsonrpc_error.decode_revert_data::<ValidationOutput>() {
+        let ValidationReturnInfo {
+            pre_op_gas,
+            prefund,
+            sig_failed,
+            valid_after,
+            valid_until,
+            paymaster_context,
+        } = output.return_info;
+        return Ok(Ok(SimulateValidationResult {
+            pre_op_gas,
+            prefund,
+            sig_failed,
+            valid_after,
+            valid_until,
+            has_paymaster_context: !paymaster_context.is_empty(),
+        }));
+    }
+    if let Some(FailedOp { reason, .. }) = jsonrpc_error.decode_revert_data::<FailedOp>() {
+        return Ok(Err(reason));
+    }
+    Err(error).context("simulateValidation should revert with a validation result")
+}
+
 /// Equivalent to the generated `ValidationResult` or
 /// `ValidationResultWithAggregation` from `EntryPoint`, but with named structs
 /// instead of tuples and with a helper for deserializing.
@@ -85,6 +153,7 @@ impl From<ValidationResultWithAggregation> for ValidationOutput {
 #[derive(Debug)]
 pub(crate) struct ValidationReturnInfo {
     pub(crate) pre_op_gas: U256,
+    pub(crate) prefund: U256,
     pub(crate) sig_failed: bool,
     pub(crate) valid_after: Timestamp,
     pub(crate) valid_until: Timestamp,
@@ -93,16 +162,10 @@ pub(crate) struct ValidationReturnInfo {
 
 impl From<(U256, U256, bool, u64, u64, Bytes)> for ValidationReturnInfo {
     fn from(value: (U256, U256, bool, u64, u64, Bytes)) -> Self {
-        let (
-            pre_op_gas,
-            _, /* prefund */
-            sig_failed,
-            valid_after,
-            valid_until,
-            paymaster_context,
-        ) = value;
+        let (pre_op_gas, prefund, sig_failed, valid_after, valid_until, paymaster_context) = value;
         Self {
             pre_op_gas,
+            prefund,
             sig_failed,
             valid_after: valid_after.into(),
             valid_until: valid_until.into(),
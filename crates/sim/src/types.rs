@@ -83,18 +83,28 @@ impl<T: Display> Display for ViolationError<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             ViolationError::Violations(violations) => {
-                if violations.len() == 1 {
-                    Display::fmt(&violations[0], f)
-                } else {
-                    f.write_str("multiple violations: ")?;
-                    for violation in violations {
-                        Display::fmt(violation, f)?;
-                        f.write_str("; ")?;
-                    }
-                    Ok(())
-                }
+                Display::fmt(&DisplayViolations(violations), f)
             }
             ViolationError::Other(error) => Display::fmt(error, f),
         }
     }
 }
+
+/// Displays a list of violations, as the violation itself if there is only one
+#[derive(Debug)]
+pub struct DisplayViolations<'a, T>(pub &'a [T]);
+
+impl<T: Display> Display for DisplayViolations<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let [violation] = self.0 {
+            Display::fmt(violation, f)
+        } else {
+            f.write_str("multiple violations: ")?;
+            for violation in self.0 {
+                Display::fmt(violation, f)?;
+                f.write_str("; ")?;
+            }
+            Ok(())
+        }
+    }
+}
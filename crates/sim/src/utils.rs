@@ -33,6 +33,21 @@ pub(crate) fn call_data_of(selector: Selector, args: impl AbiEncode) -> Bytes {
     bytes.into()
 }
 
+/// Code prefix of an EIP-7702 delegation designator, which is followed by the
+/// 20-byte address of the contract whose code the account runs
+const DELEGATION_DESIGNATOR_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// Returns the delegate address if the code is an EIP-7702 delegation designator,
+/// i.e. the account is an EOA with delegated code.
+pub(crate) fn delegation_target(code: &[u8]) -> Option<Address> {
+    match code.strip_prefix(&DELEGATION_DESIGNATOR_PREFIX) {
+        Some(address) if address.len() == Address::len_bytes() => {
+            Some(Address::from_slice(address))
+        }
+        _ => None,
+    }
+}
+
 /// Hashes together the code from all the provided addresses. The order of the input addresses does
 /// not matter.
 pub(crate) async fn get_code_hash<P: Provider>(
@@ -54,8 +69,15 @@ pub(crate) async fn get_gas_used<P: Provider>(
     target: Address,
     value: U256,
     data: Bytes,
+    block_id: Option<BlockId>,
 ) -> anyhow::Result<GasUsedResult> {
-    call_constructor(provider, &GETGASUSED_BYTECODE, (target, value, data), None).await
+    call_constructor(
+        provider,
+        &GETGASUSED_BYTECODE,
+        (target, value, data),
+        block_id,
+    )
+    .await
 }
 
 async fn call_constructor<P: Provider, Args: AbiEncode, Ret: AbiDecode>(
@@ -20,7 +20,9 @@ use serde::{ser::SerializeStruct, Deserialize, Serialize};
 use strum::EnumIter;
 
 /// The type of an entity
-#[derive(Display, Debug, Clone, Ord, Copy, Eq, PartialEq, EnumIter, PartialOrd, Deserialize)]
+#[derive(
+    Display, Debug, Clone, Ord, Copy, Eq, Hash, PartialEq, EnumIter, PartialOrd, Deserialize,
+)]
 #[display(style = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum EntityType {
@@ -61,7 +63,7 @@ impl FromStr for EntityType {
 }
 
 /// An entity associated with a user operation
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Ord, PartialOrd)]
 pub struct Entity {
     /// The type of entity
     pub kind: EntityType,
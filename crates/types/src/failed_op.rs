@@ -0,0 +1,205 @@
+// This file is part of Rundler.
+//
+// Rundler is free software: you can redistribute it and/or modify it under the
+// terms of the GNU Lesser General Public License as published by the Free Software
+// Foundation, either version 3 of the License, or (at your option) any later version.
+//
+// Rundler is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License along with Rundler.
+// If not, see https://www.gnu.org/licenses/.
+
+use serde::{Serialize, Serializer};
+use strum::{EnumIter, EnumString, IntoStaticStr};
+
+/// Error code at the start of an entry point `FailedOp` reason, e.g. `AA21` in
+/// `"AA21 didn't pay prefund"`.
+///
+/// The first digit identifies the entity at fault: `AA1x` for the factory,
+/// `AA2x` for the account, `AA3x` for the paymaster, `AA4x` for verification gas,
+/// `AA5x` for postOp, and `AA9x` for other entry point errors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, EnumIter, EnumString, IntoStaticStr)]
+pub enum FailedOpCode {
+    /// The sender is already deployed, but the op has an init code
+    #[strum(serialize = "AA10")]
+    SenderAlreadyConstructed,
+    /// The factory call reverted or ran out of gas
+    #[strum(serialize = "AA13")]
+    InitCodeFailed,
+    /// The factory returned an address other than the sender
+    #[strum(serialize = "AA14")]
+    InitCodeReturnedWrongSender,
+    /// The factory call did not deploy code at the sender address
+    #[strum(serialize = "AA15")]
+    InitCodeDidNotDeploySender,
+    /// The sender is not deployed and the op has no init code
+    #[strum(serialize = "AA20")]
+    AccountNotDeployed,
+    /// The account did not pay the entry point its required prefund
+    #[strum(serialize = "AA21")]
+    AccountPrefundNotPaid,
+    /// The account's signature is expired or not yet valid
+    #[strum(serialize = "AA22")]
+    AccountExpiredOrNotDue,
+    /// The account's validation reverted or ran out of gas
+    #[strum(serialize = "AA23")]
+    AccountValidationReverted,
+    /// The account's signature is invalid for the aggregator
+    #[strum(serialize = "AA24")]
+    AccountSignatureError,
+    /// The op's nonce is not valid for the account
+    #[strum(serialize = "AA25")]
+    InvalidAccountNonce,
+    /// The paymaster is not deployed
+    #[strum(serialize = "AA30")]
+    PaymasterNotDeployed,
+    /// The paymaster's deposit does not cover the op's max cost
+    #[strum(serialize = "AA31")]
+    PaymasterDepositTooLow,
+    /// The paymaster's signature is expired or not yet valid
+    #[strum(serialize = "AA32")]
+    PaymasterExpiredOrNotDue,
+    /// The paymaster's validation reverted or ran out of gas
+    #[strum(serialize = "AA33")]
+    PaymasterValidationReverted,
+    /// The paymaster's signature is invalid
+    #[strum(serialize = "AA34")]
+    PaymasterSignatureError,
+    /// Validation used more gas than the op's verification gas limit
+    #[strum(serialize = "AA40")]
+    OverVerificationGasLimit,
+    /// The verification gas limit is too low to leave gas for the paymaster's postOp
+    #[strum(serialize = "AA41")]
+    TooLittleVerificationGas,
+    /// The paymaster's postOp reverted
+    #[strum(serialize = "AA50")]
+    PostOpReverted,
+    /// The prefund is below the op's actual gas cost
+    #[strum(serialize = "AA51")]
+    PrefundBelowActualGasCost,
+    /// The bundle's beneficiary is the zero address
+    #[strum(serialize = "AA90")]
+    InvalidBeneficiary,
+    /// The entry point failed to pay the bundle's beneficiary
+    #[strum(serialize = "AA91")]
+    FailedSendToBeneficiary,
+    /// An entry point method that may only be called internally was called
+    #[strum(serialize = "AA92")]
+    InternalCallOnly,
+    /// The paymaster and data field is too short to contain an address
+    #[strum(serialize = "AA93")]
+    InvalidPaymasterAndData,
+    /// The op's gas values overflow when combined
+    #[strum(serialize = "AA94")]
+    GasValuesOverflow,
+    /// The bundle transaction ran out of gas
+    #[strum(serialize = "AA95")]
+    OutOfGas,
+    /// The op's aggregator is invalid
+    #[strum(serialize = "AA96")]
+    InvalidAggregator,
+}
+
+impl FailedOpCode {
+    /// Parses the error code from the start of a `FailedOp` reason, returning
+    /// `None` if the reason does not start with a known code.
+    pub fn from_reason(reason: &str) -> Option<Self> {
+        let code = reason.get(..4)?;
+        // The code must be followed by the end of the reason or a separator,
+        // e.g. not "AA100"
+        if reason[4..].starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        code.parse().ok()
+    }
+
+    /// The code as it appears in `FailedOp` reasons, e.g. `"AA21"`
+    pub fn code(&self) -> &'static str {
+        self.into()
+    }
+
+    /// A human readable description of the error
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::SenderAlreadyConstructed => "sender is already deployed, but initCode is set",
+            Self::InitCodeFailed => "initCode reverted or ran out of gas",
+            Self::InitCodeReturnedWrongSender => "initCode returned an address other than sender",
+            Self::InitCodeDidNotDeploySender => "initCode did not deploy sender",
+            Self::AccountNotDeployed => "sender is not deployed and initCode is empty",
+            Self::AccountPrefundNotPaid => "account did not pay the required prefund",
+            Self::AccountExpiredOrNotDue => "account signature is expired or not yet valid",
+            Self::AccountValidationReverted => "account validation reverted or ran out of gas",
+            Self::AccountSignatureError => "account signature is invalid for the aggregator",
+            Self::InvalidAccountNonce => "nonce is invalid for the account",
+            Self::PaymasterNotDeployed => "paymaster is not deployed",
+            Self::PaymasterDepositTooLow => "paymaster deposit is too low to pay for the op",
+            Self::PaymasterExpiredOrNotDue => "paymaster signature is expired or not yet valid",
+            Self::PaymasterValidationReverted => "paymaster validation reverted or ran out of gas",
+            Self::PaymasterSignatureError => "paymaster signature is invalid",
+            Self::OverVerificationGasLimit => "validation used more than verificationGasLimit",
+            Self::TooLittleVerificationGas => {
+                "verificationGasLimit leaves too little gas for the paymaster's postOp"
+            }
+            Self::PostOpReverted => "paymaster postOp reverted",
+            Self::PrefundBelowActualGasCost => "prefund is below the op's actual gas cost",
+            Self::InvalidBeneficiary => "bundle beneficiary is the zero address",
+            Self::FailedSendToBeneficiary => "failed to pay the bundle beneficiary",
+            Self::InternalCallOnly => "entry point method may only be called internally",
+            Self::InvalidPaymasterAndData => "paymasterAndData is too short to contain an address",
+            Self::GasValuesOverflow => "gas values overflow",
+            Self::OutOfGas => "bundle transaction ran out of gas",
+            Self::InvalidAggregator => "aggregator is invalid",
+        }
+    }
+}
+
+impl Serialize for FailedOpCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn test_from_reason() {
+        assert_eq!(
+            FailedOpCode::from_reason("AA21 didn't pay prefund"),
+            Some(FailedOpCode::AccountPrefundNotPaid)
+        );
+        assert_eq!(
+            FailedOpCode::from_reason("AA13 initCode failed or OOG"),
+            Some(FailedOpCode::InitCodeFailed)
+        );
+        assert_eq!(
+            FailedOpCode::from_reason("AA95"),
+            Some(FailedOpCode::OutOfGas)
+        );
+        assert_eq!(
+            FailedOpCode::from_reason("AA23 reverted: invalid signature"),
+            Some(FailedOpCode::AccountValidationReverted)
+        );
+        assert_eq!(FailedOpCode::from_reason("AA11 unknown"), None);
+        assert_eq!(FailedOpCode::from_reason("AA100 too long"), None);
+        assert_eq!(FailedOpCode::from_reason("AA2"), None);
+        assert_eq!(FailedOpCode::from_reason("execution reverted"), None);
+        assert_eq!(FailedOpCode::from_reason(""), None);
+    }
+
+    #[test]
+    fn test_code_round_trip() {
+        for code in FailedOpCode::iter() {
+            assert_eq!(FailedOpCode::from_reason(code.code()), Some(code));
+            assert_eq!(
+                serde_json::to_value(code).unwrap(),
+                serde_json::Value::String(code.code().to_string())
+            );
+        }
+    }
+}
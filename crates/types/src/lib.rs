@@ -33,6 +33,9 @@ pub use contracts::shared_types::{UserOperation, UserOpsPerAggregator};
 mod entity;
 pub use entity::{Entity, EntityType, EntityUpdate, EntityUpdateType};
 
+mod failed_op;
+pub use failed_op::FailedOpCode;
+
 mod gas;
 pub use gas::GasFees;
 
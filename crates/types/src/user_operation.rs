@@ -39,7 +39,8 @@ impl UserOperation {
     /// Hash a user operation with the given entry point and chain ID.
     ///
     /// The hash is used to uniquely identify a user operation in the entry point.
-    /// It does not include the signature field.
+    /// It does not include the signature field, and matches the entry point's
+    /// `getUserOpHash()`.
     pub fn op_hash(&self, entry_point: Address, chain_id: u64) -> H256 {
         keccak256(encode(&[
             Token::FixedBytes(keccak256(self.pack_for_hash()).to_vec()),
@@ -260,6 +261,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hash_known_entry_point() {
+        // Testing the user operation from `test_hash` against the v0.6 entry point
+        // deployment at 0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789 on Ethereum
+        // mainnet (chain ID 1) and Polygon (chain ID 137).
+        //
+        // The expected hashes are built word by word, following the entry point's
+        // getUserOpHash() rather than the ABI encoder `op_hash` uses:
+        //
+        //   keccak256(abi.encode(keccak256(pack(userOp)), address(this), block.chainid))
+        //
+        // where pack(userOp) encodes the fields in order, replacing initCode, callData
+        // and paymasterAndData with their hashes and leaving out the signature.
+        fn word(value: U256) -> [u8; 32] {
+            let mut word = [0; 32];
+            value.to_big_endian(&mut word);
+            word
+        }
+        fn address_word(address: Address) -> [u8; 32] {
+            let mut word = [0; 32];
+            word[12..].copy_from_slice(address.as_bytes());
+            word
+        }
+        fn get_user_op_hash(op: &UserOperation, entry_point: Address, chain_id: u64) -> H256 {
+            let packed = [
+                address_word(op.sender),
+                word(op.nonce),
+                keccak256(&op.init_code),
+                keccak256(&op.call_data),
+                word(op.call_gas_limit),
+                word(op.verification_gas_limit),
+                word(op.pre_verification_gas),
+                word(op.max_fee_per_gas),
+                word(op.max_priority_fee_per_gas),
+                keccak256(&op.paymaster_and_data),
+            ]
+            .concat();
+            H256(keccak256(
+                [
+                    keccak256(packed),
+                    address_word(entry_point),
+                    word(chain_id.into()),
+                ]
+                .concat(),
+            ))
+        }
+
+        let operation = UserOperation {
+            sender: "0x1306b01bc3e4ad202612d3843387e94737673f53"
+                .parse()
+                .unwrap(),
+            nonce: 8942.into(),
+            init_code: "0x6942069420694206942069420694206942069420"
+                .parse()
+                .unwrap(),
+            call_data: "0x0000000000000000000000000000000000000000080085"
+                .parse()
+                .unwrap(),
+            call_gas_limit: 10000.into(),
+            verification_gas_limit: 100000.into(),
+            pre_verification_gas: 100.into(),
+            max_fee_per_gas: 99999.into(),
+            max_priority_fee_per_gas: 9999999.into(),
+            paymaster_and_data:
+                "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"
+                    .parse()
+                    .unwrap(),
+            signature: "0xda0929f527cded8d0a1eaf2e8861d7f7e2d8160b7b13942f99dd367df4473a"
+                .parse()
+                .unwrap(),
+        };
+        let entry_point = "0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789"
+            .parse()
+            .unwrap();
+        for chain_id in [1, 137] {
+            assert_eq!(
+                operation.op_hash(entry_point, chain_id),
+                get_user_op_hash(&operation, entry_point, chain_id)
+            );
+        }
+        // Known answers, so that a change to both encodings is also caught
+        assert_eq!(
+            operation.op_hash(entry_point, 1),
+            "0xed077f1aea368430f5720b6a990db71313c5f265917390115cc8b7281e0c75ec"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            operation.op_hash(entry_point, 137),
+            "0xcc0708a6017f0f74f7a7e9be3b4b746ebfe536d3fcef6748b3cba425bc88601d"
+                .parse()
+                .unwrap()
+        );
+        // The signature isn't part of the hash
+        assert_eq!(
+            operation.op_hash(entry_point, 1),
+            UserOperation {
+                signature: Bytes::default(),
+                ..operation.clone()
+            }
+            .op_hash(entry_point, 1)
+        );
+    }
+
     #[test]
     fn test_get_address_from_field() {
         let paymaster_and_data: Bytes =
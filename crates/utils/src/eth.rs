@@ -13,17 +13,25 @@
 
 //! Utilities for working with an Ethereum-like chain via Ethers.
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    fmt::Debug,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
+use async_trait::async_trait;
 use ethers::{
     abi::{AbiDecode, RawLog},
     contract::ContractError,
     providers::{
-        Http, HttpRateLimitRetryPolicy, Middleware, Provider, RetryClient, RetryClientBuilder,
+        Http, HttpRateLimitRetryPolicy, JsonRpcClient, Middleware, Provider, RetryClient,
+        RetryClientBuilder, RpcError,
     },
-    types::{Bytes, Log},
+    types::{Address, Bytes, Log},
 };
+use serde::{de::DeserializeOwned, Serialize};
 use url::Url;
 
 /// Gets the revert data from a contract error if it is a revert error,
@@ -51,20 +59,213 @@ pub fn parse_revert_message(revert_data: &[u8]) -> Option<String> {
         .map(|err| err.reason)
 }
 
+tokio::task_local! {
+    static RPC_METHOD: &'static str;
+}
+
+/// Runs `f`, labeling the provider requests it makes through a [`MetricsClient`]
+/// with `rpc_method`, the RPC method being served.
+///
+/// Requests made from tasks spawned by `f` are not labeled.
+pub async fn with_rpc_method<F: Future>(rpc_method: &'static str, f: F) -> F::Output {
+    RPC_METHOD.scope(rpc_method, f).await
+}
+
+/// Returns the RPC method set by [`with_rpc_method`] for the current task, or
+/// `"none"` outside of one
+pub fn current_rpc_method() -> &'static str {
+    RPC_METHOD
+        .try_with(|rpc_method| *rpc_method)
+        .unwrap_or("none")
+}
+
+/// JSON-RPC client wrapper that records the number and latency of requests
+/// made through the inner client.
+///
+/// Requests are labeled by JSON-RPC method, by the RPC method they were made to
+/// serve (see [`with_rpc_method`]), and, for the request count, by whether they
+/// failed with an error response (`rpc`) or without one (`transport`).
+///
+/// Latency includes any retries made by the inner client.
+#[derive(Debug)]
+pub struct MetricsClient<C> {
+    inner: C,
+}
+
+impl<C> MetricsClient<C> {
+    /// Wraps a client to record metrics for its requests
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for MetricsClient<C> {
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let start = Instant::now();
+        let result = self.inner.request(method, params).await;
+        let rpc_method = current_rpc_method();
+        let error = match &result {
+            Ok(_) => "none",
+            Err(error) if error.is_error_response() => "rpc",
+            Err(_) => "transport",
+        };
+        ProviderMetrics::increment_num_requests(method.to_string(), rpc_method, error);
+        ProviderMetrics::record_request_latency(method.to_string(), rpc_method, start.elapsed());
+        result
+    }
+}
+
+/// How long an endpoint is skipped by [`FailoverClient`] after a failed request
+const FAILOVER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// JSON-RPC client wrapper that sends requests to the first healthy client in a
+/// list of clients, failing over to the next one when a request fails.
+///
+/// Clients are tried in priority order, so the first client is the primary and
+/// the rest are only used while those before them are unhealthy. A client is
+/// marked unhealthy for a cooldown period when a request to it fails without a
+/// JSON-RPC error response, e.g. a connection error or timeout. Error responses,
+/// such as reverts, come from a working node and are returned without failing
+/// over. If every client is unhealthy, all of them are tried in order.
+///
+/// Each request is sent to a client once, so that the first transport error
+/// fails over. Retries belong outside this client, where they retry the whole
+/// failover sequence.
+#[derive(Debug)]
+pub struct FailoverClient<C> {
+    endpoints: Vec<FailoverEndpoint<C>>,
+    cooldown: Duration,
+}
+
+#[derive(Debug)]
+struct FailoverEndpoint<C> {
+    client: C,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl<C> FailoverEndpoint<C> {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until
+            .lock()
+            .unwrap()
+            .map_or(true, |until| now >= until)
+    }
+
+    fn set_unhealthy_until(&self, until: Option<Instant>) {
+        *self.unhealthy_until.lock().unwrap() = until;
+    }
+}
+
+impl<C> FailoverClient<C> {
+    /// Creates a client that fails over between the given clients, in priority order
+    ///
+    /// # Panics
+    ///
+    /// Panics if `clients` is empty.
+    pub fn new(clients: Vec<C>, cooldown: Duration) -> Self {
+        assert!(!clients.is_empty(), "failover client requires a client");
+        Self {
+            endpoints: clients
+                .into_iter()
+                .map(|client| FailoverEndpoint {
+                    client,
+                    unhealthy_until: Mutex::new(None),
+                })
+                .collect(),
+            cooldown,
+        }
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for FailoverClient<C> {
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let now = Instant::now();
+        // Healthy endpoints first, in priority order, then the unhealthy ones as
+        // a last resort
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .partition(|(_, endpoint)| endpoint.is_healthy(now));
+
+        let mut last_error = None;
+        for (index, endpoint) in healthy.into_iter().chain(unhealthy) {
+            if last_error.is_some() {
+                ProviderMetrics::increment_failovers(method.to_string(), current_rpc_method());
+            }
+            match endpoint.client.request(method, &params).await {
+                Ok(result) => {
+                    endpoint.set_unhealthy_until(None);
+                    return Ok(result);
+                }
+                Err(error) if error.is_error_response() => {
+                    endpoint.set_unhealthy_until(None);
+                    return Err(error);
+                }
+                Err(error) => {
+                    tracing::warn!("{method} failed on provider endpoint {index}: {error:?}");
+                    endpoint.set_unhealthy_until(Some(Instant::now() + self.cooldown));
+                    last_error = Some(error);
+                }
+            }
+        }
+        Err(last_error.expect("failover client should have at least one endpoint"))
+    }
+}
+
+struct ProviderMetrics {}
+
+impl ProviderMetrics {
+    fn increment_num_requests(method_name: String, rpc_method: &'static str, error: &'static str) {
+        metrics::increment_counter!("provider_num_requests", "method_name" => method_name, "rpc_method" => rpc_method, "error" => error)
+    }
+
+    fn increment_failovers(method_name: String, rpc_method: &'static str) {
+        metrics::increment_counter!("provider_failovers", "method_name" => method_name, "rpc_method" => rpc_method)
+    }
+
+    fn record_request_latency(method_name: String, rpc_method: &'static str, latency: Duration) {
+        metrics::histogram!("provider_request_latency", latency, "method_name" => method_name, "rpc_method" => rpc_method)
+    }
+}
+
 /// Construct a new Ethers provider from a URL and a poll interval.
 ///
-/// Creates a provider with a retry client that retries 10 times, with an initial backoff of 500ms.
+/// The URL may be a comma-separated list of URLs, in which case requests fail over from
+/// each URL to the next via [`FailoverClient`], with the first URL as the primary.
+///
+/// Creates a provider with a retry client around the failover client that retries 10 times, with
+/// an initial backoff of 500ms. Requests are counted and timed per method via [`MetricsClient`].
 pub fn new_provider(
     url: &str,
     poll_interval: Option<Duration>,
-) -> anyhow::Result<Arc<Provider<RetryClient<Http>>>> {
-    let parsed_url = Url::parse(url).context("provider url should be valid")?;
-
+) -> anyhow::Result<Arc<Provider<MetricsClient<RetryClient<FailoverClient<Http>>>>>> {
     let http_client = reqwest::Client::builder()
         .connect_timeout(Duration::from_secs(1))
         .build()
         .context("failed to build reqwest client")?;
-    let http = Http::new_with_client(parsed_url, http_client);
+
+    let clients = url
+        .split(',')
+        .map(|url| {
+            let parsed_url = Url::parse(url.trim()).context("provider url should be valid")?;
+            Ok(Http::new_with_client(parsed_url, http_client.clone()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
     let client = RetryClientBuilder::default()
         // these retries are if the server returns a 429
@@ -72,9 +273,12 @@ pub fn new_provider(
         // these retries are if the connection is dubious
         .timeout_retries(3)
         .initial_backoff(Duration::from_millis(500))
-        .build(http, Box::<HttpRateLimitRetryPolicy>::default());
+        .build(
+            FailoverClient::new(clients, FAILOVER_COOLDOWN),
+            Box::<HttpRateLimitRetryPolicy>::default(),
+        );
 
-    let mut provider = Provider::new(client);
+    let mut provider = Provider::new(MetricsClient::new(client));
     if let Some(poll_interval) = poll_interval {
         provider = provider.interval(poll_interval);
     }
@@ -82,6 +286,51 @@ pub fn new_provider(
     Ok(Arc::new(provider))
 }
 
+/// Checks that the node at every URL in a comma-separated list is on the expected
+/// chain and has code at each of the given addresses, e.g. the entry points.
+///
+/// Each URL is checked on its own, as a provider from [`new_provider`] only talks
+/// to the first healthy URL, and a misconfigured fallback would otherwise go
+/// unnoticed until the primary fails.
+pub async fn check_chain(url: &str, chain_id: u64, addresses: &[Address]) -> anyhow::Result<()> {
+    for (index, url) in url.split(',').enumerate() {
+        let provider =
+            Provider::<Http>::try_from(url.trim()).context("provider url should be valid")?;
+        check_provider_chain(&provider, chain_id, addresses)
+            .await
+            .with_context(|| format!("chain check failed for provider url {index}"))?;
+    }
+    Ok(())
+}
+
+async fn check_provider_chain<C: JsonRpcClient>(
+    provider: &Provider<C>,
+    chain_id: u64,
+    addresses: &[Address],
+) -> anyhow::Result<()> {
+    let provider_chain_id = provider
+        .get_chainid()
+        .await
+        .context("should fetch chain id from provider")?;
+    if provider_chain_id != chain_id.into() {
+        anyhow::bail!(
+            "Provider chain id {provider_chain_id} does not match configured chain id {chain_id}"
+        );
+    }
+
+    for &address in addresses {
+        let code = provider
+            .get_code(address, None)
+            .await
+            .context("should fetch code from provider")?;
+        if code.is_empty() {
+            anyhow::bail!("{address:?} has no code on chain {chain_id}");
+        }
+    }
+
+    Ok(())
+}
+
 /// Converts an ethers `Log` into an ethabi `RawLog`.
 pub fn log_to_raw_log(log: Log) -> RawLog {
     let Log { topics, data, .. } = log;
@@ -90,3 +339,186 @@ pub fn log_to_raw_log(log: Log) -> RawLog {
         data: data.to_vec(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::{
+        providers::{JsonRpcError, MockProvider, MockResponse},
+        types::{U256, U64},
+    };
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+    use super::*;
+
+    // A mock client with no queued responses fails like an unreachable node
+    fn failover_client(
+        cooldown: Duration,
+    ) -> (MockProvider, MockProvider, FailoverClient<MockProvider>) {
+        let primary = MockProvider::new();
+        let secondary = MockProvider::new();
+        let client = FailoverClient::new(vec![primary.clone(), secondary.clone()], cooldown);
+        (primary, secondary, client)
+    }
+
+    async fn block_number<C: JsonRpcClient>(client: &C) -> Result<U64, C::Error> {
+        client.request("eth_blockNumber", ()).await
+    }
+
+    #[tokio::test]
+    async fn test_failover_uses_primary_when_healthy() {
+        let (primary, secondary, client) = failover_client(FAILOVER_COOLDOWN);
+        primary.push(U64::from(1)).unwrap();
+        secondary.push(U64::from(2)).unwrap();
+
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(1));
+        // the secondary was not used
+        assert_eq!(block_number(&secondary).await.unwrap(), U64::from(2));
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_transport_error() {
+        let (primary, secondary, client) = failover_client(FAILOVER_COOLDOWN);
+        secondary.push(U64::from(2)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(2));
+
+        // the primary is skipped during its cooldown
+        primary.push(U64::from(1)).unwrap();
+        secondary.push(U64::from(3)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(3));
+    }
+
+    #[tokio::test]
+    async fn test_failover_primary_recovers_after_cooldown() {
+        let (primary, secondary, client) = failover_client(Duration::ZERO);
+        secondary.push(U64::from(2)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(2));
+
+        primary.push(U64::from(1)).unwrap();
+        secondary.push(U64::from(3)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(1));
+    }
+
+    #[tokio::test]
+    async fn test_failover_returns_error_responses() {
+        let (primary, secondary, client) = failover_client(FAILOVER_COOLDOWN);
+        primary.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        }));
+        secondary.push(U64::from(2)).unwrap();
+
+        let error = block_number(&client).await.unwrap_err();
+        assert_eq!(error.as_error_response().unwrap().code, 3);
+        // the secondary was not used, and the primary is still healthy
+        assert_eq!(block_number(&secondary).await.unwrap(), U64::from(2));
+        primary.push(U64::from(1)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(1));
+    }
+
+    #[tokio::test]
+    async fn test_failover_all_unhealthy() {
+        let (primary, _secondary, client) = failover_client(FAILOVER_COOLDOWN);
+        assert!(block_number(&client).await.is_err());
+
+        // unhealthy clients are still tried as a last resort
+        primary.push(U64::from(1)).unwrap();
+        assert_eq!(block_number(&client).await.unwrap(), U64::from(1));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_client_labels() {
+        let recorder = DebuggingRecorder::per_thread();
+        recorder.install().unwrap();
+
+        let mock = MockProvider::new();
+        let client = MetricsClient::new(mock.clone());
+        // responses are popped from the back
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        }));
+        mock.push(U64::from(1)).unwrap();
+        with_rpc_method("eth_sendUserOperation", async {
+            block_number(&client).await.unwrap();
+            block_number(&client).await.unwrap_err();
+        })
+        .await;
+        // no queued response fails like an unreachable node
+        block_number(&client).await.unwrap_err();
+
+        let mut counts = Snapshotter::current_thread_snapshot()
+            .unwrap()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == "provider_num_requests")
+            .map(|(key, _, _, value)| {
+                let labels = key
+                    .key()
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                (labels, value)
+            })
+            .collect::<Vec<_>>();
+        counts.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            counts,
+            vec![
+                (
+                    "method_name=eth_blockNumber,rpc_method=eth_sendUserOperation,error=none"
+                        .to_string(),
+                    DebugValue::Counter(1)
+                ),
+                (
+                    "method_name=eth_blockNumber,rpc_method=eth_sendUserOperation,error=rpc"
+                        .to_string(),
+                    DebugValue::Counter(1)
+                ),
+                (
+                    "method_name=eth_blockNumber,rpc_method=none,error=transport".to_string(),
+                    DebugValue::Counter(1)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_chain() {
+        let entry_point = Address::random();
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        mock.push(Bytes::from(vec![1])).unwrap();
+        mock.push(U256::from(1)).unwrap();
+
+        check_provider_chain(&provider, 1, &[entry_point])
+            .await
+            .unwrap();
+        mock.assert_request("eth_chainId", ()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_chain_wrong_chain_id() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(2)).unwrap();
+
+        let error = check_provider_chain(&provider, 1, &[Address::random()])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("does not match"));
+    }
+
+    #[tokio::test]
+    async fn test_check_provider_chain_missing_code() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(Bytes::new()).unwrap();
+        mock.push(U256::from(1)).unwrap();
+
+        let error = check_provider_chain(&provider, 1, &[Address::random()])
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("has no code"));
+    }
+}